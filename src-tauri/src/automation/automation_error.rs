@@ -0,0 +1,7 @@
+#[derive(Debug)]
+pub enum RuleError {
+    /// The rules file could not be read
+    IoError,
+    /// The rules are not valid TOML or JSON, with the parser message
+    InvalidRules(String),
+}
@@ -0,0 +1,466 @@
+use chrono::{Datelike, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::rule_data::{Action, Conditions, RuleSet, TimeWindow};
+use crate::daemon::template::{render_template, TemplateEvent};
+
+/// State the conditions of a rule are checked against
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Context {
+    pub ssid: Option<String>,
+    pub bssid: Option<String>,
+    pub interface: Option<String>,
+    /// Address and alias of the Bluetooth device the event is about
+    pub device: Vec<String>,
+    pub on_battery: Option<bool>,
+    /// Local time, the current time if not set
+    pub time: Option<NaiveDateTime>,
+}
+
+impl Context {
+    /// Overrides the context with what the event payload reports
+    ///
+    /// Wi-Fi events carry `ssid`, `bssid` and the interface as `device`,
+    /// quota events an `interface`, Bluetooth events the device itself or
+    /// nested as `device`.
+    pub fn with_payload(mut self, payload: &Value) -> Self {
+        let text = |value: &Value| value.as_str().map(str::to_string);
+
+        if let Some(ssid) = text(&payload["ssid"]) {
+            self.ssid = Some(ssid);
+        }
+        if let Some(bssid) = text(&payload["bssid"]) {
+            self.bssid = Some(bssid);
+        }
+        if let Some(interface) = text(&payload["interface"]).or(text(&payload["device"])) {
+            self.interface = Some(interface);
+        }
+        if let Some(on_battery) = payload["on_battery"].as_bool() {
+            self.on_battery = Some(on_battery);
+        }
+
+        let device = match &payload["device"] {
+            Value::Object(_) => &payload["device"],
+            _ => payload,
+        };
+        let names: Vec<String> = ["address", "alias", "name"]
+            .iter()
+            .filter_map(|field| text(&device[field]))
+            .collect();
+        if !names.is_empty() {
+            self.device = names;
+        }
+
+        self
+    }
+}
+
+/// Outcome of checking one triggered rule
+#[derive(Debug, Clone, Serialize)]
+pub struct Evaluation {
+    pub rule: String,
+    pub matched: bool,
+    /// First condition that did not hold
+    pub reason: Option<String>,
+    /// Actions with their placeholders filled in, empty unless matched
+    pub actions: Vec<Action>,
+}
+
+/// Checks which rules an event triggers, without running anything
+///
+/// This is the dry run of the automation engine: the engine runs the
+/// actions of exactly the rules reported as matched.
+///
+/// # Arguments
+/// * `rules` - the rules to check
+/// * `event` - name of the event, e.g. `wifi_connected`
+/// * `payload` - payload of the event, also used for the placeholders
+/// * `context` - current state, overridden by the payload
+///
+/// # Returns
+/// One evaluation per rule triggered by the event, in file order
+pub fn evaluate(
+    rules: &RuleSet,
+    event: &str,
+    payload: &Value,
+    context: &Context,
+) -> Vec<Evaluation> {
+    let context = context.clone().with_payload(payload);
+    let now = context.time.unwrap_or_else(|| Local::now().naive_local());
+    let template_event = TemplateEvent::new(event, payload.clone());
+
+    rules
+        .rules
+        .iter()
+        .filter(|rule| rule.on.iter().any(|trigger| trigger == event))
+        .map(|rule| {
+            let reason = match rule.enabled {
+                true => check_conditions(&rule.when, &context, now).err(),
+                false => Some("Rule is disabled".to_string()),
+            };
+            let actions = match reason {
+                None => rule
+                    .actions
+                    .iter()
+                    .map(|action| render_action(action, &template_event))
+                    .collect(),
+                Some(_) => Vec::new(),
+            };
+
+            Evaluation {
+                rule: rule.name.clone(),
+                matched: reason.is_none(),
+                reason,
+                actions,
+            }
+        })
+        .collect()
+}
+
+/// `Err` with the first condition that does not hold
+fn check_conditions(
+    conditions: &Conditions,
+    context: &Context,
+    now: NaiveDateTime,
+) -> Result<(), String> {
+    check_list("SSID", &conditions.ssid, context.ssid.as_deref(), false)?;
+    check_list("BSSID", &conditions.bssid, context.bssid.as_deref(), true)?;
+    check_list(
+        "Interface",
+        &conditions.interface,
+        context.interface.as_deref(),
+        false,
+    )?;
+
+    if !conditions.device.is_empty()
+        && !context.device.iter().any(|name| {
+            conditions
+                .device
+                .iter()
+                .any(|device| device.eq_ignore_ascii_case(name))
+        })
+    {
+        return Err(match context.device.first() {
+            Some(name) => format!("Device {} is not one of {:?}", name, conditions.device),
+            None => "Event is not about a Bluetooth device".to_string(),
+        });
+    }
+
+    if let Some(expected) = conditions.on_battery {
+        match context.on_battery {
+            Some(on_battery) if on_battery == expected => {}
+            Some(_) if expected => return Err("Not on battery".to_string()),
+            Some(_) => return Err("On battery".to_string()),
+            None => return Err("Power source unknown".to_string()),
+        }
+    }
+
+    if let Some(window) = &conditions.time {
+        if !in_window(window, now) {
+            return Err(format!(
+                "{} is outside the time window",
+                now.format("%a %H:%M")
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_list(
+    name: &str,
+    allowed: &[String],
+    value: Option<&str>,
+    ignore_case: bool,
+) -> Result<(), String> {
+    if allowed.is_empty() {
+        return Ok(());
+    }
+    let Some(value) = value else {
+        return Err(format!("No {} known", name));
+    };
+
+    let matches = allowed.iter().any(|allowed| match ignore_case {
+        true => allowed.eq_ignore_ascii_case(value),
+        false => allowed == value,
+    });
+    match matches {
+        true => Ok(()),
+        false => Err(format!("{} {} is not one of {:?}", name, value, allowed)),
+    }
+}
+
+fn in_window(window: &TimeWindow, now: NaiveDateTime) -> bool {
+    if !window.days.is_empty() && !window.days.contains(&now.weekday()) {
+        return false;
+    }
+
+    let time = now.time();
+    match (window.after, window.before) {
+        (Some(after), Some(before)) if after <= before => time >= after && time < before,
+        // Spans midnight
+        (Some(after), Some(before)) => time >= after || time < before,
+        (Some(after), None) => time >= after,
+        (None, Some(before)) => time < before,
+        (None, None) => true,
+    }
+}
+
+/// Fills the placeholders in every string of the action
+fn render_action(action: &Action, event: &TemplateEvent) -> Action {
+    fn render(value: &mut Value, event: &TemplateEvent) {
+        match value {
+            Value::String(text) => *text = render_template(text, event),
+            Value::Array(items) => items.iter_mut().for_each(|item| render(item, event)),
+            Value::Object(fields) => fields.values_mut().for_each(|field| render(field, event)),
+            _ => {}
+        }
+    }
+
+    let Ok(mut value) = serde_json::to_value(action) else {
+        return action.clone();
+    };
+    render(&mut value, event);
+    serde_json::from_value(value).unwrap_or_else(|_| action.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime};
+    use serde_json::json;
+
+    use super::*;
+    use crate::automation::rule_data::RuleFormat;
+
+    const RULES: &str = r#"
+        [[rule]]
+        name = "Office VPN"
+        on = "wifi_connected"
+        when = { ssid = "Office", time = { after = "07:00", before = "19:00", days = ["mon", "tue", "wed", "thu", "fri"] } }
+        actions = [
+            { action = "connect", profile = "Office VPN" },
+            { action = "set_profile", profile = "{{payload.ssid}}", options = { "ipv4.dns" = "10.0.0.53" } },
+        ]
+
+        [[rule]]
+        name = "Save power"
+        on = "power_changed"
+        when = { on_battery = true }
+        actions = [{ action = "background_scans", enabled = false }]
+
+        [[rule]]
+        name = "Headset"
+        on = ["bt_device_connected"]
+        when = { device = ["WH-1000XM4", "00:11:22:33:44:55"] }
+        actions = [{ action = "set_audio_profile", device = "{{payload.address}}", profile = "a2dp-sink" }]
+
+        [[rule]]
+        name = "Disabled"
+        enabled = false
+        on = "wifi_connected"
+        actions = [{ action = "notify", title = "Joined {{payload.ssid}}" }]
+    "#;
+
+    fn rules() -> RuleSet {
+        RuleSet::parse(RULES, RuleFormat::Toml).expect("rules parse")
+    }
+
+    /// Wednesday 2026-10-14 at the given time
+    fn wednesday(hour: u32, minute: u32) -> Context {
+        Context {
+            time: NaiveDate::from_ymd_opt(2026, 10, 14)
+                .and_then(|d| d.and_hms_opt(hour, minute, 0)),
+            ..Context::default()
+        }
+    }
+
+    fn connected(ssid: &str) -> Value {
+        json!({ "ssid": ssid, "bssid": "AA:BB:CC:DD:EE:FF", "signal": 80, "device": "wlan0" })
+    }
+
+    #[test]
+    fn parses_toml_and_json() {
+        let rules = rules();
+        assert_eq!(rules.rules.len(), 4);
+        assert_eq!(rules.rules[0].when.ssid, vec!["Office"]);
+        assert_eq!(rules.rules[2].on, vec!["bt_device_connected"]);
+        assert!(!rules.rules[3].enabled);
+        assert!(rules.warnings().is_empty());
+
+        let json = r#"{ "rules": [{ "name": "Log", "on": ["quota_exceeded"],
+            "actions": [{ "action": "run", "command": "logger", "args": ["{{payload.interface}}"] }] }] }"#;
+        let rules = RuleSet::parse(json, RuleFormat::Json).expect("json rules parse");
+        assert_eq!(rules.rules[0].name, "Log");
+        assert!(rules.rules[0].when.ssid.is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_action() {
+        let rules = r#"
+            [[rule]]
+            name = "Broken"
+            on = "wifi_connected"
+            actions = [{ action = "reboot" }]
+        "#;
+        assert!(RuleSet::parse(rules, RuleFormat::Toml).is_err());
+    }
+
+    #[test]
+    fn warns_about_unknown_triggers_and_duplicates() {
+        let rules = r#"
+            [[rule]]
+            name = "A"
+            on = "wifi_conected"
+            actions = []
+
+            [[rule]]
+            name = "A"
+            on = "power_changed"
+            actions = [{ action = "background_scans", enabled = true }]
+        "#;
+        let warnings = RuleSet::parse(rules, RuleFormat::Toml).unwrap().warnings();
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings.iter().any(|w| w.contains("wifi_conected")));
+        assert!(warnings.iter().any(|w| w.contains("used twice")));
+        assert!(warnings.iter().any(|w| w.contains("no actions")));
+    }
+
+    #[test]
+    fn only_triggered_rules_are_evaluated() {
+        let evaluations = evaluate(&rules(), "wifi_ap_seen", &json!({}), &wednesday(9, 0));
+        assert!(evaluations.is_empty());
+
+        let evaluations = evaluate(
+            &rules(),
+            "wifi_connected",
+            &connected("Office"),
+            &wednesday(9, 0),
+        );
+        let names: Vec<&str> = evaluations.iter().map(|e| e.rule.as_str()).collect();
+        assert_eq!(names, vec!["Office VPN", "Disabled"]);
+    }
+
+    #[test]
+    fn matches_ssid_and_renders_actions() {
+        let evaluations = evaluate(
+            &rules(),
+            "wifi_connected",
+            &connected("Office"),
+            &wednesday(9, 0),
+        );
+        let office = &evaluations[0];
+        assert!(office.matched, "{:?}", office.reason);
+        assert_eq!(
+            office.actions[1],
+            Action::SetProfile {
+                profile: "Office".to_string(),
+                options: [("ipv4.dns".to_string(), "10.0.0.53".to_string())].into(),
+            }
+        );
+
+        let disabled = &evaluations[1];
+        assert!(!disabled.matched);
+        assert!(disabled.actions.is_empty());
+    }
+
+    #[test]
+    fn other_ssid_does_not_match() {
+        let evaluations = evaluate(
+            &rules(),
+            "wifi_connected",
+            &connected("Home"),
+            &wednesday(9, 0),
+        );
+        assert!(!evaluations[0].matched);
+        assert!(evaluations[0].reason.as_deref().unwrap().contains("Home"));
+    }
+
+    #[test]
+    fn respects_time_window_and_weekdays() {
+        let payload = connected("Office");
+        assert!(!evaluate(&rules(), "wifi_connected", &payload, &wednesday(6, 59))[0].matched);
+        assert!(evaluate(&rules(), "wifi_connected", &payload, &wednesday(7, 0))[0].matched);
+        assert!(!evaluate(&rules(), "wifi_connected", &payload, &wednesday(19, 0))[0].matched);
+
+        let saturday = Context {
+            time: NaiveDate::from_ymd_opt(2026, 10, 17).and_then(|d| d.and_hms_opt(9, 0, 0)),
+            ..Context::default()
+        };
+        assert!(!evaluate(&rules(), "wifi_connected", &payload, &saturday)[0].matched);
+    }
+
+    #[test]
+    fn time_window_spans_midnight() {
+        let window = TimeWindow {
+            after: NaiveTime::from_hms_opt(22, 0, 0),
+            before: NaiveTime::from_hms_opt(6, 0, 0),
+            days: Vec::new(),
+        };
+        let at = |hour| wednesday(hour, 0).time.unwrap();
+        assert!(in_window(&window, at(23)));
+        assert!(in_window(&window, at(2)));
+        assert!(!in_window(&window, at(12)));
+    }
+
+    #[test]
+    fn checks_power_source() {
+        let on_battery = evaluate(
+            &rules(),
+            "power_changed",
+            &json!({ "on_battery": true }),
+            &wednesday(9, 0),
+        );
+        assert!(on_battery[0].matched);
+        assert_eq!(
+            on_battery[0].actions,
+            vec![Action::BackgroundScans { enabled: false }]
+        );
+
+        let on_ac = evaluate(
+            &rules(),
+            "power_changed",
+            &json!({ "on_battery": false }),
+            &wednesday(9, 0),
+        );
+        assert!(!on_ac[0].matched);
+    }
+
+    #[test]
+    fn matches_bluetooth_device_by_alias_or_address() {
+        let headset =
+            json!({ "address": "AA:00:00:00:00:01", "alias": "WH-1000XM4", "connected": true });
+        let evaluations = evaluate(&rules(), "bt_device_connected", &headset, &wednesday(9, 0));
+        assert!(evaluations[0].matched);
+        assert_eq!(
+            evaluations[0].actions[0],
+            Action::SetAudioProfile {
+                device: "AA:00:00:00:00:01".to_string(),
+                profile: "a2dp-sink".to_string(),
+            }
+        );
+
+        let by_address = json!({ "address": "00:11:22:33:44:55", "alias": "Speaker" });
+        assert!(
+            evaluate(
+                &rules(),
+                "bt_device_connected",
+                &by_address,
+                &wednesday(9, 0)
+            )[0]
+            .matched
+        );
+
+        let mouse = json!({ "address": "AA:00:00:00:00:02", "alias": "Mouse" });
+        assert!(!evaluate(&rules(), "bt_device_connected", &mouse, &wednesday(9, 0))[0].matched);
+    }
+
+    #[test]
+    fn reads_nested_device_of_battery_alerts() {
+        let alert = json!({ "device": { "address": "00:11:22:33:44:55", "alias": "Mouse" }, "threshold": 20 });
+        let context = Context::default().with_payload(&alert);
+        assert_eq!(context.device, vec!["00:11:22:33:44:55", "Mouse"]);
+        assert_eq!(context.interface, None);
+    }
+}
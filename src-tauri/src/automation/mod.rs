@@ -0,0 +1,3 @@
+pub mod automation_error;
+pub mod evaluator;
+pub mod rule_data;
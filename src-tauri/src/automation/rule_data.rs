@@ -0,0 +1,227 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use chrono::{NaiveTime, Weekday};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::automation_error::RuleError;
+use crate::paths::config_dir;
+
+/// Events rules are usually triggered by, other daemon events work as well
+pub const KNOWN_TRIGGERS: [&str; 12] = [
+    "wifi_connected",
+    "wifi_disconnected",
+    "wifi_ap_seen",
+    "connectivity_changed",
+    "quota_exceeded",
+    "power_changed",
+    "bt_device_connected",
+    "bt_device_disconnected",
+    "bt_device_found",
+    "bt_device_lost",
+    "bluetooth_low_battery",
+    "roaming_decision",
+];
+
+/// Seconds a rule waits before it fires again if `cooldown_secs` is not set
+pub const DEFAULT_COOLDOWN_SECS: u64 = 5;
+
+/// Format of a rules file
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleFormat {
+    Toml,
+    Json,
+}
+
+/// Time of day and weekdays a rule is limited to
+///
+/// A window whose `after` is later than `before` spans midnight.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeWindow {
+    /// Start of the window, e.g. `08:00`
+    pub after: Option<NaiveTime>,
+    /// End of the window, exclusive
+    pub before: Option<NaiveTime>,
+    /// Weekdays like `mon` or `Monday`, every day if empty
+    pub days: Vec<Weekday>,
+}
+
+/// Conditions that must all hold for a triggered rule to run
+///
+/// Every list matches if it contains the value; an empty list matches
+/// anything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Conditions {
+    #[serde(deserialize_with = "one_or_many")]
+    pub ssid: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub bssid: Vec<String>,
+    /// Network interface, e.g. `wlan0`
+    #[serde(deserialize_with = "one_or_many")]
+    pub interface: Vec<String>,
+    /// Bluetooth device by address or alias
+    #[serde(deserialize_with = "one_or_many")]
+    pub device: Vec<String>,
+    pub time: Option<TimeWindow>,
+    pub on_battery: Option<bool>,
+}
+
+/// What a rule does, string fields may use the placeholders of
+/// [`crate::daemon::template::render_template`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Connects to a Wi-Fi network by SSID or BSSID, or activates a saved
+    /// profile of any type (e.g. a VPN)
+    Connect {
+        network: Option<String>,
+        password: Option<String>,
+        profile: Option<String>,
+    },
+    /// Disconnects a Wi-Fi device (the connected one if not set), or
+    /// deactivates a profile
+    Disconnect {
+        interface: Option<String>,
+        profile: Option<String>,
+    },
+    /// Runs a program, without a shell
+    Run {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// Changes settings of a saved profile, e.g. `ipv4.dns`
+    SetProfile {
+        profile: String,
+        options: BTreeMap<String, String>,
+    },
+    /// Switches the audio profile of a Bluetooth device, e.g. `a2dp-sink`
+    SetAudioProfile { device: String, profile: String },
+    /// Pauses or resumes the periodic scans of the daemon
+    BackgroundScans { enabled: bool },
+    /// Shows a desktop notification through the GUI
+    Notify {
+        title: String,
+        #[serde(default)]
+        body: String,
+    },
+}
+
+/// A declarative automation rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    /// Events that trigger the rule
+    #[serde(deserialize_with = "one_or_many")]
+    pub on: Vec<String>,
+    #[serde(default)]
+    pub when: Conditions,
+    pub actions: Vec<Action>,
+    /// Seconds the rule waits before it fires again, [`DEFAULT_COOLDOWN_SECS`]
+    /// if not set; keeps rules from triggering each other in a loop
+    pub cooldown_secs: Option<u64>,
+}
+
+/// The rules of `rules.toml` or `rules.json`
+///
+/// TOML files list rules as `[[rule]]` tables, JSON files as a `rules` array.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuleSet {
+    #[serde(rename = "rule", alias = "rules", default)]
+    pub rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Path of the rules file, `rules.toml` unless only `rules.json` exists
+    pub fn path() -> PathBuf {
+        let toml = config_dir().join("rules.toml");
+        let json = config_dir().join("rules.json");
+        if !toml.exists() && json.exists() {
+            json
+        } else {
+            toml
+        }
+    }
+
+    /// Loads the rules file, no rules if there is none
+    ///
+    /// # Returns
+    /// - `Ok(RuleSet)` with the rules of the file
+    /// - `Err(RuleError)` if the file cannot be read or parsed
+    pub fn load() -> Result<Self, RuleError> {
+        let path = Self::path();
+        if !path.exists() {
+            return Ok(RuleSet::default());
+        }
+        let content = std::fs::read_to_string(&path).map_err(|_| RuleError::IoError)?;
+        let format = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => RuleFormat::Json,
+            _ => RuleFormat::Toml,
+        };
+
+        Self::parse(&content, format)
+    }
+
+    /// Parses rules written in TOML or JSON
+    pub fn parse(content: &str, format: RuleFormat) -> Result<Self, RuleError> {
+        match format {
+            RuleFormat::Toml => {
+                toml::from_str(content).map_err(|e| RuleError::InvalidRules(e.to_string()))
+            }
+            RuleFormat::Json => {
+                serde_json::from_str(content).map_err(|e| RuleError::InvalidRules(e.to_string()))
+            }
+        }
+    }
+
+    /// Problems that do not stop the rules from loading but likely are mistakes
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mut names = std::collections::HashSet::new();
+
+        for rule in &self.rules {
+            if !names.insert(rule.name.as_str()) {
+                warnings.push(format!("Rule name '{}' is used twice", rule.name));
+            }
+            if rule.on.is_empty() {
+                warnings.push(format!("Rule '{}' has no trigger", rule.name));
+            }
+            for event in &rule.on {
+                if !KNOWN_TRIGGERS.contains(&event.as_str()) {
+                    warnings.push(format!(
+                        "Rule '{}' is triggered by unknown event '{}'",
+                        rule.name, event
+                    ));
+                }
+            }
+            if rule.actions.is_empty() {
+                warnings.push(format!("Rule '{}' has no actions", rule.name));
+            }
+        }
+
+        warnings
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// Accepts a single string where a list is expected
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
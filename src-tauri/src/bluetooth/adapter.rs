@@ -0,0 +1,188 @@
+use super::{
+    adapter_data::{AdapterSetting, BluetoothAdapter},
+    bluez::{
+        adapter_path, connection, managed_objects, property, Adapter1Proxy, ADAPTER_INTERFACE,
+    },
+    bluez_error::BluetoothError,
+};
+
+/// Lists all Bluetooth controllers known to BlueZ
+///
+/// # Returns
+/// - `Ok(Vec<BluetoothAdapter>)` sorted by object path
+/// - `Err(BluetoothError)` if BlueZ cannot be reached
+pub async fn get_adapters() -> Result<Vec<BluetoothAdapter>, BluetoothError> {
+    let conn = connection().await?;
+    let objects = managed_objects(&conn).await?;
+
+    let mut adapters: Vec<BluetoothAdapter> = objects
+        .iter()
+        .filter_map(|(path, interfaces)| {
+            let props = interfaces
+                .iter()
+                .find(|(name, _)| name.as_str() == ADAPTER_INTERFACE)
+                .map(|(_, props)| props)?;
+            let path = path.to_string();
+
+            Some(BluetoothAdapter {
+                id: path.rsplit('/').next().unwrap_or_default().to_string(),
+                address: property(props, "Address").unwrap_or_default(),
+                address_type: property(props, "AddressType").unwrap_or_default(),
+                name: property(props, "Name").unwrap_or_default(),
+                alias: property(props, "Alias").unwrap_or_default(),
+                class: property(props, "Class").unwrap_or_default(),
+                powered: property(props, "Powered").unwrap_or_default(),
+                discoverable: property(props, "Discoverable").unwrap_or_default(),
+                discoverable_timeout: property(props, "DiscoverableTimeout").unwrap_or_default(),
+                pairable: property(props, "Pairable").unwrap_or_default(),
+                pairable_timeout: property(props, "PairableTimeout").unwrap_or_default(),
+                discovering: property(props, "Discovering").unwrap_or_default(),
+                path,
+            })
+        })
+        .collect();
+
+    adapters.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(adapters)
+}
+
+/// Builds a proxy for the given adapter, checking that it exists
+///
+/// # Arguments
+/// * `adapter` - adapter name (`hci0`) or object path
+pub async fn adapter_proxy(adapter: &str) -> Result<Adapter1Proxy<'static>, BluetoothError> {
+    let conn = connection().await?;
+    let proxy = Adapter1Proxy::builder(&conn)
+        .path(adapter_path(adapter))
+        .map_err(|_| BluetoothError::NoSuchAdapter)?
+        .build()
+        .await
+        .map_err(BluetoothError::from_dbus)?;
+
+    // Proxies are created lazily, reading a property fails for unknown adapters
+    match proxy.address().await.map_err(BluetoothError::from_dbus) {
+        Ok(_) => Ok(proxy),
+        Err(BluetoothError::DoesNotExist) => Err(BluetoothError::NoSuchAdapter),
+        Err(e) => Err(e),
+    }
+}
+
+/// Changes a single property of an adapter
+///
+/// # Arguments
+/// * `adapter` - adapter name (`hci0`) or object path
+/// * `setting` - the property to change and its new value
+///
+/// # Returns
+/// - `Ok(())` if BlueZ accepted the new value
+/// - `Err(BluetoothError)` if the adapter does not exist or rejected the value
+pub async fn set_adapter_setting(
+    adapter: &str,
+    setting: AdapterSetting,
+) -> Result<(), BluetoothError> {
+    let proxy = adapter_proxy(adapter).await?;
+
+    match setting {
+        AdapterSetting::Powered(value) => proxy.set_powered(value).await,
+        AdapterSetting::Alias(value) => proxy.set_alias(&value).await,
+        AdapterSetting::Discoverable(value) => proxy.set_discoverable(value).await,
+        AdapterSetting::DiscoverableTimeout(value) => proxy.set_discoverable_timeout(value).await,
+        AdapterSetting::Pairable(value) => proxy.set_pairable(value).await,
+        AdapterSetting::PairableTimeout(value) => proxy.set_pairable_timeout(value).await,
+    }
+    .map_err(BluetoothError::from_dbus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluetooth::{
+        bluez::with_connection,
+        mock_bluez::{add_adapter, Calls, MockAdapter, PrivateBus},
+    };
+
+    #[tokio::test]
+    async fn lists_adapters_and_changes_their_properties() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let bluez = bus.serve_bluez().await;
+        let calls = Calls::default();
+        add_adapter(
+            &bluez,
+            "/org/bluez/hci1",
+            MockAdapter::new("00:00:00:00:00:02", &calls),
+        )
+        .await;
+        add_adapter(
+            &bluez,
+            "/org/bluez/hci0",
+            MockAdapter::new("00:00:00:00:00:01", &calls),
+        )
+        .await;
+
+        with_connection(bus.connect().await, async {
+            let adapters = get_adapters().await.unwrap();
+            let ids: Vec<_> = adapters.iter().map(|a| a.id.as_str()).collect();
+            assert_eq!(ids, ["hci0", "hci1"]);
+            assert_eq!(adapters[0].address, "00:00:00:00:00:01");
+            assert_eq!(adapters[0].discoverable_timeout, 180);
+            assert!(adapters[0].powered);
+
+            set_adapter_setting("hci0", AdapterSetting::Powered(false))
+                .await
+                .unwrap();
+            set_adapter_setting("/org/bluez/hci0", AdapterSetting::Alias("Desk".to_string()))
+                .await
+                .unwrap();
+            set_adapter_setting("hci0", AdapterSetting::DiscoverableTimeout(60))
+                .await
+                .unwrap();
+
+            let adapters = get_adapters().await.unwrap();
+            assert!(!adapters[0].powered);
+            assert_eq!(adapters[0].alias, "Desk");
+            assert_eq!(adapters[0].discoverable_timeout, 60);
+            // The other adapter is left alone
+            assert!(adapters[1].powered);
+            assert_eq!(adapters[1].alias, "Adapter 00:00:00:00:00:02");
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn reports_unknown_adapters_and_rejected_values() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let bluez = bus.serve_bluez().await;
+        let calls = Calls::default();
+        add_adapter(
+            &bluez,
+            "/org/bluez/hci0",
+            MockAdapter::new("00:00:00:00:00:01", &calls),
+        )
+        .await;
+
+        with_connection(bus.connect().await, async {
+            assert!(adapter_proxy("hci0").await.is_ok());
+            assert!(matches!(
+                adapter_proxy("hci9").await,
+                Err(BluetoothError::NoSuchAdapter)
+            ));
+            assert!(matches!(
+                set_adapter_setting("hci9", AdapterSetting::Powered(true)).await,
+                Err(BluetoothError::NoSuchAdapter)
+            ));
+            assert!(matches!(
+                set_adapter_setting("hci0", AdapterSetting::DiscoverableTimeout(5000)).await,
+                Err(BluetoothError::InvalidArguments)
+            ));
+            let adapters = get_adapters().await.unwrap();
+            assert_eq!(adapters[0].discoverable_timeout, 180);
+        })
+        .await;
+    }
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+/// Represents a local Bluetooth controller as exposed by BlueZ (`org.bluez.Adapter1`)
+#[derive(Debug, Clone, Serialize)]
+pub struct BluetoothAdapter {
+    /// D-Bus object path (e.g. `/org/bluez/hci0`)
+    pub path: String,
+    /// Kernel name of the controller (e.g. `hci0`)
+    pub id: String,
+    /// Bluetooth device address of the controller
+    pub address: String,
+    /// `public` or `random`
+    pub address_type: String,
+    /// System name (hostname based)
+    pub name: String,
+    /// User friendly name shown to remote devices
+    pub alias: String,
+    /// Class of device
+    pub class: u32,
+    /// Whether the radio is switched on
+    pub powered: bool,
+    /// Whether remote devices can find the adapter
+    pub discoverable: bool,
+    /// Seconds until discoverable mode switches off again, 0 for never
+    pub discoverable_timeout: u32,
+    /// Whether remote devices can pair with the adapter
+    pub pairable: bool,
+    /// Seconds until pairable mode switches off again, 0 for never
+    pub pairable_timeout: u32,
+    /// Whether a device discovery is running
+    pub discovering: bool,
+}
+
+/// A writable adapter property together with its new value
+#[derive(Debug, Clone, Deserialize)]
+pub enum AdapterSetting {
+    Powered(bool),
+    Alias(String),
+    Discoverable(bool),
+    DiscoverableTimeout(u32),
+    Pairable(bool),
+    PairableTimeout(u32),
+}
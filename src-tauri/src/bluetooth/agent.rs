@@ -0,0 +1,525 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock, Mutex,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{sync::oneshot, time};
+use zbus::{interface, proxy, zvariant::ObjectPath, DBusError};
+
+use super::{bluez::connection, bluez_error::BluetoothError};
+
+/// Object path the agent is exported at
+pub const AGENT_PATH: &str = "/com/wiblue/agent";
+/// Input/output capability announced to BlueZ, we can display and enter keys
+const AGENT_CAPABILITY: &str = "KeyboardDisplay";
+/// How long the agent waits for the user to answer a request
+pub const AGENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Requests waiting for an answer from the user, keyed by request id
+static PENDING: LazyLock<Mutex<HashMap<u64, oneshot::Sender<AgentResponse>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// What BlueZ asks the user during pairing or authorization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AgentRequestKind {
+    /// Enter the PIN code shown on (or printed on) the remote device
+    RequestPinCode,
+    /// Show a PIN code that has to be entered on the remote device
+    DisplayPinCode { pincode: String },
+    /// Enter the 6 digit passkey shown on the remote device
+    RequestPasskey,
+    /// Show a passkey that has to be typed on the remote device
+    DisplayPasskey { passkey: u32, entered: u16 },
+    /// Confirm that the remote device shows the same passkey
+    RequestConfirmation { passkey: u32 },
+    /// Allow pairing without any key (Just Works)
+    RequestAuthorization,
+    /// Allow the remote device to use a service
+    AuthorizeService { uuid: String },
+}
+
+/// A request forwarded to the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentRequest {
+    /// Identifier the answer has to reference
+    pub id: u64,
+    /// Object path of the remote device
+    pub device: String,
+    pub kind: AgentRequestKind,
+    /// Whether BlueZ waits for an answer (display requests do not)
+    pub needs_response: bool,
+}
+
+/// Something the agent wants the user interface to know about
+#[derive(Debug, Clone, Serialize)]
+pub enum AgentEvent {
+    Request(AgentRequest),
+    /// BlueZ, the remote device or the timeout cancelled a pending request
+    Cancelled {
+        id: Option<u64>,
+    },
+}
+
+/// Answer of the user to a request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AgentResponse {
+    Accept,
+    Reject,
+    PinCode(String),
+    Passkey(u32),
+}
+
+/// Errors reported back to BlueZ
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "org.bluez.Error")]
+enum AgentError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    Rejected(String),
+    Canceled(String),
+}
+
+#[proxy(
+    interface = "org.bluez.AgentManager1",
+    default_service = "org.bluez",
+    default_path = "/org/bluez"
+)]
+trait AgentManager1 {
+    fn register_agent(&self, agent: &ObjectPath<'_>, capability: &str) -> zbus::Result<()>;
+
+    fn request_default_agent(&self, agent: &ObjectPath<'_>) -> zbus::Result<()>;
+}
+
+/// `org.bluez.Agent1` implementation forwarding every request to the user
+struct PairingAgent {
+    notify: Box<dyn Fn(AgentEvent) + Send + Sync>,
+    timeout: Duration,
+    /// Request currently shown to the user, BlueZ only cancels the latest one
+    current: Mutex<Option<u64>>,
+}
+
+impl PairingAgent {
+    /// Forwards a request that only has to be displayed
+    fn display(&self, device: &ObjectPath<'_>, kind: AgentRequestKind) {
+        let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst);
+        *self.current.lock().unwrap() = Some(id);
+        (self.notify)(AgentEvent::Request(AgentRequest {
+            id,
+            device: device.to_string(),
+            kind,
+            needs_response: false,
+        }));
+    }
+
+    /// Forwards a request and waits until the user answers or the timeout expires
+    async fn ask(
+        &self,
+        device: &ObjectPath<'_>,
+        kind: AgentRequestKind,
+    ) -> Result<AgentResponse, AgentError> {
+        let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+        PENDING.lock().unwrap().insert(id, sender);
+        *self.current.lock().unwrap() = Some(id);
+
+        (self.notify)(AgentEvent::Request(AgentRequest {
+            id,
+            device: device.to_string(),
+            kind,
+            needs_response: true,
+        }));
+
+        let answer = time::timeout(self.timeout, receiver).await;
+        PENDING.lock().unwrap().remove(&id);
+
+        match answer {
+            Ok(Ok(AgentResponse::Reject)) => Err(AgentError::Rejected("Rejected by user".into())),
+            Ok(Ok(response)) => Ok(response),
+            // The sender is dropped when BlueZ cancels the request
+            Ok(Err(_)) => Err(AgentError::Canceled("Request cancelled".into())),
+            Err(_) => {
+                (self.notify)(AgentEvent::Cancelled { id: Some(id) });
+                Err(AgentError::Canceled("No answer from user".into()))
+            }
+        }
+    }
+}
+
+#[interface(name = "org.bluez.Agent1")]
+impl PairingAgent {
+    fn release(&self) {
+        println!("Pairing agent released by BlueZ");
+    }
+
+    async fn request_pin_code(&self, device: ObjectPath<'_>) -> Result<String, AgentError> {
+        match self.ask(&device, AgentRequestKind::RequestPinCode).await? {
+            AgentResponse::PinCode(pincode) => Ok(pincode),
+            _ => Err(AgentError::Rejected("Expected a PIN code".into())),
+        }
+    }
+
+    fn display_pin_code(&self, device: ObjectPath<'_>, pincode: String) {
+        self.display(&device, AgentRequestKind::DisplayPinCode { pincode });
+    }
+
+    async fn request_passkey(&self, device: ObjectPath<'_>) -> Result<u32, AgentError> {
+        match self.ask(&device, AgentRequestKind::RequestPasskey).await? {
+            AgentResponse::Passkey(passkey) => Ok(passkey),
+            _ => Err(AgentError::Rejected("Expected a passkey".into())),
+        }
+    }
+
+    fn display_passkey(&self, device: ObjectPath<'_>, passkey: u32, entered: u16) {
+        self.display(
+            &device,
+            AgentRequestKind::DisplayPasskey { passkey, entered },
+        );
+    }
+
+    async fn request_confirmation(
+        &self,
+        device: ObjectPath<'_>,
+        passkey: u32,
+    ) -> Result<(), AgentError> {
+        self.ask(&device, AgentRequestKind::RequestConfirmation { passkey })
+            .await
+            .map(|_| ())
+    }
+
+    async fn request_authorization(&self, device: ObjectPath<'_>) -> Result<(), AgentError> {
+        self.ask(&device, AgentRequestKind::RequestAuthorization)
+            .await
+            .map(|_| ())
+    }
+
+    async fn authorize_service(
+        &self,
+        device: ObjectPath<'_>,
+        uuid: String,
+    ) -> Result<(), AgentError> {
+        self.ask(&device, AgentRequestKind::AuthorizeService { uuid })
+            .await
+            .map(|_| ())
+    }
+
+    fn cancel(&self) {
+        let id = self.current.lock().unwrap().take();
+        if let Some(id) = id {
+            // Dropping the sender wakes up the waiting request
+            PENDING.lock().unwrap().remove(&id);
+        }
+        (self.notify)(AgentEvent::Cancelled { id });
+    }
+}
+
+/// Exports the pairing agent and registers it as the default agent with BlueZ
+///
+/// # Arguments
+/// * `notify` - called for every request that has to be shown to the user
+///
+/// # Returns
+/// - `Ok(())` if BlueZ accepted the agent
+/// - `Err(BluetoothError)` if BlueZ cannot be reached or already has our agent
+pub async fn register_agent(
+    notify: impl Fn(AgentEvent) + Send + Sync + 'static,
+) -> Result<(), BluetoothError> {
+    let conn = connection().await?;
+
+    let agent = PairingAgent {
+        notify: Box::new(notify),
+        timeout: AGENT_TIMEOUT,
+        current: Mutex::new(None),
+    };
+    let added = conn
+        .object_server()
+        .at(AGENT_PATH, agent)
+        .await
+        .map_err(BluetoothError::from_dbus)?;
+    if !added {
+        return Ok(());
+    }
+
+    let manager = AgentManager1Proxy::new(&conn)
+        .await
+        .map_err(BluetoothError::from_dbus)?;
+    let path = ObjectPath::from_static_str_unchecked(AGENT_PATH);
+    manager
+        .register_agent(&path, AGENT_CAPABILITY)
+        .await
+        .map_err(BluetoothError::from_dbus)?;
+    manager
+        .request_default_agent(&path)
+        .await
+        .map_err(BluetoothError::from_dbus)
+}
+
+/// Delivers the answer of the user to a pending request
+///
+/// # Returns
+/// - `Ok(())` if the request was still waiting
+/// - `Err(BluetoothError::InvalidArguments)` if it was answered, cancelled or timed out already
+pub fn respond(id: u64, response: AgentResponse) -> Result<(), BluetoothError> {
+    let sender = PENDING
+        .lock()
+        .unwrap()
+        .remove(&id)
+        .ok_or(BluetoothError::InvalidArguments)?;
+
+    sender
+        .send(response)
+        .map_err(|_| BluetoothError::InvalidArguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+    use zbus::{Connection, Error};
+
+    use super::*;
+    use crate::bluetooth::{
+        bluez::with_connection,
+        mock_bluez::{Calls, PrivateBus},
+    };
+
+    const DEVICE: &str = "/org/bluez/hci0/dev_00_11_22_33_44_55";
+
+    #[proxy(interface = "org.bluez.Agent1")]
+    trait Agent1 {
+        fn request_pin_code(&self, device: &ObjectPath<'_>) -> zbus::Result<String>;
+        fn request_passkey(&self, device: &ObjectPath<'_>) -> zbus::Result<u32>;
+        fn display_passkey(
+            &self,
+            device: &ObjectPath<'_>,
+            passkey: u32,
+            entered: u16,
+        ) -> zbus::Result<()>;
+        fn request_confirmation(&self, device: &ObjectPath<'_>, passkey: u32) -> zbus::Result<()>;
+        fn authorize_service(&self, device: &ObjectPath<'_>, uuid: &str) -> zbus::Result<()>;
+        fn cancel(&self) -> zbus::Result<()>;
+    }
+
+    struct MockAgentManager {
+        calls: Calls,
+    }
+
+    #[interface(name = "org.bluez.AgentManager1")]
+    impl MockAgentManager {
+        fn register_agent(&self, agent: ObjectPath<'_>, capability: String) {
+            let call = format!("RegisterAgent {} {}", agent, capability);
+            self.calls.lock().unwrap().push(call);
+        }
+
+        fn request_default_agent(&self, agent: ObjectPath<'_>) {
+            let call = format!("RequestDefaultAgent {}", agent);
+            self.calls.lock().unwrap().push(call);
+        }
+    }
+
+    /// Serves an agent on its own connection and plays BlueZ calling it
+    async fn serve_agent(
+        bus: &PrivateBus,
+        timeout: Duration,
+    ) -> (
+        Connection,
+        Agent1Proxy<'static>,
+        mpsc::UnboundedReceiver<AgentEvent>,
+    ) {
+        let (sender, events) = mpsc::unbounded_channel();
+        let agent = PairingAgent {
+            notify: Box::new(move |event| {
+                let _ = sender.send(event);
+            }),
+            timeout,
+            current: Mutex::new(None),
+        };
+        let agent_conn = bus.connect().await;
+        agent_conn
+            .object_server()
+            .at(AGENT_PATH, agent)
+            .await
+            .unwrap();
+
+        let bluez = Agent1Proxy::builder(&bus.connect().await)
+            .destination(agent_conn.unique_name().unwrap().to_owned())
+            .unwrap()
+            .path(AGENT_PATH)
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        (agent_conn, bluez, events)
+    }
+
+    /// Waits for the next request forwarded to the user
+    async fn next_request(events: &mut mpsc::UnboundedReceiver<AgentEvent>) -> AgentRequest {
+        match events.recv().await {
+            Some(AgentEvent::Request(request)) => request,
+            other => panic!("expected a request, got {:?}", other),
+        }
+    }
+
+    fn error_name(error: Error) -> String {
+        match error {
+            Error::MethodError(name, _, _) => name.to_string(),
+            other => panic!("expected a method error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn forwards_the_answers_of_the_user() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let (_agent, bluez, mut events) = serve_agent(&bus, AGENT_TIMEOUT).await;
+        let device = ObjectPath::try_from(DEVICE).unwrap();
+
+        let call = tokio::spawn({
+            let bluez = bluez.clone();
+            let device = device.to_owned();
+            async move { bluez.request_passkey(&device).await }
+        });
+        let request = next_request(&mut events).await;
+        assert_eq!(request.device, DEVICE);
+        assert!(request.needs_response);
+        assert!(matches!(request.kind, AgentRequestKind::RequestPasskey));
+        respond(request.id, AgentResponse::Passkey(123456)).unwrap();
+        assert_eq!(call.await.unwrap().unwrap(), 123456);
+        // Each request is answered once
+        assert!(matches!(
+            respond(request.id, AgentResponse::Accept),
+            Err(BluetoothError::InvalidArguments)
+        ));
+
+        let call = tokio::spawn({
+            let bluez = bluez.clone();
+            let device = device.to_owned();
+            async move { bluez.request_confirmation(&device, 42).await }
+        });
+        let request = next_request(&mut events).await;
+        assert!(matches!(
+            request.kind,
+            AgentRequestKind::RequestConfirmation { passkey: 42 }
+        ));
+        respond(request.id, AgentResponse::Accept).unwrap();
+        call.await.unwrap().unwrap();
+
+        bluez.display_passkey(&device, 987654, 2).await.unwrap();
+        let request = next_request(&mut events).await;
+        assert!(!request.needs_response);
+        assert!(matches!(
+            request.kind,
+            AgentRequestKind::DisplayPasskey {
+                passkey: 987654,
+                entered: 2
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_refused_and_mismatched_answers() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let (_agent, bluez, mut events) = serve_agent(&bus, AGENT_TIMEOUT).await;
+        let device = ObjectPath::try_from(DEVICE).unwrap();
+
+        let call = tokio::spawn({
+            let bluez = bluez.clone();
+            let device = device.to_owned();
+            async move { bluez.authorize_service(&device, "0000110b").await }
+        });
+        let request = next_request(&mut events).await;
+        respond(request.id, AgentResponse::Reject).unwrap();
+        let error = call.await.unwrap().unwrap_err();
+        assert_eq!(error_name(error), "org.bluez.Error.Rejected");
+
+        // A passkey does not answer a PIN code request
+        let call = tokio::spawn({
+            let bluez = bluez.clone();
+            let device = device.to_owned();
+            async move { bluez.request_pin_code(&device).await }
+        });
+        let request = next_request(&mut events).await;
+        respond(request.id, AgentResponse::Passkey(1234)).unwrap();
+        let error = call.await.unwrap().unwrap_err();
+        assert_eq!(error_name(error), "org.bluez.Error.Rejected");
+    }
+
+    #[tokio::test]
+    async fn cancels_requests_on_timeout_and_on_request() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let (_agent, bluez, mut events) = serve_agent(&bus, Duration::from_millis(200)).await;
+        let device = ObjectPath::try_from(DEVICE).unwrap();
+
+        let error = bluez.request_passkey(&device).await.unwrap_err();
+        assert_eq!(error_name(error), "org.bluez.Error.Canceled");
+        let request = next_request(&mut events).await;
+        assert!(matches!(
+            events.recv().await,
+            Some(AgentEvent::Cancelled { id: Some(id) }) if id == request.id
+        ));
+        assert!(respond(request.id, AgentResponse::Passkey(1)).is_err());
+
+        let call = tokio::spawn({
+            let bluez = bluez.clone();
+            let device = device.to_owned();
+            async move { bluez.request_pin_code(&device).await }
+        });
+        let request = next_request(&mut events).await;
+        bluez.cancel().await.unwrap();
+        let error = call.await.unwrap().unwrap_err();
+        assert_eq!(error_name(error), "org.bluez.Error.Canceled");
+        assert!(matches!(
+            events.recv().await,
+            Some(AgentEvent::Cancelled { id: Some(id) }) if id == request.id
+        ));
+    }
+
+    #[tokio::test]
+    async fn registers_as_the_default_agent() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let bluez = bus.serve_bluez().await;
+        let calls = Calls::default();
+        let manager = MockAgentManager {
+            calls: calls.clone(),
+        };
+        bluez
+            .object_server()
+            .at("/org/bluez", manager)
+            .await
+            .unwrap();
+
+        let conn = bus.connect().await;
+        with_connection(conn.clone(), register_agent(|_| {}))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                format!("RegisterAgent {} {}", AGENT_PATH, AGENT_CAPABILITY),
+                format!("RequestDefaultAgent {}", AGENT_PATH),
+            ]
+        );
+        // The agent is exported on the BlueZ connection
+        assert!(conn
+            .object_server()
+            .interface::<_, PairingAgent>(AGENT_PATH)
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,445 @@
+use std::env;
+
+use tokio::process::Command;
+
+use super::{
+    audio_data::{AudioCodec, AudioProfile, AudioProfileKind, BluetoothAudioDevice},
+    bluez::{connection, managed_objects, property, MEDIA_TRANSPORT_INTERFACE},
+    bluez_error::{AudioError, BluetoothError},
+    device::device_proxy,
+};
+
+/// Overrides the `pactl` binary, e.g. with a stand-in audio server for testing
+pub const PACTL_ENV: &str = "WIBLUE_PACTL";
+
+/// A sound card as listed by `pactl list cards`
+struct Card {
+    name: String,
+    /// Bluetooth address from the card properties
+    address: Option<String>,
+    profiles: Vec<AudioProfile>,
+    active_profile: Option<String>,
+}
+
+/// Runs `pactl` with the C locale so the output can be parsed
+///
+/// Works with PulseAudio as well as PipeWire through `pipewire-pulse`.
+async fn pactl(args: &[&str]) -> Result<String, AudioError> {
+    let program = env::var(PACTL_ENV).unwrap_or_else(|_| "pactl".to_string());
+    let output = Command::new(program)
+        .args(args)
+        .env("LC_ALL", "C")
+        .output()
+        .await
+        .map_err(|e| {
+            eprintln!("Error running pactl: {:?}", e);
+            AudioError::CommandExecutionFailure
+        })?;
+
+    if !output.status.success() {
+        eprintln!(
+            "pactl {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(AudioError::CommandExecutionFailure);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Parses a profile line of `pactl list cards`
+///
+/// e.g. `a2dp-sink: High Fidelity Playback (A2DP Sink, codec SBC) (sinks: 1, sources: 0, priority: 18, available: yes)`
+fn parse_profile(line: &str) -> Option<AudioProfile> {
+    let (name, rest) = line.split_once(": ")?;
+    let (description, details) = match rest.rfind(" (sinks:") {
+        Some(index) => {
+            let details = &rest[index + 2..];
+            (&rest[..index], details.strip_suffix(')').unwrap_or(details))
+        }
+        None => (rest, ""),
+    };
+
+    let mut profile = AudioProfile {
+        name: name.to_string(),
+        description: description.to_string(),
+        kind: AudioProfileKind::from_profile_name(name),
+        codec: AudioCodec::from_profile_description(description),
+        sinks: 0,
+        sources: 0,
+        priority: 0,
+        // Older PulseAudio versions do not report availability
+        available: true,
+    };
+    for detail in details.split(", ") {
+        match detail.split_once(": ") {
+            Some(("sinks", value)) => profile.sinks = value.parse().unwrap_or_default(),
+            Some(("sources", value)) => profile.sources = value.parse().unwrap_or_default(),
+            Some(("priority", value)) => profile.priority = value.parse().unwrap_or_default(),
+            Some(("available", value)) => profile.available = value != "no",
+            _ => {}
+        }
+    }
+
+    Some(profile)
+}
+
+/// Parses the output of `pactl list cards` into the Bluetooth cards
+fn parse_cards(output: &str) -> Vec<Card> {
+    let mut cards = Vec::new();
+    let mut current: Option<Card> = None;
+    let mut in_profiles = false;
+
+    for line in output.lines() {
+        if line.starts_with("Card #") {
+            cards.extend(current.take());
+            current = Some(Card {
+                name: String::new(),
+                address: None,
+                profiles: Vec::new(),
+                active_profile: None,
+            });
+            in_profiles = false;
+            continue;
+        }
+        let Some(card) = current.as_mut() else {
+            continue;
+        };
+
+        let depth = line.chars().take_while(|c| *c == '\t').count();
+        let line = line.trim();
+        if depth == 1 {
+            in_profiles = line == "Profiles:";
+            if let Some(name) = line.strip_prefix("Name: ") {
+                card.name = name.to_string();
+            } else if let Some(profile) = line.strip_prefix("Active Profile: ") {
+                card.active_profile = Some(profile.to_string());
+            }
+        } else if depth == 2 && in_profiles {
+            card.profiles.extend(parse_profile(line));
+        } else if depth == 2 {
+            if let Some((key, value)) = line.split_once(" = ") {
+                if key == "api.bluez5.address" || key == "device.string" {
+                    card.address = Some(value.trim_matches('"').to_string());
+                }
+            }
+        }
+    }
+    cards.extend(current);
+
+    cards
+        .into_iter()
+        .filter(|card| card.name.starts_with("bluez_card."))
+        .collect()
+}
+
+/// Finds the sound card of a Bluetooth device
+async fn find_card(address: &str) -> Result<Card, AudioError> {
+    let card_name = format!("bluez_card.{}", address.replace(':', "_"));
+
+    parse_cards(&pactl(&["list", "cards"]).await?)
+        .into_iter()
+        .find(|card| {
+            card.name.eq_ignore_ascii_case(&card_name)
+                || card
+                    .address
+                    .as_deref()
+                    .is_some_and(|a| a.eq_ignore_ascii_case(address))
+        })
+        .ok_or(AudioError::NoAudioCard)
+}
+
+/// Reads codec and state of the active BlueZ media transport of a device
+async fn transport_codec(
+    device_path: &str,
+) -> Result<Option<(AudioCodec, String)>, BluetoothError> {
+    let conn = connection().await?;
+    let objects = managed_objects(&conn).await?;
+
+    let mut transports: Vec<(AudioCodec, String)> = objects
+        .values()
+        .filter_map(|interfaces| {
+            let props = interfaces
+                .iter()
+                .find(|(name, _)| name.as_str() == MEDIA_TRANSPORT_INTERFACE)
+                .map(|(_, props)| props)?;
+            let device = property::<zbus::zvariant::OwnedObjectPath>(props, "Device")?;
+            if device.as_str() != device_path {
+                return None;
+            }
+
+            let uuid: String = property(props, "UUID").unwrap_or_default();
+            let configuration: Vec<u8> = property(props, "Configuration").unwrap_or_default();
+            let codec = AudioCodec::from_transport(
+                &uuid,
+                property(props, "Codec").unwrap_or_default(),
+                &configuration,
+            );
+            Some((codec, property(props, "State").unwrap_or_default()))
+        })
+        .collect();
+
+    // Prefer the transport that is streaming
+    transports.sort_by_key(|(_, state)| state != "active");
+    Ok(transports.into_iter().next())
+}
+
+/// Lists the audio profiles of a connected device and the codec in use
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+///
+/// # Returns
+/// - `Ok(BluetoothAudioDevice)` with the card profiles and the negotiated codec
+/// - `Err(AudioError::NoAudioCard)` if the audio server has no card for the device
+pub async fn get_audio_device(device: &str) -> Result<BluetoothAudioDevice, AudioError> {
+    let proxy = device_proxy(device).await.map_err(AudioError::Bluetooth)?;
+    let address = proxy
+        .address()
+        .await
+        .map_err(|e| AudioError::Bluetooth(BluetoothError::from_dbus(e)))?;
+    let path = proxy.inner().path().to_string();
+
+    let card = find_card(&address).await?;
+    let transport = transport_codec(&path)
+        .await
+        .map_err(AudioError::Bluetooth)?;
+
+    let profile_codec = card
+        .profiles
+        .iter()
+        .find(|p| Some(&p.name) == card.active_profile.as_ref())
+        .and_then(|p| p.codec.clone());
+    let (codec, transport_state) = match transport {
+        Some((codec, state)) => (Some(codec), Some(state)),
+        None => (profile_codec, None),
+    };
+
+    Ok(BluetoothAudioDevice {
+        device: path,
+        address,
+        card: card.name,
+        profiles: card.profiles,
+        active_profile: card.active_profile,
+        codec,
+        transport_state,
+    })
+}
+
+/// Switches the card of a device to another profile (e.g. from A2DP to HFP)
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+/// * `profile` - profile name as listed by [`get_audio_device`]
+///
+/// # Returns
+/// - `Ok(BluetoothAudioDevice)` with the profile and codec after switching
+/// - `Err(AudioError::NoSuchProfile)` if the card does not offer the profile
+/// - `Err(AudioError::ProfileUnavailable)` if the device currently cannot use it
+pub async fn set_audio_profile(
+    device: &str,
+    profile: &str,
+) -> Result<BluetoothAudioDevice, AudioError> {
+    let audio = get_audio_device(device).await?;
+
+    match audio.profiles.iter().find(|p| p.name == profile) {
+        None => return Err(AudioError::NoSuchProfile),
+        Some(p) if !p.available => return Err(AudioError::ProfileUnavailable),
+        Some(_) => {}
+    }
+
+    pactl(&["set-card-profile", &audio.card, profile]).await?;
+    get_audio_device(device).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    use super::*;
+    use crate::bluetooth::{
+        bluez::with_connection,
+        mock_bluez::{add_device, Calls, MockDevice, PrivateBus},
+    };
+
+    /// `pactl list cards` with PipeWire, shortened
+    const CARDS: &str = "Card #42
+\tName: alsa_card.pci-0000_00_1f.3
+\tDriver: alsa
+\tProperties:
+\t\tdevice.string = \"0\"
+\tProfiles:
+\t\toff: Off (sinks: 0, sources: 0, priority: 0, available: yes)
+\tActive Profile: off
+Card #57
+\tName: bluez_card.AA_BB_CC_DD_EE_FF
+\tDriver: module-bluez5-device.c
+\tOwner Module: 28
+\tProperties:
+\t\tapi.bluez5.address = \"AA:BB:CC:DD:EE:FF\"
+\t\tdevice.description = \"WH-1000XM4\"
+\tProfiles:
+\t\toff: Off (sinks: 0, sources: 0, priority: 0, available: yes)
+\t\ta2dp-sink-ldac: High Fidelity Playback (A2DP Sink, codec LDAC) (sinks: 1, sources: 0, priority: 18, available: yes)
+\t\ta2dp-sink: High Fidelity Playback (A2DP Sink, codec SBC) (sinks: 1, sources: 0, priority: 16, available: yes)
+\t\theadset-head-unit: Headset Head Unit (HSP/HFP, codec mSBC) (sinks: 1, sources: 1, priority: 2, available: no)
+\tActive Profile: a2dp-sink-ldac
+\tPorts:
+\t\theadphone-output: Headphone (type: Headphones, priority: 0, latency offset: 0 usec, available)
+\t\t\tPart of profile(s): a2dp-sink-ldac, a2dp-sink
+";
+
+    #[test]
+    fn parses_bluetooth_cards_only() {
+        let cards = parse_cards(CARDS);
+        assert_eq!(cards.len(), 1);
+
+        let card = &cards[0];
+        assert_eq!(card.name, "bluez_card.AA_BB_CC_DD_EE_FF");
+        assert_eq!(card.address.as_deref(), Some("AA:BB:CC:DD:EE:FF"));
+        assert_eq!(card.active_profile.as_deref(), Some("a2dp-sink-ldac"));
+        // Ports are no profiles even though they look alike
+        assert_eq!(
+            card.profiles
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>(),
+            ["off", "a2dp-sink-ldac", "a2dp-sink", "headset-head-unit"]
+        );
+
+        let headset = &card.profiles[3];
+        assert_eq!(headset.kind, AudioProfileKind::HeadsetHeadUnit);
+        assert_eq!(headset.codec, Some(AudioCodec::Msbc));
+        assert_eq!((headset.sinks, headset.sources), (1, 1));
+        assert!(!headset.available);
+    }
+
+    #[test]
+    fn parses_profile_details() {
+        let profile = parse_profile(
+            "a2dp-sink-ldac: High Fidelity Playback (A2DP Sink, codec LDAC) (sinks: 1, sources: 0, priority: 18, available: yes)",
+        )
+        .unwrap();
+        assert_eq!(
+            profile.description,
+            "High Fidelity Playback (A2DP Sink, codec LDAC)"
+        );
+        assert_eq!(profile.kind, AudioProfileKind::A2dp);
+        assert_eq!(profile.codec, Some(AudioCodec::Ldac));
+        assert_eq!(
+            (profile.sinks, profile.sources, profile.priority),
+            (1, 0, 18)
+        );
+        assert!(profile.available);
+    }
+
+    #[test]
+    fn parses_profiles_without_details() {
+        // Older PulseAudio versions do not report availability
+        let old = parse_profile(
+            "a2dp_sink: High Fidelity Playback (A2DP Sink) (sinks: 1, sources: 0, priority: 10)",
+        )
+        .unwrap();
+        assert_eq!((old.sinks, old.priority), (1, 10));
+        assert!(old.available);
+
+        let plain = parse_profile("off: Off").unwrap();
+        assert_eq!((plain.description.as_str(), plain.sinks), ("Off", 0));
+
+        assert!(parse_profile("no separator").is_none());
+    }
+
+    #[test]
+    fn parses_truncated_profile_lines() {
+        let open = parse_profile("a2dp-sink: Playback (sinks: 1, sources: 0").unwrap();
+        assert_eq!((open.description.as_str(), open.sources), ("Playback", 0));
+        assert_eq!(open.sinks, 1);
+
+        // Ends in a multibyte character instead of the closing parenthesis
+        let cut = parse_profile("a2dp-sink: Wiedergabe (sinks: 1, priority: ü").unwrap();
+        assert_eq!((cut.sinks, cut.priority), (1, 0));
+
+        assert!(parse_profile("x: y (sinks:").is_some());
+    }
+
+    /// Stand-in for `pactl` serving [`CARDS`] and remembering the active profile
+    const FAKE_PACTL: &str = r#"#!/bin/sh
+dir=$(dirname "$0")
+echo "LC_ALL=$LC_ALL $*" >> "$dir/calls"
+case "$1" in
+list) sed "s/Active Profile: a2dp-sink-ldac$/Active Profile: $(cat "$dir/profile")/" "$dir/cards" ;;
+set-card-profile) echo "$3" > "$dir/profile" ;;
+*) exit 1 ;;
+esac
+"#;
+
+    #[tokio::test]
+    async fn switches_profiles_through_pactl() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let bluez = bus.serve_bluez().await;
+        let calls = Calls::default();
+        let headphones = MockDevice::new("/org/bluez/hci0", "AA:BB:CC:DD:EE:FF", &calls);
+        add_device(&bluez, headphones).await;
+        add_device(
+            &bluez,
+            MockDevice::new("/org/bluez/hci0", "00:11:22:33:44:55", &calls),
+        )
+        .await;
+
+        let dir = std::env::temp_dir().join(format!("wiblue-pactl-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("cards"), CARDS).unwrap();
+        fs::write(dir.join("profile"), "a2dp-sink-ldac").unwrap();
+        let _ = fs::remove_file(dir.join("calls"));
+        let script = dir.join("pactl");
+        fs::write(&script, FAKE_PACTL).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        // No other test runs pactl
+        env::set_var(PACTL_ENV, &script);
+
+        with_connection(bus.connect().await, async {
+            let audio = get_audio_device("AA:BB:CC:DD:EE:FF").await.unwrap();
+            assert_eq!(audio.card, "bluez_card.AA_BB_CC_DD_EE_FF");
+            assert_eq!(audio.active_profile.as_deref(), Some("a2dp-sink-ldac"));
+            // Without a media transport the codec comes from the profile
+            assert_eq!(audio.codec, Some(AudioCodec::Ldac));
+            assert_eq!(audio.transport_state, None);
+
+            let audio = set_audio_profile("AA:BB:CC:DD:EE:FF", "a2dp-sink")
+                .await
+                .unwrap();
+            assert_eq!(audio.active_profile.as_deref(), Some("a2dp-sink"));
+            assert_eq!(audio.codec, Some(AudioCodec::Sbc));
+
+            assert!(matches!(
+                set_audio_profile("AA:BB:CC:DD:EE:FF", "headset-head-unit").await,
+                Err(AudioError::ProfileUnavailable)
+            ));
+            assert!(matches!(
+                set_audio_profile("AA:BB:CC:DD:EE:FF", "a2dp-source").await,
+                Err(AudioError::NoSuchProfile)
+            ));
+            assert!(matches!(
+                get_audio_device("00:11:22:33:44:55").await,
+                Err(AudioError::NoAudioCard)
+            ));
+        })
+        .await;
+
+        let pactl_calls = fs::read_to_string(dir.join("calls")).unwrap();
+        assert!(pactl_calls
+            .lines()
+            .all(|call| call.starts_with("LC_ALL=C ")));
+        assert_eq!(
+            pactl_calls
+                .lines()
+                .filter(|call| call.contains("set-card-profile"))
+                .collect::<Vec<_>>(),
+            ["LC_ALL=C set-card-profile bluez_card.AA_BB_CC_DD_EE_FF a2dp-sink"]
+        );
+    }
+}
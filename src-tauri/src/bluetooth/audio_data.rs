@@ -0,0 +1,157 @@
+use serde::Serialize;
+
+/// Audio codec negotiated on a Bluetooth audio transport
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum AudioCodec {
+    Sbc,
+    Mpeg,
+    Aac,
+    Atrac,
+    AptX,
+    AptXHd,
+    AptXLl,
+    Ldac,
+    Lc3,
+    /// Narrowband speech codec of HFP/HSP
+    Cvsd,
+    /// Wideband speech codec of HFP
+    Msbc,
+    /// Unknown codec, with the codec id or name reported
+    Other(String),
+}
+
+impl AudioCodec {
+    /// Decodes the `Codec` and `Configuration` properties of a `org.bluez.MediaTransport1`
+    ///
+    /// Vendor codecs (`0xff`) carry a 32-bit vendor id and a 16-bit codec id
+    /// in the first six bytes of the configuration.
+    pub fn from_transport(uuid: &str, codec: u8, configuration: &[u8]) -> Self {
+        // HFP transports number their codecs differently from A2DP ones
+        if is_hfp_uuid(uuid) {
+            return match codec {
+                0x01 => AudioCodec::Cvsd,
+                0x02 => AudioCodec::Msbc,
+                other => AudioCodec::Other(format!("HFP 0x{:02x}", other)),
+            };
+        }
+
+        match codec {
+            0x00 => AudioCodec::Sbc,
+            0x01 => AudioCodec::Mpeg,
+            0x02 => AudioCodec::Aac,
+            0x04 => AudioCodec::Atrac,
+            0x06 => AudioCodec::Lc3,
+            0xff if configuration.len() >= 6 => {
+                let vendor = u32::from_le_bytes([
+                    configuration[0],
+                    configuration[1],
+                    configuration[2],
+                    configuration[3],
+                ]);
+                let id = u16::from_le_bytes([configuration[4], configuration[5]]);
+                match (vendor, id) {
+                    (0x0000_004f, 0x0001) => AudioCodec::AptX,
+                    (0x0000_00d7, 0x0024) => AudioCodec::AptXHd,
+                    (0x0000_000a, 0x0002) => AudioCodec::AptXLl,
+                    (0x0000_012d, 0x00aa) => AudioCodec::Ldac,
+                    _ => AudioCodec::Other(format!("vendor 0x{:08x} codec 0x{:04x}", vendor, id)),
+                }
+            }
+            other => AudioCodec::Other(format!("0x{:02x}", other)),
+        }
+    }
+
+    /// Reads the codec from a card profile description (`... (A2DP Sink, codec LDAC)`)
+    ///
+    /// PipeWire names the codec of every A2DP profile, which helps when BlueZ
+    /// exposes no transport (e.g. HFP handled by the audio server itself).
+    pub fn from_profile_description(description: &str) -> Option<Self> {
+        let codec = description.split("codec ").nth(1)?;
+        let codec = codec.trim_end_matches(')').trim();
+
+        Some(match codec.to_ascii_uppercase().as_str() {
+            "SBC" | "SBC-XQ" => AudioCodec::Sbc,
+            "AAC" => AudioCodec::Aac,
+            "APTX" => AudioCodec::AptX,
+            "APTX HD" | "APTX-HD" => AudioCodec::AptXHd,
+            "APTX LL" | "APTX-LL" => AudioCodec::AptXLl,
+            "LDAC" => AudioCodec::Ldac,
+            "LC3" => AudioCodec::Lc3,
+            "CVSD" => AudioCodec::Cvsd,
+            "MSBC" => AudioCodec::Msbc,
+            _ => AudioCodec::Other(codec.to_string()),
+        })
+    }
+}
+
+/// Whether a transport UUID belongs to the Handsfree or Headset profile
+fn is_hfp_uuid(uuid: &str) -> bool {
+    let uuid = uuid.to_ascii_lowercase();
+    ["0000111e", "0000111f", "00001108", "00001112"]
+        .iter()
+        .any(|short| uuid.starts_with(short))
+}
+
+/// Broad category of a card profile
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum AudioProfileKind {
+    /// No audio, the device stays connected
+    Off,
+    /// High quality playback without microphone
+    A2dp,
+    /// Headset with microphone at speech quality
+    HeadsetHeadUnit,
+    /// LE Audio unicast or broadcast
+    LeAudio,
+    Other,
+}
+
+impl AudioProfileKind {
+    /// Categorizes a PulseAudio/PipeWire card profile name
+    pub fn from_profile_name(name: &str) -> Self {
+        match name {
+            "off" => AudioProfileKind::Off,
+            n if n.starts_with("a2dp") => AudioProfileKind::A2dp,
+            n if n.starts_with("headset") || n.starts_with("handsfree") => {
+                AudioProfileKind::HeadsetHeadUnit
+            }
+            n if n.starts_with("bap") => AudioProfileKind::LeAudio,
+            _ => AudioProfileKind::Other,
+        }
+    }
+}
+
+/// A profile of the sound card the audio server creates for a device
+#[derive(Debug, Clone, Serialize)]
+pub struct AudioProfile {
+    /// Profile name used to switch to it (e.g. `a2dp-sink-ldac`)
+    pub name: String,
+    /// Human readable description (e.g. `High Fidelity Playback (A2DP Sink, codec LDAC)`)
+    pub description: String,
+    pub kind: AudioProfileKind,
+    /// Codec named in the description, if any
+    pub codec: Option<AudioCodec>,
+    /// Number of playback and capture devices the profile provides
+    pub sinks: u32,
+    pub sources: u32,
+    /// Priority the audio server uses to pick the default profile
+    pub priority: u32,
+    /// Whether the device currently supports the profile
+    pub available: bool,
+}
+
+/// Audio side of a connected Bluetooth device
+#[derive(Debug, Clone, Serialize)]
+pub struct BluetoothAudioDevice {
+    /// Object path of the Bluetooth device
+    pub device: String,
+    pub address: String,
+    /// Card name in the audio server (e.g. `bluez_card.00_11_22_33_44_55`)
+    pub card: String,
+    pub profiles: Vec<AudioProfile>,
+    pub active_profile: Option<String>,
+    /// Codec of the active transport, falls back to the active profile description
+    pub codec: Option<AudioCodec>,
+    /// State of the BlueZ transport (`idle`, `pending` or `active`)
+    pub transport_state: Option<String>,
+}
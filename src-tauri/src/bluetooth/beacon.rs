@@ -0,0 +1,414 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use chrono::Utc;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time,
+};
+use zbus::{fdo::ObjectManager, interface, proxy, zvariant::ObjectPath, Connection};
+
+use super::{
+    beacon_data::{Advertiser, BeaconEvent, BeaconFrame, BeaconScanConfig, RssiSample},
+    bluez::{adapter_path, connection, BLUEZ_PATH},
+    bluez_error::BluetoothError,
+    device_data::{BluetoothDevice, DeviceEvent, DiscoveryFilter, DiscoveryTransport},
+    discovery::{start_discovery, stop_discovery, watch_devices},
+};
+
+/// Object path the advertisement monitors are exported below
+pub const MONITOR_ROOT_PATH: &str = "/com/wiblue/adv_monitor";
+const MONITOR_PATH: &str = "/com/wiblue/adv_monitor/monitor0";
+/// Number of readings averaged for the distance estimate
+const AVERAGE_SAMPLES: usize = 5;
+/// Path loss exponent of the distance estimate, 2 is free space
+const PATH_LOSS_EXPONENT: f64 = 2.0;
+
+/// AD types matched by the passive monitor
+const AD_TYPE_SERVICE_DATA: u8 = 0x16;
+const AD_TYPE_MANUFACTURER_DATA: u8 = 0xff;
+
+/// Running scans keyed by adapter path, dropping the sender stops the scan
+static SCANS: LazyLock<Mutex<HashMap<String, oneshot::Sender<()>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Advertisers seen by any scan, keyed by device path
+static ADVERTISERS: LazyLock<Mutex<HashMap<String, Advertiser>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[proxy(
+    interface = "org.bluez.AdvertisementMonitorManager1",
+    default_service = "org.bluez"
+)]
+trait AdvertisementMonitorManager1 {
+    fn register_monitor(&self, application: &ObjectPath<'_>) -> zbus::Result<()>;
+
+    fn unregister_monitor(&self, application: &ObjectPath<'_>) -> zbus::Result<()>;
+}
+
+/// `org.bluez.AdvertisementMonitor1` matching the beacon formats we decode
+///
+/// BlueZ scans passively for the patterns and updates the matching devices.
+struct AdvertisementMonitor {
+    /// Notifies the scan about devices going out of range
+    lost: mpsc::UnboundedSender<String>,
+}
+
+#[interface(name = "org.bluez.AdvertisementMonitor1")]
+impl AdvertisementMonitor {
+    fn release(&self) {
+        println!("Advertisement monitor released by BlueZ");
+    }
+
+    fn activate(&self) {}
+
+    fn device_found(&self, _device: ObjectPath<'_>) {}
+
+    fn device_lost(&self, device: ObjectPath<'_>) {
+        let _ = self.lost.send(device.to_string());
+    }
+
+    #[zbus(property, name = "Type")]
+    fn monitor_type(&self) -> String {
+        "or_patterns".to_string()
+    }
+
+    /// `(start position, AD type, content)` of each pattern, any match reports the device
+    #[zbus(property)]
+    fn patterns(&self) -> Vec<(u8, u8, Vec<u8>)> {
+        let mut patterns = vec![
+            // Eddystone and Fast Pair service data, UUIDs are little endian
+            (0, AD_TYPE_SERVICE_DATA, vec![0xaa, 0xfe]),
+            (0, AD_TYPE_SERVICE_DATA, vec![0x2c, 0xfe]),
+        ];
+        // Apple, Ruuvi and the other companies named in manufacturer data
+        for company_id in [0x004c_u16, 0x0499, 0x0006, 0x0075, 0x00e0, 0x0059] {
+            patterns.push((
+                0,
+                AD_TYPE_MANUFACTURER_DATA,
+                company_id.to_le_bytes().to_vec(),
+            ));
+        }
+        patterns
+    }
+}
+
+/// How the scan gets BlueZ to report advertisements
+enum ScanMode {
+    Passive { conn: Connection, adapter: String },
+    Discovery { adapter: String },
+}
+
+impl ScanMode {
+    /// Registers a passive monitor, or starts an LE discovery if that is unsupported
+    async fn start(
+        adapter: &str,
+        passive: bool,
+        lost: mpsc::UnboundedSender<String>,
+    ) -> Result<Self, BluetoothError> {
+        if passive {
+            match register_monitor(adapter, lost).await {
+                Ok(conn) => {
+                    return Ok(ScanMode::Passive {
+                        conn,
+                        adapter: adapter.to_string(),
+                    })
+                }
+                Err(e) => eprintln!(
+                    "Passive scanning unavailable, falling back to discovery: {:?}",
+                    e
+                ),
+            }
+        }
+
+        let filter = DiscoveryFilter {
+            transport: Some(DiscoveryTransport::Le),
+            ..Default::default()
+        };
+        start_discovery(adapter, &filter).await?;
+        Ok(ScanMode::Discovery {
+            adapter: adapter.to_string(),
+        })
+    }
+
+    async fn stop(self) {
+        match self {
+            ScanMode::Passive { conn, adapter } => {
+                if let Ok(manager) = monitor_manager(&conn, &adapter).await {
+                    let root = ObjectPath::from_static_str_unchecked(MONITOR_ROOT_PATH);
+                    let _ = manager.unregister_monitor(&root).await;
+                }
+                let server = conn.object_server();
+                let _ = server.remove::<AdvertisementMonitor, _>(MONITOR_PATH).await;
+                let _ = server.remove::<ObjectManager, _>(MONITOR_ROOT_PATH).await;
+            }
+            ScanMode::Discovery { adapter } => {
+                let _ = stop_discovery(&adapter).await;
+            }
+        }
+    }
+}
+
+async fn monitor_manager(
+    conn: &Connection,
+    adapter: &str,
+) -> Result<AdvertisementMonitorManager1Proxy<'static>, BluetoothError> {
+    AdvertisementMonitorManager1Proxy::builder(conn)
+        .path(adapter_path(adapter))
+        .map_err(|_| BluetoothError::NoSuchAdapter)?
+        .build()
+        .await
+        .map_err(BluetoothError::from_dbus)
+}
+
+/// Exports the advertisement monitor and registers it with an adapter
+async fn register_monitor(
+    adapter: &str,
+    lost: mpsc::UnboundedSender<String>,
+) -> Result<Connection, BluetoothError> {
+    let conn = connection().await?;
+    let server = conn.object_server();
+    let added = server
+        .at(MONITOR_ROOT_PATH, ObjectManager)
+        .await
+        .map_err(BluetoothError::from_dbus)?;
+    // The monitor is exported once, another adapter scanning passively owns it
+    if !added {
+        return Err(BluetoothError::InProgress);
+    }
+    server
+        .at(MONITOR_PATH, AdvertisementMonitor { lost })
+        .await
+        .map_err(BluetoothError::from_dbus)?;
+
+    let result = async {
+        let root = ObjectPath::from_static_str_unchecked(MONITOR_ROOT_PATH);
+        monitor_manager(&conn, adapter)
+            .await?
+            .register_monitor(&root)
+            .await
+            .map_err(BluetoothError::from_dbus)
+    }
+    .await;
+
+    match result {
+        Ok(()) => Ok(conn),
+        Err(e) => {
+            let _ = server.remove::<AdvertisementMonitor, _>(MONITOR_PATH).await;
+            let _ = server.remove::<ObjectManager, _>(MONITOR_ROOT_PATH).await;
+            Err(e)
+        }
+    }
+}
+
+/// Estimates the distance from the calibrated power at one meter
+fn estimate_distance(measured_power: i8, rssi: f64) -> f64 {
+    10f64.powf((measured_power as f64 - rssi) / (10.0 * PATH_LOSS_EXPONENT))
+}
+
+/// Records an advertisement of a device and returns the updated advertiser
+///
+/// Devices without decodable advertising data are ignored.
+fn record(device: &BluetoothDevice, history_len: usize) -> Option<Advertiser> {
+    let frames = BeaconFrame::decode(&device.manufacturer_data, &device.service_data);
+    if frames.is_empty() {
+        return None;
+    }
+
+    let now = Utc::now();
+    let mut advertisers = ADVERTISERS.lock().unwrap();
+    let advertiser = advertisers
+        .entry(device.path.clone())
+        .or_insert_with(|| Advertiser {
+            path: device.path.clone(),
+            address: device.address.clone(),
+            alias: device.alias.clone(),
+            frames: Vec::new(),
+            rssi: None,
+            average_rssi: None,
+            distance: None,
+            rssi_history: VecDeque::new(),
+            first_seen: now,
+            last_seen: now,
+        });
+
+    advertiser.alias = device.alias.clone();
+    advertiser.frames = frames;
+    advertiser.last_seen = now;
+    if let Some(rssi) = device.rssi {
+        advertiser.rssi = Some(rssi);
+        advertiser.rssi_history.push_back(RssiSample {
+            timestamp: now,
+            rssi,
+        });
+        while advertiser.rssi_history.len() > history_len.max(1) {
+            advertiser.rssi_history.pop_front();
+        }
+
+        let recent: Vec<f64> = advertiser
+            .rssi_history
+            .iter()
+            .rev()
+            .take(AVERAGE_SAMPLES)
+            .map(|sample| sample.rssi as f64)
+            .collect();
+        let average = recent.iter().sum::<f64>() / recent.len() as f64;
+        advertiser.average_rssi = Some(average);
+        advertiser.distance = advertiser
+            .frames
+            .iter()
+            .find_map(|frame| frame.measured_power())
+            .map(|power| estimate_distance(power, average));
+    }
+
+    Some(advertiser.clone())
+}
+
+/// Removes an advertiser, returning the event to report if it was known
+fn forget(path: &str) -> Option<BeaconEvent> {
+    ADVERTISERS
+        .lock()
+        .unwrap()
+        .remove(path)
+        .map(|advertiser| BeaconEvent::Lost {
+            path: advertiser.path,
+            address: advertiser.address,
+        })
+}
+
+/// Removes advertisers not seen within the timeout
+fn expire(timeout: Duration) -> Vec<BeaconEvent> {
+    let Ok(timeout) = chrono::Duration::from_std(timeout) else {
+        return Vec::new();
+    };
+    let deadline = Utc::now() - timeout;
+
+    let mut advertisers = ADVERTISERS.lock().unwrap();
+    let expired: Vec<String> = advertisers
+        .values()
+        .filter(|advertiser| advertiser.last_seen < deadline)
+        .map(|advertiser| advertiser.path.clone())
+        .collect();
+
+    expired
+        .iter()
+        .filter_map(|path| advertisers.remove(path))
+        .map(|advertiser| BeaconEvent::Lost {
+            path: advertiser.path,
+            address: advertiser.address,
+        })
+        .collect()
+}
+
+/// Scans for LE advertisements and decodes the beacons among them
+///
+/// Uses a passive advertisement monitor where BlueZ supports one, so nearby
+/// devices are not sent scan requests, and an LE discovery otherwise. Runs
+/// until [`stop_beacon_scan`] is called or the bus connection closes.
+///
+/// # Arguments
+/// * `adapter` - adapter name (`hci0`) or object path
+/// * `config` - history length, timeout and scan mode
+/// * `callback` - called for every decoded advertisement and lost advertiser
+///
+/// # Returns
+/// - `Ok(())` once the scan was stopped
+/// - `Err(BluetoothError::InProgress)` if the adapter is already scanning for beacons
+/// - `Err(BluetoothError)` if the scan could not be started
+pub async fn scan_beacons(
+    adapter: &str,
+    config: BeaconScanConfig,
+    callback: impl Fn(BeaconEvent),
+) -> Result<(), BluetoothError> {
+    let adapter = adapter_path(adapter);
+    if !adapter.starts_with(BLUEZ_PATH) {
+        return Err(BluetoothError::NoSuchAdapter);
+    }
+
+    let (stop, mut stopped) = oneshot::channel();
+    match SCANS.lock().unwrap().entry(adapter.clone()) {
+        Entry::Occupied(_) => return Err(BluetoothError::InProgress),
+        Entry::Vacant(entry) => {
+            entry.insert(stop);
+        }
+    }
+
+    let (lost_sender, mut lost) = mpsc::unbounded_channel();
+    let mode = match ScanMode::start(&adapter, config.passive, lost_sender).await {
+        Ok(mode) => mode,
+        Err(e) => {
+            SCANS.lock().unwrap().remove(&adapter);
+            return Err(e);
+        }
+    };
+
+    let callback = &callback;
+    let watch = watch_devices(|event| match event {
+        DeviceEvent::Found(device) | DeviceEvent::Updated(device) => {
+            if device.adapter != adapter {
+                return;
+            }
+            if let Some(advertiser) = record(&device, config.history_len) {
+                callback(BeaconEvent::Seen(advertiser));
+            }
+        }
+        DeviceEvent::Lost { path, .. } => {
+            if let Some(event) = forget(&path) {
+                callback(event);
+            }
+        }
+    });
+    tokio::pin!(watch);
+
+    let timeout = Duration::from_secs(config.timeout_secs.max(1));
+    let mut expiry = time::interval(timeout / 2);
+    let result = loop {
+        tokio::select! {
+            _ = &mut stopped => break Ok(()),
+            result = &mut watch => break result,
+            Some(path) = lost.recv() => {
+                if let Some(event) = forget(&path) {
+                    callback(event);
+                }
+            }
+            _ = expiry.tick() => {
+                for event in expire(timeout) {
+                    callback(event);
+                }
+            }
+        }
+    };
+
+    SCANS.lock().unwrap().remove(&adapter);
+    mode.stop().await;
+    result
+}
+
+/// Stops a scan started with [`scan_beacons`]
+///
+/// # Returns
+/// - `Ok(())` if the adapter was scanning
+/// - `Err(BluetoothError::DoesNotExist)` if there was no scan
+pub fn stop_beacon_scan(adapter: &str) -> Result<(), BluetoothError> {
+    SCANS
+        .lock()
+        .unwrap()
+        .remove(&adapter_path(adapter))
+        .map(|_| ())
+        .ok_or(BluetoothError::DoesNotExist)
+}
+
+/// Returns the advertisers currently in range, strongest signal first
+pub fn get_advertisers() -> Vec<Advertiser> {
+    let mut advertisers: Vec<Advertiser> = ADVERTISERS.lock().unwrap().values().cloned().collect();
+
+    advertisers.sort_by(|a, b| {
+        b.average_rssi
+            .unwrap_or(f64::MIN)
+            .total_cmp(&a.average_rssi.unwrap_or(f64::MIN))
+    });
+    advertisers
+}
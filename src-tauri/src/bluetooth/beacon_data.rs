@@ -0,0 +1,601 @@
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::uuid_names::{company_name, short_uuid};
+
+const APPLE_COMPANY_ID: u16 = 0x004c;
+const RUUVI_COMPANY_ID: u16 = 0x0499;
+const EDDYSTONE_SERVICE: u16 = 0xfeaa;
+const FAST_PAIR_SERVICE: u16 = 0xfe2c;
+
+/// Type of an iBeacon frame inside Apple manufacturer data
+const IBEACON_TYPE: u8 = 0x02;
+const IBEACON_LENGTH: u8 = 0x15;
+
+/// Expansion codes of Eddystone-URL frames
+const EDDYSTONE_URL_SCHEMES: &[&str] = &["http://www.", "https://www.", "http://", "https://"];
+const EDDYSTONE_URL_EXPANSIONS: &[&str] = &[
+    ".com/", ".org/", ".edu/", ".net/", ".info/", ".biz/", ".gov/", ".com", ".org", ".edu", ".net",
+    ".info", ".biz", ".gov",
+];
+
+/// A message of the Apple Continuity protocol
+#[derive(Debug, Clone, Serialize)]
+pub struct ContinuityMessage {
+    pub type_id: u8,
+    /// e.g. `Nearby Info` or `Proximity Pairing`, `Unknown` for undocumented types
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl ContinuityMessage {
+    fn name(type_id: u8) -> &'static str {
+        match type_id {
+            0x03 => "AirPrint",
+            0x05 => "AirDrop",
+            0x06 => "HomeKit",
+            0x07 => "Proximity Pairing",
+            0x08 => "Hey Siri",
+            0x09 => "AirPlay Target",
+            0x0a => "AirPlay Source",
+            0x0b => "Magic Switch",
+            0x0c => "Handoff",
+            0x0d => "Tethering Target",
+            0x0e => "Tethering Source",
+            0x0f => "Nearby Action",
+            0x10 => "Nearby Info",
+            0x12 => "Find My",
+            _ => "Unknown",
+        }
+    }
+}
+
+/// A decoded frame of an advertisement
+#[derive(Debug, Clone, Serialize)]
+pub enum BeaconFrame {
+    IBeacon {
+        uuid: String,
+        major: u16,
+        minor: u16,
+        /// Calibrated RSSI at one meter in dBm
+        measured_power: i8,
+    },
+    EddystoneUid {
+        /// 10-byte namespace as hex
+        namespace: String,
+        /// 6-byte instance as hex
+        instance: String,
+        /// Calibrated transmit power at zero meters in dBm
+        tx_power: i8,
+    },
+    EddystoneUrl {
+        url: String,
+        tx_power: i8,
+    },
+    /// Telemetry of an Eddystone beacon
+    EddystoneTlm {
+        battery_mv: Option<u16>,
+        /// Beacon temperature in °C
+        temperature: Option<f32>,
+        /// Advertisements sent since power-up
+        advertisement_count: u32,
+        /// Time since power-up in seconds
+        uptime_secs: f64,
+    },
+    /// Google Fast Pair, only discoverable devices reveal their model
+    FastPair {
+        /// 24-bit model id as hex (e.g. `F52494`)
+        model_id: Option<String>,
+        discoverable: bool,
+    },
+    AppleContinuity {
+        messages: Vec<ContinuityMessage>,
+    },
+    /// Ruuvi sensor tag, data format 5 (RAWv2)
+    Ruuvi {
+        /// Temperature in °C
+        temperature: Option<f32>,
+        /// Relative humidity in percent
+        humidity: Option<f32>,
+        /// Air pressure in Pa
+        pressure: Option<u32>,
+        battery_mv: Option<u16>,
+        tx_power: Option<i8>,
+    },
+    /// Manufacturer data that has no dedicated decoder
+    Manufacturer {
+        company_id: u16,
+        company: Option<String>,
+        data: Vec<u8>,
+    },
+}
+
+impl BeaconFrame {
+    /// Decodes the advertising data of a device
+    ///
+    /// # Arguments
+    /// * `manufacturer_data` - manufacturer data keyed by company identifier
+    /// * `service_data` - service data keyed by 128-bit service UUID
+    ///
+    /// # Returns
+    /// Every frame found, manufacturer data without a decoder as
+    /// [`BeaconFrame::Manufacturer`]
+    pub fn decode(
+        manufacturer_data: &HashMap<u16, Vec<u8>>,
+        service_data: &HashMap<String, Vec<u8>>,
+    ) -> Vec<BeaconFrame> {
+        let mut frames = Vec::new();
+
+        let mut companies: Vec<_> = manufacturer_data.iter().collect();
+        companies.sort_by_key(|(id, _)| **id);
+        for (&company_id, data) in companies {
+            let decoded = match company_id {
+                APPLE_COMPANY_ID => decode_apple(data),
+                RUUVI_COMPANY_ID => decode_ruuvi(data).into_iter().collect(),
+                _ => Vec::new(),
+            };
+            if decoded.is_empty() {
+                frames.push(BeaconFrame::Manufacturer {
+                    company_id,
+                    company: company_name(company_id).map(str::to_string),
+                    data: data.clone(),
+                });
+            } else {
+                frames.extend(decoded);
+            }
+        }
+
+        for (uuid, data) in service_data {
+            let frame = match short_uuid(uuid) {
+                Some(EDDYSTONE_SERVICE) => decode_eddystone(data),
+                Some(FAST_PAIR_SERVICE) => Some(decode_fast_pair(data)),
+                _ => None,
+            };
+            frames.extend(frame);
+        }
+
+        frames
+    }
+
+    /// Calibrated RSSI at one meter, used to estimate the distance
+    pub fn measured_power(&self) -> Option<i8> {
+        match self {
+            BeaconFrame::IBeacon { measured_power, .. } => Some(*measured_power),
+            // Eddystone calibrates at zero meters, signals lose about 41 dB over the first meter
+            BeaconFrame::EddystoneUid { tx_power, .. }
+            | BeaconFrame::EddystoneUrl { tx_power, .. } => Some(tx_power.saturating_sub(41)),
+            _ => None,
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Splits Apple manufacturer data into iBeacon and Continuity messages
+fn decode_apple(data: &[u8]) -> Vec<BeaconFrame> {
+    let mut frames = Vec::new();
+    let mut messages = Vec::new();
+
+    let mut rest = data;
+    while let [type_id, length, tail @ ..] = rest {
+        let length = *length as usize;
+        if tail.len() < length {
+            break;
+        }
+        let (body, next) = tail.split_at(length);
+        rest = next;
+
+        if *type_id == IBEACON_TYPE && length == IBEACON_LENGTH as usize {
+            let uuid = hex(&body[..16]).to_lowercase();
+            frames.push(BeaconFrame::IBeacon {
+                uuid: format!(
+                    "{}-{}-{}-{}-{}",
+                    &uuid[..8],
+                    &uuid[8..12],
+                    &uuid[12..16],
+                    &uuid[16..20],
+                    &uuid[20..]
+                ),
+                major: u16::from_be_bytes([body[16], body[17]]),
+                minor: u16::from_be_bytes([body[18], body[19]]),
+                measured_power: body[20] as i8,
+            });
+        } else {
+            messages.push(ContinuityMessage {
+                type_id: *type_id,
+                name: ContinuityMessage::name(*type_id).to_string(),
+                data: body.to_vec(),
+            });
+        }
+    }
+
+    if !messages.is_empty() {
+        frames.push(BeaconFrame::AppleContinuity { messages });
+    }
+    frames
+}
+
+/// Decodes the UID, URL and unencrypted TLM frames of Eddystone
+fn decode_eddystone(data: &[u8]) -> Option<BeaconFrame> {
+    match data {
+        [0x00, tx_power, id @ ..] if id.len() >= 16 => Some(BeaconFrame::EddystoneUid {
+            namespace: hex(&id[..10]),
+            instance: hex(&id[10..16]),
+            tx_power: *tx_power as i8,
+        }),
+        [0x10, tx_power, scheme, encoded @ ..] => {
+            let mut url = EDDYSTONE_URL_SCHEMES.get(*scheme as usize)?.to_string();
+            for byte in encoded {
+                match EDDYSTONE_URL_EXPANSIONS.get(*byte as usize) {
+                    Some(expansion) => url.push_str(expansion),
+                    None if byte.is_ascii_graphic() => url.push(*byte as char),
+                    None => return None,
+                }
+            }
+            Some(BeaconFrame::EddystoneUrl {
+                url,
+                tx_power: *tx_power as i8,
+            })
+        }
+        [0x20, 0x00, tlm @ ..] if tlm.len() >= 12 => {
+            let battery = u16::from_be_bytes([tlm[0], tlm[1]]);
+            let temperature = i16::from_be_bytes([tlm[2], tlm[3]]);
+            Some(BeaconFrame::EddystoneTlm {
+                battery_mv: Some(battery).filter(|mv| *mv != 0),
+                temperature: (temperature != i16::MIN).then(|| temperature as f32 / 256.0),
+                advertisement_count: u32::from_be_bytes([tlm[4], tlm[5], tlm[6], tlm[7]]),
+                uptime_secs: u32::from_be_bytes([tlm[8], tlm[9], tlm[10], tlm[11]]) as f64 / 10.0,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Decodes Fast Pair service data, a bare model id while the device is discoverable
+fn decode_fast_pair(data: &[u8]) -> BeaconFrame {
+    match data {
+        [_, _, _] => BeaconFrame::FastPair {
+            model_id: Some(hex(data)),
+            discoverable: true,
+        },
+        _ => BeaconFrame::FastPair {
+            model_id: None,
+            discoverable: false,
+        },
+    }
+}
+
+/// Decodes Ruuvi data format 5, every field has a value marking it unavailable
+fn decode_ruuvi(data: &[u8]) -> Option<BeaconFrame> {
+    if data.len() < 15 || data[0] != 5 {
+        return None;
+    }
+
+    let temperature = i16::from_be_bytes([data[1], data[2]]);
+    let humidity = u16::from_be_bytes([data[3], data[4]]);
+    let pressure = u16::from_be_bytes([data[5], data[6]]);
+    let power = u16::from_be_bytes([data[13], data[14]]);
+    let battery = power >> 5;
+    let tx_power = power & 0x1f;
+
+    Some(BeaconFrame::Ruuvi {
+        temperature: (temperature != i16::MIN).then_some(temperature as f32 * 0.005),
+        humidity: (humidity != u16::MAX).then_some(humidity as f32 * 0.0025),
+        pressure: (pressure != u16::MAX).then(|| pressure as u32 + 50_000),
+        battery_mv: (battery != 0x7ff).then_some(battery + 1600),
+        tx_power: (tx_power != 0x1f).then(|| tx_power as i8 * 2 - 40),
+    })
+}
+
+/// A single RSSI reading
+#[derive(Debug, Clone, Serialize)]
+pub struct RssiSample {
+    pub timestamp: DateTime<Utc>,
+    pub rssi: i16,
+}
+
+/// A device seen advertising, with its decoded frames and signal history
+#[derive(Debug, Clone, Serialize)]
+pub struct Advertiser {
+    /// D-Bus object path of the device
+    pub path: String,
+    pub address: String,
+    pub alias: String,
+    pub frames: Vec<BeaconFrame>,
+    /// Latest RSSI in dBm
+    pub rssi: Option<i16>,
+    /// Mean of the last few readings, smooths out fading
+    pub average_rssi: Option<f64>,
+    /// Rough distance in meters, for beacons announcing their calibrated power
+    pub distance: Option<f64>,
+    /// Readings, oldest first
+    pub rssi_history: VecDeque<RssiSample>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Change to the set of advertisers in range
+#[derive(Debug, Clone, Serialize)]
+pub enum BeaconEvent {
+    /// An advertiser was seen, with its updated frames and history
+    Seen(Advertiser),
+    /// An advertiser was not seen for a while or went out of range
+    Lost { path: String, address: String },
+}
+
+/// Configuration of the advertisement scanner, missing fields keep their default
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BeaconScanConfig {
+    /// Number of RSSI readings kept per advertiser
+    pub history_len: usize,
+    /// Seconds without an advertisement after which an advertiser is lost
+    pub timeout_secs: u64,
+    /// Prefer passive scanning through an advertisement monitor,
+    /// falls back to an active LE discovery if BlueZ does not support it
+    pub passive: bool,
+}
+
+impl Default for BeaconScanConfig {
+    fn default() -> Self {
+        BeaconScanConfig {
+            history_len: 60,
+            timeout_secs: 30,
+            passive: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(value: Option<f32>, expected: f32) -> bool {
+        value.is_some_and(|v| (v - expected).abs() < 0.001)
+    }
+
+    /// iBeacon frame of an Estimote beacon, major 1, minor 2, -59 dBm at one meter
+    const IBEACON: [u8; 23] = [
+        0x02, 0x15, 0xf7, 0x82, 0x6d, 0xa6, 0x4f, 0xa2, 0x4e, 0x98, 0x80, 0x24, 0xbc, 0x5b, 0x71,
+        0xe0, 0x89, 0x3e, 0x00, 0x01, 0x00, 0x02, 0xc5,
+    ];
+
+    #[test]
+    fn decodes_ibeacon() {
+        let frames = decode_apple(&IBEACON);
+        let [BeaconFrame::IBeacon {
+            uuid,
+            major,
+            minor,
+            measured_power,
+        }] = frames.as_slice()
+        else {
+            panic!("unexpected frames {:?}", frames);
+        };
+        assert_eq!(uuid, "f7826da6-4fa2-4e98-8024-bc5b71e0893e");
+        assert_eq!((*major, *minor, *measured_power), (1, 2, -59));
+        assert_eq!(frames[0].measured_power(), Some(-59));
+    }
+
+    #[test]
+    fn splits_apple_continuity_messages() {
+        // Nearby Info followed by Handoff
+        let data = [
+            0x10, 0x05, 0x01, 0x18, 0x1c, 0x6f, 0x3e, 0x0c, 0x02, 0xaa, 0xbb,
+        ];
+        let frames = decode_apple(&data);
+        let [BeaconFrame::AppleContinuity { messages }] = frames.as_slice() else {
+            panic!("unexpected frames {:?}", frames);
+        };
+        assert_eq!(
+            messages
+                .iter()
+                .map(|m| (m.type_id, m.name.as_str(), m.data.len()))
+                .collect::<Vec<_>>(),
+            [(0x10, "Nearby Info", 5), (0x0c, "Handoff", 2)]
+        );
+    }
+
+    #[test]
+    fn stops_at_truncated_apple_messages() {
+        assert!(decode_apple(&IBEACON[..22]).is_empty());
+        assert!(decode_apple(&[0x10]).is_empty());
+
+        // Complete messages before the cut are kept
+        let frames = decode_apple(&[0x10, 0x01, 0x01, 0x0c, 0x05, 0xaa]);
+        let [BeaconFrame::AppleContinuity { messages }] = frames.as_slice() else {
+            panic!("unexpected frames {:?}", frames);
+        };
+        assert_eq!(messages.len(), 1);
+    }
+
+    #[test]
+    fn decodes_eddystone_uid() {
+        let mut data = vec![0x00, 0xee];
+        data.extend(0x01..=0x10);
+        data.extend([0x00, 0x00]);
+
+        let frame = decode_eddystone(&data).unwrap();
+        let BeaconFrame::EddystoneUid {
+            namespace,
+            instance,
+            tx_power,
+        } = &frame
+        else {
+            panic!("unexpected frame {:?}", frame);
+        };
+        assert_eq!(namespace, "0102030405060708090A");
+        assert_eq!(instance, "0B0C0D0E0F10");
+        assert_eq!(*tx_power, -18);
+        assert_eq!(frame.measured_power(), Some(-59));
+    }
+
+    #[test]
+    fn decodes_eddystone_url() {
+        // https://www. example .com/ wiblue
+        let mut data = vec![0x10, 0xeb, 0x01];
+        data.extend(b"example");
+        data.push(0x00);
+        data.extend(b"wiblue");
+
+        let Some(BeaconFrame::EddystoneUrl { url, tx_power }) = decode_eddystone(&data) else {
+            panic!("no URL frame");
+        };
+        assert_eq!(url, "https://www.example.com/wiblue");
+        assert_eq!(tx_power, -21);
+
+        // Unknown scheme and control characters
+        assert!(decode_eddystone(&[0x10, 0xeb, 0x04]).is_none());
+        assert!(decode_eddystone(&[0x10, 0xeb, 0x02, b'a', 0x20]).is_none());
+    }
+
+    #[test]
+    fn decodes_eddystone_tlm() {
+        let data = [
+            0x20, 0x00, 0x0b, 0xb8, 0x18, 0x80, 0x00, 0x00, 0x00, 0x64, 0x00, 0x00, 0x03, 0xe8,
+        ];
+        let Some(BeaconFrame::EddystoneTlm {
+            battery_mv,
+            temperature,
+            advertisement_count,
+            uptime_secs,
+        }) = decode_eddystone(&data)
+        else {
+            panic!("no TLM frame");
+        };
+        assert_eq!(battery_mv, Some(3000));
+        assert!(close(temperature, 24.5));
+        assert_eq!((advertisement_count, uptime_secs), (100, 100.0));
+
+        // Beacons without battery or temperature sensor send 0 and 0x8000
+        let Some(BeaconFrame::EddystoneTlm {
+            battery_mv,
+            temperature,
+            ..
+        }) = decode_eddystone(&[0x20, 0x00, 0, 0, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0])
+        else {
+            panic!("no TLM frame");
+        };
+        assert_eq!((battery_mv, temperature), (None, None));
+    }
+
+    #[test]
+    fn rejects_truncated_eddystone_frames() {
+        assert!(decode_eddystone(&[]).is_none());
+        assert!(decode_eddystone(&[0x00, 0xee, 0x01, 0x02]).is_none());
+        assert!(decode_eddystone(&[0x10, 0xeb]).is_none());
+        assert!(decode_eddystone(&[0x20, 0x00, 0x0b, 0xb8]).is_none());
+        // Encrypted TLM
+        assert!(decode_eddystone(&[0x20, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).is_none());
+    }
+
+    #[test]
+    fn decodes_ruuvi_format_5() {
+        // Example from the Ruuvi data format 5 specification
+        let data = [
+            0x05, 0x12, 0xfc, 0x53, 0x94, 0xc3, 0x7c, 0x00, 0x04, 0xff, 0xfc, 0x04, 0x0c, 0xac,
+            0x36, 0x42, 0x00, 0xcd, 0xcb, 0xb8, 0x33, 0x4c, 0x88, 0x4f,
+        ];
+        let Some(BeaconFrame::Ruuvi {
+            temperature,
+            humidity,
+            pressure,
+            battery_mv,
+            tx_power,
+        }) = decode_ruuvi(&data)
+        else {
+            panic!("no Ruuvi frame");
+        };
+        assert!(close(temperature, 24.3));
+        assert!(close(humidity, 53.49));
+        assert_eq!(pressure, Some(100_044));
+        assert_eq!((battery_mv, tx_power), (Some(2977), Some(4)));
+    }
+
+    #[test]
+    fn reads_unavailable_ruuvi_values_as_none() {
+        let mut data = [0xff; 15];
+        data[0] = 0x05;
+        data[1..3].copy_from_slice(&[0x80, 0x00]);
+        let Some(BeaconFrame::Ruuvi {
+            temperature,
+            humidity,
+            pressure,
+            battery_mv,
+            tx_power,
+        }) = decode_ruuvi(&data)
+        else {
+            panic!("no Ruuvi frame");
+        };
+        assert_eq!((temperature, humidity, pressure), (None, None, None));
+        assert_eq!((battery_mv, tx_power), (None, None));
+
+        // Truncated payload and other data formats
+        assert!(decode_ruuvi(&data[..14]).is_none());
+        assert!(decode_ruuvi(&[]).is_none());
+        data[0] = 0x03;
+        assert!(decode_ruuvi(&data).is_none());
+    }
+
+    #[test]
+    fn decodes_fast_pair() {
+        let BeaconFrame::FastPair {
+            model_id,
+            discoverable,
+        } = decode_fast_pair(&[0xf5, 0x24, 0x94])
+        else {
+            unreachable!()
+        };
+        assert_eq!((model_id.as_deref(), discoverable), (Some("F52494"), true));
+
+        // Account key filters of paired devices and truncated model ids
+        for data in [&[0x00, 0x41, 0x12, 0x34, 0x56][..], &[0xf5, 0x24], &[]] {
+            let BeaconFrame::FastPair {
+                model_id,
+                discoverable,
+            } = decode_fast_pair(data)
+            else {
+                unreachable!()
+            };
+            assert_eq!((model_id, discoverable), (None, false));
+        }
+    }
+
+    #[test]
+    fn keeps_unknown_manufacturer_data() {
+        let manufacturer_data =
+            HashMap::from([(0x0006, vec![0x01, 0x09]), (0x004c, IBEACON.to_vec())]);
+        let service_data = HashMap::from([(
+            "0000fe2c-0000-1000-8000-00805f9b34fb".to_string(),
+            vec![0xf5, 0x24, 0x94],
+        )]);
+
+        let frames = BeaconFrame::decode(&manufacturer_data, &service_data);
+        assert!(matches!(
+            frames.as_slice(),
+            [
+                BeaconFrame::Manufacturer {
+                    company_id: 0x0006,
+                    ..
+                },
+                BeaconFrame::IBeacon { .. },
+                BeaconFrame::FastPair {
+                    discoverable: true,
+                    ..
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn partial_scan_configs_use_the_defaults() {
+        let config: BeaconScanConfig = serde_json::from_str(r#"{"passive": false}"#).unwrap();
+        assert!(!config.passive);
+        assert_eq!(config.history_len, 60);
+        assert_eq!(config.timeout_secs, 30);
+    }
+}
@@ -0,0 +1,283 @@
+use std::{collections::HashMap, env, future::Future};
+
+use tokio::sync::OnceCell;
+use zbus::{
+    connection,
+    fdo::{ManagedObjects, ObjectManagerProxy},
+    proxy,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+    Connection,
+};
+
+use super::bluez_error::BluetoothError;
+
+/// Well-known bus name of the BlueZ daemon
+pub const BLUEZ_SERVICE: &str = "org.bluez";
+/// Object path prefix of all BlueZ objects
+pub const BLUEZ_PATH: &str = "/org/bluez";
+pub const ADAPTER_INTERFACE: &str = "org.bluez.Adapter1";
+pub const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+pub const BATTERY_INTERFACE: &str = "org.bluez.Battery1";
+pub const MEDIA_TRANSPORT_INTERFACE: &str = "org.bluez.MediaTransport1";
+pub const NETWORK_INTERFACE: &str = "org.bluez.Network1";
+pub const GATT_SERVICE_INTERFACE: &str = "org.bluez.GattService1";
+pub const GATT_CHARACTERISTIC_INTERFACE: &str = "org.bluez.GattCharacteristic1";
+pub const GATT_DESCRIPTOR_INTERFACE: &str = "org.bluez.GattDescriptor1";
+
+/// Bus address to use instead of the system bus, e.g. a mock BlueZ on a private bus
+pub const BUS_ADDRESS_ENV: &str = "WIBLUE_BLUEZ_BUS_ADDRESS";
+
+static CONNECTION: OnceCell<Connection> = OnceCell::const_new();
+
+tokio::task_local! {
+    /// Connection set by [`with_connection`] for the current task
+    static SCOPED_CONNECTION: Connection;
+}
+
+/// Runs `future` with every BlueZ call going through `conn`
+///
+/// Lets tests talk to a mock BlueZ on a private bus without touching the
+/// shared connection. Tasks spawned by `future` use the shared connection.
+pub async fn with_connection<F: Future>(conn: Connection, future: F) -> F::Output {
+    SCOPED_CONNECTION.scope(conn, future).await
+}
+
+/// Returns the shared connection to the bus BlueZ lives on
+///
+/// Connects to the system bus unless `WIBLUE_BLUEZ_BUS_ADDRESS` is set, or
+/// returns the connection of an enclosing [`with_connection`].
+pub async fn connection() -> Result<Connection, BluetoothError> {
+    if let Ok(conn) = SCOPED_CONNECTION.try_with(Connection::clone) {
+        return Ok(conn);
+    }
+
+    CONNECTION
+        .get_or_try_init(|| async {
+            let builder = match env::var(BUS_ADDRESS_ENV) {
+                Ok(address) => connection::Builder::address(address.as_str()),
+                Err(_) => connection::Builder::system(),
+            };
+
+            match builder {
+                Ok(b) => b.build().await,
+                Err(e) => Err(e),
+            }
+            .map_err(|e| {
+                eprintln!("Error connecting to the BlueZ bus: {:?}", e);
+                BluetoothError::ConnectionFailure
+            })
+        })
+        .await
+        .cloned()
+}
+
+/// Fetches every object BlueZ exports together with its interfaces and properties
+pub async fn managed_objects(conn: &Connection) -> Result<ManagedObjects, BluetoothError> {
+    let manager = ObjectManagerProxy::builder(conn)
+        .destination(BLUEZ_SERVICE)
+        .and_then(|b| b.path("/"))
+        .map_err(BluetoothError::from_dbus)?
+        .build()
+        .await
+        .map_err(BluetoothError::from_dbus)?;
+
+    manager
+        .get_managed_objects()
+        .await
+        .map_err(|e| BluetoothError::from_dbus(e.into()))
+}
+
+/// Reads a property from a property map returned by BlueZ
+pub fn property<T>(properties: &HashMap<String, OwnedValue>, name: &str) -> Option<T>
+where
+    T: TryFrom<OwnedValue>,
+{
+    properties
+        .get(name)
+        .and_then(|value| value.try_clone().ok())
+        .and_then(|value| T::try_from(value).ok())
+}
+
+/// Reads a dictionary of byte arrays (`a{qv}` or `a{sv}` holding `ay`)
+///
+/// Used for the manufacturer and service data of advertising devices.
+pub fn byte_map_property<K>(
+    properties: &HashMap<String, OwnedValue>,
+    name: &str,
+) -> HashMap<K, Vec<u8>>
+where
+    K: std::hash::Hash + Eq + for<'a> TryFrom<&'a Value<'a>>,
+{
+    let mut map = HashMap::new();
+
+    let Some(Value::Dict(dict)) = properties.get(name).map(|v| &**v) else {
+        return map;
+    };
+
+    for (key, value) in dict.iter() {
+        let Ok(key) = K::try_from(key) else {
+            continue;
+        };
+        let value = match value {
+            Value::Value(inner) => &**inner,
+            other => other,
+        };
+        if let Value::Array(array) = value {
+            let bytes = array
+                .iter()
+                .filter_map(|b| match b {
+                    Value::U8(b) => Some(*b),
+                    _ => None,
+                })
+                .collect();
+            map.insert(key, bytes);
+        }
+    }
+
+    map
+}
+
+/// Turns an adapter name (`hci0`) or object path into an object path
+pub fn adapter_path(adapter: &str) -> String {
+    if adapter.starts_with('/') {
+        adapter.to_string()
+    } else {
+        format!("{}/{}", BLUEZ_PATH, adapter)
+    }
+}
+
+#[proxy(interface = "org.bluez.Adapter1", default_service = "org.bluez")]
+pub trait Adapter1 {
+    #[zbus(property)]
+    fn address(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn alias(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn set_alias(&self, value: &str) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn powered(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn set_powered(&self, value: bool) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn discoverable(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn set_discoverable(&self, value: bool) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn discoverable_timeout(&self) -> zbus::Result<u32>;
+
+    #[zbus(property)]
+    fn set_discoverable_timeout(&self, value: u32) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn pairable(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn set_pairable(&self, value: bool) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn pairable_timeout(&self) -> zbus::Result<u32>;
+
+    #[zbus(property)]
+    fn set_pairable_timeout(&self, value: u32) -> zbus::Result<()>;
+
+    fn set_discovery_filter(&self, filter: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+
+    fn start_discovery(&self) -> zbus::Result<()>;
+
+    fn stop_discovery(&self) -> zbus::Result<()>;
+
+    fn remove_device(&self, device: &ObjectPath<'_>) -> zbus::Result<()>;
+}
+
+#[proxy(interface = "org.bluez.Device1", default_service = "org.bluez")]
+pub trait Device1 {
+    fn pair(&self) -> zbus::Result<()>;
+
+    fn cancel_pairing(&self) -> zbus::Result<()>;
+
+    fn connect(&self) -> zbus::Result<()>;
+
+    fn disconnect(&self) -> zbus::Result<()>;
+
+    fn connect_profile(&self, uuid: &str) -> zbus::Result<()>;
+
+    fn disconnect_profile(&self, uuid: &str) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn address(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn alias(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn adapter(&self) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(property, name = "UUIDs")]
+    fn uuids(&self) -> zbus::Result<Vec<String>>;
+
+    #[zbus(property)]
+    fn paired(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn connected(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn services_resolved(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn trusted(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn set_trusted(&self, value: bool) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn blocked(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn set_blocked(&self, value: bool) -> zbus::Result<()>;
+}
+
+#[proxy(interface = "org.bluez.Network1", default_service = "org.bluez")]
+pub trait Network1 {
+    /// Connects to the given PAN role and returns the name of the created interface
+    fn connect(&self, uuid: &str) -> zbus::Result<String>;
+
+    fn disconnect(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn connected(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn interface(&self) -> zbus::Result<String>;
+
+    #[zbus(property, name = "UUID")]
+    fn uuid(&self) -> zbus::Result<String>;
+}
+
+#[proxy(
+    interface = "org.bluez.GattCharacteristic1",
+    default_service = "org.bluez"
+)]
+pub trait GattCharacteristic1 {
+    fn read_value(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<Vec<u8>>;
+
+    fn write_value(&self, value: &[u8], options: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+
+    fn start_notify(&self) -> zbus::Result<()>;
+
+    fn stop_notify(&self) -> zbus::Result<()>;
+}
+
+#[proxy(interface = "org.bluez.GattDescriptor1", default_service = "org.bluez")]
+pub trait GattDescriptor1 {
+    fn read_value(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<Vec<u8>>;
+
+    fn write_value(&self, value: &[u8], options: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+}
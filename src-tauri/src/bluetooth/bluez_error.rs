@@ -0,0 +1,135 @@
+#[derive(Debug)]
+pub enum BluetoothError {
+    ConnectionFailure,
+    NoSuchAdapter,
+    NoSuchDevice,
+    DoesNotExist,
+    NotReady,
+    InvalidArguments,
+    NotSupported,
+    NotAvailable,
+    AlreadyConnected,
+    NotConnected,
+    InProgress,
+    NotPermitted,
+    NotAuthorized,
+    Failed,
+    DBusError,
+}
+
+impl BluetoothError {
+    /// Maps an error returned by BlueZ to the matching variant
+    ///
+    /// BlueZ reports failures as D-Bus errors named `org.bluez.Error.<Reason>`.
+    pub fn from_dbus(error: zbus::Error) -> Self {
+        let name = match &error {
+            zbus::Error::MethodError(name, _, _) => name.to_string(),
+            zbus::Error::FDO(e) => match **e {
+                zbus::fdo::Error::UnknownObject(_) => "org.bluez.Error.DoesNotExist".to_string(),
+                zbus::fdo::Error::InvalidArgs(_) => "org.bluez.Error.InvalidArguments".to_string(),
+                _ => String::new(),
+            },
+            _ => String::new(),
+        };
+        eprintln!("BlueZ call failed: {:?}", error);
+
+        match name.trim_start_matches("org.bluez.Error.") {
+            "DoesNotExist" | "org.freedesktop.DBus.Error.UnknownObject" => {
+                BluetoothError::DoesNotExist
+            }
+            "NotReady" => BluetoothError::NotReady,
+            "InvalidArguments" | "InvalidValueLength" | "InvalidOffset" => {
+                BluetoothError::InvalidArguments
+            }
+            "NotSupported" => BluetoothError::NotSupported,
+            "NotAvailable" => BluetoothError::NotAvailable,
+            "AlreadyConnected" => BluetoothError::AlreadyConnected,
+            "NotConnected" => BluetoothError::NotConnected,
+            "InProgress" => BluetoothError::InProgress,
+            "NotPermitted" => BluetoothError::NotPermitted,
+            "NotAuthorized" => BluetoothError::NotAuthorized,
+            "Failed" => BluetoothError::Failed,
+            _ => BluetoothError::DBusError,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PairingError {
+    AuthenticationFailed,
+    AuthenticationRejected,
+    AuthenticationTimeout,
+    AuthenticationCanceled,
+    AlreadyExists,
+    InProgress,
+    ConnectionAttemptFailed,
+    Bluetooth(BluetoothError),
+}
+
+impl PairingError {
+    /// Maps an error returned by `org.bluez.Device1.Pair` to the matching variant
+    pub fn from_dbus(error: zbus::Error) -> Self {
+        let name = match &error {
+            zbus::Error::MethodError(name, _, _) => name.to_string(),
+            _ => String::new(),
+        };
+
+        match name.trim_start_matches("org.bluez.Error.") {
+            "AuthenticationFailed" => PairingError::AuthenticationFailed,
+            "AuthenticationRejected" => PairingError::AuthenticationRejected,
+            "AuthenticationTimeout" => PairingError::AuthenticationTimeout,
+            "AuthenticationCanceled" => PairingError::AuthenticationCanceled,
+            "AlreadyExists" => PairingError::AlreadyExists,
+            "InProgress" => PairingError::InProgress,
+            "ConnectionAttemptFailed" => PairingError::ConnectionAttemptFailed,
+            _ => PairingError::Bluetooth(BluetoothError::from_dbus(error)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AudioError {
+    CommandExecutionFailure,
+    NoAudioCard,
+    NoSuchProfile,
+    ProfileUnavailable,
+    Bluetooth(BluetoothError),
+}
+
+#[derive(Debug)]
+pub enum ObexError {
+    ConnectionFailure,
+    InvalidArguments,
+    NotFound,
+    Forbidden,
+    TransferFailed,
+    IoError,
+    Failed,
+    Bluetooth(BluetoothError),
+}
+
+impl ObexError {
+    /// Maps an error returned by obexd (`org.bluez.obex.Error.<Reason>`)
+    pub fn from_dbus(error: zbus::Error) -> Self {
+        let name = match &error {
+            zbus::Error::MethodError(name, _, _) => name.to_string(),
+            zbus::Error::FDO(e) => match **e {
+                zbus::fdo::Error::ServiceUnknown(_) => {
+                    return ObexError::ConnectionFailure;
+                }
+                zbus::fdo::Error::UnknownObject(_) => "org.bluez.obex.Error.NotFound".to_string(),
+                _ => String::new(),
+            },
+            _ => String::new(),
+        };
+        eprintln!("obexd call failed: {:?}", error);
+
+        match name.trim_start_matches("org.bluez.obex.Error.") {
+            "InvalidArguments" => ObexError::InvalidArguments,
+            "NotFound" | "org.freedesktop.DBus.Error.UnknownObject" => ObexError::NotFound,
+            "Forbidden" | "NotAuthorized" => ObexError::Forbidden,
+            "org.freedesktop.DBus.Error.ServiceUnknown" => ObexError::ConnectionFailure,
+            _ => ObexError::Failed,
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use zbus::zvariant::ObjectPath;
+
+use super::{
+    adapter::adapter_proxy,
+    bluez::{connection, Device1Proxy},
+    bluez_error::BluetoothError,
+    device_data::{BluetoothDevice, DeviceSetting},
+    discovery::get_devices,
+};
+
+/// Turns a device object path or address into an object path
+///
+/// Addresses are looked up among the devices BlueZ knows, so the device must
+/// have been discovered or paired before.
+///
+/// # Arguments
+/// * `device` - object path (`/org/bluez/hci0/dev_...`) or address (`AA:BB:CC:DD:EE:FF`)
+pub async fn resolve_device_path(device: &str) -> Result<String, BluetoothError> {
+    if device.starts_with('/') {
+        return Ok(device.to_string());
+    }
+
+    get_devices(None)
+        .await?
+        .into_iter()
+        .find(|d| d.address.eq_ignore_ascii_case(device))
+        .map(|d| d.path)
+        .ok_or(BluetoothError::NoSuchDevice)
+}
+
+/// Builds a proxy for the given device, checking that it exists
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+pub async fn device_proxy(device: &str) -> Result<Device1Proxy<'static>, BluetoothError> {
+    let conn = connection().await?;
+    let proxy = Device1Proxy::builder(&conn)
+        .path(resolve_device_path(device).await?)
+        .map_err(|_| BluetoothError::NoSuchDevice)?
+        .build()
+        .await
+        .map_err(BluetoothError::from_dbus)?;
+
+    match proxy.address().await.map_err(BluetoothError::from_dbus) {
+        Ok(_) => Ok(proxy),
+        Err(BluetoothError::DoesNotExist) => Err(BluetoothError::NoSuchDevice),
+        Err(e) => Err(e),
+    }
+}
+
+/// Connects a device, either every auto-connectable profile or a single one
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+/// * `profile` - UUID of the profile to connect, `None` connects the whole device
+///
+/// # Returns
+/// - `Ok(())` once the device or profile is connected
+/// - `Err(BluetoothError)` if the device is unreachable or does not offer the profile
+pub async fn connect_device(device: &str, profile: Option<&str>) -> Result<(), BluetoothError> {
+    let proxy = device_proxy(device).await?;
+
+    match profile {
+        Some(uuid) => proxy.connect_profile(uuid).await,
+        None => proxy.connect().await,
+    }
+    .map_err(BluetoothError::from_dbus)
+}
+
+/// Disconnects a device, either completely or a single profile
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+/// * `profile` - UUID of the profile to disconnect, `None` disconnects the whole device
+pub async fn disconnect_device(device: &str, profile: Option<&str>) -> Result<(), BluetoothError> {
+    let proxy = device_proxy(device).await?;
+
+    match profile {
+        Some(uuid) => proxy.disconnect_profile(uuid).await,
+        None => proxy.disconnect().await,
+    }
+    .map_err(BluetoothError::from_dbus)
+}
+
+/// Changes a single property of a device
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+/// * `setting` - the property to change and its new value
+pub async fn set_device_setting(
+    device: &str,
+    setting: DeviceSetting,
+) -> Result<(), BluetoothError> {
+    let proxy = device_proxy(device).await?;
+
+    match setting {
+        DeviceSetting::Trusted(value) => proxy.set_trusted(value).await,
+        DeviceSetting::Blocked(value) => proxy.set_blocked(value).await,
+    }
+    .map_err(BluetoothError::from_dbus)
+}
+
+/// Removes a device from its adapter, deleting the pairing keys
+///
+/// The device disappears from BlueZ until it is discovered again.
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+pub async fn remove_device(device: &str) -> Result<(), BluetoothError> {
+    let proxy = device_proxy(device).await?;
+    let adapter = proxy.adapter().await.map_err(BluetoothError::from_dbus)?;
+    let path = ObjectPath::try_from(proxy.inner().path().as_str())
+        .map_err(|_| BluetoothError::NoSuchDevice)?;
+
+    adapter_proxy(adapter.as_str())
+        .await?
+        .remove_device(&path)
+        .await
+        .map_err(BluetoothError::from_dbus)
+}
+
+/// Lists the paired devices with their connection state and profiles
+///
+/// # Arguments
+/// * `adapter` - only return devices paired with this adapter (name or object path)
+///
+/// # Returns
+/// - `Ok(Vec<BluetoothDevice>)` connected devices first, then by name
+/// - `Err(BluetoothError)` if BlueZ cannot be reached
+pub async fn get_paired_devices(
+    adapter: Option<&str>,
+) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+    let mut devices: Vec<BluetoothDevice> = get_devices(adapter)
+        .await?
+        .into_iter()
+        .filter(|d| d.paired)
+        .collect();
+
+    devices.sort_by(|a, b| b.connected.cmp(&a.connected).then(a.alias.cmp(&b.alias)));
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluetooth::{
+        bluez::with_connection,
+        mock_bluez::{add_adapter, add_device, Calls, MockAdapter, MockDevice, PrivateBus},
+    };
+
+    const HEADSET: &str = "/org/bluez/hci0/dev_00_11_22_33_44_55";
+    const AUDIO_SINK: &str = "0000110b-0000-1000-8000-00805f9b34fb";
+
+    #[tokio::test]
+    async fn connects_and_disconnects_devices_and_profiles() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let bluez = bus.serve_bluez().await;
+        let calls = Calls::default();
+        let mut headset = MockDevice::new("/org/bluez/hci0", "00:11:22:33:44:55", &calls);
+        headset.uuids = vec![AUDIO_SINK.to_string()];
+        add_device(&bluez, headset).await;
+
+        with_connection(bus.connect().await, async {
+            // Addresses are matched case-insensitively
+            assert_eq!(
+                resolve_device_path("00:11:22:33:44:55").await.unwrap(),
+                HEADSET
+            );
+            connect_device("00:11:22:33:44:55", None).await.unwrap();
+            assert!(matches!(
+                connect_device(HEADSET, None).await,
+                Err(BluetoothError::AlreadyConnected)
+            ));
+            connect_device(HEADSET, Some(AUDIO_SINK)).await.unwrap();
+            assert!(matches!(
+                connect_device(HEADSET, Some("0000180d-0000-1000-8000-00805f9b34fb")).await,
+                Err(BluetoothError::NotAvailable)
+            ));
+            disconnect_device(HEADSET, Some(AUDIO_SINK)).await.unwrap();
+            disconnect_device(HEADSET, None).await.unwrap();
+            assert!(matches!(
+                disconnect_device(HEADSET, None).await,
+                Err(BluetoothError::NotConnected)
+            ));
+        })
+        .await;
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                format!("Connect {}", HEADSET),
+                format!("ConnectProfile {} {}", AUDIO_SINK, HEADSET),
+                format!("DisconnectProfile {} {}", AUDIO_SINK, HEADSET),
+                format!("Disconnect {}", HEADSET),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn trusts_blocks_and_removes_devices() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let bluez = bus.serve_bluez().await;
+        let calls = Calls::default();
+        add_adapter(
+            &bluez,
+            "/org/bluez/hci0",
+            MockAdapter::new("00:00:00:00:00:01", &calls),
+        )
+        .await;
+        add_device(
+            &bluez,
+            MockDevice::new("/org/bluez/hci0", "00:11:22:33:44:55", &calls),
+        )
+        .await;
+
+        with_connection(bus.connect().await, async {
+            set_device_setting(HEADSET, DeviceSetting::Trusted(true))
+                .await
+                .unwrap();
+            set_device_setting("00:11:22:33:44:55", DeviceSetting::Blocked(true))
+                .await
+                .unwrap();
+            let device = get_devices(None).await.unwrap().remove(0);
+            assert!(device.trusted);
+            assert!(device.blocked);
+
+            remove_device("00:11:22:33:44:55").await.unwrap();
+        })
+        .await;
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [format!("RemoveDevice {}", HEADSET)]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_unknown_devices() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let _bluez = bus.serve_bluez().await;
+
+        with_connection(bus.connect().await, async {
+            assert!(matches!(
+                resolve_device_path("00:11:22:33:44:55").await,
+                Err(BluetoothError::NoSuchDevice)
+            ));
+            assert!(matches!(
+                connect_device(HEADSET, None).await,
+                Err(BluetoothError::NoSuchDevice)
+            ));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn lists_connected_paired_devices_first() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let bluez = bus.serve_bluez().await;
+        let calls = Calls::default();
+        for (address, paired, connected) in [
+            ("00:00:00:00:00:0A", true, false),
+            ("00:00:00:00:00:0B", false, true),
+            ("00:00:00:00:00:0C", true, true),
+            ("00:00:00:00:00:0D", true, false),
+        ] {
+            let mut device = MockDevice::new("/org/bluez/hci0", address, &calls);
+            device.paired = paired;
+            device.connected = connected;
+            add_device(&bluez, device).await;
+        }
+
+        let paired = with_connection(bus.connect().await, get_paired_devices(Some("hci0")))
+            .await
+            .unwrap();
+        let addresses: Vec<_> = paired.iter().map(|d| d.address.as_str()).collect();
+        assert_eq!(
+            addresses,
+            [
+                "00:00:00:00:00:0C",
+                "00:00:00:00:00:0A",
+                "00:00:00:00:00:0D"
+            ]
+        );
+    }
+}
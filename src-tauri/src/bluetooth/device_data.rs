@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use zbus::zvariant::OwnedValue;
+
+use super::bluez::{byte_map_property, property};
+
+/// Represents a remote Bluetooth device as exposed by BlueZ (`org.bluez.Device1`)
+#[derive(Debug, Clone, Serialize)]
+pub struct BluetoothDevice {
+    /// D-Bus object path (e.g. `/org/bluez/hci0/dev_00_11_22_33_44_55`)
+    pub path: String,
+    /// Object path of the adapter that sees the device
+    pub adapter: String,
+    /// Bluetooth device address
+    pub address: String,
+    /// `public` or `random`
+    pub address_type: String,
+    /// Name the device advertises, if any
+    pub name: Option<String>,
+    /// Name shown to the user, falls back to the address
+    pub alias: String,
+    /// Received signal strength in dBm, only present while the device is discovered
+    pub rssi: Option<i16>,
+    /// Advertised transmit power in dBm
+    pub tx_power: Option<i16>,
+    /// GAP appearance of LE devices
+    pub appearance: Option<u16>,
+    /// Class of device of BR/EDR devices
+    pub class: Option<u32>,
+    /// Freedesktop icon name derived from class or appearance (e.g. `audio-headset`)
+    pub icon: Option<String>,
+    pub paired: bool,
+    pub trusted: bool,
+    pub blocked: bool,
+    pub connected: bool,
+    /// Service UUIDs the device offers
+    pub uuids: Vec<String>,
+    /// Well-known profiles among `uuids`, the ones that can be connected individually
+    pub profiles: Vec<BluetoothProfile>,
+    /// Manufacturer specific advertising data keyed by company identifier
+    pub manufacturer_data: HashMap<u16, Vec<u8>>,
+    /// Service advertising data keyed by service UUID
+    pub service_data: HashMap<String, Vec<u8>>,
+}
+
+impl BluetoothDevice {
+    /// Builds a device from the `org.bluez.Device1` properties of an object
+    pub fn from_properties(path: &str, props: &HashMap<String, OwnedValue>) -> Self {
+        let address: String = property(props, "Address").unwrap_or_default();
+        let uuids: Vec<String> = property(props, "UUIDs").unwrap_or_default();
+
+        BluetoothDevice {
+            path: path.to_string(),
+            adapter: property::<zbus::zvariant::OwnedObjectPath>(props, "Adapter")
+                .map(|p| p.to_string())
+                .unwrap_or_default(),
+            address_type: property(props, "AddressType").unwrap_or_default(),
+            name: property(props, "Name"),
+            alias: property(props, "Alias").unwrap_or_else(|| address.clone()),
+            rssi: property(props, "RSSI"),
+            tx_power: property(props, "TxPower"),
+            appearance: property(props, "Appearance"),
+            class: property(props, "Class"),
+            icon: property(props, "Icon"),
+            paired: property(props, "Paired").unwrap_or_default(),
+            trusted: property(props, "Trusted").unwrap_or_default(),
+            blocked: property(props, "Blocked").unwrap_or_default(),
+            connected: property(props, "Connected").unwrap_or_default(),
+            profiles: uuids
+                .iter()
+                .filter_map(|uuid| BluetoothProfile::from_uuid(uuid))
+                .collect(),
+            uuids,
+            manufacturer_data: byte_map_property(props, "ManufacturerData"),
+            service_data: byte_map_property(props, "ServiceData"),
+            address,
+        }
+    }
+}
+
+/// Profile UUIDs BlueZ can connect on their own, with a readable name
+const PROFILES: &[(&str, &str)] = &[
+    ("00001101-0000-1000-8000-00805f9b34fb", "Serial Port"),
+    ("00001105-0000-1000-8000-00805f9b34fb", "OBEX Object Push"),
+    ("00001106-0000-1000-8000-00805f9b34fb", "OBEX File Transfer"),
+    ("00001108-0000-1000-8000-00805f9b34fb", "Headset"),
+    ("0000110a-0000-1000-8000-00805f9b34fb", "Audio Source"),
+    ("0000110b-0000-1000-8000-00805f9b34fb", "Audio Sink"),
+    (
+        "0000110c-0000-1000-8000-00805f9b34fb",
+        "A/V Remote Control Target",
+    ),
+    ("0000110e-0000-1000-8000-00805f9b34fb", "A/V Remote Control"),
+    (
+        "00001112-0000-1000-8000-00805f9b34fb",
+        "Headset Audio Gateway",
+    ),
+    ("00001115-0000-1000-8000-00805f9b34fb", "PAN User"),
+    (
+        "00001116-0000-1000-8000-00805f9b34fb",
+        "Network Access Point",
+    ),
+    ("0000111e-0000-1000-8000-00805f9b34fb", "Handsfree"),
+    (
+        "0000111f-0000-1000-8000-00805f9b34fb",
+        "Handsfree Audio Gateway",
+    ),
+    (
+        "00001124-0000-1000-8000-00805f9b34fb",
+        "Human Interface Device",
+    ),
+    ("0000112f-0000-1000-8000-00805f9b34fb", "Phonebook Access"),
+    ("00001132-0000-1000-8000-00805f9b34fb", "Message Access"),
+    ("00001812-0000-1000-8000-00805f9b34fb", "HID over GATT"),
+];
+
+/// A profile a device supports
+#[derive(Debug, Clone, Serialize)]
+pub struct BluetoothProfile {
+    pub uuid: String,
+    /// e.g. `Audio Sink` or `Handsfree`
+    pub name: String,
+}
+
+impl BluetoothProfile {
+    /// Returns the profile for a well-known profile UUID
+    pub fn from_uuid(uuid: &str) -> Option<Self> {
+        PROFILES
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(uuid))
+            .map(|(uuid, name)| BluetoothProfile {
+                uuid: uuid.to_string(),
+                name: name.to_string(),
+            })
+    }
+}
+
+/// A writable device property together with its new value
+#[derive(Debug, Clone, Deserialize)]
+pub enum DeviceSetting {
+    /// Trusted devices may connect without asking the user
+    Trusted(bool),
+    /// Blocked devices are disconnected and refused
+    Blocked(bool),
+}
+
+/// Transport a discovery should look for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DiscoveryTransport {
+    /// Interleaved BR/EDR and LE discovery
+    Auto,
+    /// Classic Bluetooth only
+    BrEdr,
+    /// Low Energy only
+    Le,
+}
+
+/// Restricts which devices a discovery reports, missing fields do not filter
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscoveryFilter {
+    pub transport: Option<DiscoveryTransport>,
+    /// Only report devices with a stronger signal (in dBm)
+    pub rssi: Option<i16>,
+    /// Only report devices advertising one of these service UUIDs
+    pub uuids: Vec<String>,
+    /// Only report devices whose address or name starts with this pattern
+    pub pattern: Option<String>,
+}
+
+/// Change to the set of known devices
+#[derive(Debug, Clone, Serialize)]
+pub enum DeviceEvent {
+    /// A device appeared
+    Found(BluetoothDevice),
+    /// Properties of a known device changed (e.g. RSSI or name)
+    Updated(BluetoothDevice),
+    /// A device was removed by BlueZ
+    Lost { path: String, address: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_discovery_filters_use_the_defaults() {
+        let filter: DiscoveryFilter = serde_json::from_str(r#"{"rssi": -70}"#).unwrap();
+        assert_eq!(filter.rssi, Some(-70));
+        assert!(filter.transport.is_none());
+        assert!(filter.uuids.is_empty());
+        assert!(filter.pattern.is_none());
+
+        let filter: DiscoveryFilter = serde_json::from_str("{}").unwrap();
+        assert!(filter.rssi.is_none());
+    }
+}
@@ -0,0 +1,171 @@
+use std::{collections::HashMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use tokio::time;
+
+use super::{
+    bluez::{connection, managed_objects, property, BATTERY_INTERFACE, DEVICE_INTERFACE},
+    bluez_error::BluetoothError,
+};
+
+/// Battery and signal readings of a connected device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BluetoothDeviceStats {
+    /// D-Bus object path of the device
+    pub path: String,
+    pub address: String,
+    pub alias: String,
+    /// Battery level in percent, only for devices exposing `org.bluez.Battery1`
+    pub battery: Option<u8>,
+    /// Received signal strength in dBm, BlueZ only updates it while discovering
+    pub rssi: Option<i16>,
+    /// Advertised transmit power in dBm
+    pub tx_power: Option<i16>,
+}
+
+/// Readings of every connected device at one point in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BluetoothStats {
+    pub devices: Vec<BluetoothDeviceStats>,
+}
+
+/// Raised once when a device battery drops to or below a threshold
+#[derive(Debug, Clone, Serialize)]
+pub struct LowBatteryAlert {
+    pub device: BluetoothDeviceStats,
+    /// The threshold that was crossed, in percent
+    pub threshold: u8,
+}
+
+/// Configuration for the Bluetooth monitor
+#[derive(Debug, Clone, Deserialize)]
+pub struct BluetoothMonitorConfig {
+    /// Seconds between two readings, at least one
+    pub interval_secs: u64,
+    /// Battery levels in percent that raise an alert when reached
+    pub low_battery_thresholds: Vec<u8>,
+}
+
+impl Default for BluetoothMonitorConfig {
+    fn default() -> Self {
+        BluetoothMonitorConfig {
+            interval_secs: 30,
+            low_battery_thresholds: vec![20, 10, 5],
+        }
+    }
+}
+
+/// Monitor remembering which battery alerts were already raised per device
+pub struct BluetoothMonitor {
+    config: BluetoothMonitorConfig,
+    /// Lowest threshold already reported, keyed by device path
+    alerted: HashMap<String, u8>,
+}
+
+impl BluetoothMonitor {
+    pub fn new(config: BluetoothMonitorConfig) -> Self {
+        BluetoothMonitor {
+            config,
+            alerted: HashMap::new(),
+        }
+    }
+
+    /// Reads battery level, RSSI and TX power of all connected devices
+    pub async fn get_stats(&self) -> Result<BluetoothStats, BluetoothError> {
+        let conn = connection().await?;
+        let objects = managed_objects(&conn).await?;
+
+        let mut devices: Vec<BluetoothDeviceStats> = objects
+            .iter()
+            .filter_map(|(path, interfaces)| {
+                let mut device = None;
+                let mut battery = None;
+                for (name, props) in interfaces {
+                    match name.as_str() {
+                        DEVICE_INTERFACE => device = Some(props),
+                        BATTERY_INTERFACE => battery = property(props, "Percentage"),
+                        _ => {}
+                    }
+                }
+                let device = device?;
+                if !property::<bool>(device, "Connected").unwrap_or_default() {
+                    return None;
+                }
+
+                let address: String = property(device, "Address").unwrap_or_default();
+                Some(BluetoothDeviceStats {
+                    path: path.to_string(),
+                    alias: property(device, "Alias").unwrap_or_else(|| address.clone()),
+                    address,
+                    battery,
+                    rssi: property(device, "RSSI"),
+                    tx_power: property(device, "TxPower"),
+                })
+            })
+            .collect();
+
+        devices.sort_by(|a, b| a.alias.cmp(&b.alias));
+        Ok(BluetoothStats { devices })
+    }
+
+    /// Returns the alerts for thresholds newly crossed since the last reading
+    ///
+    /// A device is reported again once its battery was charged above the
+    /// threshold and drops below it another time.
+    fn check_battery(&mut self, stats: &BluetoothStats) -> Vec<LowBatteryAlert> {
+        let mut alerts = Vec::new();
+
+        for device in &stats.devices {
+            let Some(level) = device.battery else {
+                continue;
+            };
+            let lowest_crossed = self
+                .config
+                .low_battery_thresholds
+                .iter()
+                .filter(|threshold| level <= **threshold)
+                .min()
+                .copied();
+
+            match (lowest_crossed, self.alerted.get(&device.path).copied()) {
+                (Some(threshold), Some(alerted)) if threshold >= alerted => {}
+                (Some(threshold), _) => {
+                    self.alerted.insert(device.path.clone(), threshold);
+                    alerts.push(LowBatteryAlert {
+                        device: device.clone(),
+                        threshold,
+                    });
+                }
+                (None, _) => {
+                    self.alerted.remove(&device.path);
+                }
+            }
+        }
+
+        alerts
+    }
+
+    /// Continuously reads device statistics with the configured interval
+    ///
+    /// # Arguments
+    /// * `callback` - called with every reading
+    /// * `on_low_battery` - called for every battery threshold a device crosses
+    pub async fn monitor(
+        &mut self,
+        callback: impl Fn(BluetoothStats),
+        on_low_battery: impl Fn(LowBatteryAlert),
+    ) {
+        loop {
+            match self.get_stats().await {
+                Ok(stats) => {
+                    for alert in self.check_battery(&stats) {
+                        on_low_battery(alert);
+                    }
+                    callback(stats);
+                }
+                Err(e) => eprintln!("Error getting Bluetooth stats: {:?}", e),
+            }
+            time::sleep(Duration::from_secs(self.config.interval_secs.max(1))).await;
+        }
+    }
+}
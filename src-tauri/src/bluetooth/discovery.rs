@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use futures::StreamExt;
+use zbus::{
+    message::Type as MessageType,
+    zvariant::{OwnedObjectPath, OwnedValue, Value},
+    MatchRule, MessageStream,
+};
+
+use super::{
+    adapter::adapter_proxy,
+    bluez::{connection, managed_objects, BLUEZ_SERVICE, DEVICE_INTERFACE},
+    bluez_error::BluetoothError,
+    device_data::{BluetoothDevice, DeviceEvent, DiscoveryFilter, DiscoveryTransport},
+};
+
+type Properties = HashMap<String, OwnedValue>;
+
+/// Lists the devices BlueZ currently knows about
+///
+/// Includes paired devices as well as devices found by a running or recent
+/// discovery, which is what `get_networks` returns for Wi-Fi.
+///
+/// # Arguments
+/// * `adapter` - only return devices seen by this adapter (name or object path)
+///
+/// # Returns
+/// - `Ok(Vec<BluetoothDevice>)` sorted by signal strength, strongest first
+/// - `Err(BluetoothError)` if BlueZ cannot be reached
+pub async fn get_devices(adapter: Option<&str>) -> Result<Vec<BluetoothDevice>, BluetoothError> {
+    let conn = connection().await?;
+    let objects = managed_objects(&conn).await?;
+
+    let mut devices: Vec<BluetoothDevice> = objects
+        .iter()
+        .filter_map(|(path, interfaces)| {
+            interfaces
+                .iter()
+                .find(|(name, _)| name.as_str() == DEVICE_INTERFACE)
+                .map(|(_, props)| BluetoothDevice::from_properties(path.as_str(), props))
+        })
+        .filter(|device| match adapter {
+            Some(adapter) => path_name(&device.adapter) == path_name(adapter),
+            None => true,
+        })
+        .collect();
+
+    devices.sort_by(|a, b| b.rssi.unwrap_or(i16::MIN).cmp(&a.rssi.unwrap_or(i16::MIN)));
+    Ok(devices)
+}
+
+/// Last segment of an object path, `hci0` for both `hci0` and `/org/bluez/hci0`
+fn path_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Starts device discovery on an adapter
+///
+/// # Arguments
+/// * `adapter` - adapter name (`hci0`) or object path
+/// * `filter` - restricts the reported devices, an empty filter reports everything
+///
+/// # Returns
+/// - `Ok(())` if the discovery started
+/// - `Err(BluetoothError)` if the adapter is off, busy or rejected the filter
+pub async fn start_discovery(
+    adapter: &str,
+    filter: &DiscoveryFilter,
+) -> Result<(), BluetoothError> {
+    let proxy = adapter_proxy(adapter).await?;
+
+    let mut dict: HashMap<&str, Value<'_>> = HashMap::new();
+    if let Some(transport) = &filter.transport {
+        let transport = match transport {
+            DiscoveryTransport::Auto => "auto",
+            DiscoveryTransport::BrEdr => "bredr",
+            DiscoveryTransport::Le => "le",
+        };
+        dict.insert("Transport", Value::from(transport));
+    }
+    if let Some(rssi) = filter.rssi {
+        dict.insert("RSSI", Value::from(rssi));
+    }
+    if !filter.uuids.is_empty() {
+        dict.insert("UUIDs", Value::from(filter.uuids.clone()));
+    }
+    if let Some(pattern) = &filter.pattern {
+        dict.insert("Pattern", Value::from(pattern.as_str()));
+    }
+    // Report every advertisement so RSSI and advertising data stay current
+    dict.insert("DuplicateData", Value::from(true));
+
+    proxy
+        .set_discovery_filter(dict)
+        .await
+        .map_err(BluetoothError::from_dbus)?;
+    proxy
+        .start_discovery()
+        .await
+        .map_err(BluetoothError::from_dbus)
+}
+
+/// Stops a discovery started by this application
+pub async fn stop_discovery(adapter: &str) -> Result<(), BluetoothError> {
+    adapter_proxy(adapter)
+        .await?
+        .stop_discovery()
+        .await
+        .map_err(BluetoothError::from_dbus)
+}
+
+/// Watches BlueZ for devices appearing, changing and disappearing
+///
+/// Listens to the `InterfacesAdded`/`InterfacesRemoved` signals of the object
+/// manager and to `PropertiesChanged` of every device, and reports each change
+/// with the complete device. Runs until the bus connection closes.
+///
+/// # Arguments
+/// * `callback` - called for every device event
+///
+/// # Returns
+/// - `Err(BluetoothError)` if BlueZ cannot be reached or the connection closes
+pub async fn watch_devices(callback: impl Fn(DeviceEvent)) -> Result<(), BluetoothError> {
+    let conn = connection().await?;
+
+    let rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .sender(BLUEZ_SERVICE)
+        .and_then(|b| b.path_namespace("/"))
+        .map_err(BluetoothError::from_dbus)?
+        .build();
+    let mut stream = MessageStream::for_match_rule(rule, &conn, None)
+        .await
+        .map_err(BluetoothError::from_dbus)?;
+
+    // Properties of every known device, signals only carry the changed ones
+    let mut known: HashMap<String, Properties> = managed_objects(&conn)
+        .await?
+        .into_iter()
+        .filter_map(|(path, mut interfaces)| {
+            let key = interfaces
+                .keys()
+                .find(|name| name.as_str() == DEVICE_INTERFACE)?
+                .clone();
+            Some((path.to_string(), interfaces.remove(&key)?))
+        })
+        .collect();
+
+    while let Some(message) = stream.next().await {
+        let Ok(message) = message else {
+            continue;
+        };
+        let header = message.header();
+        let (Some(interface), Some(member)) = (header.interface(), header.member()) else {
+            continue;
+        };
+
+        match (interface.as_str(), member.as_str()) {
+            ("org.freedesktop.DBus.ObjectManager", "InterfacesAdded") => {
+                let Ok((path, mut interfaces)) = message
+                    .body()
+                    .deserialize::<(OwnedObjectPath, HashMap<String, Properties>)>()
+                else {
+                    continue;
+                };
+                let Some(props) = interfaces.remove(DEVICE_INTERFACE) else {
+                    continue;
+                };
+
+                let path = path.to_string();
+                callback(DeviceEvent::Found(BluetoothDevice::from_properties(
+                    &path, &props,
+                )));
+                known.insert(path, props);
+            }
+            ("org.freedesktop.DBus.ObjectManager", "InterfacesRemoved") => {
+                let Ok((path, interfaces)) = message
+                    .body()
+                    .deserialize::<(OwnedObjectPath, Vec<String>)>()
+                else {
+                    continue;
+                };
+                if !interfaces.iter().any(|i| i == DEVICE_INTERFACE) {
+                    continue;
+                }
+
+                let path = path.to_string();
+                if let Some(props) = known.remove(&path) {
+                    let device = BluetoothDevice::from_properties(&path, &props);
+                    callback(DeviceEvent::Lost {
+                        path,
+                        address: device.address,
+                    });
+                }
+            }
+            ("org.freedesktop.DBus.Properties", "PropertiesChanged") => {
+                let Ok((changed_interface, changed, invalidated)) =
+                    message
+                        .body()
+                        .deserialize::<(String, Properties, Vec<String>)>()
+                else {
+                    continue;
+                };
+                if changed_interface != DEVICE_INTERFACE {
+                    continue;
+                }
+                let Some(path) = header.path().map(|p| p.to_string()) else {
+                    continue;
+                };
+
+                let props = known.entry(path.clone()).or_default();
+                for name in invalidated {
+                    props.remove(&name);
+                }
+                props.extend(changed);
+                callback(DeviceEvent::Updated(BluetoothDevice::from_properties(
+                    &path, props,
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    Err(BluetoothError::ConnectionFailure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluetooth::{
+        bluez::with_connection,
+        mock_bluez::{add_device, Calls, MockDevice, PrivateBus},
+    };
+
+    #[tokio::test]
+    async fn filters_devices_by_exact_adapter_name() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let bluez = bus.serve_bluez().await;
+        let calls = Calls::default();
+        let mut far = MockDevice::new("/org/bluez/hci1", "00:11:22:33:44:55", &calls);
+        far.rssi = -70;
+        let mut near = MockDevice::new("/org/bluez/hci11", "66:77:88:99:AA:BB", &calls);
+        near.rssi = -40;
+        add_device(&bluez, far).await;
+        add_device(&bluez, near).await;
+
+        let addresses = |devices: Vec<BluetoothDevice>| {
+            devices.into_iter().map(|d| d.address).collect::<Vec<_>>()
+        };
+        let (all, hci1, hci11) = with_connection(bus.connect().await, async {
+            (
+                get_devices(None).await,
+                get_devices(Some("hci1")).await,
+                get_devices(Some("/org/bluez/hci11")).await,
+            )
+        })
+        .await;
+
+        // Sorted by signal strength, strongest first
+        assert_eq!(
+            addresses(all.unwrap()),
+            ["66:77:88:99:AA:BB", "00:11:22:33:44:55"]
+        );
+        assert_eq!(addresses(hci1.unwrap()), ["00:11:22:33:44:55"]);
+        assert_eq!(addresses(hci11.unwrap()), ["66:77:88:99:AA:BB"]);
+    }
+}
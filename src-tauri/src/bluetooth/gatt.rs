@@ -0,0 +1,543 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::{LazyLock, Mutex},
+};
+
+use futures::StreamExt;
+use tokio::sync::oneshot;
+use zbus::{
+    message::Type as MessageType,
+    zvariant::{OwnedObjectPath, OwnedValue, Value},
+    MatchRule, MessageStream,
+};
+
+use super::{
+    bluez::{
+        connection, managed_objects, property, GattCharacteristic1Proxy, GattDescriptor1Proxy,
+        BLUEZ_SERVICE, GATT_CHARACTERISTIC_INTERFACE, GATT_DESCRIPTOR_INTERFACE,
+        GATT_SERVICE_INTERFACE,
+    },
+    bluez_error::BluetoothError,
+    device::{device_proxy, resolve_device_path},
+    gatt_data::{
+        GattCharacteristic, GattDescriptor, GattNotification, GattService, GattValue, GattWriteType,
+    },
+    uuid_names::uuid_name,
+};
+
+/// Running subscriptions keyed by characteristic path, dropping the sender stops one
+static SUBSCRIPTIONS: LazyLock<Mutex<HashMap<String, oneshot::Sender<()>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Lists the services, characteristics and descriptors of a connected LE device
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+///
+/// # Returns
+/// - `Ok(Vec<GattService>)` sorted by handle, as exported by BlueZ
+/// - `Err(BluetoothError::NotConnected)` if the device is not connected
+/// - `Err(BluetoothError::NotReady)` while BlueZ is still resolving services
+pub async fn get_gatt_services(device: &str) -> Result<Vec<GattService>, BluetoothError> {
+    let path = resolve_device_path(device).await?;
+    let proxy = device_proxy(&path).await?;
+    if !proxy.connected().await.unwrap_or(false) {
+        return Err(BluetoothError::NotConnected);
+    }
+    if !proxy.services_resolved().await.unwrap_or(false) {
+        return Err(BluetoothError::NotReady);
+    }
+
+    let conn = connection().await?;
+    let objects = managed_objects(&conn).await?;
+    let prefix = format!("{}/", path);
+
+    // Object paths encode the handle, so sorting them keeps the device order
+    let mut attributes: Vec<(&OwnedObjectPath, &HashMap<String, OwnedValue>, &str)> = objects
+        .iter()
+        .filter(|(object, _)| object.as_str().starts_with(&prefix))
+        .flat_map(|(object, interfaces)| {
+            interfaces
+                .iter()
+                .map(move |(name, props)| (object, props, name.as_str()))
+        })
+        .collect();
+    attributes.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+
+    let mut services: Vec<GattService> = Vec::new();
+    for (object, props, interface) in attributes {
+        let uuid: String = property(props, "UUID").unwrap_or_default();
+        let name = uuid_name(&uuid).map(str::to_string);
+        let path = object.to_string();
+        let flags: Vec<String> = property(props, "Flags").unwrap_or_default();
+
+        match interface {
+            GATT_SERVICE_INTERFACE => services.push(GattService {
+                path,
+                uuid,
+                name,
+                primary: property(props, "Primary").unwrap_or_default(),
+                characteristics: Vec::new(),
+            }),
+            GATT_CHARACTERISTIC_INTERFACE => {
+                let service = property::<OwnedObjectPath>(props, "Service")
+                    .map(|p| p.to_string())
+                    .unwrap_or_default();
+                if let Some(service) = services.iter_mut().find(|s| s.path == service) {
+                    service.characteristics.push(GattCharacteristic {
+                        path,
+                        uuid,
+                        name,
+                        flags,
+                        notifying: property(props, "Notifying").unwrap_or_default(),
+                        descriptors: Vec::new(),
+                    });
+                }
+            }
+            GATT_DESCRIPTOR_INTERFACE => {
+                let characteristic = property::<OwnedObjectPath>(props, "Characteristic")
+                    .map(|p| p.to_string())
+                    .unwrap_or_default();
+                if let Some(characteristic) = services
+                    .iter_mut()
+                    .flat_map(|s| s.characteristics.iter_mut())
+                    .find(|c| c.path == characteristic)
+                {
+                    characteristic.descriptors.push(GattDescriptor {
+                        path,
+                        uuid,
+                        name,
+                        flags,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(services)
+}
+
+/// Whether an attribute path points to a descriptor (`.../char000b/desc000d`)
+fn is_descriptor(path: &str) -> bool {
+    path.rsplit('/')
+        .next()
+        .is_some_and(|segment| segment.starts_with("desc"))
+}
+
+async fn characteristic_proxy(
+    path: &str,
+) -> Result<GattCharacteristic1Proxy<'static>, BluetoothError> {
+    let conn = connection().await?;
+    GattCharacteristic1Proxy::builder(&conn)
+        .path(path.to_string())
+        .map_err(|_| BluetoothError::DoesNotExist)?
+        .build()
+        .await
+        .map_err(BluetoothError::from_dbus)
+}
+
+async fn descriptor_proxy(path: &str) -> Result<GattDescriptor1Proxy<'static>, BluetoothError> {
+    let conn = connection().await?;
+    GattDescriptor1Proxy::builder(&conn)
+        .path(path.to_string())
+        .map_err(|_| BluetoothError::DoesNotExist)?
+        .build()
+        .await
+        .map_err(BluetoothError::from_dbus)
+}
+
+/// Reads the value of a characteristic or descriptor
+///
+/// # Arguments
+/// * `path` - object path of the characteristic or descriptor
+///
+/// # Returns
+/// - `Ok(GattValue)` with the decoded value
+/// - `Err(BluetoothError)` if the attribute is not readable or the device is gone
+pub async fn read_value(path: &str) -> Result<GattValue, BluetoothError> {
+    let bytes = if is_descriptor(path) {
+        descriptor_proxy(path)
+            .await?
+            .read_value(HashMap::new())
+            .await
+    } else {
+        characteristic_proxy(path)
+            .await?
+            .read_value(HashMap::new())
+            .await
+    }
+    .map_err(BluetoothError::from_dbus)?;
+
+    Ok(GattValue::decode(bytes))
+}
+
+/// Writes the value of a characteristic or descriptor
+///
+/// # Arguments
+/// * `path` - object path of the characteristic or descriptor
+/// * `value` - bytes to write
+/// * `write_type` - whether the device acknowledges the write, ignored for descriptors
+pub async fn write_value(
+    path: &str,
+    value: &[u8],
+    write_type: GattWriteType,
+) -> Result<(), BluetoothError> {
+    if is_descriptor(path) {
+        return descriptor_proxy(path)
+            .await?
+            .write_value(value, HashMap::new())
+            .await
+            .map_err(BluetoothError::from_dbus);
+    }
+
+    let mut options: HashMap<&str, Value<'_>> = HashMap::new();
+    let write_type = match write_type {
+        GattWriteType::WithResponse => "request",
+        GattWriteType::WithoutResponse => "command",
+    };
+    options.insert("type", Value::from(write_type));
+
+    characteristic_proxy(path)
+        .await?
+        .write_value(value, options)
+        .await
+        .map_err(BluetoothError::from_dbus)
+}
+
+/// Subscribes to notifications or indications of a characteristic
+///
+/// Enables notifications and reports every value change until
+/// [`unsubscribe`] is called or the device disconnects.
+///
+/// # Arguments
+/// * `path` - object path of the characteristic
+/// * `callback` - called with every notified value
+///
+/// # Returns
+/// - `Ok(())` once the subscription ended
+/// - `Err(BluetoothError::InProgress)` if the characteristic is already subscribed
+/// - `Err(BluetoothError)` if notifications cannot be enabled
+pub async fn subscribe(
+    path: &str,
+    callback: impl Fn(GattNotification),
+) -> Result<(), BluetoothError> {
+    let (stop, mut stopped) = oneshot::channel();
+    match SUBSCRIPTIONS.lock().unwrap().entry(path.to_string()) {
+        Entry::Occupied(_) => return Err(BluetoothError::InProgress),
+        Entry::Vacant(entry) => {
+            entry.insert(stop);
+        }
+    }
+
+    let setup = async {
+        let conn = connection().await?;
+        let proxy = characteristic_proxy(path).await?;
+
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(BLUEZ_SERVICE)
+            .and_then(|b| b.path(path.to_string()))
+            .and_then(|b| b.interface("org.freedesktop.DBus.Properties"))
+            .and_then(|b| b.member("PropertiesChanged"))
+            .map_err(BluetoothError::from_dbus)?
+            .build();
+        let stream = MessageStream::for_match_rule(rule, &conn, None)
+            .await
+            .map_err(BluetoothError::from_dbus)?;
+
+        proxy
+            .start_notify()
+            .await
+            .map_err(BluetoothError::from_dbus)?;
+        Ok((proxy, stream))
+    };
+    let (proxy, mut stream) = match setup.await {
+        Ok(setup) => setup,
+        Err(e) => {
+            SUBSCRIPTIONS.lock().unwrap().remove(path);
+            return Err(e);
+        }
+    };
+
+    loop {
+        tokio::select! {
+            _ = &mut stopped => break,
+            message = stream.next() => {
+                let Some(Ok(message)) = message else {
+                    break;
+                };
+                let Ok((_, changed, _)) = message
+                    .body()
+                    .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>()
+                else {
+                    continue;
+                };
+
+                if let Some(value) = property::<Vec<u8>>(&changed, "Value") {
+                    callback(GattNotification {
+                        characteristic: path.to_string(),
+                        value: GattValue::decode(value),
+                    });
+                }
+                // BlueZ stops notifying when the device disconnects
+                if property::<bool>(&changed, "Notifying") == Some(false) {
+                    break;
+                }
+            }
+        }
+    }
+
+    SUBSCRIPTIONS.lock().unwrap().remove(path);
+    let _ = proxy.stop_notify().await;
+    Ok(())
+}
+
+/// Stops a subscription started with [`subscribe`]
+///
+/// # Returns
+/// - `Ok(())` if the characteristic was subscribed
+/// - `Err(BluetoothError::DoesNotExist)` if there was no subscription
+pub fn unsubscribe(path: &str) -> Result<(), BluetoothError> {
+    SUBSCRIPTIONS
+        .lock()
+        .unwrap()
+        .remove(path)
+        .map(|_| ())
+        .ok_or(BluetoothError::DoesNotExist)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::{sync::mpsc, time};
+    use zbus::Connection;
+
+    use super::*;
+    use crate::bluetooth::{
+        bluez::with_connection,
+        mock_bluez::{
+            add_device, add_object, Calls, MockCharacteristic, MockDescriptor, MockDevice,
+            MockService, PrivateBus,
+        },
+    };
+
+    const DEVICE: &str = "/org/bluez/hci0/dev_00_11_22_33_44_55";
+    const BATTERY_SERVICE: &str = "/org/bluez/hci0/dev_00_11_22_33_44_55/service000a";
+    const BATTERY_LEVEL: &str = "/org/bluez/hci0/dev_00_11_22_33_44_55/service000a/char000b";
+    const PRESENTATION: &str =
+        "/org/bluez/hci0/dev_00_11_22_33_44_55/service000a/char000b/desc000d";
+    const HEART_RATE_SERVICE: &str = "/org/bluez/hci0/dev_00_11_22_33_44_55/service0010";
+
+    /// A connected device with a battery service (one characteristic with a
+    /// descriptor) and an empty heart rate service
+    async fn serve_device(bus: &PrivateBus, calls: &Calls) -> Connection {
+        let bluez = bus.serve_bluez().await;
+        let mut device = MockDevice::new("/org/bluez/hci0", "00:11:22:33:44:55", calls);
+        device.connected = true;
+        add_device(&bluez, device).await;
+
+        for (path, uuid) in [
+            (BATTERY_SERVICE, "0000180f-0000-1000-8000-00805f9b34fb"),
+            (HEART_RATE_SERVICE, "0000180d-0000-1000-8000-00805f9b34fb"),
+        ] {
+            let service = MockService {
+                uuid: uuid.to_string(),
+                device: DEVICE.to_string(),
+            };
+            add_object(&bluez, path, service).await;
+        }
+        let mut level = MockCharacteristic::new(
+            BATTERY_SERVICE,
+            "char000b",
+            "00002a19-0000-1000-8000-00805f9b34fb",
+            calls,
+        );
+        level.value = vec![87];
+        add_object(&bluez, BATTERY_LEVEL, level).await;
+        let presentation = MockDescriptor {
+            path: PRESENTATION.to_string(),
+            uuid: "00002904-0000-1000-8000-00805f9b34fb".to_string(),
+            characteristic: BATTERY_LEVEL.to_string(),
+            value: vec![4, 0],
+            calls: calls.clone(),
+        };
+        add_object(&bluez, PRESENTATION, presentation).await;
+        bluez
+    }
+
+    #[tokio::test]
+    async fn groups_attributes_by_service_and_characteristic() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let calls = Calls::default();
+        let _bluez = serve_device(&bus, &calls).await;
+
+        let services = with_connection(bus.connect().await, get_gatt_services(DEVICE))
+            .await
+            .unwrap();
+
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].path, BATTERY_SERVICE);
+        assert_eq!(services[0].name.as_deref(), Some("Battery"));
+        assert!(services[0].primary);
+        assert_eq!(services[0].characteristics.len(), 1);
+        let level = &services[0].characteristics[0];
+        assert_eq!(level.path, BATTERY_LEVEL);
+        assert_eq!(level.flags, ["read", "write", "notify"]);
+        assert_eq!(level.descriptors.len(), 1);
+        assert_eq!(level.descriptors[0].path, PRESENTATION);
+        assert_eq!(services[1].path, HEART_RATE_SERVICE);
+        assert!(services[1].characteristics.is_empty());
+    }
+
+    #[tokio::test]
+    async fn needs_a_connected_device() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let bluez = bus.serve_bluez().await;
+        let calls = Calls::default();
+        add_device(
+            &bluez,
+            MockDevice::new("/org/bluez/hci0", "00:11:22:33:44:55", &calls),
+        )
+        .await;
+
+        let result = with_connection(bus.connect().await, get_gatt_services(DEVICE)).await;
+        assert!(matches!(result, Err(BluetoothError::NotConnected)));
+    }
+
+    #[tokio::test]
+    async fn reads_and_writes_characteristics_and_descriptors() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let calls = Calls::default();
+        let _bluez = serve_device(&bus, &calls).await;
+
+        with_connection(bus.connect().await, async {
+            let level = read_value(BATTERY_LEVEL).await.unwrap();
+            assert_eq!(level.bytes, [87]);
+            assert_eq!(level.uint, Some(87));
+            assert_eq!(read_value(PRESENTATION).await.unwrap().bytes, [4, 0]);
+
+            write_value(BATTERY_LEVEL, &[1, 2], GattWriteType::WithResponse)
+                .await
+                .unwrap();
+            write_value(BATTERY_LEVEL, &[3], GattWriteType::WithoutResponse)
+                .await
+                .unwrap();
+            write_value(PRESENTATION, &[5, 0], GattWriteType::WithoutResponse)
+                .await
+                .unwrap();
+            assert_eq!(read_value(BATTERY_LEVEL).await.unwrap().bytes, [3]);
+
+            let missing = format!("{}/char00ff", BATTERY_SERVICE);
+            assert!(matches!(
+                read_value(&missing).await,
+                Err(BluetoothError::DoesNotExist)
+            ));
+        })
+        .await;
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                format!("WriteValue {} request [1, 2]", BATTERY_LEVEL),
+                format!("WriteValue {} command [3]", BATTERY_LEVEL),
+                format!("WriteValue {} [5, 0]", PRESENTATION),
+            ]
+        );
+    }
+
+    /// Waits until the mock received `call`
+    async fn wait_for(calls: &Calls, call: &str) {
+        for _ in 0..100 {
+            if calls.lock().unwrap().iter().any(|c| c == call) {
+                return;
+            }
+            time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("{} was never called", call);
+    }
+
+    /// Changes the value of the battery level and notifies subscribers
+    async fn notify(bluez: &Connection, value: Vec<u8>, notifying: bool) {
+        let iface = bluez
+            .object_server()
+            .interface::<_, MockCharacteristic>(BATTERY_LEVEL)
+            .await
+            .unwrap();
+        let mut characteristic = iface.get_mut().await;
+        characteristic.value = value;
+        characteristic
+            .value_changed(iface.signal_emitter())
+            .await
+            .unwrap();
+        if !notifying {
+            characteristic.notifying = false;
+            characteristic
+                .notifying_changed(iface.signal_emitter())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_notifications_until_unsubscribed_or_stopped() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let calls = Calls::default();
+        let bluez = serve_device(&bus, &calls).await;
+        let conn = bus.connect().await;
+
+        let (sender, mut notifications) = mpsc::unbounded_channel();
+        let subscription = tokio::spawn(with_connection(conn.clone(), async move {
+            subscribe(BATTERY_LEVEL, move |notification| {
+                let _ = sender.send(notification);
+            })
+            .await
+        }));
+        wait_for(&calls, &format!("StartNotify {}", BATTERY_LEVEL)).await;
+
+        let again = with_connection(conn.clone(), subscribe(BATTERY_LEVEL, |_| {})).await;
+        assert!(matches!(again, Err(BluetoothError::InProgress)));
+
+        notify(&bluez, vec![86], true).await;
+        notify(&bluez, vec![85], true).await;
+        for level in [86, 85] {
+            let notification = notifications.recv().await.unwrap();
+            assert_eq!(notification.characteristic, BATTERY_LEVEL);
+            assert_eq!(notification.value.bytes, [level]);
+        }
+
+        unsubscribe(BATTERY_LEVEL).unwrap();
+        subscription.await.unwrap().unwrap();
+        wait_for(&calls, &format!("StopNotify {}", BATTERY_LEVEL)).await;
+        assert!(matches!(
+            unsubscribe(BATTERY_LEVEL),
+            Err(BluetoothError::DoesNotExist)
+        ));
+
+        // Subscriptions also end when the device stops notifying
+        calls.lock().unwrap().clear();
+        let subscription = tokio::spawn(with_connection(conn, subscribe(BATTERY_LEVEL, |_| {})));
+        wait_for(&calls, &format!("StartNotify {}", BATTERY_LEVEL)).await;
+        // The device disconnected
+        notify(&bluez, vec![84], false).await;
+        time::timeout(Duration::from_secs(5), subscription)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(unsubscribe(BATTERY_LEVEL).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+
+use super::bluez_error::BluetoothError;
+
+/// A GATT service of a connected LE device (`org.bluez.GattService1`)
+#[derive(Debug, Clone, Serialize)]
+pub struct GattService {
+    /// D-Bus object path (e.g. `/org/bluez/hci0/dev_.../service000a`)
+    pub path: String,
+    pub uuid: String,
+    /// Name from the assigned numbers list, `None` for vendor services
+    pub name: Option<String>,
+    /// Primary services are the ones a device exposes directly
+    pub primary: bool,
+    pub characteristics: Vec<GattCharacteristic>,
+}
+
+/// A characteristic of a GATT service (`org.bluez.GattCharacteristic1`)
+#[derive(Debug, Clone, Serialize)]
+pub struct GattCharacteristic {
+    pub path: String,
+    pub uuid: String,
+    pub name: Option<String>,
+    /// Supported operations, e.g. `read`, `write-without-response` or `notify`
+    pub flags: Vec<String>,
+    /// Whether notifications or indications are currently enabled
+    pub notifying: bool,
+    pub descriptors: Vec<GattDescriptor>,
+}
+
+/// A descriptor of a characteristic (`org.bluez.GattDescriptor1`)
+#[derive(Debug, Clone, Serialize)]
+pub struct GattDescriptor {
+    pub path: String,
+    pub uuid: String,
+    pub name: Option<String>,
+    pub flags: Vec<String>,
+}
+
+/// How a characteristic value is written
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub enum GattWriteType {
+    /// The device acknowledges the write (ATT Write Request)
+    #[default]
+    WithResponse,
+    /// Fire and forget (ATT Write Command)
+    WithoutResponse,
+}
+
+/// A value as entered by the user, encoded to bytes before writing
+#[derive(Debug, Clone, Deserialize)]
+pub enum GattValueInput {
+    /// Hex digits, optionally separated by spaces or colons (`01 ff`, `01:ff`)
+    Hex(String),
+    Utf8(String),
+    /// Unsigned little-endian integer of `size` bytes
+    Uint {
+        value: u64,
+        size: u8,
+    },
+    /// Signed little-endian integer of `size` bytes
+    Int {
+        value: i64,
+        size: u8,
+    },
+}
+
+impl GattValueInput {
+    /// Encodes the value to the bytes sent to the device
+    ///
+    /// # Returns
+    /// - `Ok(Vec<u8>)` with the encoded value
+    /// - `Err(BluetoothError::InvalidArguments)` for malformed hex, a size other
+    ///   than 1 to 8 bytes or a number that does not fit in `size` bytes
+    pub fn encode(&self) -> Result<Vec<u8>, BluetoothError> {
+        match self {
+            GattValueInput::Hex(hex) => {
+                let digits: String = hex
+                    .chars()
+                    .filter(|c| !matches!(c, ' ' | ':' | '-'))
+                    .collect();
+                let digits = digits.strip_prefix("0x").unwrap_or(&digits);
+                // Only ASCII hex digits are left, so slicing by two bytes is safe
+                if !digits.len().is_multiple_of(2) || !digits.bytes().all(|b| b.is_ascii_hexdigit())
+                {
+                    return Err(BluetoothError::InvalidArguments);
+                }
+
+                (0..digits.len())
+                    .step_by(2)
+                    .map(|i| {
+                        u8::from_str_radix(&digits[i..i + 2], 16)
+                            .map_err(|_| BluetoothError::InvalidArguments)
+                    })
+                    .collect()
+            }
+            GattValueInput::Utf8(text) => Ok(text.as_bytes().to_vec()),
+            GattValueInput::Uint { value, size } => {
+                let size = integer_size(*size)?;
+                if size < 8 && *value >> (size * 8) != 0 {
+                    return Err(BluetoothError::InvalidArguments);
+                }
+                Ok(value.to_le_bytes()[..size].to_vec())
+            }
+            GattValueInput::Int { value, size } => {
+                let size = integer_size(*size)?;
+                let bits = size as u32 * 8;
+                if bits < 64 && (*value < -(1 << (bits - 1)) || *value >= 1 << (bits - 1)) {
+                    return Err(BluetoothError::InvalidArguments);
+                }
+                Ok(value.to_le_bytes()[..size].to_vec())
+            }
+        }
+    }
+}
+
+/// Checks that an integer size is between 1 and 8 bytes
+fn integer_size(size: u8) -> Result<usize, BluetoothError> {
+    match size {
+        1..=8 => Ok(size as usize),
+        _ => Err(BluetoothError::InvalidArguments),
+    }
+}
+
+/// A value read from or notified by a device, shown in several representations
+#[derive(Debug, Clone, Serialize)]
+pub struct GattValue {
+    pub bytes: Vec<u8>,
+    /// Space separated hex bytes (`01 ff`)
+    pub hex: String,
+    /// The value as text, if it is valid UTF-8 without control characters
+    pub utf8: Option<String>,
+    /// Little-endian unsigned integer, for values of 1 to 8 bytes
+    pub uint: Option<u64>,
+    /// Little-endian signed integer, for values of 1 to 8 bytes
+    pub int: Option<i64>,
+}
+
+impl GattValue {
+    /// Decodes raw bytes into every supported representation
+    pub fn decode(bytes: Vec<u8>) -> Self {
+        let hex = bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let utf8 = String::from_utf8(bytes.clone())
+            .ok()
+            // Strings are often padded with trailing zeros
+            .map(|s| s.trim_end_matches('\0').to_string())
+            .filter(|s| !s.is_empty() && !s.chars().any(char::is_control));
+
+        let (uint, int) = if (1..=8).contains(&bytes.len()) {
+            let mut buffer = [0u8; 8];
+            buffer[..bytes.len()].copy_from_slice(&bytes);
+            let uint = u64::from_le_bytes(buffer);
+            // Sign-extend from the top bit of the last byte
+            let shift = 64 - bytes.len() as u32 * 8;
+            let int = ((uint << shift) as i64) >> shift;
+            (Some(uint), Some(int))
+        } else {
+            (None, None)
+        };
+
+        GattValue {
+            bytes,
+            hex,
+            utf8,
+            uint,
+            int,
+        }
+    }
+}
+
+/// A notification or indication received from a subscribed characteristic
+#[derive(Debug, Clone, Serialize)]
+pub struct GattNotification {
+    /// Object path of the characteristic
+    pub characteristic: String,
+    pub value: GattValue,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(input: GattValueInput) -> Option<Vec<u8>> {
+        input.encode().ok()
+    }
+
+    #[test]
+    fn encodes_hex_with_separators() {
+        let hex = |s: &str| encode(GattValueInput::Hex(s.to_string()));
+        assert_eq!(hex("01ff"), Some(vec![0x01, 0xff]));
+        assert_eq!(hex("01 FF"), Some(vec![0x01, 0xff]));
+        assert_eq!(hex("01:ff-0a"), Some(vec![0x01, 0xff, 0x0a]));
+        assert_eq!(hex("0x01ff"), Some(vec![0x01, 0xff]));
+        assert_eq!(hex(""), Some(vec![]));
+    }
+
+    #[test]
+    fn rejects_malformed_hex() {
+        let hex = |s: &str| encode(GattValueInput::Hex(s.to_string()));
+        assert_eq!(hex("0"), None);
+        assert_eq!(hex("0g"), None);
+        assert_eq!(hex("+f"), None);
+        // Multibyte characters must not be sliced through
+        assert_eq!(hex("é1"), None);
+        assert_eq!(hex("€f"), None);
+        assert_eq!(hex("0€"), None);
+    }
+
+    #[test]
+    fn encodes_utf8() {
+        assert_eq!(
+            encode(GattValueInput::Utf8("hé".to_string())),
+            Some(vec![b'h', 0xc3, 0xa9])
+        );
+    }
+
+    #[test]
+    fn encodes_integers_little_endian_within_range() {
+        let uint = |value, size| encode(GattValueInput::Uint { value, size });
+        assert_eq!(uint(0x1234, 2), Some(vec![0x34, 0x12]));
+        assert_eq!(uint(255, 1), Some(vec![0xff]));
+        assert_eq!(uint(256, 1), None);
+        assert_eq!(uint(u64::MAX, 8), Some(vec![0xff; 8]));
+        assert_eq!(uint(1, 0), None);
+        assert_eq!(uint(1, 9), None);
+
+        let int = |value, size| encode(GattValueInput::Int { value, size });
+        assert_eq!(int(-1, 2), Some(vec![0xff, 0xff]));
+        assert_eq!(int(-128, 1), Some(vec![0x80]));
+        assert_eq!(int(127, 1), Some(vec![0x7f]));
+        assert_eq!(int(128, 1), None);
+        assert_eq!(int(-129, 1), None);
+        assert_eq!(int(i64::MIN, 8), Some(i64::MIN.to_le_bytes().to_vec()));
+    }
+
+    #[test]
+    fn decodes_every_representation() {
+        let value = GattValue::decode(vec![0x34, 0x12]);
+        assert_eq!(value.hex, "34 12");
+        assert_eq!(value.uint, Some(0x1234));
+        assert_eq!(value.int, Some(0x1234));
+        // 0x12 is a control character, so the value is no text
+        assert_eq!(value.utf8, None);
+
+        // The top bit of the last byte is the sign
+        let negative = GattValue::decode(vec![0xfe, 0xff]);
+        assert_eq!(negative.uint, Some(0xfffe));
+        assert_eq!(negative.int, Some(-2));
+
+        let full = GattValue::decode(vec![0xff; 8]);
+        assert_eq!((full.uint, full.int), (Some(u64::MAX), Some(-1)));
+    }
+
+    #[test]
+    fn decodes_padded_text_and_long_values() {
+        let name = GattValue::decode(b"Sensor\0\0".to_vec());
+        assert_eq!(name.utf8.as_deref(), Some("Sensor"));
+
+        let long = GattValue::decode(b"Temperature sensor".to_vec());
+        assert_eq!(long.utf8.as_deref(), Some("Temperature sensor"));
+        assert_eq!((long.uint, long.int), (None, None));
+
+        let empty = GattValue::decode(Vec::new());
+        assert_eq!(
+            (empty.hex.as_str(), empty.utf8, empty.uint),
+            ("", None, None)
+        );
+
+        assert_eq!(GattValue::decode(vec![0xc3, 0x28]).utf8, None);
+    }
+}
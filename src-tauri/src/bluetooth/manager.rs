@@ -0,0 +1,191 @@
+use super::{
+    adapter::{get_adapters, set_adapter_setting},
+    adapter_data::{AdapterSetting, BluetoothAdapter},
+    bluez_error::{BluetoothError, PairingError},
+    device::{
+        connect_device, disconnect_device, get_paired_devices, remove_device, set_device_setting,
+    },
+    device_data::{BluetoothDevice, DeviceSetting},
+    discovery::get_devices,
+    pairing::pair_device,
+};
+
+/// Trait defining operations for managing Bluetooth devices, the counterpart of `WifiManager`
+// Callers use the concrete implementation, so the futures stay `Send` where needed
+#[allow(async_fn_in_trait)]
+pub trait BluetoothManager {
+    /// Lists the Bluetooth controllers
+    ///
+    /// # Returns
+    /// - `Ok(Vec<BluetoothAdapter>)` containing every adapter
+    /// - `Err(BluetoothError)` if BlueZ cannot be reached
+    async fn adapters() -> Result<Vec<BluetoothAdapter>, BluetoothError>;
+
+    /// Changes a property of an adapter
+    ///
+    /// # Arguments
+    /// * `adapter` - adapter name (`hci0`) or object path
+    /// * `setting` - the property to change and its new value
+    async fn set_adapter(adapter: &str, setting: AdapterSetting) -> Result<(), BluetoothError>;
+
+    /// Lists the devices BlueZ knows about
+    ///
+    /// # Arguments
+    /// * `adapter` - only return devices seen by this adapter
+    ///
+    /// # Returns
+    /// - `Ok(Vec<BluetoothDevice>)` containing discovered and paired devices
+    /// - `Err(BluetoothError)` if BlueZ cannot be reached
+    async fn scan(adapter: Option<&str>) -> Result<Vec<BluetoothDevice>, BluetoothError>;
+
+    /// Lists the paired devices
+    ///
+    /// # Arguments
+    /// * `adapter` - only return devices paired with this adapter
+    async fn paired(adapter: Option<&str>) -> Result<Vec<BluetoothDevice>, BluetoothError>;
+
+    /// Pairs with a device
+    ///
+    /// # Arguments
+    /// * `device` - object path or address of the device
+    ///
+    /// # Returns
+    /// - `Ok(())` if pairing succeeds
+    /// - `Err(PairingError)` if pairing fails
+    async fn pair(device: &str) -> Result<(), PairingError>;
+
+    /// Connects a device or one of its profiles
+    ///
+    /// # Arguments
+    /// * `device` - object path or address of the device
+    /// * `profile` - UUID of a single profile to connect
+    ///
+    /// # Returns
+    /// - `Ok(())` if connection succeeds
+    /// - `Err(BluetoothError)` if connection fails
+    async fn connect(device: &str, profile: Option<&str>) -> Result<(), BluetoothError>;
+
+    /// Disconnects a device or one of its profiles
+    ///
+    /// # Arguments
+    /// * `device` - object path or address of the device
+    /// * `profile` - UUID of a single profile to disconnect
+    async fn disconnect(device: &str, profile: Option<&str>) -> Result<(), BluetoothError>;
+
+    /// Changes the trusted or blocked state of a device
+    ///
+    /// # Arguments
+    /// * `device` - object path or address of the device
+    /// * `setting` - the property to change and its new value
+    async fn set_device(device: &str, setting: DeviceSetting) -> Result<(), BluetoothError>;
+
+    /// Removes (unpairs) a device
+    ///
+    /// # Arguments
+    /// * `device` - object path or address of the device
+    async fn remove(device: &str) -> Result<(), BluetoothError>;
+}
+
+impl BluetoothManager for BluetoothDevice {
+    async fn adapters() -> Result<Vec<BluetoothAdapter>, BluetoothError> {
+        get_adapters().await
+    }
+
+    async fn set_adapter(adapter: &str, setting: AdapterSetting) -> Result<(), BluetoothError> {
+        set_adapter_setting(adapter, setting).await
+    }
+
+    async fn scan(adapter: Option<&str>) -> Result<Vec<Self>, BluetoothError> {
+        get_devices(adapter).await
+    }
+
+    async fn paired(adapter: Option<&str>) -> Result<Vec<Self>, BluetoothError> {
+        get_paired_devices(adapter).await
+    }
+
+    async fn pair(device: &str) -> Result<(), PairingError> {
+        pair_device(device).await
+    }
+
+    async fn connect(device: &str, profile: Option<&str>) -> Result<(), BluetoothError> {
+        connect_device(device, profile).await
+    }
+
+    async fn disconnect(device: &str, profile: Option<&str>) -> Result<(), BluetoothError> {
+        disconnect_device(device, profile).await
+    }
+
+    async fn set_device(device: &str, setting: DeviceSetting) -> Result<(), BluetoothError> {
+        set_device_setting(device, setting).await
+    }
+
+    async fn remove(device: &str) -> Result<(), BluetoothError> {
+        remove_device(device).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluetooth::{
+        bluez::with_connection,
+        mock_bluez::{add_adapter, add_device, Calls, MockAdapter, MockDevice, PrivateBus},
+    };
+
+    #[tokio::test]
+    async fn manages_adapters_and_devices() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let bluez = bus.serve_bluez().await;
+        let calls = Calls::default();
+        add_adapter(
+            &bluez,
+            "/org/bluez/hci0",
+            MockAdapter::new("00:00:00:00:00:01", &calls),
+        )
+        .await;
+        let keyboard = MockDevice::new("/org/bluez/hci0", "00:11:22:33:44:55", &calls);
+        let keyboard_path = keyboard.path.clone();
+        add_device(&bluez, keyboard).await;
+
+        with_connection(bus.connect().await, async {
+            BluetoothDevice::set_adapter("hci0", AdapterSetting::Pairable(false))
+                .await
+                .unwrap();
+            let adapters = BluetoothDevice::adapters().await.unwrap();
+            assert!(!adapters[0].pairable);
+
+            let devices = BluetoothDevice::scan(Some("hci0")).await.unwrap();
+            assert_eq!(devices.len(), 1);
+            assert!(BluetoothDevice::paired(None).await.unwrap().is_empty());
+
+            BluetoothDevice::pair("00:11:22:33:44:55").await.unwrap();
+            BluetoothDevice::set_device("00:11:22:33:44:55", DeviceSetting::Trusted(true))
+                .await
+                .unwrap();
+            BluetoothDevice::connect("00:11:22:33:44:55", None)
+                .await
+                .unwrap();
+            let paired = BluetoothDevice::paired(None).await.unwrap();
+            assert!(paired[0].paired && paired[0].trusted && paired[0].connected);
+
+            BluetoothDevice::disconnect("00:11:22:33:44:55", None)
+                .await
+                .unwrap();
+            BluetoothDevice::remove("00:11:22:33:44:55").await.unwrap();
+        })
+        .await;
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                format!("Pair {}", keyboard_path),
+                format!("Connect {}", keyboard_path),
+                format!("Disconnect {}", keyboard_path),
+                format!("RemoveDevice {}", keyboard_path),
+            ]
+        );
+    }
+}
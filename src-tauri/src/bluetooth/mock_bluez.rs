@@ -0,0 +1,559 @@
+//! Private D-Bus daemons and a BlueZ stand-in for tests
+//!
+//! Tests start a [`PrivateBus`], export mock objects on the connection
+//! returned by [`PrivateBus::serve_bluez`] and run the code under test
+//! within [`super::bluez::with_connection`].
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+};
+
+use zbus::{
+    connection,
+    fdo::{self, ObjectManager},
+    interface,
+    object_server::SignalEmitter,
+    zvariant::{OwnedObjectPath, OwnedValue},
+    Connection, DBusError,
+};
+
+use super::bluez::BLUEZ_SERVICE;
+
+/// Method calls received by the mock objects, e.g. `Connect /org/bluez/hci0/dev_...`
+pub type Calls = Arc<Mutex<Vec<String>>>;
+
+/// A dbus-daemon of its own, killed when dropped
+pub struct PrivateBus {
+    daemon: Child,
+    pub address: String,
+}
+
+impl PrivateBus {
+    /// Starts a private bus, `None` if dbus-daemon is not installed
+    pub fn start() -> Option<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some(PrivateBus {
+            daemon,
+            address: address.trim().to_string(),
+        })
+    }
+
+    /// A new client connection to the bus
+    pub async fn connect(&self) -> Connection {
+        connection::Builder::address(self.address.as_str())
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+    }
+
+    /// Owns `org.bluez` and serves an object manager at `/`, mock objects
+    /// are added to the object server of the returned connection
+    pub async fn serve_bluez(&self) -> Connection {
+        connection::Builder::address(self.address.as_str())
+            .unwrap()
+            .name(BLUEZ_SERVICE)
+            .unwrap()
+            .serve_at("/", ObjectManager)
+            .unwrap()
+            .build()
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// Errors BlueZ answers with
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "org.bluez.Error")]
+pub enum MockError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    AlreadyConnected(String),
+    NotConnected(String),
+    NotAvailable(String),
+    AlreadyExists(String),
+    AuthenticationFailed(String),
+}
+
+/// `org.bluez.Adapter1` with the properties wiblue reads and changes
+pub struct MockAdapter {
+    pub address: String,
+    pub alias: String,
+    pub powered: bool,
+    pub discoverable: bool,
+    pub discoverable_timeout: u32,
+    pub pairable: bool,
+    pub calls: Calls,
+}
+
+impl MockAdapter {
+    pub fn new(address: &str, calls: &Calls) -> Self {
+        MockAdapter {
+            address: address.to_string(),
+            alias: format!("Adapter {}", address),
+            powered: true,
+            discoverable: false,
+            discoverable_timeout: 180,
+            pairable: true,
+            calls: calls.clone(),
+        }
+    }
+}
+
+#[interface(name = "org.bluez.Adapter1")]
+impl MockAdapter {
+    #[zbus(property)]
+    fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    #[zbus(property)]
+    fn address_type(&self) -> String {
+        "public".to_string()
+    }
+
+    #[zbus(property)]
+    fn name(&self) -> String {
+        "mock".to_string()
+    }
+
+    #[zbus(property)]
+    fn alias(&self) -> String {
+        self.alias.clone()
+    }
+
+    #[zbus(property)]
+    fn set_alias(&mut self, value: String) {
+        self.alias = value;
+    }
+
+    #[zbus(property)]
+    fn class(&self) -> u32 {
+        0x7c010c
+    }
+
+    #[zbus(property)]
+    fn powered(&self) -> bool {
+        self.powered
+    }
+
+    #[zbus(property)]
+    fn set_powered(&mut self, value: bool) {
+        self.powered = value;
+    }
+
+    #[zbus(property)]
+    fn discoverable(&self) -> bool {
+        self.discoverable
+    }
+
+    #[zbus(property)]
+    fn set_discoverable(&mut self, value: bool) {
+        self.discoverable = value;
+    }
+
+    #[zbus(property)]
+    fn discoverable_timeout(&self) -> u32 {
+        self.discoverable_timeout
+    }
+
+    /// BlueZ limits the timeout like the kernel does
+    #[zbus(property)]
+    fn set_discoverable_timeout(&mut self, value: u32) -> fdo::Result<()> {
+        if value > 3600 {
+            return Err(fdo::Error::InvalidArgs("Timeout too long".to_string()));
+        }
+        self.discoverable_timeout = value;
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn pairable(&self) -> bool {
+        self.pairable
+    }
+
+    #[zbus(property)]
+    fn set_pairable(&mut self, value: bool) {
+        self.pairable = value;
+    }
+
+    #[zbus(property)]
+    fn pairable_timeout(&self) -> u32 {
+        0
+    }
+
+    #[zbus(property)]
+    fn discovering(&self) -> bool {
+        false
+    }
+
+    fn remove_device(&self, device: OwnedObjectPath) {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("RemoveDevice {}", device.as_str()));
+    }
+}
+
+/// `org.bluez.Device1` of a device in range
+pub struct MockDevice {
+    pub path: String,
+    pub address: String,
+    pub adapter: String,
+    pub rssi: i16,
+    pub paired: bool,
+    pub connected: bool,
+    pub trusted: bool,
+    pub blocked: bool,
+    pub uuids: Vec<String>,
+    pub calls: Calls,
+}
+
+impl MockDevice {
+    /// A device seen by `adapter` (e.g. `/org/bluez/hci0`)
+    pub fn new(adapter: &str, address: &str, calls: &Calls) -> Self {
+        MockDevice {
+            path: format!("{}/dev_{}", adapter, address.replace(':', "_")),
+            address: address.to_string(),
+            adapter: adapter.to_string(),
+            rssi: -60,
+            paired: false,
+            connected: false,
+            trusted: false,
+            blocked: false,
+            uuids: Vec::new(),
+            calls: calls.clone(),
+        }
+    }
+
+    fn record(&self, call: &str) {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("{} {}", call, self.path));
+    }
+}
+
+#[interface(name = "org.bluez.Device1")]
+impl MockDevice {
+    async fn connect(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<(), MockError> {
+        if self.connected {
+            return Err(MockError::AlreadyConnected("Already connected".into()));
+        }
+        self.record("Connect");
+        self.connected = true;
+        self.connected_changed(&emitter).await?;
+        Ok(())
+    }
+
+    async fn disconnect(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<(), MockError> {
+        if !self.connected {
+            return Err(MockError::NotConnected("Not connected".into()));
+        }
+        self.record("Disconnect");
+        self.connected = false;
+        self.connected_changed(&emitter).await?;
+        Ok(())
+    }
+
+    fn connect_profile(&self, uuid: String) -> Result<(), MockError> {
+        if !self.uuids.contains(&uuid) {
+            return Err(MockError::NotAvailable("Profile not available".into()));
+        }
+        self.record(&format!("ConnectProfile {}", uuid));
+        Ok(())
+    }
+
+    fn disconnect_profile(&self, uuid: String) -> Result<(), MockError> {
+        self.record(&format!("DisconnectProfile {}", uuid));
+        Ok(())
+    }
+
+    async fn pair(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> Result<(), MockError> {
+        if self.paired {
+            return Err(MockError::AlreadyExists("Already paired".into()));
+        }
+        self.record("Pair");
+        if self.blocked {
+            return Err(MockError::AuthenticationFailed("Blocked".into()));
+        }
+        self.paired = true;
+        self.paired_changed(&emitter).await?;
+        Ok(())
+    }
+
+    fn cancel_pairing(&self) {
+        self.record("CancelPairing");
+    }
+
+    #[zbus(property)]
+    fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    #[zbus(property)]
+    fn alias(&self) -> String {
+        format!("Device {}", self.address)
+    }
+
+    #[zbus(property)]
+    fn adapter(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(self.adapter.as_str()).unwrap()
+    }
+
+    #[zbus(property, name = "RSSI")]
+    fn rssi(&self) -> i16 {
+        self.rssi
+    }
+
+    #[zbus(property, name = "UUIDs")]
+    fn uuids(&self) -> Vec<String> {
+        self.uuids.clone()
+    }
+
+    #[zbus(property)]
+    fn paired(&self) -> bool {
+        self.paired
+    }
+
+    #[zbus(property)]
+    fn connected(&self) -> bool {
+        self.connected
+    }
+
+    #[zbus(property)]
+    fn services_resolved(&self) -> bool {
+        self.connected
+    }
+
+    #[zbus(property)]
+    fn trusted(&self) -> bool {
+        self.trusted
+    }
+
+    #[zbus(property)]
+    fn set_trusted(&mut self, value: bool) {
+        self.trusted = value;
+    }
+
+    #[zbus(property)]
+    fn blocked(&self) -> bool {
+        self.blocked
+    }
+
+    #[zbus(property)]
+    fn set_blocked(&mut self, value: bool) {
+        self.blocked = value;
+    }
+}
+
+/// `org.bluez.GattService1` of a connected device
+pub struct MockService {
+    pub uuid: String,
+    pub device: String,
+}
+
+#[interface(name = "org.bluez.GattService1")]
+impl MockService {
+    #[zbus(property, name = "UUID")]
+    fn uuid(&self) -> String {
+        self.uuid.clone()
+    }
+
+    #[zbus(property)]
+    fn primary(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn device(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(self.device.as_str()).unwrap()
+    }
+}
+
+/// `org.bluez.GattCharacteristic1`, change `value` and emit `value_changed` to notify
+pub struct MockCharacteristic {
+    pub path: String,
+    pub uuid: String,
+    pub service: String,
+    pub flags: Vec<String>,
+    pub value: Vec<u8>,
+    pub notifying: bool,
+    pub calls: Calls,
+}
+
+impl MockCharacteristic {
+    /// A characteristic at `<service>/<name>`
+    pub fn new(service: &str, name: &str, uuid: &str, calls: &Calls) -> Self {
+        MockCharacteristic {
+            path: format!("{}/{}", service, name),
+            uuid: uuid.to_string(),
+            service: service.to_string(),
+            flags: vec![
+                "read".to_string(),
+                "write".to_string(),
+                "notify".to_string(),
+            ],
+            value: Vec::new(),
+            notifying: false,
+            calls: calls.clone(),
+        }
+    }
+}
+
+/// Write type BlueZ was asked for, `request` unless the options say otherwise
+fn write_type(options: &HashMap<String, OwnedValue>) -> String {
+    options
+        .get("type")
+        .and_then(|value| String::try_from(value.clone()).ok())
+        .unwrap_or_else(|| "request".to_string())
+}
+
+#[interface(name = "org.bluez.GattCharacteristic1")]
+impl MockCharacteristic {
+    fn read_value(&self, _options: HashMap<String, OwnedValue>) -> Vec<u8> {
+        self.value.clone()
+    }
+
+    fn write_value(&mut self, value: Vec<u8>, options: HashMap<String, OwnedValue>) {
+        let call = format!(
+            "WriteValue {} {} {:?}",
+            self.path,
+            write_type(&options),
+            value
+        );
+        self.calls.lock().unwrap().push(call);
+        self.value = value;
+    }
+
+    async fn start_notify(
+        &mut self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("StartNotify {}", self.path));
+        self.notifying = true;
+        self.notifying_changed(&emitter).await?;
+        Ok(())
+    }
+
+    fn stop_notify(&mut self) {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("StopNotify {}", self.path));
+        self.notifying = false;
+    }
+
+    #[zbus(property, name = "UUID")]
+    fn uuid(&self) -> String {
+        self.uuid.clone()
+    }
+
+    #[zbus(property)]
+    fn service(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(self.service.as_str()).unwrap()
+    }
+
+    #[zbus(property)]
+    fn flags(&self) -> Vec<String> {
+        self.flags.clone()
+    }
+
+    #[zbus(property)]
+    fn value(&self) -> Vec<u8> {
+        self.value.clone()
+    }
+
+    #[zbus(property)]
+    fn notifying(&self) -> bool {
+        self.notifying
+    }
+}
+
+/// `org.bluez.GattDescriptor1`
+pub struct MockDescriptor {
+    pub path: String,
+    pub uuid: String,
+    pub characteristic: String,
+    pub value: Vec<u8>,
+    pub calls: Calls,
+}
+
+#[interface(name = "org.bluez.GattDescriptor1")]
+impl MockDescriptor {
+    fn read_value(&self, _options: HashMap<String, OwnedValue>) -> Vec<u8> {
+        self.value.clone()
+    }
+
+    fn write_value(&mut self, value: Vec<u8>, _options: HashMap<String, OwnedValue>) {
+        let call = format!("WriteValue {} {:?}", self.path, value);
+        self.calls.lock().unwrap().push(call);
+        self.value = value;
+    }
+
+    #[zbus(property, name = "UUID")]
+    fn uuid(&self) -> String {
+        self.uuid.clone()
+    }
+
+    #[zbus(property)]
+    fn characteristic(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(self.characteristic.as_str()).unwrap()
+    }
+
+    #[zbus(property)]
+    fn flags(&self) -> Vec<String> {
+        vec!["read".to_string(), "write".to_string()]
+    }
+}
+
+/// Exports an adapter at `path`, e.g. `/org/bluez/hci0`
+pub async fn add_adapter(bluez: &Connection, path: &str, adapter: MockAdapter) {
+    assert!(bluez.object_server().at(path, adapter).await.unwrap());
+}
+
+/// Exports a device at its path
+pub async fn add_device(bluez: &Connection, device: MockDevice) {
+    let path = device.path.clone();
+    assert!(bluez.object_server().at(path, device).await.unwrap());
+}
+
+/// Exports any mock object, e.g. a GATT attribute, at `path`
+pub async fn add_object<I: zbus::object_server::Interface>(
+    bluez: &Connection,
+    path: &str,
+    object: I,
+) {
+    assert!(bluez.object_server().at(path, object).await.unwrap());
+}
@@ -0,0 +1,25 @@
+pub mod adapter;
+pub mod adapter_data;
+pub mod agent;
+pub mod audio;
+pub mod audio_data;
+pub mod beacon;
+pub mod beacon_data;
+pub mod bluez;
+pub mod bluez_error;
+pub mod device;
+pub mod device_data;
+pub mod device_stats;
+pub mod discovery;
+pub mod gatt;
+pub mod gatt_data;
+pub mod manager;
+#[cfg(test)]
+pub(crate) mod mock_bluez;
+pub mod obex;
+pub mod obex_agent;
+pub mod obex_data;
+pub mod pairing;
+pub mod pan;
+pub mod pan_data;
+pub mod uuid_names;
@@ -0,0 +1,520 @@
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+};
+
+use futures::StreamExt;
+use tokio::sync::OnceCell;
+use zbus::{
+    connection,
+    message::Type as MessageType,
+    proxy,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+    Connection, MatchRule, MessageStream,
+};
+
+use super::{
+    bluez::property,
+    bluez_error::ObexError,
+    device::device_proxy,
+    obex_data::{
+        ObexFolderEntry, ObexSettings, TransferDirection, TransferProgress, TransferStatus,
+    },
+};
+
+/// Well-known bus name of the BlueZ OBEX daemon
+pub const OBEX_SERVICE: &str = "org.bluez.obex";
+pub const TRANSFER_INTERFACE: &str = "org.bluez.obex.Transfer1";
+
+/// Bus address to use instead of the session bus, e.g. a mock obexd on a private bus
+pub const OBEX_BUS_ADDRESS_ENV: &str = "WIBLUE_OBEX_BUS_ADDRESS";
+
+static CONNECTION: OnceCell<Connection> = OnceCell::const_new();
+
+/// Returns the shared connection to the bus obexd lives on
+///
+/// obexd runs per user, so this is the session bus unless
+/// `WIBLUE_OBEX_BUS_ADDRESS` is set.
+pub async fn obex_connection() -> Result<Connection, ObexError> {
+    CONNECTION
+        .get_or_try_init(|| async {
+            let builder = match env::var(OBEX_BUS_ADDRESS_ENV) {
+                Ok(address) => connection::Builder::address(address.as_str()),
+                Err(_) => connection::Builder::session(),
+            };
+
+            match builder {
+                Ok(b) => b.build().await,
+                Err(e) => Err(e),
+            }
+            .map_err(|e| {
+                eprintln!("Error connecting to the OBEX bus: {:?}", e);
+                ObexError::ConnectionFailure
+            })
+        })
+        .await
+        .cloned()
+}
+
+#[proxy(
+    interface = "org.bluez.obex.Client1",
+    default_service = "org.bluez.obex",
+    default_path = "/org/bluez/obex"
+)]
+trait Client1 {
+    fn create_session(
+        &self,
+        destination: &str,
+        args: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedObjectPath>;
+
+    fn remove_session(&self, session: &ObjectPath<'_>) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.bluez.obex.ObjectPush1",
+    default_service = "org.bluez.obex"
+)]
+trait ObjectPush1 {
+    fn send_file(
+        &self,
+        sourcefile: &str,
+    ) -> zbus::Result<(OwnedObjectPath, HashMap<String, OwnedValue>)>;
+}
+
+#[proxy(
+    interface = "org.bluez.obex.FileTransfer1",
+    default_service = "org.bluez.obex"
+)]
+trait FileTransfer1 {
+    fn change_folder(&self, folder: &str) -> zbus::Result<()>;
+
+    fn list_folder(&self) -> zbus::Result<Vec<HashMap<String, OwnedValue>>>;
+
+    fn get_file(
+        &self,
+        targetfile: &str,
+        sourcefile: &str,
+    ) -> zbus::Result<(OwnedObjectPath, HashMap<String, OwnedValue>)>;
+}
+
+#[proxy(
+    interface = "org.bluez.obex.Transfer1",
+    default_service = "org.bluez.obex"
+)]
+pub trait Transfer1 {
+    fn cancel(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn name(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn size(&self) -> zbus::Result<u64>;
+
+    #[zbus(property, name = "Type")]
+    fn mime_type(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn session(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[proxy(
+    interface = "org.bluez.obex.Session1",
+    default_service = "org.bluez.obex"
+)]
+pub trait Session1 {
+    #[zbus(property)]
+    fn destination(&self) -> zbus::Result<String>;
+}
+
+/// An OBEX session, removed again with [`Session::close`]
+struct Session {
+    conn: Connection,
+    path: OwnedObjectPath,
+}
+
+impl Session {
+    /// Opens a session to a device for the given OBEX target (`opp` or `ftp`)
+    async fn open(device: &str, target: &str) -> Result<Self, ObexError> {
+        let address = device_address(device).await?;
+        let conn = obex_connection().await?;
+
+        let mut args: HashMap<&str, Value<'_>> = HashMap::new();
+        args.insert("Target", Value::from(target));
+        let path = Client1Proxy::new(&conn)
+            .await
+            .map_err(ObexError::from_dbus)?
+            .create_session(&address, args)
+            .await
+            .map_err(ObexError::from_dbus)?;
+
+        Ok(Session { conn, path })
+    }
+
+    /// Watches transfers of this session, must be created before starting one
+    async fn transfer_stream(&self) -> Result<MessageStream, ObexError> {
+        transfer_stream(&self.conn, self.path.as_str()).await
+    }
+
+    async fn close(self) {
+        if let Ok(client) = Client1Proxy::new(&self.conn).await {
+            let _ = client.remove_session(&self.path).await;
+        }
+    }
+}
+
+/// Turns a device object path or address into an address
+async fn device_address(device: &str) -> Result<String, ObexError> {
+    if !device.starts_with('/') {
+        return Ok(device.to_string());
+    }
+
+    device_proxy(device)
+        .await
+        .map_err(ObexError::Bluetooth)?
+        .address()
+        .await
+        .map_err(|_| ObexError::NotFound)
+}
+
+/// Subscribes to property changes of every transfer below `namespace`
+pub(crate) async fn transfer_stream(
+    conn: &Connection,
+    namespace: &str,
+) -> Result<MessageStream, ObexError> {
+    let rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .sender(OBEX_SERVICE)
+        .and_then(|b| b.path_namespace(namespace.to_string()))
+        .and_then(|b| b.interface("org.freedesktop.DBus.Properties"))
+        .and_then(|b| b.member("PropertiesChanged"))
+        .map_err(ObexError::from_dbus)?
+        .build();
+
+    MessageStream::for_match_rule(rule, conn, None)
+        .await
+        .map_err(ObexError::from_dbus)
+}
+
+/// Reports the progress of a transfer until it completes or fails
+///
+/// # Arguments
+/// * `stream` - stream from [`transfer_stream`], created before the transfer started
+/// * `progress` - the initial state, as returned when the transfer was created
+/// * `callback` - called on every change
+pub(crate) async fn follow_transfer(
+    mut stream: MessageStream,
+    mut progress: TransferProgress,
+    callback: impl Fn(TransferProgress),
+) -> Result<TransferProgress, ObexError> {
+    callback(progress.clone());
+
+    while !progress.status.is_finished() {
+        let Some(Ok(message)) = stream.next().await else {
+            return Err(ObexError::ConnectionFailure);
+        };
+        if message.header().path().map(|p| p.as_str()) != Some(progress.transfer.as_str()) {
+            continue;
+        }
+        let Ok((interface, changed, _)) =
+            message
+                .body()
+                .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>()
+        else {
+            continue;
+        };
+        if interface != TRANSFER_INTERFACE {
+            continue;
+        }
+
+        if let Some(transferred) = property(&changed, "Transferred") {
+            progress.transferred = transferred;
+        }
+        if let Some(size) = property(&changed, "Size") {
+            progress.size = Some(size);
+        }
+        if let Some(status) = property::<String>(&changed, "Status") {
+            progress.status = TransferStatus::from_obex(&status);
+            if progress.status == TransferStatus::Complete {
+                progress.transferred = progress.size.unwrap_or(progress.transferred);
+            }
+        }
+        callback(progress.clone());
+    }
+
+    match progress.status {
+        TransferStatus::Complete => Ok(progress),
+        _ => Err(ObexError::TransferFailed),
+    }
+}
+
+/// Builds the initial progress from the properties returned with a new transfer
+fn initial_progress(
+    transfer: &OwnedObjectPath,
+    properties: &HashMap<String, OwnedValue>,
+    direction: TransferDirection,
+) -> TransferProgress {
+    TransferProgress {
+        transfer: transfer.to_string(),
+        direction,
+        name: property(properties, "Name").unwrap_or_default(),
+        filename: property(properties, "Filename"),
+        size: property::<u64>(properties, "Size").filter(|size| *size > 0),
+        transferred: property(properties, "Transferred").unwrap_or_default(),
+        status: TransferStatus::from_obex(
+            &property::<String>(properties, "Status").unwrap_or_else(|| "queued".to_string()),
+        ),
+    }
+}
+
+/// Sends a file to a device using OBEX Object Push
+///
+/// Runs until the device accepted and received the file, or rejected it.
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+/// * `file` - local file to send
+/// * `callback` - called with the progress of the transfer
+///
+/// # Returns
+/// - `Ok(TransferProgress)` once the transfer completed
+/// - `Err(ObexError::TransferFailed)` if the device rejected or aborted it
+pub async fn send_file(
+    device: &str,
+    file: &Path,
+    callback: impl Fn(TransferProgress),
+) -> Result<TransferProgress, ObexError> {
+    let file = file.canonicalize().map_err(|_| ObexError::IoError)?;
+    let session = Session::open(device, "opp").await?;
+
+    let result = async {
+        let stream = session.transfer_stream().await?;
+        let (transfer, properties) = ObjectPush1Proxy::builder(&session.conn)
+            .path(session.path.clone())
+            .map_err(ObexError::from_dbus)?
+            .build()
+            .await
+            .map_err(ObexError::from_dbus)?
+            .send_file(&file.to_string_lossy())
+            .await
+            .map_err(ObexError::from_dbus)?;
+
+        let progress = initial_progress(&transfer, &properties, TransferDirection::Outgoing);
+        follow_transfer(stream, progress, callback).await
+    }
+    .await;
+
+    session.close().await;
+    result
+}
+
+/// Opens an FTP session and changes into `folder`, one level at a time
+async fn ftp_session(
+    device: &str,
+    folder: &str,
+) -> Result<(Session, FileTransfer1Proxy<'static>), ObexError> {
+    let session = Session::open(device, "ftp").await?;
+
+    let result = async {
+        let ftp = FileTransfer1Proxy::builder(&session.conn)
+            .path(session.path.clone())
+            .map_err(ObexError::from_dbus)?
+            .build()
+            .await
+            .map_err(ObexError::from_dbus)?;
+        for component in folder.split('/').filter(|c| !c.is_empty()) {
+            ftp.change_folder(component)
+                .await
+                .map_err(ObexError::from_dbus)?;
+        }
+        Ok(ftp)
+    }
+    .await;
+
+    match result {
+        Ok(ftp) => Ok((session, ftp)),
+        Err(e) => {
+            session.close().await;
+            Err(e)
+        }
+    }
+}
+
+/// Lists a folder on a device offering OBEX File Transfer
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+/// * `folder` - folder relative to the root, e.g. `DCIM/Camera` (empty for the root)
+///
+/// # Returns
+/// - `Ok(Vec<ObexFolderEntry>)` folders first, then files, each sorted by name
+/// - `Err(ObexError)` if the device does not offer FTP or the folder does not exist
+pub async fn list_folder(device: &str, folder: &str) -> Result<Vec<ObexFolderEntry>, ObexError> {
+    let (session, ftp) = ftp_session(device, folder).await?;
+    let listing = ftp.list_folder().await.map_err(ObexError::from_dbus);
+    session.close().await;
+
+    let mut entries: Vec<ObexFolderEntry> = listing?
+        .iter()
+        .map(|entry| ObexFolderEntry {
+            name: property(entry, "Name").unwrap_or_default(),
+            is_folder: property::<String>(entry, "Type").as_deref() == Some("folder"),
+            size: property(entry, "Size"),
+            modified: property(entry, "Modified"),
+        })
+        .collect();
+
+    entries.sort_by(|a, b| b.is_folder.cmp(&a.is_folder).then(a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+/// Downloads a file from a device offering OBEX File Transfer
+///
+/// The file is stored in the configured receive directory.
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+/// * `remote_path` - path of the file on the device, e.g. `DCIM/Camera/IMG_0001.jpg`
+/// * `callback` - called with the progress of the transfer
+///
+/// # Returns
+/// - `Ok(PathBuf)` of the downloaded file
+/// - `Err(ObexError)` if the file does not exist or the transfer fails
+pub async fn download_file(
+    device: &str,
+    remote_path: &str,
+    callback: impl Fn(TransferProgress),
+) -> Result<PathBuf, ObexError> {
+    let (folder, name) = remote_path
+        .trim_matches('/')
+        .rsplit_once('/')
+        .unwrap_or(("", remote_path.trim_matches('/')));
+    if name.is_empty() {
+        return Err(ObexError::InvalidArguments);
+    }
+
+    let receive_dir = ObexSettings::load().receive_dir;
+    std::fs::create_dir_all(&receive_dir).map_err(|_| ObexError::IoError)?;
+    let target = unique_path(&receive_dir, name);
+
+    let (session, ftp) = ftp_session(device, folder).await?;
+    let result = async {
+        let stream = session.transfer_stream().await?;
+        let (transfer, properties) = ftp
+            .get_file(&target.to_string_lossy(), name)
+            .await
+            .map_err(ObexError::from_dbus)?;
+
+        let mut progress = initial_progress(&transfer, &properties, TransferDirection::Incoming);
+        progress.name = name.to_string();
+        follow_transfer(stream, progress, callback).await
+    }
+    .await;
+    session.close().await;
+
+    result.map(|_| target)
+}
+
+/// Cancels a running transfer
+///
+/// # Arguments
+/// * `transfer` - object path of the transfer from a progress event
+pub async fn cancel_transfer(transfer: &str) -> Result<(), ObexError> {
+    let conn = obex_connection().await?;
+
+    Transfer1Proxy::builder(&conn)
+        .path(transfer.to_string())
+        .map_err(|_| ObexError::NotFound)?
+        .build()
+        .await
+        .map_err(ObexError::from_dbus)?
+        .cancel()
+        .await
+        .map_err(ObexError::from_dbus)
+}
+
+/// Returns a path in `dir` for `name` that does not exist yet
+///
+/// Only the file name part of `name` is used, so a sender cannot write
+/// outside of `dir`. Existing files get a ` (1)`, ` (2)`, ... suffix.
+pub fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let name = Path::new(name)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .filter(|n| n != "..")
+        .unwrap_or_else(|| "received".to_string());
+
+    let candidate = dir.join(&name);
+    if !candidate.exists() {
+        return candidate;
+    }
+
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => {
+            (stem.to_string(), format!(".{}", extension))
+        }
+        _ => (name.clone(), String::new()),
+    };
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap_or(candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn receive_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("wiblue-obex-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn keeps_received_files_inside_the_directory() {
+        let dir = receive_dir("inside");
+
+        assert_eq!(unique_path(&dir, "../x"), dir.join("x"));
+        assert_eq!(unique_path(&dir, "../../etc/passwd"), dir.join("passwd"));
+        assert_eq!(unique_path(&dir, "/abs/path"), dir.join("path"));
+        assert_eq!(unique_path(&dir, ".."), dir.join("received"));
+        assert_eq!(unique_path(&dir, "."), dir.join("received"));
+        assert_eq!(unique_path(&dir, ""), dir.join("received"));
+        assert_eq!(unique_path(&dir, "photo.jpg"), dir.join("photo.jpg"));
+    }
+
+    #[test]
+    fn numbers_colliding_names() {
+        let dir = receive_dir("collisions");
+        for name in [
+            "photo.jpg",
+            "photo (1).jpg",
+            "notes",
+            ".profile",
+            "received",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        assert_eq!(unique_path(&dir, "photo.jpg"), dir.join("photo (2).jpg"));
+        assert_eq!(unique_path(&dir, "../photo.jpg"), dir.join("photo (2).jpg"));
+        assert_eq!(unique_path(&dir, "notes"), dir.join("notes (1)"));
+        // Hidden files have no extension
+        assert_eq!(unique_path(&dir, ".profile"), dir.join(".profile (1)"));
+        assert_eq!(
+            unique_path(&dir, "archive.tar.gz"),
+            dir.join("archive.tar.gz")
+        );
+        fs::write(dir.join("archive.tar.gz"), "").unwrap();
+        assert_eq!(
+            unique_path(&dir, "archive.tar.gz"),
+            dir.join("archive.tar (1).gz")
+        );
+        assert_eq!(unique_path(&dir, ".."), dir.join("received (1)"));
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, LazyLock, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::{sync::oneshot, time};
+use zbus::{interface, proxy, zvariant::ObjectPath, Connection, DBusError};
+
+use super::{
+    bluez_error::ObexError,
+    obex::{
+        follow_transfer, obex_connection, transfer_stream, unique_path, Session1Proxy,
+        Transfer1Proxy,
+    },
+    obex_data::{
+        IncomingPush, ObexEvent, ObexSettings, TransferDirection, TransferProgress, TransferStatus,
+    },
+};
+
+/// Object path the OBEX agent is exported at
+pub const OBEX_AGENT_PATH: &str = "/com/wiblue/obex_agent";
+/// How long an incoming push waits for the user before it is rejected
+pub const PUSH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Pushes waiting for the user, keyed by request id
+static PENDING: LazyLock<Mutex<HashMap<u64, oneshot::Sender<bool>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Errors reported back to obexd
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "org.bluez.obex.Error")]
+enum ObexAgentError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    Rejected(String),
+    Canceled(String),
+}
+
+#[proxy(
+    interface = "org.bluez.obex.AgentManager1",
+    default_service = "org.bluez.obex",
+    default_path = "/org/bluez/obex"
+)]
+trait ObexAgentManager1 {
+    fn register_agent(&self, agent: &ObjectPath<'_>) -> zbus::Result<()>;
+}
+
+/// `org.bluez.obex.Agent1` implementation asking the user about every push
+struct ObexAgent {
+    conn: Connection,
+    notify: Arc<dyn Fn(ObexEvent) + Send + Sync>,
+    /// Push currently shown to the user, obexd only cancels the latest one
+    current: Mutex<Option<u64>>,
+}
+
+impl ObexAgent {
+    /// Collects what the user needs to decide about a push
+    async fn describe(&self, id: u64, transfer: &ObjectPath<'_>) -> IncomingPush {
+        let mut push = IncomingPush {
+            id,
+            transfer: transfer.to_string(),
+            device: None,
+            name: String::new(),
+            size: None,
+            mime_type: None,
+        };
+
+        let details = async {
+            let proxy = Transfer1Proxy::builder(&self.conn)
+                .path(transfer.to_owned())
+                .ok()?
+                .build()
+                .await
+                .ok()?;
+            push.name = proxy.name().await.unwrap_or_default();
+            push.size = proxy.size().await.ok().filter(|size| *size > 0);
+            push.mime_type = proxy.mime_type().await.ok().filter(|t| !t.is_empty());
+
+            let session = Session1Proxy::builder(&self.conn)
+                .path(proxy.session().await.ok()?)
+                .ok()?
+                .build()
+                .await
+                .ok()?;
+            push.device = session.destination().await.ok();
+            Some(())
+        };
+        details.await;
+
+        push
+    }
+}
+
+#[interface(name = "org.bluez.obex.Agent1")]
+impl ObexAgent {
+    fn release(&self) {
+        println!("OBEX agent released by obexd");
+    }
+
+    async fn authorize_push(&self, transfer: ObjectPath<'_>) -> Result<String, ObexAgentError> {
+        let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+        PENDING.lock().unwrap().insert(id, sender);
+        *self.current.lock().unwrap() = Some(id);
+
+        let push = self.describe(id, &transfer).await;
+        (self.notify)(ObexEvent::IncomingPush(push.clone()));
+
+        let answer = time::timeout(PUSH_TIMEOUT, receiver).await;
+        PENDING.lock().unwrap().remove(&id);
+
+        match answer {
+            Ok(Ok(true)) => {}
+            Ok(Ok(false)) => return Err(ObexAgentError::Rejected("Rejected by user".into())),
+            Ok(Err(_)) => return Err(ObexAgentError::Canceled("Push cancelled".into())),
+            Err(_) => {
+                (self.notify)(ObexEvent::Cancelled { id: Some(id) });
+                return Err(ObexAgentError::Canceled("No answer from user".into()));
+            }
+        }
+
+        let receive_dir = ObexSettings::load().receive_dir;
+        if let Err(e) = std::fs::create_dir_all(&receive_dir) {
+            eprintln!("Error creating receive directory: {:?}", e);
+            return Err(ObexAgentError::Rejected("Cannot store file".into()));
+        }
+        let target = unique_path(&receive_dir, &push.name);
+
+        // Subscribe before obexd starts the transfer, so no update is missed
+        let session = transfer.as_str().rsplit_once('/').map(|(s, _)| s);
+        match transfer_stream(&self.conn, session.unwrap_or(transfer.as_str())).await {
+            Ok(stream) => {
+                let progress = TransferProgress {
+                    transfer: transfer.to_string(),
+                    direction: TransferDirection::Incoming,
+                    name: push.name,
+                    filename: Some(target.to_string_lossy().to_string()),
+                    size: push.size,
+                    transferred: 0,
+                    status: TransferStatus::Queued,
+                };
+                let notify = self.notify.clone();
+                tokio::spawn(async move {
+                    let result = follow_transfer(stream, progress, |progress| {
+                        notify(ObexEvent::Progress(progress))
+                    })
+                    .await;
+                    if let Err(e) = result {
+                        eprintln!("Incoming transfer failed: {:?}", e);
+                    }
+                });
+            }
+            Err(e) => eprintln!("Error watching incoming transfer: {:?}", e),
+        }
+
+        Ok(target.to_string_lossy().to_string())
+    }
+
+    fn cancel(&self) {
+        let id = self.current.lock().unwrap().take();
+        if let Some(id) = id {
+            // Dropping the sender wakes up the waiting push
+            PENDING.lock().unwrap().remove(&id);
+        }
+        (self.notify)(ObexEvent::Cancelled { id });
+    }
+}
+
+/// Exports the OBEX agent and registers it with obexd to receive pushes
+///
+/// # Arguments
+/// * `notify` - called for incoming pushes and the progress of accepted ones
+///
+/// # Returns
+/// - `Ok(())` if obexd accepted the agent
+/// - `Err(ObexError)` if obexd is not running or another agent is registered
+pub async fn register_obex_agent(
+    notify: impl Fn(ObexEvent) + Send + Sync + 'static,
+) -> Result<(), ObexError> {
+    let conn = obex_connection().await?;
+
+    let agent = ObexAgent {
+        conn: conn.clone(),
+        notify: Arc::new(notify),
+        current: Mutex::new(None),
+    };
+    let added = conn
+        .object_server()
+        .at(OBEX_AGENT_PATH, agent)
+        .await
+        .map_err(ObexError::from_dbus)?;
+    if !added {
+        return Ok(());
+    }
+
+    ObexAgentManager1Proxy::new(&conn)
+        .await
+        .map_err(ObexError::from_dbus)?
+        .register_agent(&ObjectPath::from_static_str_unchecked(OBEX_AGENT_PATH))
+        .await
+        .map_err(ObexError::from_dbus)
+}
+
+/// Delivers the decision of the user about an incoming push
+///
+/// # Returns
+/// - `Ok(())` if the push was still waiting
+/// - `Err(ObexError::NotFound)` if it was answered, cancelled or timed out already
+pub fn respond_push(id: u64, accept: bool) -> Result<(), ObexError> {
+    let sender = PENDING
+        .lock()
+        .unwrap()
+        .remove(&id)
+        .ok_or(ObexError::NotFound)?;
+
+    sender.send(accept).map_err(|_| ObexError::NotFound)
+}
@@ -0,0 +1,133 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths::{config_dir, data_dir};
+
+/// State of an OBEX transfer (`org.bluez.obex.Transfer1.Status`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum TransferStatus {
+    Queued,
+    Active,
+    Suspended,
+    Complete,
+    Error,
+}
+
+impl TransferStatus {
+    pub fn from_obex(status: &str) -> Self {
+        match status {
+            "queued" => TransferStatus::Queued,
+            "active" => TransferStatus::Active,
+            "suspended" => TransferStatus::Suspended,
+            "complete" => TransferStatus::Complete,
+            _ => TransferStatus::Error,
+        }
+    }
+
+    /// Whether the transfer ended, successfully or not
+    pub fn is_finished(&self) -> bool {
+        matches!(self, TransferStatus::Complete | TransferStatus::Error)
+    }
+}
+
+/// Direction of a transfer as seen from this machine
+#[derive(Debug, Clone, Serialize)]
+pub enum TransferDirection {
+    Outgoing,
+    Incoming,
+}
+
+/// Progress of a running transfer, reported on every change
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferProgress {
+    /// Object path of the transfer, used to cancel it
+    pub transfer: String,
+    pub direction: TransferDirection,
+    /// File name sent to or received from the device
+    pub name: String,
+    /// Local file being read or written
+    pub filename: Option<String>,
+    /// Total size in bytes, `None` if the sender did not announce it
+    pub size: Option<u64>,
+    pub transferred: u64,
+    pub status: TransferStatus,
+}
+
+/// An entry of a folder listing on an OBEX FTP server
+#[derive(Debug, Clone, Serialize)]
+pub struct ObexFolderEntry {
+    pub name: String,
+    pub is_folder: bool,
+    pub size: Option<u64>,
+    /// Modification time as reported by the device (`20240131T101500Z`)
+    pub modified: Option<String>,
+}
+
+/// A file a remote device wants to push to us, waiting for the user
+#[derive(Debug, Clone, Serialize)]
+pub struct IncomingPush {
+    /// Identifier the answer has to reference
+    pub id: u64,
+    /// Object path of the incoming transfer
+    pub transfer: String,
+    /// Address of the sending device
+    pub device: Option<String>,
+    pub name: String,
+    pub size: Option<u64>,
+    /// MIME type announced by the sender
+    pub mime_type: Option<String>,
+}
+
+/// Something the OBEX agent wants the user interface to know about
+#[derive(Debug, Clone, Serialize)]
+pub enum ObexEvent {
+    IncomingPush(IncomingPush),
+    /// The sender or obexd cancelled a pending push
+    Cancelled {
+        id: Option<u64>,
+    },
+    Progress(TransferProgress),
+}
+
+/// Persistent OBEX settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObexSettings {
+    /// Directory accepted pushes are stored in
+    pub receive_dir: PathBuf,
+}
+
+impl Default for ObexSettings {
+    fn default() -> Self {
+        let downloads = std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join("Downloads"))
+            .filter(|dir| dir.is_dir());
+
+        ObexSettings {
+            receive_dir: downloads.unwrap_or_else(|| data_dir().join("received")),
+        }
+    }
+}
+
+impl ObexSettings {
+    fn path() -> PathBuf {
+        config_dir().join("obex.json")
+    }
+
+    /// Loads the settings, falling back to the defaults
+    pub fn load() -> Self {
+        std::fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the settings to the wiblue config directory
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
@@ -0,0 +1,96 @@
+use super::{
+    bluez_error::{BluetoothError, PairingError},
+    device::device_proxy,
+};
+
+/// Pairs with a device
+///
+/// Keys, PIN codes and confirmations are requested from the user through the
+/// registered pairing agent while this call is running.
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+///
+/// # Returns
+/// - `Ok(())` once the device is paired
+/// - `Err(PairingError)` if authentication fails, is rejected or times out
+pub async fn pair_device(device: &str) -> Result<(), PairingError> {
+    let proxy = device_proxy(device)
+        .await
+        .map_err(PairingError::Bluetooth)?;
+
+    if proxy.paired().await.unwrap_or(false) {
+        return Err(PairingError::AlreadyExists);
+    }
+
+    proxy.pair().await.map_err(PairingError::from_dbus)
+}
+
+/// Cancels a pairing started with [`pair_device`]
+pub async fn cancel_pairing(device: &str) -> Result<(), BluetoothError> {
+    device_proxy(device)
+        .await?
+        .cancel_pairing()
+        .await
+        .map_err(BluetoothError::from_dbus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluetooth::{
+        bluez::with_connection,
+        mock_bluez::{add_device, Calls, MockDevice, PrivateBus},
+    };
+
+    #[tokio::test]
+    async fn pairs_once_and_cancels() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let bluez = bus.serve_bluez().await;
+        let calls = Calls::default();
+        let speaker = MockDevice::new("/org/bluez/hci0", "00:11:22:33:44:55", &calls);
+        let speaker_path = speaker.path.clone();
+        add_device(&bluez, speaker).await;
+
+        with_connection(bus.connect().await, async {
+            pair_device("00:11:22:33:44:55").await.unwrap();
+            assert!(matches!(
+                pair_device(&speaker_path).await,
+                Err(PairingError::AlreadyExists)
+            ));
+            cancel_pairing(&speaker_path).await.unwrap();
+            assert!(matches!(
+                pair_device("66:77:88:99:AA:BB").await,
+                Err(PairingError::Bluetooth(BluetoothError::NoSuchDevice))
+            ));
+        })
+        .await;
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                format!("Pair {}", speaker_path),
+                format!("CancelPairing {}", speaker_path)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_failed_authentication() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let bluez = bus.serve_bluez().await;
+        let calls = Calls::default();
+        let mut speaker = MockDevice::new("/org/bluez/hci0", "00:11:22:33:44:55", &calls);
+        speaker.blocked = true;
+        add_device(&bluez, speaker).await;
+
+        let result = with_connection(bus.connect().await, pair_device("00:11:22:33:44:55")).await;
+        assert!(matches!(result, Err(PairingError::AuthenticationFailed)));
+    }
+}
@@ -0,0 +1,125 @@
+use super::{
+    bluez::{
+        connection, managed_objects, property, Device1Proxy, Network1Proxy, NETWORK_INTERFACE,
+    },
+    bluez_error::BluetoothError,
+    device::device_proxy,
+    device_data::BluetoothDevice,
+    discovery::get_devices,
+    pan_data::{PanConnection, PanRole},
+};
+
+/// Builds a `org.bluez.Network1` proxy for a device offering a PAN role
+async fn network_proxy(
+    device: &Device1Proxy<'_>,
+) -> Result<Network1Proxy<'static>, BluetoothError> {
+    let conn = connection().await?;
+
+    Network1Proxy::builder(&conn)
+        .path(device.inner().path().to_owned())
+        .map_err(|_| BluetoothError::NoSuchDevice)?
+        .build()
+        .await
+        .map_err(BluetoothError::from_dbus)
+}
+
+/// Connects to the personal area network of a device, e.g. to tether through a phone
+///
+/// BlueZ creates a `bnep` interface for the connection. Addresses are assigned
+/// by the network manager or DHCP client running on the system, like for any
+/// other link.
+///
+/// # Arguments
+/// * `device` - object path or address of a paired device
+/// * `role` - role of the remote device, usually [`PanRole::Nap`]
+///
+/// # Returns
+/// - `Ok(PanConnection)` with the name of the created interface
+/// - `Err(BluetoothError::NotSupported)` if the device does not offer the role
+/// - `Err(BluetoothError::AlreadyConnected)` if a PAN connection is already up
+pub async fn connect_pan(device: &str, role: PanRole) -> Result<PanConnection, BluetoothError> {
+    let device = device_proxy(device).await?;
+    let uuids = device.uuids().await.map_err(BluetoothError::from_dbus)?;
+    if !uuids.iter().any(|u| u.eq_ignore_ascii_case(role.uuid())) {
+        return Err(BluetoothError::NotSupported);
+    }
+
+    let network = network_proxy(&device).await?;
+    // A device carries a single PAN connection, whatever role it uses
+    if network.connected().await.unwrap_or_default() {
+        return Err(BluetoothError::AlreadyConnected);
+    }
+    let interface = network
+        .connect(role.as_str())
+        .await
+        .map_err(BluetoothError::from_dbus)?;
+
+    Ok(PanConnection {
+        device: device.inner().path().to_string(),
+        address: device.address().await.map_err(BluetoothError::from_dbus)?,
+        alias: device.alias().await.map_err(BluetoothError::from_dbus)?,
+        interface,
+        role: Some(role),
+    })
+}
+
+/// Closes the PAN connection of a device, removing its `bnep` interface
+///
+/// # Arguments
+/// * `device` - object path or address of the device
+pub async fn disconnect_pan(device: &str) -> Result<(), BluetoothError> {
+    network_proxy(&device_proxy(device).await?)
+        .await?
+        .disconnect()
+        .await
+        .map_err(BluetoothError::from_dbus)
+}
+
+/// Lists the active PAN connections and their interfaces
+///
+/// # Returns
+/// - `Ok(Vec<PanConnection>)` one entry per connected device
+/// - `Err(BluetoothError)` if BlueZ cannot be reached
+pub async fn get_pan_connections() -> Result<Vec<PanConnection>, BluetoothError> {
+    let conn = connection().await?;
+    let objects = managed_objects(&conn).await?;
+    let devices = get_devices(None).await?;
+
+    let connections = objects
+        .iter()
+        .filter_map(|(path, interfaces)| {
+            let props = interfaces
+                .iter()
+                .find(|(name, _)| name.as_str() == NETWORK_INTERFACE)
+                .map(|(_, props)| props)?;
+            if !property::<bool>(props, "Connected").unwrap_or_default() {
+                return None;
+            }
+
+            let device = devices.iter().find(|d| d.path == path.as_str())?;
+            let role = property::<String>(props, "UUID").and_then(|u| PanRole::from_uuid(&u));
+            Some(pan_connection(
+                device,
+                property(props, "Interface").unwrap_or_default(),
+                role,
+            ))
+        })
+        .collect();
+
+    Ok(connections)
+}
+
+/// Describes the PAN connection of a device
+fn pan_connection(
+    device: &BluetoothDevice,
+    interface: String,
+    role: Option<PanRole>,
+) -> PanConnection {
+    PanConnection {
+        device: device.path.clone(),
+        address: device.address.clone(),
+        alias: device.alias.clone(),
+        interface,
+        role,
+    }
+}
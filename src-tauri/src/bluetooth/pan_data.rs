@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+/// Role of the remote device in a Bluetooth PAN connection
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PanRole {
+    /// Network Access Point, e.g. a phone sharing its mobile data
+    Nap,
+    /// Group ad-hoc Network of several devices
+    Gn,
+    /// Another PAN user, a direct link between two devices
+    Panu,
+}
+
+impl PanRole {
+    /// Name `org.bluez.Network1.Connect` accepts for the role
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PanRole::Nap => "nap",
+            PanRole::Gn => "gn",
+            PanRole::Panu => "panu",
+        }
+    }
+
+    /// Service UUID a device offering the role advertises
+    pub fn uuid(&self) -> &'static str {
+        match self {
+            PanRole::Nap => "00001116-0000-1000-8000-00805f9b34fb",
+            PanRole::Gn => "00001117-0000-1000-8000-00805f9b34fb",
+            PanRole::Panu => "00001115-0000-1000-8000-00805f9b34fb",
+        }
+    }
+
+    /// Parses the `UUID` property of a connected `org.bluez.Network1`
+    pub fn from_uuid(uuid: &str) -> Option<Self> {
+        [PanRole::Nap, PanRole::Gn, PanRole::Panu]
+            .into_iter()
+            .find(|role| role.uuid().eq_ignore_ascii_case(uuid) || role.as_str() == uuid)
+    }
+}
+
+/// An active Bluetooth PAN connection and the network interface it created
+#[derive(Debug, Clone, Serialize)]
+pub struct PanConnection {
+    /// Object path of the remote device
+    pub device: String,
+    pub address: String,
+    /// Name shown to the user, falls back to the address
+    pub alias: String,
+    /// Name of the `bnep` interface carrying the traffic (e.g. `bnep0`)
+    pub interface: String,
+    pub role: Option<PanRole>,
+}
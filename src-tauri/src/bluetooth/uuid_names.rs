@@ -0,0 +1,281 @@
+/// Suffix shared by every UUID derived from a 16-bit assigned number
+const BASE_UUID_SUFFIX: &str = "-0000-1000-8000-00805f9b34fb";
+
+/// GATT services from the Bluetooth SIG assigned numbers
+const SERVICES: &[(u16, &str)] = &[
+    (0x1800, "Generic Access"),
+    (0x1801, "Generic Attribute"),
+    (0x1802, "Immediate Alert"),
+    (0x1803, "Link Loss"),
+    (0x1804, "Tx Power"),
+    (0x1805, "Current Time"),
+    (0x1806, "Reference Time Update"),
+    (0x1807, "Next DST Change"),
+    (0x1808, "Glucose"),
+    (0x1809, "Health Thermometer"),
+    (0x180a, "Device Information"),
+    (0x180d, "Heart Rate"),
+    (0x180e, "Phone Alert Status"),
+    (0x180f, "Battery"),
+    (0x1810, "Blood Pressure"),
+    (0x1811, "Alert Notification"),
+    (0x1812, "Human Interface Device"),
+    (0x1813, "Scan Parameters"),
+    (0x1814, "Running Speed and Cadence"),
+    (0x1815, "Automation IO"),
+    (0x1816, "Cycling Speed and Cadence"),
+    (0x1818, "Cycling Power"),
+    (0x1819, "Location and Navigation"),
+    (0x181a, "Environmental Sensing"),
+    (0x181b, "Body Composition"),
+    (0x181c, "User Data"),
+    (0x181d, "Weight Scale"),
+    (0x181e, "Bond Management"),
+    (0x181f, "Continuous Glucose Monitoring"),
+    (0x1820, "Internet Protocol Support"),
+    (0x1821, "Indoor Positioning"),
+    (0x1822, "Pulse Oximeter"),
+    (0x1823, "HTTP Proxy"),
+    (0x1824, "Transport Discovery"),
+    (0x1825, "Object Transfer"),
+    (0x1826, "Fitness Machine"),
+    (0x1827, "Mesh Provisioning"),
+    (0x1828, "Mesh Proxy"),
+    (0x1829, "Reconnection Configuration"),
+    (0x183a, "Insulin Delivery"),
+    (0x183b, "Binary Sensor"),
+    (0x183c, "Emergency Configuration"),
+    (0x183e, "Physical Activity Monitor"),
+    (0x1843, "Audio Input Control"),
+    (0x1844, "Volume Control"),
+    (0x1845, "Volume Offset Control"),
+    (0x1846, "Coordinated Set Identification"),
+    (0x1848, "Media Control"),
+    (0x1849, "Generic Media Control"),
+    (0x184b, "Telephone Bearer"),
+    (0x184c, "Generic Telephone Bearer"),
+    (0x184d, "Microphone Control"),
+    (0x184e, "Audio Stream Control"),
+    (0x184f, "Broadcast Audio Scan"),
+    (0x1850, "Published Audio Capabilities"),
+    (0x1851, "Basic Audio Announcement"),
+    (0x1852, "Broadcast Audio Announcement"),
+    (0x1853, "Common Audio"),
+    (0x1854, "Hearing Access"),
+    (0x1855, "Telephony and Media Audio"),
+    (0x1856, "Public Broadcast Announcement"),
+];
+
+/// GATT characteristics from the Bluetooth SIG assigned numbers
+const CHARACTERISTICS: &[(u16, &str)] = &[
+    (0x2a00, "Device Name"),
+    (0x2a01, "Appearance"),
+    (0x2a02, "Peripheral Privacy Flag"),
+    (0x2a03, "Reconnection Address"),
+    (0x2a04, "Peripheral Preferred Connection Parameters"),
+    (0x2a05, "Service Changed"),
+    (0x2a06, "Alert Level"),
+    (0x2a07, "Tx Power Level"),
+    (0x2a08, "Date Time"),
+    (0x2a09, "Day of Week"),
+    (0x2a0a, "Day Date Time"),
+    (0x2a0c, "Exact Time 256"),
+    (0x2a0d, "DST Offset"),
+    (0x2a0e, "Time Zone"),
+    (0x2a0f, "Local Time Information"),
+    (0x2a11, "Time with DST"),
+    (0x2a12, "Time Accuracy"),
+    (0x2a13, "Time Source"),
+    (0x2a14, "Reference Time Information"),
+    (0x2a16, "Time Update Control Point"),
+    (0x2a17, "Time Update State"),
+    (0x2a18, "Glucose Measurement"),
+    (0x2a19, "Battery Level"),
+    (0x2a1c, "Temperature Measurement"),
+    (0x2a1d, "Temperature Type"),
+    (0x2a1e, "Intermediate Temperature"),
+    (0x2a21, "Measurement Interval"),
+    (0x2a22, "Boot Keyboard Input Report"),
+    (0x2a23, "System ID"),
+    (0x2a24, "Model Number String"),
+    (0x2a25, "Serial Number String"),
+    (0x2a26, "Firmware Revision String"),
+    (0x2a27, "Hardware Revision String"),
+    (0x2a28, "Software Revision String"),
+    (0x2a29, "Manufacturer Name String"),
+    (
+        0x2a2a,
+        "IEEE 11073-20601 Regulatory Certification Data List",
+    ),
+    (0x2a2b, "Current Time"),
+    (0x2a31, "Scan Refresh"),
+    (0x2a32, "Boot Keyboard Output Report"),
+    (0x2a33, "Boot Mouse Input Report"),
+    (0x2a34, "Glucose Measurement Context"),
+    (0x2a35, "Blood Pressure Measurement"),
+    (0x2a36, "Intermediate Cuff Pressure"),
+    (0x2a37, "Heart Rate Measurement"),
+    (0x2a38, "Body Sensor Location"),
+    (0x2a39, "Heart Rate Control Point"),
+    (0x2a3f, "Alert Status"),
+    (0x2a40, "Ringer Control Point"),
+    (0x2a41, "Ringer Setting"),
+    (0x2a42, "Alert Category ID Bit Mask"),
+    (0x2a43, "Alert Category ID"),
+    (0x2a44, "Alert Notification Control Point"),
+    (0x2a45, "Unread Alert Status"),
+    (0x2a46, "New Alert"),
+    (0x2a47, "Supported New Alert Category"),
+    (0x2a48, "Supported Unread Alert Category"),
+    (0x2a49, "Blood Pressure Feature"),
+    (0x2a4a, "HID Information"),
+    (0x2a4b, "Report Map"),
+    (0x2a4c, "HID Control Point"),
+    (0x2a4d, "Report"),
+    (0x2a4e, "Protocol Mode"),
+    (0x2a4f, "Scan Interval Window"),
+    (0x2a50, "PnP ID"),
+    (0x2a51, "Glucose Feature"),
+    (0x2a52, "Record Access Control Point"),
+    (0x2a53, "RSC Measurement"),
+    (0x2a54, "RSC Feature"),
+    (0x2a55, "SC Control Point"),
+    (0x2a5b, "CSC Measurement"),
+    (0x2a5c, "CSC Feature"),
+    (0x2a5d, "Sensor Location"),
+    (0x2a63, "Cycling Power Measurement"),
+    (0x2a64, "Cycling Power Vector"),
+    (0x2a65, "Cycling Power Feature"),
+    (0x2a66, "Cycling Power Control Point"),
+    (0x2a67, "Location and Speed"),
+    (0x2a68, "Navigation"),
+    (0x2a6c, "Elevation"),
+    (0x2a6d, "Pressure"),
+    (0x2a6e, "Temperature"),
+    (0x2a6f, "Humidity"),
+    (0x2a70, "True Wind Speed"),
+    (0x2a71, "True Wind Direction"),
+    (0x2a72, "Apparent Wind Speed"),
+    (0x2a73, "Apparent Wind Direction"),
+    (0x2a76, "UV Index"),
+    (0x2a77, "Irradiance"),
+    (0x2a78, "Rainfall"),
+    (0x2a7b, "Dew Point"),
+    (0x2a98, "Weight"),
+    (0x2a9d, "Weight Measurement"),
+    (0x2a9e, "Weight Scale Feature"),
+    (0x2aa6, "Central Address Resolution"),
+    (0x2aa7, "CGM Measurement"),
+    (0x2ac9, "Resolvable Private Address Only"),
+    (0x2acc, "Fitness Machine Feature"),
+    (0x2ad9, "Fitness Machine Control Point"),
+    (0x2b29, "Client Supported Features"),
+    (0x2b2a, "Database Hash"),
+    (0x2b3a, "Server Supported Features"),
+    (0x2b7d, "Volume State"),
+    (0x2b7e, "Volume Control Point"),
+    (0x2b7f, "Volume Flags"),
+];
+
+/// GATT descriptors from the Bluetooth SIG assigned numbers
+const DESCRIPTORS: &[(u16, &str)] = &[
+    (0x2900, "Characteristic Extended Properties"),
+    (0x2901, "Characteristic User Description"),
+    (0x2902, "Client Characteristic Configuration"),
+    (0x2903, "Server Characteristic Configuration"),
+    (0x2904, "Characteristic Presentation Format"),
+    (0x2905, "Characteristic Aggregate Format"),
+    (0x2906, "Valid Range"),
+    (0x2907, "External Report Reference"),
+    (0x2908, "Report Reference"),
+    (0x2909, "Number of Digitals"),
+    (0x290a, "Value Trigger Setting"),
+    (0x290b, "Environmental Sensing Configuration"),
+    (0x290c, "Environmental Sensing Measurement"),
+    (0x290d, "Environmental Sensing Trigger Setting"),
+    (0x290e, "Time Trigger Setting"),
+    (0x290f, "Complete BR-EDR Transport Block Data"),
+];
+
+/// Widely used vendor UUIDs that are not part of the assigned numbers
+const VENDOR: &[(&str, &str)] = &[
+    (
+        "6e400001-b5a3-f393-e0a9-e50e24dcca9e",
+        "Nordic UART Service",
+    ),
+    ("6e400002-b5a3-f393-e0a9-e50e24dcca9e", "Nordic UART RX"),
+    ("6e400003-b5a3-f393-e0a9-e50e24dcca9e", "Nordic UART TX"),
+    ("00001530-1212-efde-1523-785feabcd123", "Nordic Legacy DFU"),
+    ("0000fe59-0000-1000-8000-00805f9b34fb", "Nordic Secure DFU"),
+    (
+        "0000fef5-0000-1000-8000-00805f9b34fb",
+        "Dialog Semiconductor SUOTA",
+    ),
+];
+
+/// Company identifiers seen most often in manufacturer specific advertising data
+const COMPANIES: &[(u16, &str)] = &[
+    (0x0006, "Microsoft"),
+    (0x000a, "Qualcomm"),
+    (0x000d, "Texas Instruments"),
+    (0x000f, "Broadcom"),
+    (0x004c, "Apple"),
+    (0x0059, "Nordic Semiconductor"),
+    (0x0075, "Samsung"),
+    (0x0087, "Garmin"),
+    (0x009e, "Bose"),
+    (0x00e0, "Google"),
+    (0x012d, "Sony"),
+    (0x02e5, "Espressif"),
+    (0x038f, "Xiaomi"),
+    (0x0499, "Ruuvi Innovations"),
+];
+
+/// Returns the 16-bit assigned number of a UUID built on the Bluetooth base UUID
+///
+/// # Arguments
+/// * `uuid` - full 128-bit UUID (`0000180f-0000-1000-8000-00805f9b34fb`)
+pub fn short_uuid(uuid: &str) -> Option<u16> {
+    let uuid = uuid.to_ascii_lowercase();
+    let prefix = uuid.strip_suffix(BASE_UUID_SUFFIX)?;
+    let short = prefix.strip_prefix("0000")?;
+
+    u16::from_str_radix(short, 16).ok()
+}
+
+/// Looks up the name of a GATT service, characteristic or descriptor UUID
+///
+/// # Arguments
+/// * `uuid` - full 128-bit UUID
+///
+/// # Returns
+/// - `Some(&str)` for assigned numbers and a few well-known vendor UUIDs
+/// - `None` for unknown (usually vendor specific) UUIDs
+pub fn uuid_name(uuid: &str) -> Option<&'static str> {
+    if let Some((_, name)) = VENDOR
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(uuid))
+    {
+        return Some(name);
+    }
+
+    let short = short_uuid(uuid)?;
+    SERVICES
+        .iter()
+        .chain(CHARACTERISTICS)
+        .chain(DESCRIPTORS)
+        .find(|(number, _)| *number == short)
+        .map(|(_, name)| *name)
+}
+
+/// Looks up the company behind a manufacturer data company identifier
+///
+/// # Returns
+/// - `Some(&str)` for the most common advertisers
+/// - `None` for every other company
+pub fn company_name(company_id: u16) -> Option<&'static str> {
+    COMPANIES
+        .iter()
+        .find(|(id, _)| *id == company_id)
+        .map(|(_, name)| *name)
+}
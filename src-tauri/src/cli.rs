@@ -0,0 +1,1513 @@
+use std::{
+    io::{BufRead, IsTerminal},
+    path::PathBuf,
+    process::Stdio,
+    time::Duration,
+};
+
+use clap::{CommandFactory, Parser, Subcommand};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use crate::bluetooth::agent::{AgentRequest, AgentRequestKind, AgentResponse};
+use crate::daemon::{
+    client::DaemonClient,
+    dbus_service::introspection_xml,
+    exporter::ExporterSettings,
+    protocol::{socket_path, Reply},
+    server::run_daemon,
+};
+use crate::wlan::{
+    speed_test_server::{default_server_address, run_speed_test_server},
+    wifi_qr::WifiCredentials,
+};
+
+/// Environment variable holding the password of `connect` and `hotspot start`
+const PASSWORD_ENV: &str = "WIBLUE_PASSWORD";
+/// Environment variable holding the passphrase of exported profile bundles
+const PASSPHRASE_ENV: &str = "WIBLUE_PASSPHRASE";
+
+/// Manage Wi-Fi and Bluetooth from the command line
+#[derive(Parser)]
+#[command(name = "wiblue", version)]
+struct Cli {
+    /// Print JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the Wi-Fi networks in range
+    Scan,
+    /// Connect to a Wi-Fi network
+    Connect {
+        /// BSSID or SSID of the network
+        network: String,
+        /// Ask for the password, unless it is set in WIBLUE_PASSWORD
+        #[arg(long, short)]
+        password: bool,
+    },
+    /// Disconnect a Wi-Fi device
+    Disconnect {
+        /// Device to disconnect, defaults to the connected Wi-Fi device
+        #[arg(long, short)]
+        interface: Option<String>,
+    },
+    /// List the network interfaces
+    Interfaces,
+    /// Print traffic statistics of an interface
+    Stats {
+        #[arg(long, short)]
+        interface: String,
+        /// Seconds between two readings
+        #[arg(long, short = 'n', default_value_t = 1)]
+        interval: u64,
+        /// Stop after this many readings
+        #[arg(long, short)]
+        count: Option<u64>,
+    },
+    /// Manage saved Wi-Fi profiles
+    Profiles {
+        #[command(subcommand)]
+        command: ProfilesCommand,
+    },
+    /// Bluetooth adapters and devices
+    Bt {
+        #[command(subcommand)]
+        command: BtCommand,
+    },
+    /// Webhook and MQTT event sinks configured in sinks.json
+    Sinks {
+        #[command(subcommand)]
+        command: SinksCommand,
+    },
+    /// Share or join networks with Wi-Fi QR codes
+    Qr {
+        #[command(subcommand)]
+        command: QrCommand,
+    },
+    /// Share the connection through a Wi-Fi hotspot
+    Hotspot {
+        #[command(subcommand)]
+        command: HotspotCommand,
+    },
+    /// Roaming assistant switching away from weak access points
+    Roaming {
+        #[command(subcommand)]
+        command: RoamingCommand,
+    },
+    /// Automation rules of rules.toml
+    Rules {
+        #[command(subcommand)]
+        command: RulesCommand,
+    },
+    /// Run the daemon in the foreground
+    Daemon {
+        /// Serve OpenMetrics on this localhost port, overriding exporter.json
+        #[arg(long, value_name = "PORT")]
+        metrics_port: Option<u16>,
+    },
+    /// Print the D-Bus introspection XML of the daemon service
+    Introspect,
+    /// Run the bundled speed test server
+    SpeedTestServer {
+        /// Address to listen on, localhost unless --lan is given
+        address: Option<String>,
+        /// Listen on every interface instead of localhost
+        #[arg(long, conflicts_with = "address")]
+        lan: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfilesCommand {
+    /// List the saved profiles
+    List,
+    /// Delete a saved profile
+    Delete {
+        /// Name or UUID of the profile
+        profile: String,
+    },
+    /// Export Wi-Fi profiles to a portable JSON bundle
+    Export {
+        /// Names or UUIDs of the profiles, all Wi-Fi profiles if none given
+        profiles: Vec<String>,
+        /// File to write the bundle to, printed if not set
+        #[arg(long, short, value_name = "FILE")]
+        output: Option<PathBuf>,
+        /// Include the passwords, encrypted with a passphrase that is asked
+        /// for unless it is set in WIBLUE_PASSPHRASE
+        #[arg(long)]
+        passphrase: bool,
+    },
+    /// Import Wi-Fi profiles from a bundle, NetworkManager keyfiles,
+    /// wpa_supplicant.conf, iwd network files or netsh XML
+    Import {
+        /// File, or directory of files, to import
+        path: PathBuf,
+        /// bundle, keyfile, wpa_supplicant, iwd or netsh; detected if not set
+        #[arg(long)]
+        format: Option<String>,
+        /// Ask for the passphrase the passwords of a bundle are encrypted
+        /// with, unless it is set in WIBLUE_PASSPHRASE
+        #[arg(long)]
+        passphrase: bool,
+        /// What to do with profiles that collide with saved ones: skip,
+        /// replace or keep-both
+        #[arg(long, value_name = "RESOLUTION", default_value = "skip")]
+        on_conflict: String,
+        /// Resolution of a single profile, e.g. `Home=replace` or
+        /// `Home=rename:Home old`
+        #[arg(long, value_name = "NAME=RESOLUTION")]
+        resolve: Vec<String>,
+        /// Only show what would be imported
+        #[arg(long, conflicts_with = "yes")]
+        preview: bool,
+        /// Import without asking
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SinksCommand {
+    /// List the configured sinks
+    List,
+    /// Send a test event to a sink
+    Test {
+        /// Name of the sink
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum QrCommand {
+    /// Print the QR code of a saved profile, or save it as PNG or SVG
+    Show {
+        /// Name or UUID of the profile, defaults to the connected network
+        #[arg(long, short)]
+        profile: Option<String>,
+        #[arg(long, value_name = "FILE", conflicts_with = "svg")]
+        png: Option<PathBuf>,
+        #[arg(long, value_name = "FILE")]
+        svg: Option<PathBuf>,
+        /// Pixels per module of the PNG
+        #[arg(long, default_value_t = 8)]
+        scale: usize,
+    },
+    /// Decode a WIFI: URI or an image of a QR code
+    Parse {
+        /// WIFI: URI
+        #[arg(required_unless_present = "image")]
+        text: Option<String>,
+        #[arg(long, short, value_name = "FILE", conflicts_with = "text")]
+        image: Option<PathBuf>,
+    },
+    /// Connect to the network of a WIFI: URI or an image of a QR code
+    Connect {
+        /// WIFI: URI
+        #[arg(required_unless_present = "image")]
+        text: Option<String>,
+        #[arg(long, short, value_name = "FILE", conflicts_with = "text")]
+        image: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum HotspotCommand {
+    /// Start the hotspot, replacing the settings of a previous one
+    ///
+    /// The passphrase of 8 to 63 characters is asked for unless the hotspot
+    /// is open or it is set in WIBLUE_PASSWORD.
+    Start {
+        ssid: String,
+        /// Use WPA3 (SAE) instead of WPA2
+        #[arg(long, conflicts_with = "open")]
+        wpa3: bool,
+        /// Run an open hotspot without password
+        #[arg(long)]
+        open: bool,
+        /// 2.4 or 5 (GHz)
+        #[arg(long)]
+        band: Option<String>,
+        #[arg(long)]
+        channel: Option<u8>,
+        /// Wi-Fi device, defaults to the first one
+        #[arg(long, short)]
+        interface: Option<String>,
+    },
+    /// Stop the hotspot
+    Stop,
+    /// Show the hotspot and its clients
+    Status,
+    /// Check whether an adapter can run a hotspot next to a connection
+    Support {
+        #[arg(long, short)]
+        interface: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RoamingCommand {
+    /// Show the policy and the latest decisions
+    Status,
+    /// Enable the assistant, changing the given settings of roaming.json
+    Enable {
+        /// Signal quality in percent below which an access point is weak
+        #[arg(long)]
+        threshold: Option<u8>,
+        /// Seconds the signal has to stay weak
+        #[arg(long)]
+        weak_secs: Option<u64>,
+        /// Percentage points a candidate has to be stronger
+        #[arg(long)]
+        hysteresis: Option<u8>,
+        /// Preferred SSID, highest priority first; replaces the saved list
+        #[arg(long = "prefer", value_name = "SSID")]
+        preferred_ssids: Vec<String>,
+    },
+    /// Disable the assistant
+    Disable,
+}
+
+#[derive(Subcommand)]
+enum RulesCommand {
+    /// List the rules the daemon runs
+    List,
+    /// Load the rules file again
+    Reload,
+    /// Show which rules an event would trigger, without running any action
+    DryRun {
+        /// Event name, e.g. wifi_connected
+        event: String,
+        /// Event payload as JSON
+        #[arg(long)]
+        payload: Option<String>,
+        /// SSID of the connected network, the current one if not set
+        #[arg(long)]
+        ssid: Option<String>,
+        #[arg(long)]
+        bssid: Option<String>,
+        #[arg(long)]
+        interface: Option<String>,
+        /// Address or alias of the Bluetooth device
+        #[arg(long)]
+        device: Vec<String>,
+        #[arg(long)]
+        on_battery: Option<bool>,
+        /// Local time, e.g. 2024-05-06T08:30:00
+        #[arg(long, value_name = "TIME")]
+        at: Option<String>,
+        /// Check this rules file instead of the running rules
+        #[arg(long)]
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum BtCommand {
+    /// List the Bluetooth adapters
+    Adapters,
+    /// List the devices BlueZ knows
+    Devices {
+        #[arg(long, short)]
+        adapter: Option<String>,
+    },
+    /// List the paired devices
+    Paired {
+        #[arg(long, short)]
+        adapter: Option<String>,
+    },
+    /// Discover devices for a while and list them
+    Scan {
+        #[arg(long, short, default_value = "hci0")]
+        adapter: String,
+        /// Seconds to discover
+        #[arg(long, short, default_value_t = 10)]
+        duration: u64,
+    },
+    /// Pair with a device, prompting for PIN codes and passkeys
+    Pair { device: String },
+    /// Connect a device or a single profile
+    Connect {
+        device: String,
+        /// UUID of the profile to connect
+        #[arg(long, short)]
+        profile: Option<String>,
+    },
+    /// Disconnect a device or a single profile
+    Disconnect {
+        device: String,
+        #[arg(long, short)]
+        profile: Option<String>,
+    },
+    /// Trust a device, letting it connect without asking
+    Trust {
+        device: String,
+        /// Remove the trust instead
+        #[arg(long)]
+        off: bool,
+    },
+    /// Block a device
+    Block {
+        device: String,
+        /// Unblock it instead
+        #[arg(long)]
+        off: bool,
+    },
+    /// Remove a device and its pairing
+    Remove { device: String },
+    /// Battery and signal of the connected devices
+    Battery,
+    /// Audio profiles and codec of a device
+    Audio {
+        device: String,
+        /// Switch to this profile
+        #[arg(long, short)]
+        set: Option<String>,
+    },
+    /// Scan for LE beacons for a while and list them
+    Beacons {
+        #[arg(long, short, default_value = "hci0")]
+        adapter: String,
+        /// Seconds to scan
+        #[arg(long, short, default_value_t = 10)]
+        duration: u64,
+    },
+}
+
+/// A table column rendering one field of each row
+struct Column {
+    header: &'static str,
+    cell: fn(&Value) -> String,
+}
+
+const fn column(header: &'static str, cell: fn(&Value) -> String) -> Column {
+    Column { header, cell }
+}
+
+/// Prints rows as a table with aligned columns
+fn print_table(columns: &[Column], rows: &[Value]) {
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| columns.iter().map(|c| (c.cell)(row)).collect())
+        .collect();
+
+    let mut widths: Vec<usize> = columns.iter().map(|c| c.header.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: Vec<String>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(columns.iter().map(|c| c.header.to_string()).collect());
+    for row in rows {
+        line(row);
+    }
+}
+
+/// Prints a reply as JSON, or the rows it contains as a table
+fn print_output(json: bool, reply: &Reply, rows: &Value, columns: &[Column]) {
+    if json {
+        match serde_json::to_string_pretty(&reply.message) {
+            Ok(text) => println!("{}", text),
+            Err(e) => eprintln!("Error serializing output: {:?}", e),
+        }
+    } else {
+        print_table(
+            columns,
+            rows.as_array().map(Vec::as_slice).unwrap_or_default(),
+        );
+    }
+}
+
+/// Prints the message of a reply without data
+fn print_message(json: bool, reply: &Reply) {
+    if json {
+        println!(
+            "{}",
+            json!({ "message": reply.message, "status": reply.status })
+        );
+    } else {
+        println!("{}", reply.message_text());
+    }
+}
+
+/// Renders a field, `-` if it is missing
+fn text(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::Bool(true) => "yes".to_string(),
+        Value::Bool(false) => "no".to_string(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Name of a unit variant, or of the variant a struct variant is tagged with
+fn variant(value: &Value) -> String {
+    match value {
+        Value::Object(fields) => fields.keys().next().cloned().unwrap_or_default(),
+        value => text(value),
+    }
+}
+
+fn marker(value: &Value) -> String {
+    if value.as_bool() == Some(true) {
+        "*"
+    } else {
+        ""
+    }
+    .to_string()
+}
+
+const DEVICE_COLUMNS: &[Column] = &[
+    column("ADDRESS", |d| text(&d["address"])),
+    column("NAME", |d| text(&d["alias"])),
+    column("RSSI", |d| text(&d["rssi"])),
+    column("PAIRED", |d| text(&d["paired"])),
+    column("CONNECTED", |d| text(&d["connected"])),
+    column("TYPE", |d| text(&d["icon"])),
+];
+
+fn frame_name(frame: &Value) -> String {
+    let name = variant(frame);
+    match name.as_str() {
+        "IBeacon" => "iBeacon",
+        "EddystoneUid" => "Eddystone-UID",
+        "EddystoneUrl" => "Eddystone-URL",
+        "EddystoneTlm" => "Eddystone-TLM",
+        "FastPair" => "Fast Pair",
+        "AppleContinuity" => "Continuity",
+        _ => return name,
+    }
+    .to_string()
+}
+
+/// Reads a line from the terminal
+fn prompt(question: &str) -> Option<String> {
+    eprint!("{} ", question);
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer).ok()?;
+    Some(answer.trim().to_string())
+}
+
+/// Reads a secret from the environment or, if `ask` is set, from the terminal
+///
+/// Secrets are never taken from the command line, where every user can read
+/// them in the process list. Piped input works as well, e.g.
+/// `echo secret | wiblue connect Home --password`.
+///
+/// # Arguments
+/// * `ask` - whether to ask when the environment variable is not set
+/// * `env_name` - environment variable holding the secret
+/// * `question` - prompt shown on the terminal
+fn read_secret(ask: bool, env_name: &str, question: &str) -> Option<String> {
+    if let Ok(secret) = std::env::var(env_name) {
+        return Some(secret);
+    }
+    if !ask {
+        return None;
+    }
+
+    // Keep the secret off the screen while it is typed
+    let terminal = std::io::stdin().is_terminal();
+    let echo = |mode: &str| {
+        if terminal {
+            let _ = std::process::Command::new("stty")
+                .arg(mode)
+                .stdin(Stdio::inherit())
+                .status();
+        }
+    };
+    echo("-echo");
+    let secret = prompt(question);
+    echo("echo");
+    if terminal {
+        eprintln!();
+    }
+
+    secret.filter(|s| !s.is_empty())
+}
+
+/// Asks the user on the terminal, `None` for requests that only display a key
+fn ask_agent_request(request: &AgentRequest) -> Option<AgentResponse> {
+    match &request.kind {
+        AgentRequestKind::DisplayPinCode { pincode } => {
+            eprintln!("Enter PIN code {} on the device", pincode);
+            None
+        }
+        AgentRequestKind::DisplayPasskey { passkey, .. } => {
+            eprintln!("Enter passkey {:06} on the device", passkey);
+            None
+        }
+        AgentRequestKind::RequestPinCode => prompt("PIN code:").map(AgentResponse::PinCode),
+        AgentRequestKind::RequestPasskey => prompt("Passkey:")
+            .and_then(|p| p.parse().ok())
+            .map(AgentResponse::Passkey),
+        AgentRequestKind::RequestConfirmation { passkey } => {
+            prompt(&format!("Does the device show {:06}? [y/N]", passkey))
+                .map(|a| confirm_response(&a))
+        }
+        AgentRequestKind::RequestAuthorization => {
+            prompt("Pair with the device? [y/N]").map(|a| confirm_response(&a))
+        }
+        AgentRequestKind::AuthorizeService { uuid } => {
+            prompt(&format!("Allow service {}? [y/N]", uuid)).map(|a| confirm_response(&a))
+        }
+    }
+}
+
+fn confirm_response(answer: &str) -> AgentResponse {
+    match answer {
+        "y" | "Y" | "yes" => AgentResponse::Accept,
+        _ => AgentResponse::Reject,
+    }
+}
+
+/// Connects to the daemon, starting it if needed
+async fn connect() -> Result<DaemonClient, String> {
+    DaemonClient::connect(|_| {})
+        .await
+        .map_err(|e| format!("Error connecting to daemon: {:?}", e))
+}
+
+/// Calls a daemon method, failures become the message shown to the user
+async fn call(client: &DaemonClient, method: &str, params: Value) -> Result<Reply, String> {
+    client
+        .call(method, params)
+        .await
+        .map_err(|e| format!("{} ({})", e.message, e.status()))
+}
+
+async fn run_command(command: Command, json: bool) -> Result<(), String> {
+    match command {
+        Command::Daemon { metrics_port } => {
+            let mut exporter = ExporterSettings::load();
+            if let Some(port) = metrics_port {
+                exporter.enabled = true;
+                exporter.port = port;
+            }
+            return run_daemon(&socket_path(), &exporter)
+                .await
+                .map_err(|e| format!("Daemon failed: {:?}", e));
+        }
+        Command::Introspect => {
+            print!("{}", introspection_xml());
+            return Ok(());
+        }
+        Command::SpeedTestServer { address, lan } => {
+            let address = address.unwrap_or_else(|| default_server_address(lan));
+            return run_speed_test_server(&address)
+                .await
+                .map_err(|e| format!("Speed test server failed: {:?}", e));
+        }
+        Command::Bt {
+            command: BtCommand::Pair { device },
+        } => return pair(&device, json).await,
+        _ => {}
+    }
+
+    let client = connect().await?;
+    match command {
+        Command::Scan => {
+            let reply = call(&client, "wifi.scan", json!({})).await?;
+            print_output(
+                json,
+                &reply,
+                &reply.message,
+                &[
+                    column("", |n| marker(&n["currently_used"])),
+                    column("SSID", |n| text(&n["ssid"])),
+                    column("BSSID", |n| text(&n["bssid"])),
+                    column("SIGNAL", |n| text(&n["signal_strength"])),
+                    column("CHAN", |n| text(&n["channel"])),
+                    column("SECURITY", |n| variant(&n["security"])),
+                ],
+            );
+        }
+        Command::Connect { network, password } => {
+            let password = read_secret(password, PASSWORD_ENV, "Password:");
+            let params = json!({ "bssid": network, "password": password });
+            print_message(json, &call(&client, "wifi.connect", params).await?);
+        }
+        Command::Disconnect { interface } => {
+            let params = json!({ "interface": interface });
+            print_message(json, &call(&client, "wifi.disconnect", params).await?);
+        }
+        Command::Interfaces => {
+            let reply = call(&client, "net.interface_details", json!({})).await?;
+            print_output(
+                json,
+                &reply,
+                &reply.message,
+                &[
+                    column("NAME", |i| text(&i["name"])),
+                    column("TYPE", |i| text(&i["kind"])),
+                    column("UP", |i| text(&i["up"])),
+                    column("DEVICE", |i| text(&i["bluetooth_device"])),
+                ],
+            );
+        }
+        Command::Stats {
+            interface,
+            interval,
+            count,
+        } => {
+            if !json {
+                println!(
+                    "{:>12}  {:>12}  {:>12}  {:>12}",
+                    "UP/s", "DOWN/s", "TOTAL UP", "TOTAL DOWN"
+                );
+            }
+
+            let params = json!({ "interface": interface });
+            let mut readings = 0;
+            while count.is_none_or(|count| readings < count) {
+                let stats = call(&client, "net.stats", params.clone()).await?.message;
+                if json {
+                    // One object per line, so the output can be streamed
+                    println!("{}", stats);
+                } else {
+                    println!(
+                        "{:>12.0}  {:>12.0}  {:>12}  {:>12}",
+                        stats["speed_up"].as_f64().unwrap_or_default(),
+                        stats["speed_down"].as_f64().unwrap_or_default(),
+                        text(&stats["total_up"]),
+                        text(&stats["total_down"])
+                    );
+                }
+                readings += 1;
+                if count.is_none_or(|count| readings < count) {
+                    tokio::time::sleep(Duration::from_secs(interval.max(1))).await;
+                }
+            }
+        }
+        Command::Profiles { command } => match command {
+            ProfilesCommand::List => {
+                let reply = call(&client, "wifi.profiles", json!({})).await?;
+                print_output(
+                    json,
+                    &reply,
+                    &reply.message,
+                    &[
+                        column("", |p| marker(&p["active"])),
+                        column("NAME", |p| text(&p["name"])),
+                        column("UUID", |p| text(&p["uuid"])),
+                        column("DEVICE", |p| text(&p["device"])),
+                        column("AUTOCONNECT", |p| text(&p["autoconnect"])),
+                    ],
+                );
+            }
+            ProfilesCommand::Delete { profile } => {
+                let params = json!({ "profile": profile });
+                print_message(json, &call(&client, "wifi.delete_profile", params).await?);
+            }
+            ProfilesCommand::Export {
+                profiles,
+                output,
+                passphrase,
+            } => {
+                let passphrase = read_secret(passphrase, PASSPHRASE_ENV, "Passphrase:");
+                let params = json!({ "profiles": profiles, "passphrase": passphrase });
+                let reply = call(&client, "wifi.export_profiles", params).await?;
+                print_warnings(&reply.message["warnings"]);
+                let bundle = serde_json::to_string_pretty(&reply.message["bundle"])
+                    .map_err(|e| format!("Invalid reply: {}", e))?;
+                match output {
+                    Some(file) => {
+                        std::fs::write(&file, bundle)
+                            .map_err(|e| format!("Error writing {}: {}", file.display(), e))?;
+                        let count = reply.message["bundle"]["profiles"]
+                            .as_array()
+                            .map_or(0, Vec::len);
+                        eprintln!("Exported {} profiles to {}", count, file.display());
+                    }
+                    None => println!("{}", bundle),
+                }
+            }
+            ProfilesCommand::Import {
+                path,
+                format,
+                passphrase,
+                on_conflict,
+                resolve,
+                preview,
+                yes,
+            } => {
+                let default_resolution = resolution(&on_conflict)?;
+                let mut resolutions = serde_json::Map::new();
+                for entry in resolve {
+                    let (name, value) = entry
+                        .split_once('=')
+                        .ok_or_else(|| format!("Expected NAME=RESOLUTION, got {}", entry))?;
+                    resolutions.insert(name.to_string(), resolution(value)?);
+                }
+                let passphrase = read_secret(passphrase, PASSPHRASE_ENV, "Passphrase:");
+                // The daemon resolves relative paths against its own directory
+                let path = std::fs::canonicalize(&path)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                let mut params = json!({
+                    "path": path,
+                    "format": format,
+                    "passphrase": passphrase,
+                });
+
+                let reply = call(&client, "wifi.import_preview", params.clone()).await?;
+                let profiles = reply.message["profiles"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                if preview || !yes {
+                    print_output(
+                        json,
+                        &reply,
+                        &reply.message["profiles"],
+                        &[
+                            column("NAME", |p| text(&p["profile"]["name"])),
+                            column("SSID", |p| text(&p["profile"]["ssid"])),
+                            column("SECURITY", |p| text(&p["profile"]["security"])),
+                            column("PASSWORD", |p| text(&p["has_secrets"])),
+                            column("SOURCE", |p| text(&p["source"])),
+                            column("CONFLICTS WITH", |p| {
+                                let conflicts =
+                                    p["conflicts"].as_array().cloned().unwrap_or_default();
+                                conflicts
+                                    .iter()
+                                    .map(|c| text(&c["name"]))
+                                    .collect::<Vec<_>>()
+                                    .join(",")
+                            }),
+                            column("REPLACES", |p| text(&p["replaces"]["name"])),
+                        ],
+                    );
+                    if !json {
+                        print_warnings(&reply.message["warnings"]);
+                    }
+                }
+                if preview || profiles.is_empty() {
+                    return Ok(());
+                }
+
+                if !yes {
+                    for profile in &profiles {
+                        let name = text(&profile["profile"]["name"]);
+                        let conflicts = profile["conflicts"].as_array().map_or(0, Vec::len);
+                        if conflicts == 0 || resolutions.contains_key(&name) {
+                            continue;
+                        }
+                        let replaces = match &profile["replaces"]["name"] {
+                            Value::Null => "nothing, several profiles share its SSID".to_string(),
+                            name => text(name),
+                        };
+                        let answer = prompt(&format!(
+                            "{} collides with a saved profile. [s]kip, [r]eplace {}, [k]eep both or a new name (default {}):",
+                            name, replaces, on_conflict
+                        ))
+                        .unwrap_or_default();
+                        let chosen = match answer.as_str() {
+                            "" => default_resolution.clone(),
+                            "s" => json!("skip"),
+                            "r" => json!("replace"),
+                            "k" => json!("keep_both"),
+                            new_name => json!({ "rename": new_name }),
+                        };
+                        resolutions.insert(name, chosen);
+                    }
+                    let answer = prompt(&format!("Import {} profiles? [y/N]", profiles.len()));
+                    if !matches!(answer.as_deref(), Some("y" | "Y" | "yes")) {
+                        return Ok(());
+                    }
+                }
+
+                params["resolutions"] = Value::Object(resolutions);
+                params["default_resolution"] = default_resolution;
+                let reply = call(&client, "wifi.import_profiles", params).await?;
+                print_output(
+                    json,
+                    &reply,
+                    &reply.message["results"],
+                    &[
+                        column("NAME", |r| text(&r["name"])),
+                        column("SAVED AS", |r| text(&r["saved_as"])),
+                        column("OUTCOME", |r| text(&r["outcome"])),
+                        column("ERROR", |r| text(&r["error"])),
+                    ],
+                );
+            }
+        },
+        Command::Sinks { command } => match command {
+            SinksCommand::List => {
+                let reply = call(&client, "sinks.list", json!({})).await?;
+                print_output(
+                    json,
+                    &reply,
+                    &reply.message,
+                    &[
+                        column("NAME", |s| text(&s["name"])),
+                        column("KIND", |s| text(&s["kind"])),
+                        column("TARGET", |s| text(&s["target"])),
+                        column("EVENTS", |s| {
+                            let events = s["events"].as_array().cloned().unwrap_or_default();
+                            events.iter().map(text).collect::<Vec<_>>().join(",")
+                        }),
+                    ],
+                );
+            }
+            SinksCommand::Test { name } => {
+                let params = json!({ "name": name });
+                print_message(json, &call(&client, "sinks.test", params).await?);
+            }
+        },
+        Command::Qr { command } => run_qr(&client, command, json).await?,
+        Command::Hotspot { command } => run_hotspot(&client, command, json).await?,
+        Command::Roaming { command } => run_roaming(&client, command, json).await?,
+        Command::Rules { command } => run_rules(&client, command, json).await?,
+        Command::Bt { command } => run_bluetooth(&client, command, json).await?,
+        Command::Daemon { .. } | Command::Introspect | Command::SpeedTestServer { .. } => {}
+    }
+
+    Ok(())
+}
+
+async fn run_qr(client: &DaemonClient, command: QrCommand, json: bool) -> Result<(), String> {
+    // The daemon resolves relative paths against its own directory
+    let absolute = |image: Option<PathBuf>| -> Result<Option<PathBuf>, String> {
+        image
+            .map(|i| std::fs::canonicalize(&i).map_err(|e| format!("{}: {}", i.display(), e)))
+            .transpose()
+    };
+
+    match command {
+        QrCommand::Show {
+            profile,
+            png,
+            svg,
+            scale,
+        } => {
+            let reply = call(client, "wifi.qr_code", json!({ "profile": profile })).await?;
+            let credentials: WifiCredentials =
+                serde_json::from_value(reply.message["credentials"].clone())
+                    .map_err(|e| format!("Invalid reply: {}", e))?;
+            let (file, content) = match (png, svg) {
+                (Some(file), _) => (file, credentials.to_png(scale)),
+                (None, Some(file)) => (file, credentials.to_svg().map(String::into_bytes)),
+                (None, None) => {
+                    if json {
+                        print_output(json, &reply, &Value::Null, &[]);
+                    } else {
+                        let code = credentials.to_terminal().map_err(|e| format!("{:?}", e))?;
+                        println!("{}\n{}", code, text(&reply.message["uri"]));
+                    }
+                    return Ok(());
+                }
+            };
+            let content = content.map_err(|e| format!("Error rendering QR code: {:?}", e))?;
+            std::fs::write(&file, content)
+                .map_err(|e| format!("Error writing {}: {}", file.display(), e))?;
+            println!(
+                "Saved QR code of {} to {}",
+                credentials.ssid,
+                file.display()
+            );
+        }
+        QrCommand::Parse { text: uri, image } => {
+            let params = json!({ "text": uri, "image": absolute(image)? });
+            let reply = call(client, "wifi.qr_parse", params).await?;
+            print_output(
+                json,
+                &reply,
+                &Value::Array(vec![reply.message.clone()]),
+                &[
+                    column("SSID", |c| text(&c["ssid"])),
+                    column("SECURITY", |c| text(&c["security"])),
+                    column("PASSWORD", |c| text(&c["password"])),
+                    column("HIDDEN", |c| text(&c["hidden"])),
+                ],
+            );
+        }
+        QrCommand::Connect { text: uri, image } => {
+            let params = json!({ "text": uri, "image": absolute(image)? });
+            print_message(json, &call(client, "wifi.qr_connect", params).await?);
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_hotspot(
+    client: &DaemonClient,
+    command: HotspotCommand,
+    json: bool,
+) -> Result<(), String> {
+    let reply = match command {
+        HotspotCommand::Start {
+            ssid,
+            wpa3,
+            open,
+            band,
+            channel,
+            interface,
+        } => {
+            let band = match band.as_deref() {
+                None => None,
+                Some("2.4") => Some("Ghz2_4"),
+                Some("5") => Some("Ghz5"),
+                Some(band) => return Err(format!("Unknown band {}, use 2.4 or 5", band)),
+            };
+            let password = match open {
+                true => None,
+                false => read_secret(true, PASSWORD_ENV, "Passphrase:"),
+            };
+            let security = match (open, wpa3) {
+                (true, _) => "open",
+                (false, true) => "wpa3",
+                (false, false) => "wpa2",
+            };
+            let config = json!({
+                "ssid": ssid,
+                "password": password,
+                "security": security,
+                "band": band,
+                "channel": channel,
+                "interface": interface,
+            });
+            call(client, "wifi.hotspot_start", json!({ "config": config })).await?
+        }
+        HotspotCommand::Stop => {
+            print_message(json, &call(client, "wifi.hotspot_stop", json!({})).await?);
+            return Ok(());
+        }
+        HotspotCommand::Status => call(client, "wifi.hotspot_status", json!({})).await?,
+        HotspotCommand::Support { interface } => {
+            let params = json!({ "interface": interface });
+            let reply = call(client, "wifi.ap_support", params).await?;
+            if json {
+                print_output(json, &reply, &Value::Null, &[]);
+            } else {
+                let support = &reply.message;
+                println!("Interface:        {}", text(&support["interface"]));
+                println!("PHY:              {}", text(&support["phy"]));
+                println!("AP mode:          {}", text(&support["ap_mode"]));
+                println!(
+                    "With connection:  {}",
+                    text(&support["station_concurrency"])
+                );
+                println!("Same channel:     {}", text(&support["single_channel"]));
+            }
+            return Ok(());
+        }
+    };
+
+    let status = &reply.message;
+    if !json {
+        println!(
+            "{} on {}: {} ({}), {}",
+            if status["active"].as_bool() == Some(true) {
+                "Running"
+            } else {
+                "Stopped"
+            },
+            text(&status["interface"]),
+            text(&status["ssid"]),
+            text(&status["security"]),
+            text(&status["address"])
+        );
+    }
+    print_output(
+        json,
+        &reply,
+        &status["clients"],
+        &[
+            column("MAC", |c| text(&c["mac"])),
+            column("IP", |c| text(&c["ip"])),
+            column("HOSTNAME", |c| text(&c["hostname"])),
+            column("SIGNAL", |c| text(&c["signal"])),
+            column("RX", |c| text(&c["received_bytes"])),
+            column("TX", |c| text(&c["sent_bytes"])),
+            column("CONNECTED", |c| text(&c["connected_secs"])),
+        ],
+    );
+
+    Ok(())
+}
+
+async fn run_roaming(
+    client: &DaemonClient,
+    command: RoamingCommand,
+    json: bool,
+) -> Result<(), String> {
+    let status = call(client, "wifi.roaming", json!({})).await?;
+    let mut policy = status.message["policy"].clone();
+
+    match command {
+        RoamingCommand::Status => {
+            if !json {
+                let running = if status.message["running"].as_bool() == Some(true) {
+                    "running"
+                } else {
+                    "stopped"
+                };
+                println!(
+                    "Roaming assistant {}: below {}% for {}s, {}% hysteresis",
+                    running, policy["threshold"], policy["weak_secs"], policy["hysteresis"]
+                );
+            }
+            print_output(
+                json,
+                &status,
+                &status.message["decisions"],
+                &[
+                    column("TIME", |d| text(&d["timestamp"])),
+                    column("INTERFACE", |d| text(&d["interface"])),
+                    column("BSSID", |d| text(&d["bssid"])),
+                    column("ACTION", |d| text(&d["action"])),
+                    column("TARGET", |d| text(&d["target"]["bssid"])),
+                    column("REASON", |d| match &d["error"] {
+                        Value::Null => text(&d["reason"]),
+                        error => format!("{} (failed: {})", text(&d["reason"]), text(error)),
+                    }),
+                ],
+            );
+        }
+        RoamingCommand::Enable {
+            threshold,
+            weak_secs,
+            hysteresis,
+            preferred_ssids,
+        } => {
+            policy["enabled"] = json!(true);
+            if let Some(threshold) = threshold {
+                policy["threshold"] = json!(threshold);
+            }
+            if let Some(weak_secs) = weak_secs {
+                policy["weak_secs"] = json!(weak_secs);
+            }
+            if let Some(hysteresis) = hysteresis {
+                policy["hysteresis"] = json!(hysteresis);
+            }
+            if !preferred_ssids.is_empty() {
+                policy["preferred_ssids"] = json!(preferred_ssids);
+            }
+            let params = json!({ "policy": policy });
+            print_message(json, &call(client, "wifi.set_roaming", params).await?);
+        }
+        RoamingCommand::Disable => {
+            policy["enabled"] = json!(false);
+            let params = json!({ "policy": policy });
+            print_message(json, &call(client, "wifi.set_roaming", params).await?);
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_rules(client: &DaemonClient, command: RulesCommand, json: bool) -> Result<(), String> {
+    match command {
+        RulesCommand::List => {
+            let reply = call(client, "automation.rules", json!({})).await?;
+            print_output(
+                json,
+                &reply,
+                &reply.message["rules"],
+                &[
+                    column("NAME", |r| text(&r["name"])),
+                    column("ENABLED", |r| text(&r["enabled"])),
+                    column("ON", |r| {
+                        let on = r["on"].as_array().cloned().unwrap_or_default();
+                        on.iter().map(text).collect::<Vec<_>>().join(",")
+                    }),
+                    column("ACTIONS", |r| {
+                        let actions = r["actions"].as_array().cloned().unwrap_or_default();
+                        actions
+                            .iter()
+                            .map(|a| text(&a["action"]))
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    }),
+                ],
+            );
+            if !json {
+                print_warnings(&reply.message["warnings"]);
+            }
+        }
+        RulesCommand::Reload => {
+            print_message(json, &call(client, "automation.reload", json!({})).await?);
+        }
+        RulesCommand::DryRun {
+            event,
+            payload,
+            ssid,
+            bssid,
+            interface,
+            device,
+            on_battery,
+            at,
+            file,
+        } => {
+            let payload: Option<Value> = payload
+                .map(|p| serde_json::from_str(&p))
+                .transpose()
+                .map_err(|e| format!("Invalid payload: {}", e))?;
+            let (rules, format) = match file {
+                Some(file) => {
+                    let rules = std::fs::read_to_string(&file)
+                        .map_err(|e| format!("Error reading {}: {}", file.display(), e))?;
+                    let format = match file.extension().and_then(|e| e.to_str()) {
+                        Some("json") => "json",
+                        _ => "toml",
+                    };
+                    (Some(rules), Some(format))
+                }
+                None => (None, None),
+            };
+            let params = json!({
+                "event": event,
+                "payload": payload,
+                "context": {
+                    "ssid": ssid,
+                    "bssid": bssid,
+                    "interface": interface,
+                    "device": device,
+                    "on_battery": on_battery,
+                    "time": at,
+                },
+                "rules": rules,
+                "format": format,
+            });
+            let reply = call(client, "automation.dry_run", params).await?;
+            print_output(
+                json,
+                &reply,
+                &reply.message["evaluations"],
+                &[
+                    column("RULE", |e| text(&e["rule"])),
+                    column("MATCHED", |e| text(&e["matched"])),
+                    column("DETAILS", |e| match e["matched"].as_bool() {
+                        Some(true) => {
+                            let actions = e["actions"].as_array().cloned().unwrap_or_default();
+                            actions
+                                .iter()
+                                .map(Value::to_string)
+                                .collect::<Vec<_>>()
+                                .join(" ")
+                        }
+                        _ => text(&e["reason"]),
+                    }),
+                ],
+            );
+            if !json {
+                print_warnings(&reply.message["warnings"]);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Converts a conflict resolution from the command line to the daemon form
+fn resolution(value: &str) -> Result<Value, String> {
+    match value {
+        "skip" => Ok(json!("skip")),
+        "replace" => Ok(json!("replace")),
+        "keep-both" => Ok(json!("keep_both")),
+        _ => match value.strip_prefix("rename:") {
+            Some(name) if !name.is_empty() => Ok(json!({ "rename": name })),
+            _ => Err(format!(
+                "Unknown resolution {}, use skip, replace, keep-both or rename:NAME",
+                value
+            )),
+        },
+    }
+}
+
+fn print_warnings(warnings: &Value) {
+    for warning in warnings.as_array().map(Vec::as_slice).unwrap_or_default() {
+        eprintln!("Warning: {}", text(warning));
+    }
+}
+
+async fn run_bluetooth(
+    client: &DaemonClient,
+    command: BtCommand,
+    json: bool,
+) -> Result<(), String> {
+    match command {
+        BtCommand::Adapters => {
+            let reply = call(client, "bt.adapters", json!({})).await?;
+            print_output(
+                json,
+                &reply,
+                &reply.message,
+                &[
+                    column("ID", |a| text(&a["id"])),
+                    column("ADDRESS", |a| text(&a["address"])),
+                    column("NAME", |a| text(&a["alias"])),
+                    column("POWERED", |a| text(&a["powered"])),
+                    column("DISCOVERABLE", |a| text(&a["discoverable"])),
+                ],
+            );
+        }
+        BtCommand::Devices { adapter } => {
+            let reply = call(client, "bt.devices", json!({ "adapter": adapter })).await?;
+            print_output(json, &reply, &reply.message, DEVICE_COLUMNS);
+        }
+        BtCommand::Paired { adapter } => {
+            let reply = call(client, "bt.paired_devices", json!({ "adapter": adapter })).await?;
+            print_output(json, &reply, &reply.message, DEVICE_COLUMNS);
+        }
+        BtCommand::Scan { adapter, duration } => {
+            let params = json!({ "adapter": adapter });
+            call(client, "bt.start_discovery", params.clone()).await?;
+            tokio::time::sleep(Duration::from_secs(duration)).await;
+            let _ = call(client, "bt.stop_discovery", params.clone()).await;
+
+            let reply = call(client, "bt.devices", params).await?;
+            print_output(json, &reply, &reply.message, DEVICE_COLUMNS);
+        }
+        BtCommand::Connect { device, profile } => {
+            let params = json!({ "device": device, "profile": profile });
+            print_message(json, &call(client, "bt.connect", params).await?);
+        }
+        BtCommand::Disconnect { device, profile } => {
+            let params = json!({ "device": device, "profile": profile });
+            print_message(json, &call(client, "bt.disconnect", params).await?);
+        }
+        BtCommand::Trust { device, off } => {
+            let params = json!({ "device": device, "setting": { "Trusted": !off } });
+            print_message(json, &call(client, "bt.set_device", params).await?);
+        }
+        BtCommand::Block { device, off } => {
+            let params = json!({ "device": device, "setting": { "Blocked": !off } });
+            print_message(json, &call(client, "bt.set_device", params).await?);
+        }
+        BtCommand::Remove { device } => {
+            let params = json!({ "device": device });
+            print_message(json, &call(client, "bt.remove_device", params).await?);
+        }
+        BtCommand::Battery => {
+            let reply = call(client, "bt.stats", json!({})).await?;
+            print_output(
+                json,
+                &reply,
+                &reply.message["devices"],
+                &[
+                    column("ADDRESS", |d| text(&d["address"])),
+                    column("NAME", |d| text(&d["alias"])),
+                    column("BATTERY", |d| match d["battery"].as_u64() {
+                        Some(battery) => format!("{}%", battery),
+                        None => "-".to_string(),
+                    }),
+                    column("RSSI", |d| text(&d["rssi"])),
+                ],
+            );
+        }
+        BtCommand::Audio { device, set } => {
+            let reply = match set {
+                Some(profile) => {
+                    let params = json!({ "device": device, "profile": profile });
+                    call(client, "bt.set_audio_profile", params).await?
+                }
+                None => call(client, "bt.audio_profiles", json!({ "device": device })).await?,
+            };
+
+            // Flag the active profile on each row so the table can mark it
+            let active = reply.message["active_profile"].clone();
+            let mut profiles = reply.message["profiles"].clone();
+            for profile in profiles.as_array_mut().into_iter().flatten() {
+                profile["active"] = Value::from(profile["name"] == active);
+            }
+            print_output(
+                json,
+                &reply,
+                &profiles,
+                &[
+                    column("", |p| marker(&p["active"])),
+                    column("PROFILE", |p| text(&p["name"])),
+                    column("CODEC", |p| variant(&p["codec"])),
+                    column("AVAILABLE", |p| text(&p["available"])),
+                    column("DESCRIPTION", |p| text(&p["description"])),
+                ],
+            );
+        }
+        BtCommand::Beacons { adapter, duration } => {
+            let params = json!({ "adapter": adapter });
+            call(client, "bt.beacon_scan", params.clone()).await?;
+            tokio::time::sleep(Duration::from_secs(duration)).await;
+            let _ = call(client, "bt.beacon_stop", params).await;
+
+            let reply = call(client, "bt.beacons", json!({})).await?;
+            print_output(
+                json,
+                &reply,
+                &reply.message,
+                &[
+                    column("ADDRESS", |a| text(&a["address"])),
+                    column("NAME", |a| text(&a["alias"])),
+                    column("RSSI", |a| match a["average_rssi"].as_f64() {
+                        Some(rssi) => format!("{:.0}", rssi),
+                        None => "-".to_string(),
+                    }),
+                    column("DISTANCE", |a| match a["distance"].as_f64() {
+                        Some(distance) => format!("{:.1} m", distance),
+                        None => "-".to_string(),
+                    }),
+                    column("FRAMES", |a| {
+                        let frames: Vec<String> = a["frames"]
+                            .as_array()
+                            .into_iter()
+                            .flatten()
+                            .map(frame_name)
+                            .collect();
+                        frames.join(", ")
+                    }),
+                ],
+            );
+        }
+        BtCommand::Pair { .. } => {}
+    }
+
+    Ok(())
+}
+
+/// Pairs with a device, answering the requests of the pairing agent on the terminal
+async fn pair(device: &str, json: bool) -> Result<(), String> {
+    let (sender, mut requests) = mpsc::unbounded_channel();
+    let client = DaemonClient::connect(move |event| {
+        if event.event == "bt_agent_request" {
+            let _ = sender.send(event.payload);
+        }
+    })
+    .await
+    .map_err(|e| format!("Error connecting to daemon: {:?}", e))?;
+    call(
+        &client,
+        "events.subscribe",
+        json!({ "events": ["bt_agent_request"] }),
+    )
+    .await?;
+
+    let pairing = call(&client, "bt.pair", json!({ "device": device }));
+    tokio::pin!(pairing);
+    loop {
+        tokio::select! {
+            result = &mut pairing => {
+                print_message(json, &result?);
+                return Ok(());
+            }
+            Some(request) = requests.recv() => {
+                let Ok(request) = serde_json::from_value::<AgentRequest>(request) else {
+                    continue;
+                };
+                // Prompts block on the terminal, keep them off the runtime
+                let (id, needs_response) = (request.id, request.needs_response);
+                let response = tokio::task::spawn_blocking(move || ask_agent_request(&request))
+                    .await
+                    .ok()
+                    .flatten();
+                if needs_response {
+                    let response = response.unwrap_or(AgentResponse::Reject);
+                    let params = json!({ "id": id, "response": response });
+                    if let Err(e) = call(&client, "bt.agent_respond", params).await {
+                        eprintln!("Error answering request: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Whether the arguments ask for the command line interface
+///
+/// The GUI can be started with arguments of its own, e.g. by a desktop
+/// launcher, so only a known subcommand, `--help` or `--version` selects the
+/// command line interface.
+pub fn wants_cli(args: &[String]) -> bool {
+    let mut args = args
+        .iter()
+        .skip(1)
+        .map(String::as_str)
+        .filter(|arg| *arg != "--json");
+    match args.next() {
+        None => false,
+        Some("--help" | "-h" | "--version" | "-V" | "help" | "--speed-test-server") => true,
+        Some(arg) => Cli::command()
+            .get_subcommands()
+            .any(|command| command.get_name() == arg),
+    }
+}
+
+/// Runs the command line interface and returns the exit code
+///
+/// Commands are run by the daemon, which is started in the background if
+/// it does not run yet. `--speed-test-server [address]` is still accepted
+/// for the `speed-test-server` subcommand.
+pub fn run() -> i32 {
+    let args = std::env::args().map(|arg| match arg.as_str() {
+        "--speed-test-server" => "speed-test-server".to_string(),
+        _ => arg,
+    });
+    let cli = Cli::parse_from(args);
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Failed to start tokio runtime: {:?}", e);
+            return 1;
+        }
+    };
+
+    match runtime.block_on(run_command(cli.command, cli.json)) {
+        Ok(()) => 0,
+        Err(e) => {
+            if cli.json {
+                println!("{}", json!({ "error": e }));
+            } else {
+                eprintln!("{}", e);
+            }
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wants(args: &[&str]) -> bool {
+        let args: Vec<String> = ["wiblue"]
+            .iter()
+            .chain(args)
+            .map(|a| a.to_string())
+            .collect();
+        wants_cli(&args)
+    }
+
+    #[test]
+    fn selects_cli_for_subcommands_only() {
+        assert!(wants(&["scan"]));
+        assert!(wants(&["--json", "bt", "devices"]));
+        assert!(wants(&["speed-test-server"]));
+        assert!(wants(&["--speed-test-server"]));
+        assert!(wants(&["--help"]));
+        assert!(wants(&["-V"]));
+
+        // Arguments a launcher or the WebView may pass to the GUI
+        assert!(!wants(&[]));
+        assert!(!wants(&["--no-sandbox"]));
+        assert!(!wants(&["/home/user/Downloads/profiles.json"]));
+        assert!(!wants(&["--json"]));
+    }
+}
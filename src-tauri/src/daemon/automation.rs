@@ -0,0 +1,307 @@
+use std::{
+    collections::{HashMap, HashSet},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::{
+    process::Command,
+    sync::{broadcast::error::RecvError, RwLock},
+};
+
+use super::{
+    events::EventBus,
+    methods::{bt_monitor_stats, dispatch, net_monitor_connectivity, start_device_watcher},
+    monitors::ClientId,
+    watcher::{on_battery, set_background_scans, start_watcher},
+};
+use crate::{
+    automation::{
+        automation_error::RuleError,
+        evaluator::{evaluate, Context},
+        rule_data::{Action, RuleSet, DEFAULT_COOLDOWN_SECS},
+    },
+    wlan::{
+        get_networks::get_active_network,
+        networkmanager_error::ProfileError,
+        profiles::{activate_profile, deactivate_profile, modify_profile},
+    },
+};
+
+/// Seconds a `run` action may take before it is killed
+const RUN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Rules the engine currently runs
+static RULES: LazyLock<RwLock<RuleSet>> = LazyLock::new(|| RwLock::new(RuleSet::default()));
+
+/// Set once the engine listens for events
+static ENGINE_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Set once the connectivity monitor feeding `connectivity_changed` runs
+static CONNECTIVITY_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Published for every action the engine ran
+#[derive(Debug, Clone, Serialize)]
+struct ActionResult {
+    rule: String,
+    event: String,
+    action: Action,
+    /// Why the action failed, `None` if it succeeded
+    error: Option<String>,
+}
+
+/// Loads the rules and runs them against the events of the daemon
+///
+/// Starts the watchers and monitors raising the events the rules are
+/// triggered by. Rules with errors are logged and leave the engine empty
+/// until they are fixed and reloaded.
+pub async fn start_automation(events: &EventBus) {
+    if let Err(e) = reload_rules(events).await {
+        eprintln!("Error loading automation rules: {:?}", e);
+    }
+
+    if !ENGINE_STARTED.swap(true, Ordering::SeqCst) {
+        tokio::spawn(run_engine(events.clone()));
+    }
+}
+
+/// Reads the rules file again and replaces the running rules
+///
+/// # Returns
+/// - `Ok(RuleSet)` with the rules now running
+/// - `Err(RuleError)` if the file is invalid, the old rules keep running
+pub async fn reload_rules(events: &EventBus) -> Result<RuleSet, RuleError> {
+    let rules = RuleSet::load()?;
+    for warning in rules.warnings() {
+        eprintln!("Automation rules: {}", warning);
+    }
+
+    start_sources(&rules, events);
+    *RULES.write().await = rules.clone();
+    Ok(rules)
+}
+
+/// The rules the engine currently runs
+pub async fn current_rules() -> RuleSet {
+    RULES.read().await.clone()
+}
+
+/// Starts what raises the events the rules are triggered by
+fn start_sources(rules: &RuleSet, events: &EventBus) {
+    let triggers: HashSet<&str> = rules
+        .rules
+        .iter()
+        .filter(|rule| rule.enabled)
+        .flat_map(|rule| rule.on.iter().map(String::as_str))
+        .collect();
+
+    if triggers
+        .iter()
+        .any(|t| t.starts_with("wifi_") || *t == "quota_exceeded" || *t == "power_changed")
+    {
+        start_watcher(events);
+    }
+    if triggers.iter().any(|t| t.starts_with("bt_device_")) {
+        start_device_watcher(events.clone());
+    }
+    if triggers.contains("bluetooth_low_battery") {
+        if let Err(e) = bt_monitor_stats(None, events.clone(), ClientId::DAEMON) {
+            eprintln!("Error starting Bluetooth monitor for rules: {}", e.message);
+        }
+    }
+    if triggers.contains("connectivity_changed")
+        && !CONNECTIVITY_STARTED.swap(true, Ordering::SeqCst)
+    {
+        if let Err(e) = net_monitor_connectivity(None, events.clone(), ClientId::DAEMON) {
+            eprintln!(
+                "Error starting connectivity monitor for rules: {}",
+                e.message
+            );
+        }
+    }
+}
+
+/// Connected network and power source right now
+pub async fn current_context() -> Context {
+    let active = tokio::task::spawn_blocking(get_active_network)
+        .await
+        .ok()
+        .flatten();
+    let on_battery = tokio::task::spawn_blocking(on_battery).await.ok().flatten();
+
+    Context {
+        ssid: active.as_ref().map(|a| a.ssid.clone()),
+        bssid: active.as_ref().map(|a| a.bssid.clone()),
+        interface: active.map(|a| a.device).filter(|d| !d.is_empty()),
+        on_battery,
+        ..Context::default()
+    }
+}
+
+async fn run_engine(events: EventBus) {
+    let mut receiver = events.subscribe();
+    let mut last_fired: HashMap<String, Instant> = HashMap::new();
+
+    loop {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(missed)) => {
+                eprintln!("Automation engine fell behind, dropped {} events", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
+        let rules = {
+            let rules = RULES.read().await;
+            if !rules
+                .rules
+                .iter()
+                .any(|rule| rule.on.contains(&event.event))
+            {
+                continue;
+            }
+            rules.clone()
+        };
+
+        let context = current_context().await;
+        for evaluation in evaluate(&rules, &event.event, &event.payload, &context) {
+            if !evaluation.matched {
+                continue;
+            }
+
+            let cooldown = rules
+                .rules
+                .iter()
+                .find(|rule| rule.name == evaluation.rule)
+                .and_then(|rule| rule.cooldown_secs)
+                .unwrap_or(DEFAULT_COOLDOWN_SECS);
+            let now = Instant::now();
+            if last_fired
+                .get(&evaluation.rule)
+                .is_some_and(|fired| now.duration_since(*fired) < Duration::from_secs(cooldown))
+            {
+                continue;
+            }
+            last_fired.insert(evaluation.rule.clone(), now);
+
+            tokio::spawn(run_actions(
+                evaluation.rule,
+                event.event.clone(),
+                evaluation.actions,
+                events.clone(),
+            ));
+        }
+    }
+}
+
+/// Runs the actions of a rule one after another, a failed action does not
+/// stop the following ones
+async fn run_actions(rule: String, event: String, actions: Vec<Action>, events: EventBus) {
+    for action in actions {
+        let error = run_action(&action, &events).await.err();
+        if let Some(error) = &error {
+            eprintln!("Rule {}: {:?} failed: {}", rule, action, error);
+        }
+        events.emit(
+            "automation_action",
+            &ActionResult {
+                rule: rule.clone(),
+                event: event.clone(),
+                action,
+                error,
+            },
+        );
+    }
+}
+
+async fn run_action(action: &Action, events: &EventBus) -> Result<(), String> {
+    match action.clone() {
+        Action::Connect {
+            profile: Some(profile),
+            network: None,
+            ..
+        } => run_profile_command(move || activate_profile(&profile)).await,
+        Action::Connect {
+            network: Some(network),
+            password,
+            ..
+        } => {
+            call(
+                events,
+                "wifi.connect",
+                json!({ "bssid": network, "password": password }),
+            )
+            .await
+        }
+        Action::Connect { .. } => Err("Connect needs either a network or a profile".to_string()),
+        Action::Disconnect {
+            profile: Some(profile),
+            ..
+        } => run_profile_command(move || deactivate_profile(&profile)).await,
+        Action::Disconnect { interface, .. } => {
+            call(events, "wifi.disconnect", json!({ "interface": interface })).await
+        }
+        Action::Run { command, args } => run_command(&command, &args).await,
+        Action::SetProfile { profile, options } => {
+            let options: Vec<(String, String)> = options.into_iter().collect();
+            run_profile_command(move || modify_profile(&profile, &options)).await
+        }
+        Action::SetAudioProfile { device, profile } => {
+            let params = json!({ "device": device, "profile": profile });
+            call(events, "bt.set_audio_profile", params).await
+        }
+        Action::BackgroundScans { enabled } => {
+            set_background_scans(enabled);
+            Ok(())
+        }
+        Action::Notify { title, body } => {
+            events.emit(
+                "automation_notification",
+                &json!({ "title": title, "body": body }),
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Runs a daemon method, failures become the error message
+async fn call(events: &EventBus, method: &str, params: Value) -> Result<(), String> {
+    dispatch(method, params, events, ClientId::DAEMON)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.message)
+}
+
+async fn run_profile_command(
+    command: impl FnOnce() -> Result<(), ProfileError> + Send + 'static,
+) -> Result<(), String> {
+    match tokio::task::spawn_blocking(command).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(ProfileError::NoSuchProfile)) => Err("No such profile".to_string()),
+        Ok(Err(ProfileError::CommandExecutionFailure)) => Err("Error running nmcli".to_string()),
+        Err(_) => Err("Profile command panicked".to_string()),
+    }
+}
+
+async fn run_command(command: &str, args: &[String]) -> Result<(), String> {
+    let mut child = Command::new(command)
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Error starting {}: {}", command, e))?;
+
+    match tokio::time::timeout(RUN_TIMEOUT, child.wait()).await {
+        Ok(Ok(status)) if status.success() => Ok(()),
+        Ok(Ok(status)) => Err(format!("{} exited with {}", command, status)),
+        Ok(Err(e)) => Err(format!("Error waiting for {}: {}", command, e)),
+        Err(_) => Err(format!("{} did not finish in time", command)),
+    }
+}
@@ -0,0 +1,344 @@
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex as StdMutex,
+    },
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{unix::OwnedWriteHalf, UnixStream},
+    sync::{oneshot, Mutex},
+    time,
+};
+
+use super::{
+    daemon_error::DaemonError,
+    events::Event,
+    protocol::{
+        socket_path, Incoming, Reply, Request, Response, RpcError, API_VERSION, JSONRPC_VERSION,
+    },
+};
+use crate::paths::data_dir;
+
+/// How long to wait for a freshly started daemon to listen
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a call may take by default, long enough for speed tests and file transfers
+const CALL_TIMEOUT: Duration = Duration::from_secs(600);
+
+type Pending = Arc<StdMutex<HashMap<u64, oneshot::Sender<Response>>>>;
+
+/// Connection to the daemon, shared by concurrent calls
+pub struct DaemonClient {
+    writer: Mutex<OwnedWriteHalf>,
+    pending: Pending,
+    next_id: AtomicU64,
+    /// Set by the reader while it holds the `pending` lock
+    closed: Arc<AtomicBool>,
+    call_timeout: Duration,
+}
+
+impl DaemonClient {
+    /// Connects to the daemon, starting one in the background if none runs
+    ///
+    /// # Arguments
+    /// * `on_event` - called with every event the connection subscribes to
+    ///   through `events.subscribe`
+    ///
+    /// # Returns
+    /// - `Ok(DaemonClient)` once the daemon answered with a matching API version
+    /// - `Err(DaemonError)` if no daemon could be reached or started
+    pub async fn connect(
+        on_event: impl Fn(Event) + Send + Sync + 'static,
+    ) -> Result<Self, DaemonError> {
+        let socket = socket_path();
+        let stream = match UnixStream::connect(&socket).await {
+            Ok(stream) => stream,
+            Err(_) => {
+                spawn_daemon()?;
+                wait_for_daemon(&socket).await?
+            }
+        };
+
+        Self::from_stream(stream, on_event).await
+    }
+
+    /// Connects to a daemon listening on the given socket, without starting one
+    pub async fn connect_to(
+        socket: &Path,
+        on_event: impl Fn(Event) + Send + Sync + 'static,
+    ) -> Result<Self, DaemonError> {
+        let stream = UnixStream::connect(socket)
+            .await
+            .map_err(|_| DaemonError::ConnectionFailure)?;
+
+        Self::from_stream(stream, on_event).await
+    }
+
+    async fn from_stream(
+        stream: UnixStream,
+        on_event: impl Fn(Event) + Send + Sync + 'static,
+    ) -> Result<Self, DaemonError> {
+        let (reader, writer) = stream.into_split();
+        let pending: Pending = Arc::new(StdMutex::new(HashMap::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let reader_pending = pending.clone();
+        let reader_closed = closed.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                match serde_json::from_str::<Incoming>(&line) {
+                    Ok(Incoming::Notification(notification)) => on_event(notification.params),
+                    Ok(Incoming::Response(response)) => {
+                        let waiting = response.id.as_u64().and_then(|id| {
+                            reader_pending.lock().ok().and_then(|mut p| p.remove(&id))
+                        });
+                        if let Some(waiting) = waiting {
+                            let _ = waiting.send(response);
+                        }
+                    }
+                    Err(e) => eprintln!("Invalid message from daemon: {:?}", e),
+                }
+            }
+
+            // Dropping the senders fails every call still waiting, calls
+            // check `closed` under the same lock so none is added afterwards
+            let mut pending = reader_pending.lock().unwrap_or_else(|e| e.into_inner());
+            reader_closed.store(true, Ordering::SeqCst);
+            pending.clear();
+        });
+
+        // A daemon that does not answer the handshake is as good as none
+        let client = DaemonClient {
+            writer: Mutex::new(writer),
+            pending,
+            next_id: AtomicU64::new(1),
+            closed,
+            call_timeout: STARTUP_TIMEOUT,
+        };
+
+        let version = client
+            .call("daemon.version", json!({}))
+            .await
+            .map_err(|_| DaemonError::Closed)?;
+        if version.message.get("api").and_then(Value::as_u64) != Some(API_VERSION as u64) {
+            eprintln!(
+                "Daemon speaks API {}, expected {}",
+                version.message, API_VERSION
+            );
+            return Err(DaemonError::VersionMismatch);
+        }
+
+        Ok(client.with_call_timeout(CALL_TIMEOUT))
+    }
+
+    /// Sets how long a call waits for its response
+    pub fn with_call_timeout(mut self, timeout: Duration) -> Self {
+        self.call_timeout = timeout;
+        self
+    }
+
+    /// Whether the daemon closed the connection
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Calls a method and waits for its result
+    ///
+    /// # Arguments
+    /// * `method` - method name, e.g. `bt.connect`
+    /// * `params` - object with the named parameters
+    ///
+    /// # Returns
+    /// - `Ok(Reply)` with the data or message of the method
+    /// - `Err(RpcError)` if the method failed, status 503 if the daemon is gone
+    ///   and 504 if it did not answer in time
+    pub async fn call(&self, method: &str, params: Value) -> Result<Reply, RpcError> {
+        let unavailable = || RpcError::new(503, "Daemon unavailable");
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let request = Request {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id: Some(Value::from(id)),
+            method: method.to_string(),
+            params,
+        };
+        let mut line = serde_json::to_string(&request)
+            .map_err(|_| RpcError::new(500, "Error serializing request"))?;
+        line.push('\n');
+
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            if self.is_closed() {
+                return Err(unavailable());
+            }
+            pending.insert(id, sender);
+        }
+
+        let written = self.writer.lock().await.write_all(line.as_bytes()).await;
+        if written.is_err() {
+            self.forget(id);
+            return Err(unavailable());
+        }
+
+        let response = match time::timeout(self.call_timeout, receiver).await {
+            Ok(response) => response.map_err(|_| unavailable())?,
+            Err(_) => {
+                self.forget(id);
+                return Err(RpcError::new(504, "Daemon did not answer"));
+            }
+        };
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(error),
+            (Some(reply), None) => Ok(reply),
+            (None, None) => Err(RpcError::new(500, "Empty response from daemon")),
+        }
+    }
+
+    /// Stops waiting for the response to a call
+    fn forget(&self, id: u64) {
+        self.pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+    }
+}
+
+/// Starts `wiblue daemon` detached from the calling process
+///
+/// The daemon logs to `daemon.log` in the data directory.
+fn spawn_daemon() -> Result<(), DaemonError> {
+    let executable = std::env::current_exe().map_err(|_| DaemonError::ConnectionFailure)?;
+    let log = std::fs::create_dir_all(data_dir()).and_then(|_| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(data_dir().join("daemon.log"))
+    });
+    let (stdout, stderr) = match log.and_then(|file| Ok((file.try_clone()?, file))) {
+        Ok((stdout, stderr)) => (Stdio::from(stdout), Stdio::from(stderr)),
+        Err(_) => (Stdio::null(), Stdio::null()),
+    };
+
+    Command::new(executable)
+        .arg("daemon")
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(stderr)
+        // Keep the daemon alive when the terminal or GUI goes away
+        .process_group(0)
+        .spawn()
+        .map_err(|e| {
+            eprintln!("Error starting daemon: {:?}", e);
+            DaemonError::ConnectionFailure
+        })?;
+
+    Ok(())
+}
+
+async fn wait_for_daemon(socket: &Path) -> Result<UnixStream, DaemonError> {
+    let deadline = time::Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        match UnixStream::connect(socket).await {
+            Ok(stream) => return Ok(stream),
+            Err(_) if time::Instant::now() < deadline => {
+                time::sleep(Duration::from_millis(100)).await;
+            }
+            Err(_) => {
+                eprintln!("Daemon did not start listening on {}", socket.display());
+                return Err(DaemonError::ConnectionFailure);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use tokio::{io::AsyncBufReadExt, net::UnixListener};
+
+    use super::*;
+
+    /// Serves a daemon that answers the handshake and then `handle`s the connection
+    async fn fake_daemon<F>(
+        name: &str,
+        handle: impl FnOnce(UnixStream) -> F + Send + 'static,
+    ) -> PathBuf
+    where
+        F: std::future::Future<Output = ()> + Send,
+    {
+        let socket = std::env::temp_dir().join(format!(
+            "wiblue-client-{}-{}.sock",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut line = String::new();
+            stream.read_line(&mut line).await.unwrap();
+            let request: Request = serde_json::from_str(&line).unwrap();
+            let version = Response::new(
+                request.id.unwrap(),
+                Reply::data(&json!({ "api": API_VERSION })),
+            );
+            let mut stream = stream.into_inner();
+            let reply = format!("{}\n", serde_json::to_string(&version).unwrap());
+            stream.write_all(reply.as_bytes()).await.unwrap();
+            handle(stream).await;
+        });
+        socket
+    }
+
+    #[tokio::test]
+    async fn times_out_calls_the_daemon_does_not_answer() {
+        let socket = fake_daemon("silent", |stream| async move {
+            time::sleep(Duration::from_secs(5)).await;
+            drop(stream);
+        })
+        .await;
+        let client = DaemonClient::connect_to(&socket, |_| {})
+            .await
+            .unwrap()
+            .with_call_timeout(Duration::from_millis(100));
+
+        let error = client.call("wifi.scan", json!({})).await.unwrap_err();
+        assert_eq!(error.code, 504);
+        assert!(client.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn fails_calls_once_the_daemon_is_gone() {
+        let socket = fake_daemon("gone", |stream| async move { drop(stream) }).await;
+        let client = DaemonClient::connect_to(&socket, |_| {})
+            .await
+            .unwrap()
+            .with_call_timeout(Duration::from_secs(5));
+
+        for _ in 0..100 {
+            if client.is_closed() {
+                break;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(client.is_closed());
+        let error = time::timeout(Duration::from_secs(1), client.call("wifi.scan", json!({})))
+            .await
+            .expect("the call must not wait for a closed connection")
+            .unwrap_err();
+        assert_eq!(error.code, 503);
+    }
+}
@@ -0,0 +1,11 @@
+#[derive(Debug)]
+pub enum DaemonError {
+    /// The socket could not be created or is used by another daemon
+    SocketFailure,
+    /// No daemon is listening and none could be started
+    ConnectionFailure,
+    /// The daemon speaks another API version
+    VersionMismatch,
+    /// The daemon closed the connection before answering
+    Closed,
+}
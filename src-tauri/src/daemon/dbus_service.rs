@@ -0,0 +1,495 @@
+use std::{env, time::Duration};
+
+use serde::Serialize;
+use tokio::sync::broadcast::error::RecvError;
+use zbus::{
+    connection, interface,
+    object_server::{Interface, InterfaceRef, SignalEmitter},
+    zvariant::Type,
+    DBusError,
+};
+
+use super::{daemon_error::DaemonError, events::EventBus};
+use crate::wlan::{
+    get_networks::{get_active_network, ActiveNetwork},
+    manager::WifiManager,
+    network_data::WifiNetwork,
+    network_stats::NetworkMonitor,
+    networkmanager_error::{ProfileError, WifiConnectionError},
+    profiles::SavedProfile,
+};
+
+/// Well-known name the service owns on the session bus
+pub const SERVICE_NAME: &str = "com.wiblue.Manager";
+pub const MANAGER_PATH: &str = "/com/wiblue/Manager";
+
+/// Bus address to use instead of the session bus, e.g. a private bus in tests
+pub const BUS_ADDRESS_ENV: &str = "WIBLUE_SESSION_BUS_ADDRESS";
+
+/// Seconds between two updates of the connection and throughput properties
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Errors returned to D-Bus callers
+#[derive(Debug, DBusError)]
+#[zbus(prefix = "com.wiblue.Error")]
+enum ManagerError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    NoSuchNetwork(String),
+    NoPasswordProvided(String),
+    WrongPassword(String),
+    NotConnected(String),
+    NoSuchProfile(String),
+    Failed(String),
+}
+
+impl From<WifiConnectionError> for ManagerError {
+    fn from(error: WifiConnectionError) -> Self {
+        match error {
+            WifiConnectionError::NoSuchNetwork => ManagerError::NoSuchNetwork("No network".into()),
+            WifiConnectionError::NoPasswordProvided => {
+                ManagerError::NoPasswordProvided("No password provided".into())
+            }
+            WifiConnectionError::WrongPassword => {
+                ManagerError::WrongPassword("Wrong password".into())
+            }
+            WifiConnectionError::NotConnected => ManagerError::NotConnected("Not connected".into()),
+            WifiConnectionError::UnknownError | WifiConnectionError::AskingError => {
+                ManagerError::Failed("Unknown error".into())
+            }
+        }
+    }
+}
+
+impl From<ProfileError> for ManagerError {
+    fn from(error: ProfileError) -> Self {
+        match error {
+            ProfileError::NoSuchProfile => ManagerError::NoSuchProfile("No such profile".into()),
+            ProfileError::CommandExecutionFailure => {
+                ManagerError::Failed("Error running nmcli".into())
+            }
+        }
+    }
+}
+
+/// Runs a blocking nmcli call on the blocking thread pool, method calls of
+/// every client are served by the same executor
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, ManagerError> + Send + 'static,
+) -> Result<T, ManagerError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|_| ManagerError::Failed("Internal error".into()))?
+}
+
+/// A Wi-Fi network as returned by `Scan`, signature `(ssiuysbb)`
+#[derive(Debug, Serialize, Type)]
+pub struct DBusNetwork {
+    pub ssid: String,
+    pub bssid: String,
+    pub signal_strength: i32,
+    /// Frequency in MHz
+    pub frequency: u32,
+    pub channel: u8,
+    /// e.g. `WPA2`, see [`crate::wlan::network_data::WifiSecurity`]
+    pub security: String,
+    pub is_hidden: bool,
+    pub currently_used: bool,
+}
+
+impl From<WifiNetwork> for DBusNetwork {
+    fn from(network: WifiNetwork) -> Self {
+        DBusNetwork {
+            ssid: network.ssid,
+            bssid: network.bssid,
+            signal_strength: network.signal_strength,
+            frequency: network.frequency,
+            channel: network.channel,
+            security: format!("{:?}", network.security),
+            is_hidden: network.is_hidden,
+            currently_used: network.currently_used,
+        }
+    }
+}
+
+/// A saved profile as returned by `Profiles`, signature `(ssssbb)`
+#[derive(Debug, Serialize, Type)]
+pub struct DBusProfile {
+    pub name: String,
+    pub uuid: String,
+    pub connection_type: String,
+    /// Device the profile is active on, empty if inactive
+    pub device: String,
+    pub active: bool,
+    pub autoconnect: bool,
+}
+
+impl From<SavedProfile> for DBusProfile {
+    fn from(profile: SavedProfile) -> Self {
+        DBusProfile {
+            name: profile.name,
+            uuid: profile.uuid,
+            connection_type: profile.connection_type,
+            device: profile.device.unwrap_or_default(),
+            active: profile.active,
+            autoconnect: profile.autoconnect,
+        }
+    }
+}
+
+/// `com.wiblue.Manager`, the Wi-Fi manager of the daemon on the session bus
+#[derive(Default)]
+pub struct ManagerService {
+    active: Option<ActiveNetwork>,
+    upload_speed: f64,
+    download_speed: f64,
+}
+
+#[interface(name = "com.wiblue.Manager")]
+impl ManagerService {
+    /// Lists the Wi-Fi networks in range
+    async fn scan(&self) -> Result<Vec<DBusNetwork>, ManagerError> {
+        let networks = blocking(|| {
+            <WifiNetwork as WifiManager>::scan()
+                .map_err(|_| ManagerError::Failed("Error getting networks".into()))
+        })
+        .await?;
+
+        Ok(networks.into_iter().map(DBusNetwork::from).collect())
+    }
+
+    /// Connects to a network by BSSID or SSID, an empty password for open networks
+    async fn connect(&self, bssid: String, password: String) -> Result<(), ManagerError> {
+        let password = Some(password).filter(|p| !p.is_empty());
+
+        blocking(move || {
+            Ok(<WifiNetwork as WifiManager>::connect(
+                &bssid,
+                password.as_deref(),
+            )?)
+        })
+        .await
+    }
+
+    /// Disconnects a Wi-Fi device, an empty name for the connected one
+    async fn disconnect(&self, interface: String) -> Result<(), ManagerError> {
+        let interface = Some(interface).filter(|i| !i.is_empty());
+
+        blocking(move || {
+            Ok(<WifiNetwork as WifiManager>::disconnect(
+                interface.as_deref(),
+            )?)
+        })
+        .await
+    }
+
+    /// Lists the saved Wi-Fi profiles
+    async fn profiles(&self) -> Result<Vec<DBusProfile>, ManagerError> {
+        let profiles = blocking(|| Ok(<WifiNetwork as WifiManager>::profiles()?)).await?;
+
+        Ok(profiles.into_iter().map(DBusProfile::from).collect())
+    }
+
+    /// Deletes a saved profile by name or UUID
+    async fn delete_profile(&self, profile: String) -> Result<(), ManagerError> {
+        blocking(move || Ok(<WifiNetwork as WifiManager>::delete_profile(&profile)?)).await
+    }
+
+    /// Lists the network interfaces
+    async fn interfaces(&self) -> Result<Vec<String>, ManagerError> {
+        blocking(|| {
+            <WifiNetwork as WifiManager>::scan_interfaces()
+                .map_err(|_| ManagerError::Failed("Error getting interfaces".into()))
+        })
+        .await
+    }
+
+    /// SSID of the connected network, empty if disconnected
+    #[zbus(property)]
+    fn active_ssid(&self) -> String {
+        self.active
+            .as_ref()
+            .map(|a| a.ssid.clone())
+            .unwrap_or_default()
+    }
+
+    #[zbus(property)]
+    fn active_bssid(&self) -> String {
+        self.active
+            .as_ref()
+            .map(|a| a.bssid.clone())
+            .unwrap_or_default()
+    }
+
+    /// Signal quality of the connected network in percent
+    #[zbus(property)]
+    fn signal(&self) -> u8 {
+        self.active.as_ref().map(|a| a.signal).unwrap_or_default()
+    }
+
+    /// Wi-Fi device the throughput is measured on
+    #[zbus(property)]
+    fn interface(&self) -> String {
+        self.active
+            .as_ref()
+            .map(|a| a.device.clone())
+            .unwrap_or_default()
+    }
+
+    /// Upload in bytes per second
+    #[zbus(property)]
+    fn upload_speed(&self) -> f64 {
+        self.upload_speed
+    }
+
+    /// Download in bytes per second
+    #[zbus(property)]
+    fn download_speed(&self) -> f64 {
+        self.download_speed
+    }
+
+    /// A daemon event about Wi-Fi or the network, `payload` is JSON
+    #[zbus(signal)]
+    async fn network_event(
+        emitter: &SignalEmitter<'_>,
+        event: &str,
+        payload: &str,
+    ) -> zbus::Result<()>;
+
+    /// A daemon event about Bluetooth, `payload` is JSON
+    #[zbus(signal)]
+    async fn bluetooth_event(
+        emitter: &SignalEmitter<'_>,
+        event: &str,
+        payload: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// Introspection XML of the service, generated from [`ManagerService`]
+pub fn introspection_xml() -> String {
+    let mut xml = String::from(
+        "<!DOCTYPE node PUBLIC \"-//freedesktop//DTD D-BUS Object Introspection 1.0//EN\"\n \
+         \"http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd\">\n<node>\n",
+    );
+    ManagerService::default().introspect_to_writer(&mut xml, 1);
+    xml.push_str("</node>\n");
+    xml
+}
+
+/// Publishes `com.wiblue.Manager` on the session bus
+///
+/// Keeps the connection and throughput properties current and re-emits the
+/// events of the daemon as `NetworkEvent` and `BluetoothEvent` signals.
+///
+/// # Returns
+/// - `Ok(())` once the service runs
+/// - `Err(DaemonError::ConnectionFailure)` if there is no session bus or
+///   another process owns the name
+pub async fn serve_dbus(events: &EventBus) -> Result<(), DaemonError> {
+    let builder = match env::var(BUS_ADDRESS_ENV) {
+        Ok(address) => connection::Builder::address(address.as_str()),
+        Err(_) => connection::Builder::session(),
+    };
+    serve_on(builder, events).await.map(|_| ())
+}
+
+/// Publishes the service on the bus `builder` connects to
+///
+/// # Returns
+/// The connection serving the service
+async fn serve_on(
+    builder: zbus::Result<connection::Builder<'_>>,
+    events: &EventBus,
+) -> Result<zbus::Connection, DaemonError> {
+    let conn = builder
+        .and_then(|b| b.name(SERVICE_NAME))
+        .and_then(|b| b.serve_at(MANAGER_PATH, ManagerService::default()));
+    let conn = match conn {
+        Ok(b) => b.build().await,
+        Err(e) => Err(e),
+    }
+    .map_err(|e| {
+        eprintln!(
+            "Error publishing {} on the session bus: {:?}",
+            SERVICE_NAME, e
+        );
+        DaemonError::ConnectionFailure
+    })?;
+
+    let service = conn
+        .object_server()
+        .interface::<_, ManagerService>(MANAGER_PATH)
+        .await
+        .map_err(|_| DaemonError::ConnectionFailure)?;
+
+    tokio::spawn(refresh_properties(service.clone()));
+    tokio::spawn(forward_events(events.clone(), service));
+
+    Ok(conn)
+}
+
+/// Polls the connected network and the throughput of its device
+///
+/// Speeds are averaged since the previous reading, so one monitor is kept
+/// for as long as the device stays the same.
+async fn refresh_properties(service: InterfaceRef<ManagerService>) {
+    let mut monitor: Option<NetworkMonitor> = None;
+
+    loop {
+        let active = tokio::task::spawn_blocking(get_active_network)
+            .await
+            .ok()
+            .flatten();
+
+        let device = active.as_ref().map(|a| a.device.as_str());
+        if monitor.as_ref().map(NetworkMonitor::interface) != device {
+            monitor = device.and_then(|d| NetworkMonitor::new(d).ok());
+        }
+        let stats = match &mut monitor {
+            Some(monitor) => monitor.get_stats().await,
+            None => None,
+        };
+        // The device went away, a new monitor is set up once it is back
+        if stats.is_none() && monitor.as_ref().is_some_and(|m| !m.interface_exists()) {
+            monitor = None;
+        }
+        let (upload_speed, download_speed) = stats
+            .map(|stats| (stats.speed_up, stats.speed_down))
+            .unwrap_or((0.0, 0.0));
+
+        let emitter = service.signal_emitter();
+        let mut state = service.get_mut().await;
+        let (old_ssid, old_bssid, old_signal, old_interface) = (
+            state.active_ssid(),
+            state.active_bssid(),
+            state.signal(),
+            state.interface(),
+        );
+        state.active = active;
+        state.upload_speed = upload_speed;
+        state.download_speed = download_speed;
+
+        if state.active_ssid() != old_ssid {
+            let _ = state.active_ssid_changed(emitter).await;
+        }
+        if state.active_bssid() != old_bssid {
+            let _ = state.active_bssid_changed(emitter).await;
+        }
+        if state.signal() != old_signal {
+            let _ = state.signal_changed(emitter).await;
+        }
+        if state.interface() != old_interface {
+            let _ = state.interface_changed(emitter).await;
+        }
+        let _ = state.upload_speed_changed(emitter).await;
+        let _ = state.download_speed_changed(emitter).await;
+        drop(state);
+
+        tokio::time::sleep(REFRESH_INTERVAL).await;
+    }
+}
+
+/// Re-emits every daemon event as a D-Bus signal
+async fn forward_events(events: EventBus, service: InterfaceRef<ManagerService>) {
+    let mut receiver = events.subscribe();
+    loop {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return,
+        };
+
+        let payload = event.payload.to_string();
+        let emitter = service.signal_emitter();
+        let result = if event.event.starts_with("bt_") || event.event.starts_with("bluetooth_") {
+            ManagerService::bluetooth_event(emitter, &event.event, &payload).await
+        } else {
+            ManagerService::network_event(emitter, &event.event, &payload).await
+        };
+        if let Err(e) = result {
+            eprintln!("Error emitting {} signal: {:?}", event.event, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use serde_json::json;
+    use tokio::time;
+    use zbus::{fdo::DBusProxy, proxy};
+
+    use super::*;
+    use crate::bluetooth::mock_bluez::PrivateBus;
+
+    #[proxy(
+        interface = "com.wiblue.Manager",
+        default_service = "com.wiblue.Manager",
+        default_path = "/com/wiblue/Manager"
+    )]
+    trait Manager {
+        #[zbus(property)]
+        fn upload_speed(&self) -> zbus::Result<f64>;
+
+        #[zbus(signal)]
+        fn network_event(&self, event: &str, payload: &str) -> zbus::Result<()>;
+
+        #[zbus(signal)]
+        fn bluetooth_event(&self, event: &str, payload: &str) -> zbus::Result<()>;
+    }
+
+    #[test]
+    fn interface_file_matches_the_service() {
+        assert_eq!(
+            include_str!("../../dbus/com.wiblue.Manager.xml"),
+            introspection_xml()
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_the_manager_and_forwards_events() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon not available, skipping");
+            return;
+        };
+        let events = EventBus::new();
+        let _service = serve_on(connection::Builder::address(bus.address.as_str()), &events)
+            .await
+            .unwrap();
+
+        let client = bus.connect().await;
+        let owned = DBusProxy::new(&client)
+            .await
+            .unwrap()
+            .name_has_owner(SERVICE_NAME.try_into().unwrap())
+            .await
+            .unwrap();
+        assert!(owned);
+
+        let manager = ManagerProxy::new(&client).await.unwrap();
+        assert_eq!(manager.upload_speed().await.unwrap(), 0.0);
+
+        let mut network = manager.receive_network_event().await.unwrap();
+        let mut bluetooth = manager.receive_bluetooth_event().await.unwrap();
+        // The forwarding task subscribes in the background, so repeat until it runs
+        let signal = loop {
+            events.emit("wifi_connected", &json!({ "ssid": "home" }));
+            if let Ok(signal) = time::timeout(Duration::from_millis(100), network.next()).await {
+                break signal.unwrap();
+            }
+        };
+        let args = signal.args().unwrap();
+        assert_eq!(args.event, "wifi_connected");
+        assert_eq!(args.payload, r#"{"ssid":"home"}"#);
+
+        events.emit(
+            "bt_device_found",
+            &json!({ "address": "00:11:22:33:44:55" }),
+        );
+        let signal = time::timeout(Duration::from_secs(5), bluetooth.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(signal.args().unwrap().event, "bt_device_found");
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+/// Events a slow client may fall behind by before it misses some
+const EVENT_BUFFER: usize = 256;
+
+/// Something that happened in the daemon, e.g. a device found or new stats
+///
+/// Event names match the ones the GUI listens for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub event: String,
+    pub payload: Value,
+}
+
+/// Fans events out to every subscribed client
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        EventBus { sender }
+    }
+
+    /// Publishes an event, dropped if nobody is subscribed
+    pub fn emit<T: Serialize>(&self, event: &str, payload: &T) {
+        match serde_json::to_value(payload) {
+            Ok(payload) => {
+                let _ = self.sender.send(Event {
+                    event: event.to_string(),
+                    payload,
+                });
+            }
+            Err(e) => eprintln!("Error serializing {} event: {:?}", event, e),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
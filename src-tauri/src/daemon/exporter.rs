@@ -0,0 +1,624 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write as _,
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use systemstat::{Platform, System};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::Mutex,
+};
+
+use super::daemon_error::DaemonError;
+use crate::{
+    bluetooth::device_stats::{BluetoothDeviceStats, BluetoothMonitor, BluetoothMonitorConfig},
+    paths::config_dir,
+    wlan::{
+        channel_analyzer::WifiBand,
+        get_networks::{get_access_points, get_active_network, ActiveNetwork, VisibleAccessPoint},
+        network_stats::{NetworkMonitor, NetworkStats},
+        ping_monitor::{PingMonitor, PingMonitorConfig, PingStats, PingTargetKind},
+    },
+};
+
+/// Port the exporter listens on unless configured otherwise
+pub const DEFAULT_PORT: u16 = 9887;
+
+/// Content type of an OpenMetrics text exposition
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Seconds between two collections of the slower metrics (scan, Bluetooth)
+const COLLECT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Seconds between two rounds of pings
+const PING_INTERVAL_SECS: u64 = 5;
+
+/// Settings of the metrics exporter, stored in `exporter.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExporterSettings {
+    /// Serve metrics while the daemon runs
+    pub enabled: bool,
+    /// Port on 127.0.0.1 the metrics are served on
+    pub port: u16,
+    /// Ping the default gateway and DNS servers for latency metrics
+    pub ping: bool,
+}
+
+impl Default for ExporterSettings {
+    fn default() -> Self {
+        ExporterSettings {
+            enabled: false,
+            port: DEFAULT_PORT,
+            ping: true,
+        }
+    }
+}
+
+impl ExporterSettings {
+    fn path() -> PathBuf {
+        config_dir().join("exporter.json")
+    }
+
+    /// Loads the settings, falling back to the defaults
+    pub fn load() -> Self {
+        std::fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the settings to the wiblue config directory
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)
+    }
+}
+
+/// Latest readings the exporter serves, refreshed in the background
+#[derive(Default)]
+struct Snapshot {
+    interfaces: Vec<(String, NetworkStats)>,
+    active: Option<ActiveNetwork>,
+    /// Visible access points per band, `None` if the last scan failed
+    access_points: Option<Vec<(&'static str, usize)>>,
+    ping: Vec<PingStats>,
+    bluetooth: Vec<BluetoothDeviceStats>,
+}
+
+type SharedSnapshot = Arc<Mutex<Snapshot>>;
+
+/// Name, help text and reading of a per-target ping metric family
+type PingFamily = (&'static str, &'static str, fn(&PingStats) -> Option<f64>);
+
+/// Serves network and Bluetooth metrics in the OpenMetrics text format
+///
+/// Listens on `127.0.0.1` only; `GET /metrics` returns the exposition, every
+/// other path a 404. Metrics are collected in the background so a scrape
+/// never waits for a Wi-Fi scan or a D-Bus round trip.
+///
+/// # Arguments
+/// * `settings` - port and whether to ping, `enabled` is not checked here
+///
+/// # Returns
+/// - `Ok(())` once the exporter listens
+/// - `Err(DaemonError::SocketFailure)` if the port is in use
+pub async fn serve_metrics(settings: &ExporterSettings) -> Result<(), DaemonError> {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, settings.port));
+    let listener = TcpListener::bind(address).await.map_err(|e| {
+        eprintln!("Error binding metrics exporter to {}: {:?}", address, e);
+        DaemonError::SocketFailure
+    })?;
+
+    let snapshot = SharedSnapshot::default();
+    tokio::spawn(collect(snapshot.clone()));
+    if settings.ping {
+        tokio::spawn(collect_ping(snapshot.clone()));
+    }
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let snapshot = snapshot.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_scrape(stream, snapshot).await {
+                            eprintln!("Error serving metrics: {:?}", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("Error accepting metrics connection: {:?}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Refreshes the interface counters, the connected network, the scan and
+/// the Bluetooth readings
+///
+/// The interface monitors are kept between collections, as speeds are
+/// averaged since their previous reading.
+async fn collect(snapshot: SharedSnapshot) {
+    let mut monitors: HashMap<String, NetworkMonitor> = HashMap::new();
+    let bluetooth = BluetoothMonitor::new(BluetoothMonitorConfig::default());
+
+    loop {
+        // Interfaces come and go, e.g. the bnep link of a PAN connection
+        let names: Vec<String> = System::new()
+            .networks()
+            .map(|networks| networks.into_keys().collect())
+            .unwrap_or_default();
+        monitors.retain(|name, _| names.contains(name));
+        for name in &names {
+            if !monitors.contains_key(name) {
+                if let Ok(monitor) = NetworkMonitor::new(name) {
+                    monitors.insert(name.clone(), monitor);
+                }
+            }
+        }
+
+        let mut interfaces = Vec::new();
+        for (name, monitor) in monitors.iter_mut() {
+            if let Some(stats) = monitor.get_stats().await {
+                interfaces.push((name.clone(), stats));
+            }
+        }
+        interfaces.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let active = tokio::task::spawn_blocking(get_active_network)
+            .await
+            .ok()
+            .flatten();
+        // The results of NetworkManager's last scan, the exporter never scans itself
+        let access_points = tokio::task::spawn_blocking(|| get_access_points(false))
+            .await
+            .ok()
+            .map(|scan| scan.ok().map(|aps| count_access_points(&aps)));
+
+        let bluetooth_devices = bluetooth
+            .get_stats()
+            .await
+            .map(|stats| stats.devices)
+            .unwrap_or_default();
+
+        let mut state = snapshot.lock().await;
+        state.interfaces = interfaces;
+        state.active = active;
+        if let Some(access_points) = access_points {
+            state.access_points = access_points;
+        }
+        state.bluetooth = bluetooth_devices;
+        drop(state);
+
+        tokio::time::sleep(COLLECT_INTERVAL).await;
+    }
+}
+
+/// Visible access points per band, counted once if several devices see them
+fn count_access_points(access_points: &[VisibleAccessPoint]) -> Vec<(&'static str, usize)> {
+    let count = |band: WifiBand| {
+        access_points
+            .iter()
+            .filter(|ap| WifiBand::from_frequency(ap.frequency) == Some(band))
+            .map(|ap| ap.bssid.as_str())
+            .collect::<HashSet<_>>()
+            .len()
+    };
+    vec![
+        ("2.4GHz", count(WifiBand::Ghz2_4)),
+        ("5GHz", count(WifiBand::Ghz5)),
+        ("6GHz", count(WifiBand::Ghz6)),
+    ]
+}
+
+/// Pings the gateway and DNS servers, keeping the latest statistics
+async fn collect_ping(snapshot: SharedSnapshot) {
+    let config = PingMonitorConfig {
+        interval_secs: PING_INTERVAL_SECS,
+        window_size: 12,
+        ..PingMonitorConfig::default()
+    };
+    let mut monitor = match PingMonitor::new(config).await {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            eprintln!("Ping metrics unavailable: {:?}", e);
+            return;
+        }
+    };
+
+    let mut interval = tokio::time::interval(Duration::from_secs(PING_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        let stats = monitor.probe().await;
+        snapshot.lock().await.ping = stats;
+    }
+}
+
+async fn handle_scrape(stream: TcpStream, snapshot: SharedSnapshot) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    // GET /metrics HTTP/1.1
+    let parts: Vec<&str> = request_line.split_whitespace().collect();
+    let target = match parts.as_slice() {
+        ["GET", target, ..] => *target,
+        _ => {
+            return write_response(reader.get_mut(), "405 Method Not Allowed", "text/plain", "")
+                .await
+        }
+    };
+    let path = target.split_once('?').map_or(target, |(path, _)| path);
+    if path != "/metrics" {
+        return write_response(reader.get_mut(), "404 Not Found", "text/plain", "").await;
+    }
+
+    let body = render(&*snapshot.lock().await);
+    write_response(reader.get_mut(), "200 OK", CONTENT_TYPE, &body).await
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                content_type,
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.shutdown().await
+}
+
+/// One metric family of the exposition
+struct Family<'a> {
+    out: &'a mut String,
+    name: &'static str,
+    suffix: &'static str,
+}
+
+impl<'a> Family<'a> {
+    fn new(out: &'a mut String, name: &'static str, kind: &str, unit: &str, help: &str) -> Self {
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        if !unit.is_empty() {
+            let _ = writeln!(out, "# UNIT {} {}", name, unit);
+        }
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let suffix = if kind == "counter" { "_total" } else { "" };
+        Family { out, name, suffix }
+    }
+
+    fn sample(&mut self, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+            .collect();
+        let _ = if labels.is_empty() {
+            writeln!(self.out, "{}{} {}", self.name, self.suffix, value)
+        } else {
+            writeln!(
+                self.out,
+                "{}{}{{{}}} {}",
+                self.name,
+                self.suffix,
+                labels.join(","),
+                value
+            )
+        };
+    }
+}
+
+/// Escapes a label value as required by the text format
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn ping_kind(kind: &PingTargetKind) -> &'static str {
+    match kind {
+        PingTargetKind::Gateway => "gateway",
+        PingTargetKind::Dns => "dns",
+        PingTargetKind::Custom => "custom",
+    }
+}
+
+/// Renders the snapshot as an OpenMetrics text exposition
+fn render(snapshot: &Snapshot) -> String {
+    let mut out = String::new();
+
+    let mut family = Family::new(
+        &mut out,
+        "wiblue_network_transmit_bytes",
+        "counter",
+        "bytes",
+        "Bytes sent on the interface since boot",
+    );
+    for (interface, stats) in &snapshot.interfaces {
+        family.sample(&[("interface", interface)], stats.bytes_up);
+    }
+    let mut family = Family::new(
+        &mut out,
+        "wiblue_network_receive_bytes",
+        "counter",
+        "bytes",
+        "Bytes received on the interface since boot",
+    );
+    for (interface, stats) in &snapshot.interfaces {
+        family.sample(&[("interface", interface)], stats.bytes_down);
+    }
+    let mut family = Family::new(
+        &mut out,
+        "wiblue_network_transmit_speed_bytes_per_second",
+        "gauge",
+        "",
+        "Upload speed of the interface over the last collection interval",
+    );
+    for (interface, stats) in &snapshot.interfaces {
+        family.sample(&[("interface", interface)], stats.speed_up);
+    }
+    let mut family = Family::new(
+        &mut out,
+        "wiblue_network_receive_speed_bytes_per_second",
+        "gauge",
+        "",
+        "Download speed of the interface over the last collection interval",
+    );
+    for (interface, stats) in &snapshot.interfaces {
+        family.sample(&[("interface", interface)], stats.speed_down);
+    }
+
+    let mut family = Family::new(
+        &mut out,
+        "wiblue_wifi_link_quality_percent",
+        "gauge",
+        "percent",
+        "Signal quality of the connected Wi-Fi network",
+    );
+    if let Some(active) = &snapshot.active {
+        family.sample(
+            &[
+                ("interface", &active.device),
+                ("ssid", &active.ssid),
+                ("bssid", &active.bssid),
+            ],
+            active.signal,
+        );
+    }
+
+    let mut family = Family::new(
+        &mut out,
+        "wiblue_wifi_access_points",
+        "gauge",
+        "",
+        "Access points visible in the last scan",
+    );
+    for (band, count) in snapshot.access_points.iter().flatten() {
+        family.sample(&[("band", band)], count);
+    }
+
+    let ping_labels = |stats: &PingStats| {
+        [
+            ("host", stats.target.host.clone()),
+            ("address", stats.target.address.to_string()),
+            ("kind", ping_kind(&stats.target.kind).to_string()),
+        ]
+    };
+    let ping_families: [PingFamily; 3] = [
+        (
+            "wiblue_ping_rtt_seconds",
+            "Average round trip time over the recent pings",
+            |s| s.rtt_avg.map(|ms| ms / 1000.0),
+        ),
+        (
+            "wiblue_ping_jitter_seconds",
+            "Mean deviation between consecutive round trip times",
+            |s| s.jitter.map(|ms| ms / 1000.0),
+        ),
+        (
+            "wiblue_ping_packet_loss_ratio",
+            "Share of the recent pings that got no reply",
+            |s| Some(s.packet_loss / 100.0),
+        ),
+    ];
+    for (name, help, value) in ping_families {
+        let unit = if name.ends_with("_seconds") {
+            "seconds"
+        } else {
+            "ratio"
+        };
+        let mut family = Family::new(&mut out, name, "gauge", unit, help);
+        for stats in &snapshot.ping {
+            if let Some(value) = value(stats) {
+                let labels = ping_labels(stats);
+                let labels: Vec<(&str, &str)> =
+                    labels.iter().map(|(k, v)| (*k, v.as_str())).collect();
+                family.sample(&labels, value);
+            }
+        }
+    }
+
+    let mut family = Family::new(
+        &mut out,
+        "wiblue_bluetooth_battery_percent",
+        "gauge",
+        "percent",
+        "Battery level of the connected Bluetooth device",
+    );
+    for device in &snapshot.bluetooth {
+        if let Some(battery) = device.battery {
+            family.sample(
+                &[("device", &device.address), ("name", &device.alias)],
+                battery,
+            );
+        }
+    }
+    let mut family = Family::new(
+        &mut out,
+        "wiblue_bluetooth_rssi_dbm",
+        "gauge",
+        "",
+        "Received signal strength of the connected Bluetooth device",
+    );
+    for device in &snapshot.bluetooth {
+        if let Some(rssi) = device.rssi {
+            family.sample(
+                &[("device", &device.address), ("name", &device.alias)],
+                rssi,
+            );
+        }
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::*;
+    use crate::wlan::ping_monitor::PingTarget;
+
+    fn stats(bytes_up: u64, bytes_down: u64, speed_up: f64, speed_down: f64) -> NetworkStats {
+        NetworkStats {
+            bytes_up,
+            bytes_down,
+            speed_up,
+            speed_down,
+            total_up: 0,
+            total_down: 0,
+        }
+    }
+
+    fn access_point(bssid: &str, frequency: u32, device: &str) -> VisibleAccessPoint {
+        VisibleAccessPoint {
+            ssid: "Net".to_string(),
+            bssid: bssid.to_string(),
+            signal: 50,
+            frequency,
+            device: device.to_string(),
+            active: false,
+        }
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape("Home"), "Home");
+        assert_eq!(escape(r#"Bob's "Net""#), r#"Bob's \"Net\""#);
+        assert_eq!(escape("a\\b\nc"), "a\\\\b\\nc");
+    }
+
+    #[test]
+    fn renders_every_family_of_an_empty_snapshot() {
+        let out = render(&Snapshot::default());
+
+        assert!(out.starts_with("# TYPE wiblue_network_transmit_bytes counter\n"));
+        assert!(out.ends_with("# HELP wiblue_bluetooth_rssi_dbm Received signal strength of the connected Bluetooth device\n# EOF\n"));
+        assert_eq!(out.matches("# TYPE ").count(), 11);
+        // Families without readings have no samples
+        assert!(out.lines().all(|line| line.starts_with('#')));
+    }
+
+    #[test]
+    fn renders_samples_with_labels_and_units() {
+        let snapshot = Snapshot {
+            interfaces: vec![("wlan0".to_string(), stats(100, 2000, 1.5, 250.0))],
+            active: Some(ActiveNetwork {
+                ssid: "Caf\u{e9} \"Free\"".to_string(),
+                bssid: "aa:bb:cc:dd:ee:ff".to_string(),
+                signal: 72,
+                device: "wlan0".to_string(),
+            }),
+            access_points: Some(vec![("2.4GHz", 3), ("5GHz", 1), ("6GHz", 0)]),
+            ping: vec![PingStats {
+                target: PingTarget {
+                    host: "192.168.1.1".to_string(),
+                    address: "192.168.1.1".parse::<IpAddr>().unwrap(),
+                    kind: PingTargetKind::Gateway,
+                    scope_id: None,
+                },
+                last_rtt: None,
+                rtt_min: Some(1.0),
+                rtt_avg: Some(2.5),
+                rtt_max: Some(4.0),
+                jitter: None,
+                packet_loss: 25.0,
+                samples: 4,
+            }],
+            bluetooth: vec![BluetoothDeviceStats {
+                path: "/org/bluez/hci0/dev_11_22_33_44_55_66".to_string(),
+                address: "11:22:33:44:55:66".to_string(),
+                alias: "Headphones".to_string(),
+                battery: Some(80),
+                rssi: None,
+                tx_power: None,
+            }],
+        };
+        let out = render(&snapshot);
+        let samples: Vec<&str> = out.lines().filter(|l| !l.starts_with('#')).collect();
+
+        assert_eq!(
+            samples,
+            [
+                r#"wiblue_network_transmit_bytes_total{interface="wlan0"} 100"#,
+                r#"wiblue_network_receive_bytes_total{interface="wlan0"} 2000"#,
+                r#"wiblue_network_transmit_speed_bytes_per_second{interface="wlan0"} 1.5"#,
+                r#"wiblue_network_receive_speed_bytes_per_second{interface="wlan0"} 250"#,
+                r#"wiblue_wifi_link_quality_percent{interface="wlan0",ssid="Café \"Free\"",bssid="aa:bb:cc:dd:ee:ff"} 72"#,
+                r#"wiblue_wifi_access_points{band="2.4GHz"} 3"#,
+                r#"wiblue_wifi_access_points{band="5GHz"} 1"#,
+                r#"wiblue_wifi_access_points{band="6GHz"} 0"#,
+                r#"wiblue_ping_rtt_seconds{host="192.168.1.1",address="192.168.1.1",kind="gateway"} 0.0025"#,
+                r#"wiblue_ping_packet_loss_ratio{host="192.168.1.1",address="192.168.1.1",kind="gateway"} 0.25"#,
+                r#"wiblue_bluetooth_battery_percent{device="11:22:33:44:55:66",name="Headphones"} 80"#,
+            ]
+        );
+        assert!(out.contains("# UNIT wiblue_ping_rtt_seconds seconds\n"));
+        assert!(out.contains("# UNIT wiblue_ping_packet_loss_ratio ratio\n"));
+    }
+
+    #[test]
+    fn counts_access_points_per_band_once() {
+        let access_points = [
+            access_point("00:00:00:00:00:01", 2412, "wlan0"),
+            access_point("00:00:00:00:00:01", 2412, "wlan1"),
+            access_point("00:00:00:00:00:02", 2437, "wlan0"),
+            access_point("00:00:00:00:00:03", 5180, "wlan0"),
+            access_point("00:00:00:00:00:04", 5955, "wlan0"),
+        ];
+
+        assert_eq!(
+            count_access_points(&access_points),
+            [("2.4GHz", 2), ("5GHz", 1), ("6GHz", 1)]
+        );
+    }
+}
@@ -0,0 +1,1373 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock,
+    },
+};
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use super::{
+    automation::{current_context, current_rules, reload_rules},
+    events::EventBus,
+    monitors::{self, ClientId, StopFn},
+    protocol::{Reply, RpcError, INVALID_PARAMS, METHOD_NOT_FOUND},
+    sinks::{
+        sink_data::{SinkSettings, SinkTarget, DEFAULT_EVENTS},
+        sink_error::SinkError,
+        test_sink,
+    },
+    watcher::background_scans_enabled,
+};
+use crate::automation::{
+    automation_error::RuleError,
+    evaluator::{evaluate, Context},
+    rule_data::{RuleFormat, RuleSet},
+};
+use crate::bluetooth::{
+    adapter_data::AdapterSetting,
+    agent::{respond, AgentResponse},
+    audio::{get_audio_device, set_audio_profile},
+    beacon::{get_advertisers, scan_beacons, stop_beacon_scan},
+    beacon_data::{BeaconEvent, BeaconScanConfig},
+    bluez_error::{AudioError, BluetoothError, ObexError, PairingError},
+    device_data::{BluetoothDevice, DeviceEvent, DeviceSetting, DiscoveryFilter},
+    device_stats::{BluetoothMonitor, BluetoothMonitorConfig},
+    discovery::{start_discovery, stop_discovery, watch_devices},
+    gatt::{get_gatt_services, read_value, subscribe, unsubscribe, write_value},
+    gatt_data::{GattValueInput, GattWriteType},
+    manager::BluetoothManager,
+    obex::{cancel_transfer, download_file, list_folder, send_file},
+    obex_agent::respond_push,
+    obex_data::ObexSettings,
+    pairing::cancel_pairing,
+    pan::{connect_pan, disconnect_pan, get_pan_connections},
+    pan_data::PanRole,
+};
+use crate::history::history_store::{HistoryEntry, HistoryStore};
+use crate::wlan::{
+    channel_analyzer::analyze_channels,
+    connect_network::connect_to_hidden_network,
+    connectivity::{
+        check_connectivity, monitor_connectivity, ConnectivityConfig, ConnectivityState,
+    },
+    dns_diagnostics::{run_diagnostics, DnsDiagnosticsConfig},
+    get_interfaces::{get_interface_details, InterfaceKind},
+    hotspot::{get_ap_support, get_hotspot_status, start_hotspot, stop_hotspot, HotspotConfig},
+    manager::WifiManager,
+    network_data::WifiNetwork,
+    network_stats::NetworkMonitor,
+    networkmanager_error::{
+        DnsError, ExchangeError, HotspotError, PingError, ProfileError, QrError, SpeedTestError,
+        StatsError, WifiConnectionError,
+    },
+    ping_monitor::{PingMonitor, PingMonitorConfig},
+    profile_exchange::{
+        apply_import, export_profiles, preview_import, read_profiles, ConflictResolution,
+        ImportSource,
+    },
+    profile_formats::ProfileFormat,
+    roaming::{recent_decisions, roaming_active, run_roaming, stop_roaming, RoamingPolicy},
+    speed_test::{run_speed_test, SpeedTestConfig},
+    speed_test_server::{bind_speed_test_server, default_server_address, serve_speed_tests},
+    wifi_qr::{decode_image, get_profile_credentials, WifiCredentials},
+};
+
+/// Records returned by `speed_test.history` unless a limit is given
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// Monitors answering `net.stats`, keyed by client and interface
+///
+/// Speeds are averaged since the previous call, so every client keeps its
+/// own baseline.
+static STATS_MONITORS: LazyLock<Mutex<HashMap<(ClientId, String), NetworkMonitor>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Set once the task publishing BlueZ device events runs
+static DEVICE_WATCHER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Reads a named parameter, missing parameters read as `null`
+fn param<T: DeserializeOwned>(params: &Value, name: &str) -> Result<T, RpcError> {
+    T::deserialize(params.get(name).unwrap_or(&Value::Null)).map_err(|e| {
+        RpcError::new(
+            INVALID_PARAMS,
+            &format!("Invalid parameter '{}': {}", name, e),
+        )
+    })
+}
+
+/// Runs a blocking function, e.g. one calling nmcli, on the blocking thread pool
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, RpcError> {
+    tokio::task::spawn_blocking(f).await.map_err(|e| {
+        eprintln!("Error running blocking task: {:?}", e);
+        RpcError::new(500, "Internal error")
+    })
+}
+
+/// Runs a method of the API
+///
+/// # Arguments
+/// * `method` - method name, e.g. `bt.connect`
+/// * `params` - object with the named parameters of the method
+/// * `events` - bus the events of started monitors and scans are published on
+/// * `client` - connection the method was called on, monitors it starts are
+///   stopped once every client subscribed to them disconnected
+///
+/// # Returns
+/// - `Ok(Reply)` with the data or message of the method
+/// - `Err(RpcError)` with an HTTP-like status if the method failed, or a
+///   JSON-RPC error code for unknown methods and invalid parameters
+pub async fn dispatch(
+    method: &str,
+    params: Value,
+    events: &EventBus,
+    client: ClientId,
+) -> Result<Reply, RpcError> {
+    let p = &params;
+    match method {
+        "wifi.scan" => blocking(wifi_scan).await?,
+        "wifi.channel_analysis" => blocking(wifi_channel_analysis).await?,
+        "wifi.connect" => wifi_connect(param(p, "bssid")?, param(p, "password")?).await,
+        "wifi.disconnect" => {
+            let interface = param(p, "interface")?;
+            blocking(move || wifi_disconnect(interface)).await?
+        }
+        "wifi.profiles" => blocking(wifi_profiles).await?,
+        "wifi.delete_profile" => {
+            let profile = param(p, "profile")?;
+            blocking(move || wifi_delete_profile(profile)).await?
+        }
+        "wifi.roaming" => wifi_roaming(),
+        "wifi.qr_code" => {
+            let profile = param(p, "profile")?;
+            blocking(move || wifi_qr_code(profile)).await?
+        }
+        "wifi.qr_parse" => {
+            let (text, image) = (param(p, "text")?, param(p, "image")?);
+            Reply::data(&blocking(move || qr_credentials(text, image)).await??)
+        }
+        "wifi.qr_connect" => wifi_qr_connect(param(p, "text")?, param(p, "image")?).await,
+        "wifi.export_profiles" => {
+            let (profiles, passphrase) = (param(p, "profiles")?, param(p, "passphrase")?);
+            blocking(move || wifi_export_profiles(profiles, passphrase)).await?
+        }
+        "wifi.import_preview" => {
+            let (path, format, passphrase) = (
+                param(p, "path")?,
+                param(p, "format")?,
+                param(p, "passphrase")?,
+            );
+            blocking(move || wifi_import_preview(path, format, passphrase)).await?
+        }
+        "wifi.import_profiles" => {
+            let (path, format, passphrase) = (
+                param(p, "path")?,
+                param(p, "format")?,
+                param(p, "passphrase")?,
+            );
+            let (resolutions, default_resolution) =
+                (param(p, "resolutions")?, param(p, "default_resolution")?);
+            blocking(move || {
+                wifi_import_profiles(
+                    import_source(path, format, passphrase)?,
+                    resolutions,
+                    default_resolution,
+                )
+            })
+            .await?
+        }
+        "wifi.hotspot_start" => {
+            let (config, events) = (param(p, "config")?, events.clone());
+            blocking(move || wifi_hotspot_start(config, &events)).await?
+        }
+        "wifi.hotspot_stop" => {
+            let events = events.clone();
+            blocking(move || wifi_hotspot_stop(&events)).await?
+        }
+        "wifi.hotspot_status" => blocking(wifi_hotspot_status).await?,
+        "wifi.ap_support" => {
+            let interface = param(p, "interface")?;
+            blocking(move || wifi_ap_support(interface)).await?
+        }
+        "wifi.set_roaming" => wifi_set_roaming(param(p, "policy")?, events.clone()),
+        "net.interfaces" => blocking(net_interfaces).await?,
+        "net.interface_details" => net_interface_details().await,
+        "net.stats" => net_stats(param(p, "interface")?, client).await,
+        "net.monitor_stats" => net_monitor_stats(param(p, "interface")?, events.clone(), client),
+        "net.monitor_stats_stop" => {
+            stop_monitor(("net.monitor_stats", param(p, "interface")?), client)
+        }
+        "net.monitor_quality" => {
+            net_monitor_quality(param(p, "config")?, events.clone(), client).await
+        }
+        "net.monitor_quality_stop" => stop_monitor(("net.monitor_quality", String::new()), client),
+        "net.dns_diagnostics" => net_dns_diagnostics(param(p, "config")?).await,
+        "net.connectivity" => net_connectivity(param(p, "config")?).await,
+        "net.monitor_connectivity" => {
+            net_monitor_connectivity(param(p, "config")?, events.clone(), client)
+        }
+        "net.monitor_connectivity_stop" => {
+            stop_monitor(("net.monitor_connectivity", String::new()), client)
+        }
+        "speed_test.run" => speed_test_run(param(p, "config")?, events.clone()).await,
+        "speed_test.history" => speed_test_history(param(p, "bssid")?, param(p, "limit")?),
+        "speed_test.start_server" => speed_test_start_server(param(p, "lan")?, client),
+        "speed_test.stop_server" => speed_test_stop_server(client),
+        "bt.adapters" => bt_adapters().await,
+        "bt.set_adapter" => bt_set_adapter(param(p, "adapter")?, param(p, "setting")?).await,
+        "bt.devices" => bt_devices(param(p, "adapter")?).await,
+        "bt.start_discovery" => {
+            bt_start_discovery(param(p, "adapter")?, param(p, "filter")?, events.clone()).await
+        }
+        "bt.stop_discovery" => bt_stop_discovery(param(p, "adapter")?).await,
+        "bt.beacon_scan" => {
+            bt_beacon_scan(param(p, "adapter")?, param(p, "config")?, events.clone())
+        }
+        "bt.beacon_stop" => bt_beacon_stop(param(p, "adapter")?),
+        "bt.beacons" => Reply::data(&get_advertisers()),
+        "bt.pair" => bt_pair(param(p, "device")?).await,
+        "bt.cancel_pair" => bt_cancel_pair(param(p, "device")?).await,
+        "bt.agent_respond" => bt_agent_respond(param(p, "id")?, param(p, "response")?),
+        "bt.paired_devices" => bt_paired_devices(param(p, "adapter")?).await,
+        "bt.connect" => bt_connect(param(p, "device")?, param(p, "profile")?).await,
+        "bt.disconnect" => bt_disconnect(param(p, "device")?, param(p, "profile")?).await,
+        "bt.set_device" => bt_set_device(param(p, "device")?, param(p, "setting")?).await,
+        "bt.remove_device" => bt_remove_device(param(p, "device")?).await,
+        "bt.stats" => bt_stats().await,
+        "bt.monitor_stats" => bt_monitor_stats(param(p, "config")?, events.clone(), client),
+        "bt.monitor_stats_stop" => stop_monitor(("bt.monitor_stats", String::new()), client),
+        "bt.gatt_services" => bt_gatt_services(param(p, "device")?).await,
+        "bt.gatt_read" => bt_gatt_read(param(p, "path")?).await,
+        "bt.gatt_write" => {
+            bt_gatt_write(
+                param(p, "path")?,
+                param(p, "value")?,
+                param(p, "write_type")?,
+            )
+            .await
+        }
+        "bt.gatt_subscribe" => bt_gatt_subscribe(param(p, "path")?, events.clone(), client),
+        "bt.gatt_unsubscribe" => bt_gatt_unsubscribe(param(p, "path")?, client),
+        "bt.audio_profiles" => bt_audio_profiles(param(p, "device")?).await,
+        "bt.set_audio_profile" => {
+            bt_set_audio_profile(param(p, "device")?, param(p, "profile")?).await
+        }
+        "bt.pan_connect" => bt_pan_connect(param(p, "device")?, param(p, "role")?).await,
+        "bt.pan_disconnect" => bt_pan_disconnect(param(p, "device")?).await,
+        "bt.pan_connections" => bt_pan_connections().await,
+        "bt.obex_send" => bt_obex_send(param(p, "device")?, param(p, "file")?, events).await,
+        "bt.obex_list" => bt_obex_list(param(p, "device")?, param(p, "folder")?).await,
+        "bt.obex_download" => {
+            bt_obex_download(param(p, "device")?, param(p, "path")?, events).await
+        }
+        "bt.obex_cancel" => bt_obex_cancel(param(p, "transfer")?).await,
+        "bt.obex_respond" => bt_obex_respond(param(p, "id")?, param(p, "accept")?),
+        "bt.obex_receive_dir" => Ok(Reply::message(
+            &ObexSettings::load().receive_dir.to_string_lossy(),
+        )),
+        "bt.obex_set_receive_dir" => bt_obex_set_receive_dir(param(p, "dir")?),
+        "sinks.list" => sinks_list(),
+        "sinks.test" => sinks_test(param(p, "name")?).await,
+        "automation.rules" => automation_rules().await,
+        "automation.reload" => automation_reload(events).await,
+        "automation.dry_run" => {
+            automation_dry_run(
+                param(p, "event")?,
+                param(p, "payload")?,
+                param(p, "context")?,
+                param(p, "rules")?,
+                param(p, "format")?,
+            )
+            .await
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            &format!("No such method: {}", method),
+        )),
+    }
+}
+
+fn wifi_scan() -> Result<Reply, RpcError> {
+    let networks = <WifiNetwork as WifiManager>::scan()
+        .map_err(|_| RpcError::new(500, "Error getting networks"))?;
+
+    Reply::data(&networks)
+}
+
+fn wifi_channel_analysis() -> Result<Reply, RpcError> {
+    let networks = <WifiNetwork as WifiManager>::scan()
+        .map_err(|_| RpcError::new(500, "Error getting networks"))?;
+
+    Reply::data(&analyze_channels(&networks))
+}
+
+async fn wifi_connect(bssid: String, password: Option<String>) -> Result<Reply, RpcError> {
+    let password = password.filter(|p| !p.is_empty());
+    let result =
+        blocking(move || <WifiNetwork as WifiManager>::connect(&bssid, password.as_deref()))
+            .await?;
+
+    connection_reply(result).await
+}
+
+/// Checks the internet access after a connection attempt succeeded
+async fn connection_reply(result: Result<(), WifiConnectionError>) -> Result<Reply, RpcError> {
+    match result {
+        Ok(_) => match check_connectivity(&ConnectivityConfig::default()).await {
+            Ok(report) => Ok(match report.state {
+                ConnectivityState::Full => Reply::message("Connected Successfully"),
+                ConnectivityState::Portal => {
+                    Reply::with_status("Connected, captive portal login required", 511)
+                }
+                ConnectivityState::Limited | ConnectivityState::None => {
+                    Reply::with_status("Connected, no internet access", 206)
+                }
+            }),
+            Err(_) => Ok(Reply::message("Connected Successfully")),
+        },
+        Err(e) => Err(match e {
+            WifiConnectionError::NoSuchNetwork => RpcError::new(404, "No network"),
+            WifiConnectionError::NoPasswordProvided => RpcError::new(502, "No password provided"),
+            WifiConnectionError::WrongPassword => RpcError::new(401, "Wrong password"),
+            WifiConnectionError::NotConnected => RpcError::new(409, "Not connected"),
+            WifiConnectionError::UnknownError | WifiConnectionError::AskingError => {
+                RpcError::new(500, "Unknown error")
+            }
+        }),
+    }
+}
+
+fn wifi_disconnect(interface: Option<String>) -> Result<Reply, RpcError> {
+    match <WifiNetwork as WifiManager>::disconnect(interface.as_deref()) {
+        Ok(_) => Ok(Reply::message("Disconnected")),
+        Err(WifiConnectionError::NotConnected) => Err(RpcError::new(409, "Not connected")),
+        Err(WifiConnectionError::NoSuchNetwork) => Err(RpcError::new(404, "No such device")),
+        Err(_) => Err(RpcError::new(500, "Unknown error")),
+    }
+}
+
+fn wifi_profiles() -> Result<Reply, RpcError> {
+    let profiles = <WifiNetwork as WifiManager>::profiles()
+        .map_err(|_| RpcError::new(500, "Error getting profiles"))?;
+
+    Reply::data(&profiles)
+}
+
+fn wifi_delete_profile(profile: String) -> Result<Reply, RpcError> {
+    match <WifiNetwork as WifiManager>::delete_profile(&profile) {
+        Ok(_) => Ok(Reply::message("Profile deleted")),
+        Err(ProfileError::NoSuchProfile) => Err(RpcError::new(404, "No such profile")),
+        Err(ProfileError::CommandExecutionFailure) => {
+            Err(RpcError::new(500, "Error deleting profile"))
+        }
+    }
+}
+
+fn wifi_roaming() -> Result<Reply, RpcError> {
+    Reply::data(&json!({
+        "running": roaming_active(),
+        "policy": RoamingPolicy::load(),
+        "decisions": recent_decisions(),
+    }))
+}
+
+fn wifi_set_roaming(policy: RoamingPolicy, events: EventBus) -> Result<Reply, RpcError> {
+    policy
+        .save()
+        .map_err(|_| RpcError::new(500, "Error saving the roaming policy"))?;
+
+    // Restart with the new policy, the old assistant stops at its next reading
+    let _ = stop_roaming();
+    if !policy.enabled {
+        return Ok(Reply::message("Roaming assistant disabled"));
+    }
+    start_roaming(policy, events);
+
+    Ok(Reply::message("Roaming assistant enabled"))
+}
+
+/// Runs the roaming assistant and publishes its decisions as
+/// `roaming_decision`
+pub(super) fn start_roaming(policy: RoamingPolicy, events: EventBus) {
+    tokio::spawn(async move {
+        let result = run_roaming(policy, background_scans_enabled, move |decision| {
+            eprintln!(
+                "Roaming on {} ({} {} at {}%): {:?}, {}",
+                decision.interface,
+                decision.ssid,
+                decision.bssid,
+                decision.signal,
+                decision.action,
+                decision.reason
+            );
+            events.emit("roaming_decision", &decision);
+        })
+        .await;
+        if let Err(e) = result {
+            eprintln!("Roaming assistant stopped: {:?}", e);
+        }
+    });
+}
+
+fn qr_error(e: QrError) -> RpcError {
+    match e {
+        QrError::InvalidUri(reason) => {
+            RpcError::new(400, &format!("Not a Wi-Fi QR code: {}", reason))
+        }
+        QrError::UnsupportedSecurity => {
+            RpcError::new(501, "Enterprise networks cannot be shared as QR code")
+        }
+        QrError::NoSuchProfile => RpcError::new(404, "No such profile"),
+        QrError::NotConnected => RpcError::new(409, "Not connected"),
+        QrError::NoQrCode => RpcError::new(422, "The image contains no QR code"),
+        QrError::EncodingFailure => RpcError::new(500, "Error encoding the QR code"),
+        QrError::CommandExecutionFailure => RpcError::new(500, "Error running nmcli or zbarimg"),
+    }
+}
+
+fn wifi_qr_code(profile: Option<String>) -> Result<Reply, RpcError> {
+    let credentials = get_profile_credentials(profile.as_deref()).map_err(qr_error)?;
+
+    Reply::data(&json!({
+        "uri": credentials.to_uri(),
+        "svg": credentials.to_svg().map_err(qr_error)?,
+        "credentials": credentials,
+    }))
+}
+
+/// Reads credentials from the text of a QR code or from an image of it
+fn qr_credentials(
+    text: Option<String>,
+    image: Option<PathBuf>,
+) -> Result<WifiCredentials, RpcError> {
+    let text = match (text, image) {
+        (Some(text), _) => text,
+        (None, Some(image)) => decode_image(&image).map_err(qr_error)?,
+        (None, None) => {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                "Either text or image is required",
+            ))
+        }
+    };
+
+    WifiCredentials::parse(&text).map_err(qr_error)
+}
+
+async fn wifi_qr_connect(text: Option<String>, image: Option<PathBuf>) -> Result<Reply, RpcError> {
+    let credentials = blocking(move || qr_credentials(text, image)).await??;
+
+    match credentials.hidden {
+        true => {
+            let result = blocking(move || {
+                connect_to_hidden_network(&credentials.ssid, credentials.password.as_deref())
+            })
+            .await?;
+            connection_reply(result).await
+        }
+        false => wifi_connect(credentials.ssid, credentials.password).await,
+    }
+}
+
+fn exchange_error(e: ExchangeError) -> RpcError {
+    match e {
+        ExchangeError::IoError => RpcError::new(404, "Error reading the file"),
+        ExchangeError::UnknownFormat => RpcError::new(415, "Unknown profile format"),
+        ExchangeError::InvalidFile(reason) => {
+            RpcError::new(422, &format!("Invalid profile file: {}", reason))
+        }
+        ExchangeError::WrongPassphrase => RpcError::new(403, "Wrong passphrase"),
+        ExchangeError::EncryptionFailure => RpcError::new(500, "Error encrypting the passwords"),
+        ExchangeError::NoSuchProfile => RpcError::new(404, "No such profile"),
+        ExchangeError::CommandExecutionFailure => RpcError::new(500, "Error running nmcli"),
+    }
+}
+
+fn wifi_export_profiles(
+    profiles: Option<Vec<String>>,
+    passphrase: Option<String>,
+) -> Result<Reply, RpcError> {
+    let mut warnings = Vec::new();
+    let bundle = export_profiles(
+        &profiles.unwrap_or_default(),
+        passphrase.as_deref(),
+        &mut warnings,
+    )
+    .map_err(exchange_error)?;
+
+    Reply::data(&json!({ "bundle": bundle, "warnings": warnings }))
+}
+
+fn import_source(
+    path: PathBuf,
+    format: Option<ProfileFormat>,
+    passphrase: Option<String>,
+) -> Result<ImportSource, RpcError> {
+    read_profiles(&path, format, passphrase.as_deref()).map_err(exchange_error)
+}
+
+fn wifi_import_preview(
+    path: PathBuf,
+    format: Option<ProfileFormat>,
+    passphrase: Option<String>,
+) -> Result<Reply, RpcError> {
+    let source = import_source(path, format, passphrase)?;
+    let profiles = preview_import(&source).map_err(exchange_error)?;
+
+    Reply::data(&json!({ "profiles": profiles, "warnings": source.warnings }))
+}
+
+fn wifi_import_profiles(
+    source: ImportSource,
+    resolutions: Option<HashMap<String, ConflictResolution>>,
+    default_resolution: Option<ConflictResolution>,
+) -> Result<Reply, RpcError> {
+    let results = apply_import(
+        &source.profiles,
+        &resolutions.unwrap_or_default(),
+        &default_resolution.unwrap_or(ConflictResolution::Skip),
+    )
+    .map_err(exchange_error)?;
+
+    Reply::data(&json!({ "results": results, "warnings": source.warnings }))
+}
+
+fn hotspot_error(e: HotspotError) -> RpcError {
+    match e {
+        HotspotError::InvalidConfig(reason) => RpcError::new(400, &reason),
+        HotspotError::NoWifiDevice => RpcError::new(404, "No Wi-Fi device"),
+        HotspotError::NotRunning => RpcError::new(409, "Hotspot is not running"),
+        HotspotError::ApModeUnsupported => {
+            RpcError::new(501, "The Wi-Fi adapter does not support AP mode")
+        }
+        HotspotError::CommandExecutionFailure => RpcError::new(500, "Error running nmcli"),
+    }
+}
+
+fn wifi_hotspot_start(config: HotspotConfig, events: &EventBus) -> Result<Reply, RpcError> {
+    let status = start_hotspot(&config).map_err(hotspot_error)?;
+    events.emit("hotspot_started", &status);
+
+    Reply::data(&status)
+}
+
+fn wifi_hotspot_stop(events: &EventBus) -> Result<Reply, RpcError> {
+    stop_hotspot().map_err(hotspot_error)?;
+    events.emit("hotspot_stopped", &json!({}));
+
+    Ok(Reply::message("Hotspot stopped"))
+}
+
+fn wifi_hotspot_status() -> Result<Reply, RpcError> {
+    match get_hotspot_status().map_err(hotspot_error)? {
+        Some(status) => Reply::data(&status),
+        None => Err(RpcError::new(404, "No hotspot was set up")),
+    }
+}
+
+fn wifi_ap_support(interface: Option<String>) -> Result<Reply, RpcError> {
+    let support = get_ap_support(interface.as_deref()).map_err(|e| match e {
+        HotspotError::CommandExecutionFailure => RpcError::new(500, "Error running iw"),
+        e => hotspot_error(e),
+    })?;
+
+    Reply::data(&support)
+}
+
+fn net_interfaces() -> Result<Reply, RpcError> {
+    let interfaces = <WifiNetwork as WifiManager>::scan_interfaces()
+        .map_err(|_| RpcError::new(500, "Error getting interfaces"))?;
+
+    Reply::data(&interfaces)
+}
+
+async fn net_interface_details() -> Result<Reply, RpcError> {
+    let mut interfaces =
+        get_interface_details().map_err(|_| RpcError::new(500, "Error getting interfaces"))?;
+
+    // Name the device behind Bluetooth tethering links
+    if interfaces
+        .iter()
+        .any(|i| i.kind == InterfaceKind::Bluetooth)
+    {
+        let connections = get_pan_connections().await.unwrap_or_default();
+        for interface in &mut interfaces {
+            interface.bluetooth_device = connections
+                .iter()
+                .find(|c| c.interface == interface.name)
+                .map(|c| c.alias.clone());
+        }
+    }
+
+    Reply::data(&interfaces)
+}
+
+fn stats_error(error: StatsError) -> RpcError {
+    match error {
+        StatsError::InvalidInterfaceName => RpcError::new(404, "No such interface"),
+        _ => RpcError::new(500, "Failed to start monitoring"),
+    }
+}
+
+/// Traffic of an interface, speeds are averaged since the previous call
+async fn net_stats(interface: String, client: ClientId) -> Result<Reply, RpcError> {
+    let key = (client, interface);
+    let mut monitors = STATS_MONITORS.lock().await;
+    if !monitors.contains_key(&key) {
+        let monitor = NetworkMonitor::new(&key.1).map_err(stats_error)?;
+        monitors.insert(key.clone(), monitor);
+    }
+
+    let Some(monitor) = monitors.get_mut(&key) else {
+        return Err(RpcError::new(500, "Error getting network stats"));
+    };
+    match monitor.get_stats().await {
+        Some(stats) => Reply::data(&stats),
+        None => {
+            if !monitor.interface_exists() {
+                monitors.remove(&key);
+                return Err(RpcError::new(404, "No such interface"));
+            }
+            Err(RpcError::new(500, "Error getting network stats"))
+        }
+    }
+}
+
+/// Forgets the state a client that disconnected kept in the daemon
+///
+/// Stops the monitors nobody else is subscribed to and drops the baselines
+/// of its `net.stats` calls.
+pub(super) async fn client_disconnected(client: ClientId) {
+    monitors::disconnect(client);
+    STATS_MONITORS
+        .lock()
+        .await
+        .retain(|(owner, _), _| *owner != client);
+}
+
+fn monitor_reply(started: bool) -> Result<Reply, RpcError> {
+    Ok(Reply::message(match started {
+        true => "Started monitoring",
+        false => "Already monitoring",
+    }))
+}
+
+/// Unsubscribes a client from a monitor started with one of the `monitor_*` methods
+fn stop_monitor(key: monitors::MonitorKey, client: ClientId) -> Result<Reply, RpcError> {
+    match monitors::leave(&key, client) {
+        true => Ok(Reply::message("Stopped monitoring")),
+        false => Err(RpcError::new(404, "Not monitoring")),
+    }
+}
+
+fn net_monitor_stats(
+    interface: String,
+    events: EventBus,
+    client: ClientId,
+) -> Result<Reply, RpcError> {
+    let key = ("net.monitor_stats", interface.clone());
+    let started = monitors::start(key, client, move || {
+        let mut monitor = NetworkMonitor::new(&interface).map_err(|e| {
+            eprintln!("Error monitoring network stats: {:?}", e);
+            stats_error(e)
+        })?;
+
+        let store = HistoryStore::open_default().ok();
+        let task = tokio::spawn(async move {
+            monitor
+                .monitor(10, move |stats| {
+                    events.emit("network_stats", &stats);
+                    if let Some(store) = &store {
+                        let entry = HistoryEntry::NetworkStats {
+                            interface: interface.clone(),
+                            stats,
+                        };
+                        if let Err(e) = store.append(entry) {
+                            eprintln!("Error saving network stats: {:?}", e);
+                        }
+                    }
+                })
+                .await;
+        });
+        Ok((task, None))
+    })?;
+
+    monitor_reply(started)
+}
+
+/// Starts the connection quality monitor, the first client's config is used
+async fn net_monitor_quality(
+    config: Option<PingMonitorConfig>,
+    events: EventBus,
+    client: ClientId,
+) -> Result<Reply, RpcError> {
+    let key = ("net.monitor_quality", String::new());
+    if monitors::join(&key, client) {
+        return monitor_reply(false);
+    }
+
+    let mut monitor = PingMonitor::new(config.unwrap_or_default())
+        .await
+        .map_err(|e| {
+            eprintln!("Error starting connection quality monitor: {:?}", e);
+            match e {
+                PingError::NoTargets => RpcError::new(404, "No ping targets"),
+                PingError::TargetResolutionFailure => {
+                    RpcError::new(400, "Could not resolve ping target")
+                }
+                PingError::SocketCreationFailure => {
+                    RpcError::new(500, "Could not open ICMP socket")
+                }
+            }
+        })?;
+
+    let task = tokio::spawn(async move {
+        monitor
+            .monitor(move |stats| events.emit("connection_quality", &stats))
+            .await;
+    });
+    monitors::insert(key, client, task, None);
+
+    monitor_reply(true)
+}
+
+async fn net_dns_diagnostics(config: Option<DnsDiagnosticsConfig>) -> Result<Reply, RpcError> {
+    match run_diagnostics(&config.unwrap_or_default()).await {
+        Ok(report) => Reply::data(&report),
+        Err(DnsError::NoResolvers) => Err(RpcError::new(404, "No DNS resolvers found")),
+        Err(DnsError::CommandExecutionFailure) => {
+            Err(RpcError::new(500, "Error reading DNS resolvers"))
+        }
+    }
+}
+
+async fn net_connectivity(config: Option<ConnectivityConfig>) -> Result<Reply, RpcError> {
+    let report = check_connectivity(&config.unwrap_or_default())
+        .await
+        .map_err(|e| {
+            eprintln!("Error checking connectivity: {:?}", e);
+            RpcError::new(500, "Failed to check connectivity")
+        })?;
+
+    Reply::data(&report)
+}
+
+/// Starts the connectivity monitor, the first client's config is used
+pub(super) fn net_monitor_connectivity(
+    config: Option<ConnectivityConfig>,
+    events: EventBus,
+    client: ClientId,
+) -> Result<Reply, RpcError> {
+    let key = ("net.monitor_connectivity", String::new());
+    let started = monitors::start(key, client, move || {
+        let task = tokio::spawn(async move {
+            let result = monitor_connectivity(config.unwrap_or_default(), move |report| {
+                events.emit("connectivity_changed", &report);
+            })
+            .await;
+            if let Err(e) = result {
+                eprintln!("Connectivity monitoring stopped: {:?}", e);
+            }
+        });
+        Ok::<_, RpcError>((task, None))
+    })?;
+
+    monitor_reply(started)
+}
+
+async fn speed_test_run(config: SpeedTestConfig, events: EventBus) -> Result<Reply, RpcError> {
+    let result = run_speed_test(&config, move |progress| {
+        events.emit("speed_test_progress", &progress);
+    })
+    .await;
+
+    match result {
+        Ok(result) => {
+            if let Err(e) = HistoryStore::open_default()
+                .and_then(|store| store.append(HistoryEntry::SpeedTest(result.clone())))
+            {
+                eprintln!("Error saving speed test result: {:?}", e);
+            }
+            Reply::data(&result)
+        }
+        Err(SpeedTestError::InvalidEndpoint) => {
+            Err(RpcError::new(400, "Invalid speed test endpoint"))
+        }
+        Err(SpeedTestError::ConnectionFailure) => {
+            Err(RpcError::new(503, "Speed test endpoint unreachable"))
+        }
+        Err(e) => {
+            eprintln!("Speed test failed: {:?}", e);
+            Err(RpcError::new(500, "Speed test failed"))
+        }
+    }
+}
+
+fn speed_test_history(bssid: Option<String>, limit: Option<usize>) -> Result<Reply, RpcError> {
+    let store =
+        HistoryStore::open_default().map_err(|_| RpcError::new(500, "Error opening history"))?;
+    let records = store
+        .read(
+            limit.unwrap_or(DEFAULT_HISTORY_LIMIT),
+            |record| match &record.entry {
+                HistoryEntry::SpeedTest(result) => {
+                    bssid.is_none() || result.bssid.as_deref() == bssid.as_deref()
+                }
+                _ => false,
+            },
+        )
+        .map_err(|_| RpcError::new(500, "Error reading history"))?;
+
+    Reply::data(&records)
+}
+
+/// Starts the speed test server on localhost, or on every interface with
+/// `lan`; the first client's choice is used
+fn speed_test_start_server(lan: Option<bool>, client: ClientId) -> Result<Reply, RpcError> {
+    let address = default_server_address(lan.unwrap_or(false));
+    let started = monitors::start(("speed_test.server", String::new()), client, || {
+        let listener = bind_speed_test_server(&address)
+            .map_err(|_| RpcError::new(409, "Could not listen on the speed test port"))?;
+        Ok((tokio::spawn(serve_speed_tests(listener)), None))
+    })?;
+
+    Ok(Reply::message(&match started {
+        true => format!("Started speed test server on {}", address),
+        false => "Speed test server already running".to_string(),
+    }))
+}
+
+/// Stops the speed test server once no other client uses it
+fn speed_test_stop_server(client: ClientId) -> Result<Reply, RpcError> {
+    match monitors::leave(&("speed_test.server", String::new()), client) {
+        true => Ok(Reply::message("Stopped speed test server")),
+        false => Err(RpcError::new(404, "Speed test server not running")),
+    }
+}
+
+fn bluetooth_error(error: BluetoothError) -> RpcError {
+    match error {
+        BluetoothError::ConnectionFailure => RpcError::new(503, "Bluetooth service unavailable"),
+        BluetoothError::NoSuchAdapter => RpcError::new(404, "No such adapter"),
+        BluetoothError::NoSuchDevice => RpcError::new(404, "No such device"),
+        BluetoothError::DoesNotExist => RpcError::new(404, "Not found"),
+        BluetoothError::NotReady => RpcError::new(409, "Adapter not ready"),
+        BluetoothError::InvalidArguments => RpcError::new(400, "Invalid arguments"),
+        BluetoothError::NotSupported => RpcError::new(501, "Not supported"),
+        BluetoothError::NotAvailable => RpcError::new(404, "Profile not available"),
+        BluetoothError::AlreadyConnected => RpcError::new(409, "Already connected"),
+        BluetoothError::NotConnected => RpcError::new(409, "Not connected"),
+        BluetoothError::InProgress => RpcError::new(429, "Operation in progress"),
+        BluetoothError::NotPermitted => RpcError::new(403, "Not permitted"),
+        BluetoothError::NotAuthorized => RpcError::new(401, "Not authorized"),
+        BluetoothError::Failed | BluetoothError::DBusError => RpcError::new(500, "Unknown error"),
+    }
+}
+
+async fn bt_adapters() -> Result<Reply, RpcError> {
+    let adapters = <BluetoothDevice as BluetoothManager>::adapters()
+        .await
+        .map_err(bluetooth_error)?;
+
+    Reply::data(&adapters)
+}
+
+async fn bt_set_adapter(adapter: String, setting: AdapterSetting) -> Result<Reply, RpcError> {
+    <BluetoothDevice as BluetoothManager>::set_adapter(&adapter, setting)
+        .await
+        .map_err(bluetooth_error)?;
+
+    Ok(Reply::message("Adapter updated"))
+}
+
+/// Publishes the BlueZ device events, plus `bt_device_connected` and
+/// `bt_device_disconnected` when a known device changes its connection
+pub(super) fn start_device_watcher(events: EventBus) {
+    if DEVICE_WATCHER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let connected: std::sync::Mutex<HashMap<String, bool>> = Default::default();
+        let result = watch_devices(|event| {
+            let mut connected = connected.lock().unwrap();
+            match &event {
+                DeviceEvent::Found(device) => {
+                    connected.insert(device.path.clone(), device.connected);
+                    events.emit("bt_device_found", device)
+                }
+                DeviceEvent::Updated(device) => {
+                    let was = connected.insert(device.path.clone(), device.connected);
+                    events.emit("bt_device_updated", device);
+                    match (was, device.connected) {
+                        (Some(false), true) => events.emit("bt_device_connected", device),
+                        (Some(true), false) => events.emit("bt_device_disconnected", device),
+                        _ => {}
+                    }
+                }
+                DeviceEvent::Lost { path, .. } => {
+                    if connected.remove(path) == Some(true) {
+                        events.emit("bt_device_disconnected", &event);
+                    }
+                    events.emit("bt_device_lost", &event)
+                }
+            }
+        })
+        .await;
+        eprintln!("Bluetooth device watcher stopped: {:?}", result);
+        DEVICE_WATCHER_STARTED.store(false, Ordering::SeqCst);
+    });
+}
+
+async fn bt_devices(adapter: Option<String>) -> Result<Reply, RpcError> {
+    let devices = <BluetoothDevice as BluetoothManager>::scan(adapter.as_deref())
+        .await
+        .map_err(bluetooth_error)?;
+
+    Reply::data(&devices)
+}
+
+async fn bt_start_discovery(
+    adapter: String,
+    filter: Option<DiscoveryFilter>,
+    events: EventBus,
+) -> Result<Reply, RpcError> {
+    start_device_watcher(events);
+    start_discovery(&adapter, &filter.unwrap_or_default())
+        .await
+        .map_err(bluetooth_error)?;
+
+    Ok(Reply::message("Started discovery"))
+}
+
+async fn bt_stop_discovery(adapter: String) -> Result<Reply, RpcError> {
+    stop_discovery(&adapter).await.map_err(bluetooth_error)?;
+
+    Ok(Reply::message("Stopped discovery"))
+}
+
+fn bt_beacon_scan(
+    adapter: String,
+    config: Option<BeaconScanConfig>,
+    events: EventBus,
+) -> Result<Reply, RpcError> {
+    tokio::spawn(async move {
+        let result = scan_beacons(&adapter, config.unwrap_or_default(), |event| match &event {
+            BeaconEvent::Seen(advertiser) => events.emit("bt_beacon", advertiser),
+            BeaconEvent::Lost { .. } => events.emit("bt_beacon_lost", &event),
+        })
+        .await;
+        if let Err(e) = result {
+            eprintln!("Beacon scan on {} failed: {:?}", adapter, e);
+        }
+    });
+
+    Ok(Reply::message("Started beacon scan"))
+}
+
+fn bt_beacon_stop(adapter: String) -> Result<Reply, RpcError> {
+    stop_beacon_scan(&adapter).map_err(bluetooth_error)?;
+
+    Ok(Reply::message("Stopped beacon scan"))
+}
+
+async fn bt_pair(device: String) -> Result<Reply, RpcError> {
+    <BluetoothDevice as BluetoothManager>::pair(&device)
+        .await
+        .map_err(|e| match e {
+            PairingError::AuthenticationFailed => RpcError::new(401, "Authentication failed"),
+            PairingError::AuthenticationRejected => RpcError::new(403, "Pairing rejected"),
+            PairingError::AuthenticationTimeout => RpcError::new(408, "Pairing timed out"),
+            PairingError::AuthenticationCanceled => RpcError::new(499, "Pairing cancelled"),
+            PairingError::AlreadyExists => RpcError::new(409, "Already paired"),
+            PairingError::InProgress => RpcError::new(429, "Pairing in progress"),
+            PairingError::ConnectionAttemptFailed => RpcError::new(504, "Could not reach device"),
+            PairingError::Bluetooth(e) => bluetooth_error(e),
+        })?;
+
+    Ok(Reply::message("Paired Successfully"))
+}
+
+async fn bt_cancel_pair(device: String) -> Result<Reply, RpcError> {
+    cancel_pairing(&device).await.map_err(bluetooth_error)?;
+
+    Ok(Reply::message("Pairing cancelled"))
+}
+
+fn bt_agent_respond(id: u64, response: AgentResponse) -> Result<Reply, RpcError> {
+    respond(id, response).map_err(|_| RpcError::new(404, "No such request"))?;
+
+    Ok(Reply::message("Response delivered"))
+}
+
+async fn bt_paired_devices(adapter: Option<String>) -> Result<Reply, RpcError> {
+    let devices = <BluetoothDevice as BluetoothManager>::paired(adapter.as_deref())
+        .await
+        .map_err(bluetooth_error)?;
+
+    Reply::data(&devices)
+}
+
+async fn bt_connect(device: String, profile: Option<String>) -> Result<Reply, RpcError> {
+    <BluetoothDevice as BluetoothManager>::connect(&device, profile.as_deref())
+        .await
+        .map_err(bluetooth_error)?;
+
+    Ok(Reply::message("Connected Successfully"))
+}
+
+async fn bt_disconnect(device: String, profile: Option<String>) -> Result<Reply, RpcError> {
+    <BluetoothDevice as BluetoothManager>::disconnect(&device, profile.as_deref())
+        .await
+        .map_err(bluetooth_error)?;
+
+    Ok(Reply::message("Disconnected"))
+}
+
+async fn bt_set_device(device: String, setting: DeviceSetting) -> Result<Reply, RpcError> {
+    <BluetoothDevice as BluetoothManager>::set_device(&device, setting)
+        .await
+        .map_err(bluetooth_error)?;
+
+    Ok(Reply::message("Device updated"))
+}
+
+async fn bt_remove_device(device: String) -> Result<Reply, RpcError> {
+    <BluetoothDevice as BluetoothManager>::remove(&device)
+        .await
+        .map_err(bluetooth_error)?;
+
+    Ok(Reply::message("Device removed"))
+}
+
+async fn bt_stats() -> Result<Reply, RpcError> {
+    let stats = BluetoothMonitor::new(BluetoothMonitorConfig::default())
+        .get_stats()
+        .await
+        .map_err(bluetooth_error)?;
+
+    Reply::data(&stats)
+}
+
+/// Starts the Bluetooth battery and signal monitor
+///
+/// A config replaces the running monitor, so one started by the daemon with
+/// the defaults (for sinks or rules) picks up the settings of a client.
+pub(super) fn bt_monitor_stats(
+    config: Option<BluetoothMonitorConfig>,
+    events: EventBus,
+    client: ClientId,
+) -> Result<Reply, RpcError> {
+    let key = ("bt.monitor_stats", String::new());
+    let replace = config.is_some();
+    let start = move || {
+        let mut monitor = BluetoothMonitor::new(config.unwrap_or_default());
+        let store = HistoryStore::open_default().ok();
+        let alert_events = events.clone();
+        let task = tokio::spawn(async move {
+            monitor
+                .monitor(
+                    move |stats| {
+                        events.emit("bluetooth_stats", &stats);
+                        if let Some(store) = &store {
+                            if let Err(e) = store.append(HistoryEntry::BluetoothStats(stats)) {
+                                eprintln!("Error saving Bluetooth stats: {:?}", e);
+                            }
+                        }
+                    },
+                    move |alert| alert_events.emit("bluetooth_low_battery", &alert),
+                )
+                .await;
+        });
+        Ok::<_, RpcError>((task, None))
+    };
+
+    let started = match replace {
+        true => monitors::restart(key, client, start).map(|_| true)?,
+        false => monitors::start(key, client, start)?,
+    };
+    monitor_reply(started)
+}
+
+async fn bt_gatt_services(device: String) -> Result<Reply, RpcError> {
+    let services = get_gatt_services(&device).await.map_err(bluetooth_error)?;
+
+    Reply::data(&services)
+}
+
+async fn bt_gatt_read(path: String) -> Result<Reply, RpcError> {
+    let value = read_value(&path).await.map_err(bluetooth_error)?;
+
+    Reply::data(&value)
+}
+
+async fn bt_gatt_write(
+    path: String,
+    value: GattValueInput,
+    write_type: Option<GattWriteType>,
+) -> Result<Reply, RpcError> {
+    let bytes = value.encode().map_err(bluetooth_error)?;
+    write_value(&path, &bytes, write_type.unwrap_or_default())
+        .await
+        .map_err(bluetooth_error)?;
+
+    Ok(Reply::message("Value written"))
+}
+
+fn bt_gatt_subscribe(path: String, events: EventBus, client: ClientId) -> Result<Reply, RpcError> {
+    let key = ("bt.gatt_subscribe", path.clone());
+    let started = monitors::start(key, client, move || {
+        // Unsubscribing lets the subscription disable notifications on the device
+        let stop_path = path.clone();
+        let stop: StopFn = Box::new(move || {
+            let _ = unsubscribe(&stop_path);
+        });
+        let task = tokio::spawn(async move {
+            let result = subscribe(&path, |notification| {
+                events.emit("bt_gatt_notification", &notification);
+            })
+            .await;
+            if let Err(e) = result {
+                eprintln!("Error subscribing to {}: {:?}", path, e);
+            }
+            events.emit("bt_gatt_unsubscribed", &path);
+        });
+        Ok::<_, RpcError>((task, Some(stop)))
+    })?;
+
+    Ok(Reply::message(match started {
+        true => "Subscribed",
+        false => "Already subscribed",
+    }))
+}
+
+fn bt_gatt_unsubscribe(path: String, client: ClientId) -> Result<Reply, RpcError> {
+    match monitors::leave(&("bt.gatt_subscribe", path), client) {
+        true => Ok(Reply::message("Unsubscribed")),
+        false => Err(RpcError::new(404, "Not subscribed")),
+    }
+}
+
+fn audio_error(error: AudioError) -> RpcError {
+    match error {
+        AudioError::CommandExecutionFailure => RpcError::new(503, "Audio server unavailable"),
+        AudioError::NoAudioCard => RpcError::new(404, "Device has no audio card"),
+        AudioError::NoSuchProfile => RpcError::new(404, "No such profile"),
+        AudioError::ProfileUnavailable => RpcError::new(409, "Profile not available"),
+        AudioError::Bluetooth(e) => bluetooth_error(e),
+    }
+}
+
+async fn bt_audio_profiles(device: String) -> Result<Reply, RpcError> {
+    let audio = get_audio_device(&device).await.map_err(audio_error)?;
+
+    Reply::data(&audio)
+}
+
+async fn bt_set_audio_profile(device: String, profile: String) -> Result<Reply, RpcError> {
+    let audio = set_audio_profile(&device, &profile)
+        .await
+        .map_err(audio_error)?;
+
+    Reply::data(&audio)
+}
+
+async fn bt_pan_connect(device: String, role: Option<PanRole>) -> Result<Reply, RpcError> {
+    let connection = connect_pan(&device, role.unwrap_or(PanRole::Nap))
+        .await
+        .map_err(bluetooth_error)?;
+
+    Reply::data(&connection)
+}
+
+async fn bt_pan_disconnect(device: String) -> Result<Reply, RpcError> {
+    disconnect_pan(&device).await.map_err(bluetooth_error)?;
+
+    Ok(Reply::message("Disconnected"))
+}
+
+async fn bt_pan_connections() -> Result<Reply, RpcError> {
+    let connections = get_pan_connections().await.map_err(bluetooth_error)?;
+
+    Reply::data(&connections)
+}
+
+fn obex_error(error: ObexError) -> RpcError {
+    match error {
+        ObexError::ConnectionFailure => RpcError::new(503, "OBEX service unavailable"),
+        ObexError::InvalidArguments => RpcError::new(400, "Invalid arguments"),
+        ObexError::NotFound => RpcError::new(404, "Not found"),
+        ObexError::Forbidden => RpcError::new(403, "Forbidden"),
+        ObexError::TransferFailed => RpcError::new(502, "Transfer failed"),
+        ObexError::IoError => RpcError::new(500, "Error accessing file"),
+        ObexError::Failed => RpcError::new(500, "Unknown error"),
+        ObexError::Bluetooth(e) => bluetooth_error(e),
+    }
+}
+
+async fn bt_obex_send(device: String, file: String, events: &EventBus) -> Result<Reply, RpcError> {
+    send_file(&device, Path::new(&file), |progress| {
+        events.emit("bt_obex_progress", &progress);
+    })
+    .await
+    .map_err(obex_error)?;
+
+    Ok(Reply::message("File sent"))
+}
+
+async fn bt_obex_list(device: String, folder: Option<String>) -> Result<Reply, RpcError> {
+    let entries = list_folder(&device, folder.as_deref().unwrap_or_default())
+        .await
+        .map_err(obex_error)?;
+
+    Reply::data(&entries)
+}
+
+async fn bt_obex_download(
+    device: String,
+    path: String,
+    events: &EventBus,
+) -> Result<Reply, RpcError> {
+    let file = download_file(&device, &path, |progress| {
+        events.emit("bt_obex_progress", &progress);
+    })
+    .await
+    .map_err(obex_error)?;
+
+    Ok(Reply::message(&file.to_string_lossy()))
+}
+
+async fn bt_obex_cancel(transfer: String) -> Result<Reply, RpcError> {
+    cancel_transfer(&transfer).await.map_err(obex_error)?;
+
+    Ok(Reply::message("Transfer cancelled"))
+}
+
+fn bt_obex_respond(id: u64, accept: bool) -> Result<Reply, RpcError> {
+    respond_push(id, accept).map_err(|_| RpcError::new(404, "No such request"))?;
+
+    Ok(Reply::message("Response delivered"))
+}
+
+fn bt_obex_set_receive_dir(dir: String) -> Result<Reply, RpcError> {
+    let dir = PathBuf::from(dir);
+    if !dir.is_absolute() {
+        return Err(RpcError::new(400, "Directory must be an absolute path"));
+    }
+    std::fs::create_dir_all(&dir).map_err(|_| RpcError::new(500, "Error creating directory"))?;
+
+    let mut settings = ObexSettings::load();
+    settings.receive_dir = dir;
+    settings
+        .save()
+        .map_err(|_| RpcError::new(500, "Error saving settings"))?;
+
+    Ok(Reply::message("Receive directory updated"))
+}
+
+/// Configured sinks without their credentials and headers
+fn sinks_list() -> Result<Reply, RpcError> {
+    let sinks: Vec<Value> = SinkSettings::load()
+        .sinks
+        .iter()
+        .map(|sink| {
+            let (kind, target) = match &sink.target {
+                SinkTarget::Webhook(webhook) => ("webhook", webhook.url.clone()),
+                SinkTarget::Mqtt(mqtt) => (
+                    "mqtt",
+                    format!("{}:{}/{}", mqtt.host, mqtt.port, mqtt.topic),
+                ),
+            };
+            let events = if sink.events.is_empty() {
+                DEFAULT_EVENTS.iter().map(|e| e.to_string()).collect()
+            } else {
+                sink.events.clone()
+            };
+            json!({ "name": sink.name, "kind": kind, "target": target, "events": events })
+        })
+        .collect();
+
+    Reply::data(&sinks)
+}
+
+async fn sinks_test(name: String) -> Result<Reply, RpcError> {
+    match test_sink(&name).await {
+        Ok(body) => Ok(Reply::message(&body)),
+        Err(SinkError::NoSuchSink) => Err(RpcError::new(404, "No such sink")),
+        Err(SinkError::InvalidConfig(e)) => Err(RpcError::new(
+            400,
+            &format!("Invalid sink configuration: {}", e),
+        )),
+        Err(SinkError::ConnectionFailure(e)) => {
+            Err(RpcError::new(502, &format!("Sink unreachable: {}", e)))
+        }
+        Err(SinkError::Rejected(status)) => Err(RpcError::new(
+            502,
+            &format!("Sink answered with status {}", status),
+        )),
+    }
+}
+
+fn rule_error(e: RuleError) -> RpcError {
+    match e {
+        RuleError::IoError => RpcError::new(500, "Error reading the rules file"),
+        RuleError::InvalidRules(e) => RpcError::new(400, &format!("Invalid rules: {}", e)),
+    }
+}
+
+async fn automation_rules() -> Result<Reply, RpcError> {
+    let rules = current_rules().await;
+
+    Reply::data(&json!({
+        "path": RuleSet::path(),
+        "rules": rules.rules,
+        "warnings": rules.warnings(),
+    }))
+}
+
+async fn automation_reload(events: &EventBus) -> Result<Reply, RpcError> {
+    let rules = reload_rules(events).await.map_err(rule_error)?;
+
+    Ok(Reply::message(&format!(
+        "Loaded {} rules",
+        rules.rules.len()
+    )))
+}
+
+/// Evaluates the rules against an event without running any action
+///
+/// Conditions not given in `context` are read from the system. Checks
+/// `rules` instead of the running rules if given.
+async fn automation_dry_run(
+    event: String,
+    payload: Option<Value>,
+    context: Option<Context>,
+    rules: Option<String>,
+    format: Option<RuleFormat>,
+) -> Result<Reply, RpcError> {
+    let rules = match rules {
+        Some(content) => {
+            RuleSet::parse(&content, format.unwrap_or(RuleFormat::Toml)).map_err(rule_error)?
+        }
+        None => current_rules().await,
+    };
+    let payload = payload.unwrap_or(Value::Null);
+
+    let live = current_context().await;
+    let mut context = context.unwrap_or_default();
+    context.ssid = context.ssid.or(live.ssid);
+    context.bssid = context.bssid.or(live.bssid);
+    context.interface = context.interface.or(live.interface);
+    context.on_battery = context.on_battery.or(live.on_battery);
+
+    Reply::data(&json!({
+        "evaluations": evaluate(&rules, &event, &payload, &context),
+        "warnings": rules.warnings(),
+    }))
+}
@@ -0,0 +1,13 @@
+pub mod automation;
+pub mod client;
+pub mod daemon_error;
+pub mod dbus_service;
+pub mod events;
+pub mod exporter;
+pub mod methods;
+pub mod monitors;
+pub mod protocol;
+pub mod server;
+pub mod sinks;
+pub mod template;
+pub mod watcher;
@@ -0,0 +1,343 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock, Mutex,
+    },
+};
+
+use tokio::task::JoinHandle;
+
+/// A monitor started through the API, e.g. `("net.monitor_stats", "wlan0")`
+///
+/// The second part is the interface or object path the monitor watches,
+/// empty for monitors that exist only once.
+pub type MonitorKey = (&'static str, String);
+
+/// Ends a monitor gracefully instead of aborting its task
+pub type StopFn = Box<dyn FnOnce() + Send>;
+
+/// Identifies the connection a monitor was started for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ClientId(u64);
+
+impl ClientId {
+    /// The daemon itself, e.g. automation rules and sinks; never disconnects
+    pub const DAEMON: ClientId = ClientId(0);
+
+    /// A new id for a client that just connected
+    pub fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        ClientId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+struct Monitor {
+    task: JoinHandle<()>,
+    stop: Option<StopFn>,
+    subscribers: HashSet<ClientId>,
+}
+
+impl Monitor {
+    fn end(self) {
+        match self.stop {
+            Some(stop) => stop(),
+            None => self.task.abort(),
+        }
+    }
+}
+
+/// Running monitors and the clients subscribed to them
+static MONITORS: LazyLock<Mutex<HashMap<MonitorKey, Monitor>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn monitors() -> std::sync::MutexGuard<'static, HashMap<MonitorKey, Monitor>> {
+    MONITORS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Subscribes `client` to a running monitor
+///
+/// # Returns
+/// `false` if the monitor does not run (any more), it has to be started then
+pub fn join(key: &MonitorKey, client: ClientId) -> bool {
+    let mut monitors = monitors();
+    match monitors.get_mut(key) {
+        Some(monitor) if !monitor.task.is_finished() => {
+            monitor.subscribers.insert(client);
+            true
+        }
+        Some(_) => {
+            monitors.remove(key);
+            false
+        }
+        None => false,
+    }
+}
+
+/// Subscribes `client` to a monitor, starting it unless it already runs
+///
+/// The registry stays locked while `start` runs, so a monitor is never
+/// started twice.
+///
+/// # Returns
+/// - `Ok(true)` if the monitor was started
+/// - `Ok(false)` if `client` joined the running monitor
+/// - `Err(E)` if `start` failed
+pub fn start<E>(
+    key: MonitorKey,
+    client: ClientId,
+    start: impl FnOnce() -> Result<(JoinHandle<()>, Option<StopFn>), E>,
+) -> Result<bool, E> {
+    let mut monitors = monitors();
+    if let Some(monitor) = monitors.get_mut(&key) {
+        if !monitor.task.is_finished() {
+            monitor.subscribers.insert(client);
+            return Ok(false);
+        }
+    }
+
+    let (task, stop) = start()?;
+    let monitor = Monitor {
+        task,
+        stop,
+        subscribers: HashSet::from([client]),
+    };
+    monitors.insert(key, monitor);
+    Ok(true)
+}
+
+/// Replaces a monitor with a newly started one, e.g. to apply a new config
+///
+/// Clients subscribed to the replaced monitor stay subscribed. If `start`
+/// fails the running monitor is kept.
+///
+/// # Returns
+/// - `Ok(true)` if a running monitor was replaced
+/// - `Ok(false)` if none ran and the monitor was started
+/// - `Err(E)` if `start` failed
+pub fn restart<E>(
+    key: MonitorKey,
+    client: ClientId,
+    start: impl FnOnce() -> Result<(JoinHandle<()>, Option<StopFn>), E>,
+) -> Result<bool, E> {
+    let mut monitors = monitors();
+    let (task, stop) = start()?;
+    let mut monitor = Monitor {
+        task,
+        stop,
+        subscribers: HashSet::from([client]),
+    };
+
+    let replaced = monitors
+        .remove(&key)
+        .filter(|running| !running.task.is_finished());
+    if let Some(running) = &replaced {
+        monitor.subscribers.extend(&running.subscribers);
+    }
+    monitors.insert(key, monitor);
+    drop(monitors);
+
+    Ok(match replaced {
+        Some(running) => {
+            running.end();
+            true
+        }
+        None => false,
+    })
+}
+
+/// Registers a monitor that was just started, subscribing `client` to it
+///
+/// For monitors that are set up asynchronously, [`join`] them first.
+///
+/// If another client started the same monitor in the meantime, `client`
+/// joins that one and the new monitor is ended right away.
+///
+/// # Arguments
+/// * `task` - the task running the monitor
+/// * `stop` - ends the monitor gracefully, `None` aborts the task
+pub fn insert(key: MonitorKey, client: ClientId, task: JoinHandle<()>, stop: Option<StopFn>) {
+    let monitor = Monitor {
+        task,
+        stop,
+        subscribers: HashSet::from([client]),
+    };
+
+    let mut monitors = monitors();
+    match monitors.get_mut(&key) {
+        Some(running) if !running.task.is_finished() => {
+            running.subscribers.insert(client);
+            drop(monitors);
+            monitor.end();
+        }
+        _ => {
+            monitors.insert(key, monitor);
+        }
+    }
+}
+
+/// Unsubscribes `client`, ending the monitor once nobody is subscribed
+///
+/// # Returns
+/// `false` if the client was not subscribed to a running monitor
+pub fn leave(key: &MonitorKey, client: ClientId) -> bool {
+    let mut monitors = monitors();
+    let Some(monitor) = monitors.get_mut(key) else {
+        return false;
+    };
+    let subscribed = monitor.subscribers.remove(&client) && !monitor.task.is_finished();
+
+    if monitor.subscribers.is_empty() || monitor.task.is_finished() {
+        if let Some(monitor) = monitors.remove(key) {
+            drop(monitors);
+            monitor.end();
+        }
+    }
+    subscribed
+}
+
+/// Unsubscribes a client that disconnected from every monitor
+pub fn disconnect(client: ClientId) {
+    let mut monitors = monitors();
+    let unused: Vec<MonitorKey> = monitors
+        .iter_mut()
+        .filter_map(|(key, monitor)| {
+            monitor.subscribers.remove(&client);
+            monitor.subscribers.is_empty().then(|| key.clone())
+        })
+        .collect();
+    let ended: Vec<Monitor> = unused
+        .iter()
+        .filter_map(|key| monitors.remove(key))
+        .collect();
+    drop(monitors);
+
+    for monitor in ended {
+        monitor.end();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn key(name: &str) -> MonitorKey {
+        ("test.monitor", name.to_string())
+    }
+
+    fn endless() -> JoinHandle<()> {
+        tokio::spawn(async { std::future::pending::<()>().await })
+    }
+
+    #[tokio::test]
+    async fn ends_monitor_with_its_last_subscriber() {
+        let (first, second) = (ClientId::next(), ClientId::next());
+        let key = key("last");
+
+        assert!(!join(&key, first));
+        let task = endless();
+        let handle = task.abort_handle();
+        insert(key.clone(), first, task, None);
+        assert!(join(&key, second));
+
+        assert!(leave(&key, first));
+        assert!(!leave(&key, first));
+        tokio::task::yield_now().await;
+        assert!(!handle.is_finished());
+
+        disconnect(second);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(handle.is_finished());
+        assert!(!join(&key, first));
+    }
+
+    #[tokio::test]
+    async fn starts_a_monitor_once() {
+        let (first, second) = (ClientId::next(), ClientId::next());
+        let (key, failing) = (key("once"), key("failing"));
+
+        let started = start(key.clone(), first, || Ok::<_, ()>((endless(), None)));
+        assert_eq!(started, Ok(true));
+        let joined = start(key.clone(), second, || -> Result<_, ()> {
+            panic!("the running monitor must be joined")
+        });
+        assert_eq!(joined, Ok(false));
+        assert_eq!(start(failing, first, || Err(())), Err(()));
+
+        disconnect(first);
+        assert!(leave(&key, second));
+    }
+
+    #[tokio::test]
+    async fn restarts_a_monitor_for_its_subscribers() {
+        let (first, second) = (ClientId::next(), ClientId::next());
+        let key = key("restart");
+
+        assert_eq!(
+            restart(key.clone(), first, || Ok::<_, ()>((endless(), None))),
+            Ok(false)
+        );
+        let old = monitors().get(&key).unwrap().task.abort_handle();
+        assert_eq!(restart(key.clone(), second, || Err(())), Err(()));
+        assert!(!old.is_finished());
+
+        let task = endless();
+        let new = task.abort_handle();
+        assert_eq!(
+            restart(key.clone(), second, || Ok::<_, ()>((task, None))),
+            Ok(true)
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(old.is_finished());
+        assert!(!new.is_finished());
+
+        // Both clients are subscribed to the new monitor
+        assert!(leave(&key, first));
+        assert!(leave(&key, second));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(new.is_finished());
+    }
+
+    #[tokio::test]
+    async fn keeps_the_first_of_two_racing_monitors() {
+        let (first, second) = (ClientId::next(), ClientId::next());
+        let key = key("race");
+
+        let (running, duplicate) = (endless(), endless());
+        let (running_handle, duplicate_handle) = (running.abort_handle(), duplicate.abort_handle());
+        insert(key.clone(), first, running, None);
+        insert(key.clone(), second, duplicate, None);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        assert!(duplicate_handle.is_finished());
+        assert!(!running_handle.is_finished());
+        assert!(leave(&key, first));
+        assert!(leave(&key, second));
+    }
+
+    #[tokio::test]
+    async fn stops_gracefully_and_restarts_finished_monitors() {
+        let client = ClientId::next();
+        let key = key("graceful");
+
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let task = tokio::spawn(async move {
+            let _ = stopped.await;
+        });
+        let handle = task.abort_handle();
+        let stop: StopFn = Box::new(move || {
+            let _ = stop.send(());
+        });
+        insert(key.clone(), client, task, Some(stop));
+        assert!(leave(&key, client));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(handle.is_finished());
+
+        // A monitor whose task ended on its own is started again
+        insert(key.clone(), client, tokio::spawn(async {}), None);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!join(&key, client));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{env, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::events::Event;
+use crate::paths::runtime_dir;
+
+/// Version of the method set, bumped whenever a method changes incompatibly
+pub const API_VERSION: u32 = 1;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// Overrides the socket path, e.g. to run a daemon against mock services
+pub const SOCKET_ENV: &str = "WIBLUE_DAEMON_SOCKET";
+
+/// Method name of the notifications carrying events
+pub const EVENT_METHOD: &str = "event";
+
+/// Error codes defined by JSON-RPC 2.0, methods report failures with
+/// HTTP-like status codes instead
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+
+/// Path of the Unix socket the daemon listens on
+///
+/// `$WIBLUE_DAEMON_SOCKET`, or `daemon.sock` in the runtime directory.
+pub fn socket_path() -> PathBuf {
+    match env::var_os(SOCKET_ENV) {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => runtime_dir().join("daemon.sock"),
+    }
+}
+
+/// A method call, sent as a single line of JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    /// Omitted for calls that expect no response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// Result of a successful call, mirroring the responses of the GUI commands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reply {
+    /// Data returned by the method, or a human readable message
+    pub message: Value,
+    /// HTTP-like status, 200 unless the call partially succeeded
+    pub status: u16,
+}
+
+impl Reply {
+    /// Reply carrying serialized data
+    pub fn data<T: Serialize>(data: &T) -> Result<Self, RpcError> {
+        let message = serde_json::to_value(data)
+            .map_err(|_| RpcError::new(500, "Error serializing response"))?;
+
+        Ok(Reply {
+            message,
+            status: 200,
+        })
+    }
+
+    /// Reply carrying a message
+    pub fn message(message: &str) -> Self {
+        Self::with_status(message, 200)
+    }
+
+    pub fn with_status(message: &str, status: u16) -> Self {
+        Reply {
+            message: Value::from(message),
+            status,
+        }
+    }
+
+    /// The message as text, data is serialized to JSON
+    pub fn message_text(&self) -> String {
+        match &self.message {
+            Value::String(message) => message.clone(),
+            message => message.to_string(),
+        }
+    }
+}
+
+/// A failed call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcError {
+    /// JSON-RPC error code, or the HTTP-like status of a failed method
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: &str) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    /// HTTP-like status of the error, 500 for protocol errors
+    pub fn status(&self) -> u16 {
+        match self.code {
+            100..=599 => self.code as u16,
+            INVALID_PARAMS => 400,
+            METHOD_NOT_FOUND => 404,
+            _ => 500,
+        }
+    }
+}
+
+/// Answer to a call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Reply>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl Response {
+    pub fn new(id: Value, result: Result<Reply, RpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(reply) => (Some(reply), None),
+            Err(error) => (None, Some(error)),
+        };
+
+        Response {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result,
+            error,
+        }
+    }
+}
+
+/// Event pushed to a subscribed client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: Event,
+}
+
+impl Notification {
+    pub fn event(event: Event) -> Self {
+        Notification {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            method: EVENT_METHOD.to_string(),
+            params: event,
+        }
+    }
+}
+
+/// A line sent by the daemon
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Incoming {
+    Notification(Notification),
+    Response(Response),
+}
@@ -0,0 +1,345 @@
+use std::{
+    collections::HashSet,
+    fs,
+    os::unix::fs::{DirBuilderExt, PermissionsExt},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    signal::unix::{signal, SignalKind},
+    sync::{
+        broadcast::error::RecvError,
+        mpsc::{self, error::TrySendError},
+        Notify,
+    },
+    task::JoinHandle,
+};
+
+use super::{
+    automation::start_automation,
+    daemon_error::DaemonError,
+    dbus_service::serve_dbus,
+    events::EventBus,
+    exporter::{serve_metrics, ExporterSettings},
+    methods::{client_disconnected, dispatch, start_roaming},
+    monitors::ClientId,
+    protocol::{
+        Notification, Reply, Request, Response, RpcError, API_VERSION, INVALID_PARAMS,
+        INVALID_REQUEST, JSONRPC_VERSION, PARSE_ERROR,
+    },
+    sinks::start_sinks,
+};
+use crate::bluetooth::{
+    agent::{register_agent, AgentEvent},
+    obex_agent::register_obex_agent,
+    obex_data::ObexEvent,
+};
+use crate::wlan::roaming::RoamingPolicy;
+
+/// Runs the daemon until it receives SIGINT or SIGTERM
+///
+/// Registers the pairing and OBEX agents, publishes `com.wiblue.Manager` on
+/// the session bus, starts the event sinks of `sinks.json`, the automation
+/// rules and, if enabled in `roaming.json`, the roaming assistant, then serves
+/// the JSON-RPC API on the socket. Requests and responses are single lines
+/// of JSON; a connection may run several requests at once. Monitors are
+/// stopped once every client subscribed to them disconnected, and a client
+/// that stops reading its messages is disconnected.
+///
+/// # Arguments
+/// * `socket` - path of the Unix socket, see [`super::protocol::socket_path`]
+/// * `exporter` - the metrics exporter is started if `exporter.enabled`
+///
+/// # Returns
+/// - `Ok(())` once the daemon was stopped
+/// - `Err(DaemonError::SocketFailure)` if another daemon already listens or
+///   the socket cannot be created
+pub async fn run_daemon(socket: &Path, exporter: &ExporterSettings) -> Result<(), DaemonError> {
+    let listener = bind(socket).await?;
+    let events = EventBus::new();
+
+    register_agents(&events).await;
+    // Desktop integration is optional, the socket API works without a session bus
+    let _ = serve_dbus(&events).await;
+    if exporter.enabled {
+        let _ = serve_metrics(exporter).await;
+    }
+    start_sinks(&events).await;
+    start_automation(&events).await;
+    let roaming = RoamingPolicy::load();
+    if roaming.enabled {
+        start_roaming(roaming, events.clone());
+    }
+
+    let mut terminate = signal(SignalKind::terminate()).map_err(|e| {
+        eprintln!("Error installing signal handler: {:?}", e);
+        DaemonError::SocketFailure
+    })?;
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve_connection(stream, events.clone()));
+                }
+                Err(e) => eprintln!("Error accepting daemon connection: {:?}", e),
+            },
+            _ = tokio::signal::ctrl_c() => break,
+            _ = terminate.recv() => break,
+        }
+    }
+
+    let _ = fs::remove_file(socket);
+    Ok(())
+}
+
+/// Binds the socket, replacing a stale one left by a daemon that crashed
+async fn bind(socket: &Path) -> Result<UnixListener, DaemonError> {
+    if socket.exists() {
+        if UnixStream::connect(socket).await.is_ok() {
+            eprintln!("A daemon already listens on {}", socket.display());
+            return Err(DaemonError::SocketFailure);
+        }
+        let _ = fs::remove_file(socket);
+    }
+
+    if let Some(parent) = socket.parent() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)
+            .map_err(|e| {
+                eprintln!("Error creating socket directory: {:?}", e);
+                DaemonError::SocketFailure
+            })?;
+    }
+
+    let listener = UnixListener::bind(socket).map_err(|e| {
+        eprintln!("Error binding {}: {:?}", socket.display(), e);
+        DaemonError::SocketFailure
+    })?;
+    // Only the user running the daemon may control it
+    if let Err(e) = fs::set_permissions(socket, fs::Permissions::from_mode(0o600)) {
+        eprintln!("Error restricting socket permissions: {:?}", e);
+    }
+
+    Ok(listener)
+}
+
+/// Registers the pairing and OBEX agents, their requests become events
+async fn register_agents(events: &EventBus) {
+    let agent_events = events.clone();
+    let result = register_agent(move |event| match &event {
+        AgentEvent::Request(request) => agent_events.emit("bt_agent_request", request),
+        AgentEvent::Cancelled { .. } => agent_events.emit("bt_agent_cancel", &event),
+    })
+    .await;
+    if let Err(e) = result {
+        eprintln!("Error registering Bluetooth pairing agent: {:?}", e);
+    }
+
+    let obex_events = events.clone();
+    let result = register_obex_agent(move |event| match &event {
+        ObexEvent::IncomingPush(push) => obex_events.emit("bt_obex_push", push),
+        ObexEvent::Cancelled { .. } => obex_events.emit("bt_obex_cancel", &event),
+        ObexEvent::Progress(progress) => obex_events.emit("bt_obex_progress", progress),
+    })
+    .await;
+    if let Err(e) = result {
+        eprintln!("Error registering OBEX agent: {:?}", e);
+    }
+}
+
+/// Messages queued for a client that does not read them before it is dropped
+const OUTGOING_CAPACITY: usize = 1024;
+
+/// The queue of messages to a client
+#[derive(Clone)]
+struct Outgoing {
+    sender: mpsc::Sender<String>,
+    /// Notified once the queue was full, the client is dropped then
+    overflow: Arc<Notify>,
+}
+
+/// Serves one client until it disconnects
+async fn serve_connection(stream: UnixStream, events: EventBus) {
+    let client = ClientId::next();
+    let (reader, mut writer) = stream.into_split();
+    let (sender, mut outgoing) = mpsc::channel::<String>(OUTGOING_CAPACITY);
+    let sender = Outgoing {
+        sender,
+        overflow: Arc::new(Notify::new()),
+    };
+    let connected = Arc::new(AtomicBool::new(true));
+
+    let writer_task = tokio::spawn(async move {
+        while let Some(mut line) = outgoing.recv().await {
+            line.push('\n');
+            if writer.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut subscription: Option<JoinHandle<()>> = None;
+    let mut lines = BufReader::new(reader).lines();
+    let mut overflowed = false;
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => line,
+                _ => break,
+            },
+            _ = sender.overflow.notified() => {
+                eprintln!("Client does not read its messages, disconnecting");
+                overflowed = true;
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let error = RpcError::new(PARSE_ERROR, &format!("Invalid request: {}", e));
+                send(&sender, Response::new(Value::Null, Err(error)));
+                continue;
+            }
+        };
+
+        let id = request.id.clone();
+        let respond = |result: Result<Reply, RpcError>| {
+            if let Some(id) = id.clone() {
+                send(&sender, Response::new(id, result));
+            }
+        };
+
+        if request.jsonrpc != JSONRPC_VERSION {
+            respond(Err(RpcError::new(
+                INVALID_REQUEST,
+                "Only JSON-RPC 2.0 is supported",
+            )));
+            continue;
+        }
+
+        // Methods managing the connection itself, everything else runs concurrently
+        match request.method.as_str() {
+            "daemon.version" => respond(Reply::data(&json!({
+                "api": API_VERSION,
+                "version": env!("CARGO_PKG_VERSION"),
+            }))),
+            "events.subscribe" => {
+                let filter = match request.params.get("events") {
+                    None | Some(Value::Null) => None,
+                    Some(names) => match serde_json::from_value::<HashSet<String>>(names.clone()) {
+                        Ok(names) => Some(names),
+                        Err(_) => {
+                            respond(Err(RpcError::new(
+                                INVALID_PARAMS,
+                                "'events' must be a list of event names",
+                            )));
+                            continue;
+                        }
+                    },
+                };
+                if let Some(task) = subscription.take() {
+                    task.abort();
+                }
+                subscription = Some(forward_events(&events, filter, sender.clone()));
+                respond(Ok(Reply::message("Subscribed")));
+            }
+            "events.unsubscribe" => {
+                if let Some(task) = subscription.take() {
+                    task.abort();
+                }
+                respond(Ok(Reply::message("Unsubscribed")));
+            }
+            _ => {
+                let sender = sender.clone();
+                let events = events.clone();
+                let connected = connected.clone();
+                tokio::spawn(async move {
+                    let result = dispatch(&request.method, request.params, &events, client).await;
+                    // A monitor started after the client left has nobody to stop it
+                    if !connected.load(Ordering::SeqCst) {
+                        client_disconnected(client).await;
+                    }
+                    if let Some(id) = request.id {
+                        send(&sender, Response::new(id, result));
+                    }
+                });
+            }
+        }
+    }
+
+    if let Some(task) = subscription {
+        task.abort();
+    }
+    connected.store(false, Ordering::SeqCst);
+    client_disconnected(client).await;
+    drop(sender);
+    match overflowed {
+        true => writer_task.abort(),
+        false => {
+            let _ = writer_task.await;
+        }
+    }
+}
+
+/// Pushes the events a client subscribed to as notifications
+fn forward_events(
+    events: &EventBus,
+    filter: Option<HashSet<String>>,
+    sender: Outgoing,
+) -> JoinHandle<()> {
+    let mut receiver = events.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    if filter.as_ref().is_some_and(|f| !f.contains(&event.event)) {
+                        continue;
+                    }
+                    if !send(&sender, Notification::event(event)) {
+                        return;
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    eprintln!("Client fell behind, dropped {} events", missed);
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    })
+}
+
+/// Queues a message for the client
+///
+/// # Returns
+/// `false` once the client is gone or its queue is full, a full queue gets
+/// the client disconnected
+fn send<T: serde::Serialize>(sender: &Outgoing, message: T) -> bool {
+    match serde_json::to_string(&message) {
+        Ok(line) => match sender.sender.try_send(line) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                sender.overflow.notify_one();
+                false
+            }
+            Err(TrySendError::Closed(_)) => false,
+        },
+        Err(e) => {
+            eprintln!("Error serializing daemon message: {:?}", e);
+            true
+        }
+    }
+}
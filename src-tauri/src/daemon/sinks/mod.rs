@@ -0,0 +1,186 @@
+pub mod mqtt;
+pub mod sink_data;
+pub mod sink_error;
+pub mod webhook;
+
+use serde_json::json;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+
+use self::{
+    mqtt::MqttSink,
+    sink_data::{SinkConfig, SinkSettings, SinkTarget},
+    sink_error::SinkError,
+    webhook::WebhookSink,
+};
+use super::{
+    events::EventBus,
+    methods::dispatch,
+    monitors::ClientId,
+    template::{render_template, TemplateEvent},
+    watcher::start_watcher,
+};
+
+/// Events a sink may fall behind by while it retries, further ones are dropped
+const QUEUE_SIZE: usize = 64;
+
+/// A sink with its open connection
+enum Sink {
+    Webhook(WebhookSink),
+    Mqtt(Box<MqttSink>),
+}
+
+impl Sink {
+    fn new(config: &SinkConfig) -> Result<Self, SinkError> {
+        match &config.target {
+            SinkTarget::Webhook(webhook) => Ok(Sink::Webhook(WebhookSink::new(webhook.clone())?)),
+            SinkTarget::Mqtt(mqtt) => Ok(Sink::Mqtt(Box::new(MqttSink::new(
+                &config.name,
+                mqtt.clone(),
+            )?))),
+        }
+    }
+
+    /// Renders the event and delivers it once
+    async fn deliver(
+        &mut self,
+        config: &SinkConfig,
+        event: &TemplateEvent,
+    ) -> Result<(), SinkError> {
+        let body = render_body(config, event);
+        match self {
+            Sink::Webhook(webhook) => webhook.send(body).await,
+            Sink::Mqtt(mqtt) => mqtt.publish(event, body).await,
+        }
+    }
+
+    /// Keeps the connection of the sink alive while it waits for events
+    async fn idle(&mut self) {
+        match self {
+            Sink::Webhook(_) => std::future::pending().await,
+            Sink::Mqtt(mqtt) => mqtt.keep_alive().await,
+        }
+    }
+}
+
+/// Forwards the events selected in `sinks.json` to webhooks and MQTT brokers
+///
+/// Every sink gets its own queue, so a sink that is down and retrying does
+/// not hold up the others. Starts the watcher raising the connection, access
+/// point and quota events and the Bluetooth monitor raising
+/// `bluetooth_low_battery` if a sink forwards it. Does nothing if no sink is
+/// configured.
+pub async fn start_sinks(events: &EventBus) {
+    let settings = SinkSettings::load();
+    if settings.sinks.is_empty() {
+        return;
+    }
+
+    for config in settings.sinks.iter().cloned() {
+        let sink = match Sink::new(&config) {
+            Ok(sink) => sink,
+            Err(e) => {
+                eprintln!("Skipping sink {}: {:?}", config.name, e);
+                continue;
+            }
+        };
+        let (sender, queue) = mpsc::channel(QUEUE_SIZE);
+        tokio::spawn(run_sink(sink, config.clone(), queue));
+        tokio::spawn(route_events(events.clone(), config, sender));
+    }
+
+    start_watcher(events);
+    if settings
+        .sinks
+        .iter()
+        .any(|sink| sink.accepts("bluetooth_low_battery"))
+    {
+        if let Err(e) = dispatch("bt.monitor_stats", json!({}), events, ClientId::DAEMON).await {
+            eprintln!("Error starting Bluetooth monitor for sinks: {}", e.message);
+        }
+    }
+}
+
+/// Queues the events the sink accepts
+async fn route_events(events: EventBus, config: SinkConfig, sender: mpsc::Sender<TemplateEvent>) {
+    let mut receiver = events.subscribe();
+    loop {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return,
+        };
+        if !config.accepts(&event.event) {
+            continue;
+        }
+
+        let event = TemplateEvent::new(&event.event, event.payload);
+        if let Err(mpsc::error::TrySendError::Full(event)) = sender.try_send(event) {
+            eprintln!("Sink {} is backed up, dropped {}", config.name, event.event);
+        }
+    }
+}
+
+/// Delivers the queued events one after another, retrying with backoff
+async fn run_sink(mut sink: Sink, config: SinkConfig, mut queue: mpsc::Receiver<TemplateEvent>) {
+    loop {
+        let event = tokio::select! {
+            event = queue.recv() => match event {
+                Some(event) => event,
+                None => return,
+            },
+            _ = sink.idle() => continue,
+        };
+
+        let attempts = config.retry.max_attempts.max(1);
+        for attempt in 1..=attempts {
+            match sink.deliver(&config, &event).await {
+                Ok(()) => break,
+                Err(e) if e.is_transient() && attempt < attempts => {
+                    let delay = config.retry.delay(attempt - 1);
+                    eprintln!(
+                        "Sink {} failed to deliver {} ({:?}), retrying in {:?}",
+                        config.name, event.event, e, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Sink {} dropped {} after {} attempts: {:?}",
+                        config.name, event.event, attempt, e
+                    );
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Delivers a `sink_test` event to a configured sink, without retrying
+///
+/// # Arguments
+/// * `name` - name of the sink in `sinks.json`
+///
+/// # Returns
+/// - `Ok(String)` with the rendered body that was delivered
+/// - `Err(SinkError)` if the sink does not exist or the delivery failed
+pub async fn test_sink(name: &str) -> Result<String, SinkError> {
+    let settings = SinkSettings::load();
+    let config = settings.sink(name).ok_or(SinkError::NoSuchSink)?;
+
+    let event = TemplateEvent::new(
+        "sink_test",
+        json!({ "sink": config.name, "message": "Test event from wiblue" }),
+    );
+    let mut sink = Sink::new(config)?;
+    sink.deliver(config, &event).await?;
+
+    Ok(render_body(config, &event))
+}
+
+/// The template of the sink filled with the event, or the event as JSON
+fn render_body(config: &SinkConfig, event: &TemplateEvent) -> String {
+    match &config.template {
+        Some(template) => render_template(template, event),
+        None => serde_json::to_string(event).unwrap_or_default(),
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Outgoing, Packet, QoS};
+use tokio::time;
+
+use super::{sink_data::MqttConfig, sink_error::SinkError};
+use crate::daemon::template::{render_template, TemplateEvent};
+
+/// Seconds between two pings keeping an idle connection open
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Publishes events to an MQTT broker
+///
+/// The connection is opened on the first publish and kept while it works.
+/// After a failure it is dropped instead of reconnected, so the broker never
+/// receives a publish the retry sends again.
+pub struct MqttSink {
+    client_id: String,
+    config: MqttConfig,
+    qos: QoS,
+    connection: Option<(AsyncClient, EventLoop)>,
+}
+
+impl MqttSink {
+    pub fn new(name: &str, config: MqttConfig) -> Result<Self, SinkError> {
+        let qos = match config.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            qos => return Err(SinkError::InvalidConfig(format!("Unsupported QoS {}", qos))),
+        };
+
+        Ok(MqttSink {
+            client_id: config
+                .client_id
+                .clone()
+                .unwrap_or_else(|| format!("wiblue-{}", name)),
+            config,
+            qos,
+            connection: None,
+        })
+    }
+
+    fn connect(&self) -> (AsyncClient, EventLoop) {
+        let mut options = MqttOptions::new(&self.client_id, &self.config.host, self.config.port);
+        options.set_keep_alive(KEEP_ALIVE);
+        if let Some(username) = &self.config.username {
+            options.set_credentials(username, self.config.password.clone().unwrap_or_default());
+        }
+
+        AsyncClient::new(options, 10)
+    }
+
+    /// Publishes one message to the topic rendered for the event and waits
+    /// until it left (QoS 0) or the broker acknowledged it (QoS 1)
+    pub async fn publish(
+        &mut self,
+        event: &TemplateEvent,
+        payload: String,
+    ) -> Result<(), SinkError> {
+        let topic = render_template(&self.config.topic, event);
+        let (client, mut eventloop) = match self.connection.take() {
+            Some(connection) => connection,
+            None => self.connect(),
+        };
+
+        let qos = self.qos;
+        let sent = async {
+            client
+                .publish(topic, qos, self.config.retain, payload)
+                .await
+                .map_err(|e| SinkError::ConnectionFailure(e.to_string()))?;
+            // Packet id the event loop assigned to the publish, acks of others are skipped
+            let mut pkid = None;
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Outgoing(Outgoing::Publish(_))) if qos == QoS::AtMostOnce => {
+                        return Ok(());
+                    }
+                    Ok(Event::Outgoing(Outgoing::Publish(id))) if pkid.is_none() => {
+                        pkid = Some(id);
+                    }
+                    Ok(Event::Incoming(Packet::PubAck(ack))) if Some(ack.pkid) == pkid => {
+                        return Ok(());
+                    }
+                    Ok(_) => {}
+                    Err(e) => return Err(SinkError::ConnectionFailure(e.to_string())),
+                }
+            }
+        };
+        let result = time::timeout(Duration::from_millis(self.config.timeout_ms), sent)
+            .await
+            .unwrap_or_else(|_| Err(SinkError::ConnectionFailure("Timed out".into())));
+
+        if result.is_ok() {
+            self.connection = Some((client, eventloop));
+        }
+        result
+    }
+
+    /// Answers pings of an open connection, never returns while it works
+    ///
+    /// Meant to run while the sink waits for the next event.
+    pub async fn keep_alive(&mut self) {
+        let error = match &mut self.connection {
+            Some((_, eventloop)) => loop {
+                if let Err(e) = eventloop.poll().await {
+                    break e;
+                }
+            },
+            None => return std::future::pending().await,
+        };
+
+        eprintln!("MQTT connection to {} closed: {}", self.config.host, error);
+        self.connection = None;
+    }
+}
@@ -0,0 +1,192 @@
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths::config_dir;
+
+/// Events forwarded by a sink that does not list its own
+pub const DEFAULT_EVENTS: [&str; 5] = [
+    "wifi_connected",
+    "wifi_disconnected",
+    "wifi_ap_seen",
+    "quota_exceeded",
+    "bluetooth_low_battery",
+];
+
+/// How failed deliveries are retried
+///
+/// The delay doubles after every failed attempt up to `max_delay_ms`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Attempts per event including the first one
+    pub max_attempts: u32,
+    /// Milliseconds to wait before the first retry
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_delay_ms: 1000,
+            max_delay_ms: 60_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the given retry, `retry` starting at 0
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .initial_delay_ms
+            .saturating_mul(1u64.checked_shl(retry).unwrap_or(u64::MAX));
+        Duration::from_millis(delay.min(self.max_delay_ms))
+    }
+}
+
+/// HTTP endpoint events are sent to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// HTTP method, `POST` if not set
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// Extra headers, e.g. `Authorization`
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default = "default_content_type")]
+    pub content_type: String,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+/// MQTT broker events are published to, plain TCP only
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MqttConfig {
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    /// Topic template, e.g. `wiblue/{{event}}`
+    pub topic: String,
+    /// Client id, `wiblue-<sink name>` if not set
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// 0 (at most once) or 1 (at least once)
+    #[serde(default)]
+    pub qos: u8,
+    #[serde(default)]
+    pub retain: bool,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+/// Where a sink delivers to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SinkTarget {
+    Webhook(WebhookConfig),
+    Mqtt(MqttConfig),
+}
+
+/// A configured destination for daemon events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SinkConfig {
+    /// Unique name, used by `sinks.test` and in the log
+    pub name: String,
+    #[serde(flatten)]
+    pub target: SinkTarget,
+    /// Event names to forward, [`DEFAULT_EVENTS`] if empty
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// Body template, see [`crate::daemon::template::render_template`]; a JSON object with `event`,
+    /// `timestamp` and `payload` if not set
+    pub template: Option<String>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+}
+
+impl SinkConfig {
+    /// Whether the sink forwards the named event
+    pub fn accepts(&self, event: &str) -> bool {
+        if self.events.is_empty() {
+            DEFAULT_EVENTS.contains(&event)
+        } else {
+            self.events.iter().any(|e| e == event)
+        }
+    }
+}
+
+/// Configured sinks, stored in `sinks.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SinkSettings {
+    pub sinks: Vec<SinkConfig>,
+}
+
+impl SinkSettings {
+    fn path() -> PathBuf {
+        config_dir().join("sinks.json")
+    }
+
+    /// Loads the settings, falling back to no sinks
+    pub fn load() -> Self {
+        let content = match std::fs::read_to_string(Self::path()) {
+            Ok(content) => content,
+            Err(_) => return SinkSettings::default(),
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("Error reading sinks.json: {}", e);
+            SinkSettings::default()
+        })
+    }
+
+    pub fn sink(&self, name: &str) -> Option<&SinkConfig> {
+        self.sinks.iter().find(|sink| sink.name == name)
+    }
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+fn default_content_type() -> String {
+    "application/json".to_string()
+}
+
+fn default_timeout_ms() -> u64 {
+    10_000
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_retry_delay_up_to_the_maximum() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_delay_ms: 1000,
+            max_delay_ms: 5000,
+        };
+        let delays: Vec<u64> = (0..5)
+            .map(|retry| policy.delay(retry).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [1000, 2000, 4000, 5000, 5000]);
+
+        // Large retry counts must not overflow
+        assert_eq!(policy.delay(64), Duration::from_millis(5000));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(5000));
+        let unbounded = RetryPolicy {
+            max_delay_ms: u64::MAX,
+            ..policy
+        };
+        assert_eq!(unbounded.delay(63), Duration::from_millis(u64::MAX));
+    }
+}
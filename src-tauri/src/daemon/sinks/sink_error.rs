@@ -0,0 +1,23 @@
+#[derive(Debug)]
+pub enum SinkError {
+    /// No sink with that name is configured
+    NoSuchSink,
+    /// The sink configuration cannot be used, e.g. an invalid URL or QoS
+    InvalidConfig(String),
+    /// The endpoint or broker could not be reached or timed out
+    ConnectionFailure(String),
+    /// The endpoint answered with an error status
+    Rejected(u16),
+}
+
+impl SinkError {
+    /// Whether delivering the same event again may succeed
+    pub fn is_transient(&self) -> bool {
+        match self {
+            SinkError::NoSuchSink | SinkError::InvalidConfig(_) => false,
+            SinkError::ConnectionFailure(_) => true,
+            // Timeouts, rate limiting and server errors
+            SinkError::Rejected(status) => matches!(status, 408 | 429 | 500..=599),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use reqwest::{Client, Method};
+
+use super::{sink_data::WebhookConfig, sink_error::SinkError};
+
+/// Sends events to an HTTP endpoint
+pub struct WebhookSink {
+    config: WebhookConfig,
+    client: Client,
+}
+
+impl WebhookSink {
+    pub fn new(config: WebhookConfig) -> Result<Self, SinkError> {
+        let client = Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .map_err(|e| SinkError::InvalidConfig(e.to_string()))?;
+
+        Ok(WebhookSink { config, client })
+    }
+
+    /// Sends one request with the body
+    ///
+    /// # Returns
+    /// - `Ok(())` if the endpoint answered with a 2xx status
+    /// - `Err(SinkError::Rejected)` with the status of any other answer
+    /// - `Err(SinkError::ConnectionFailure)` if there was no answer
+    pub async fn send(&self, body: String) -> Result<(), SinkError> {
+        let method =
+            Method::from_bytes(self.config.method.to_uppercase().as_bytes()).map_err(|_| {
+                SinkError::InvalidConfig(format!("Invalid method {}", self.config.method))
+            })?;
+
+        let mut request = self
+            .client
+            .request(method, &self.config.url)
+            .header(reqwest::header::CONTENT_TYPE, &self.config.content_type)
+            .body(body);
+        for (name, value) in &self.config.headers {
+            request = request.header(name, value);
+        }
+
+        let response = request.send().await.map_err(|e| {
+            if e.is_builder() {
+                SinkError::InvalidConfig(e.to_string())
+            } else {
+                SinkError::ConnectionFailure(e.to_string())
+            }
+        })?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(SinkError::Rejected(status.as_u16()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Answers one request with `status`, returning the request as received
+    async fn serve_once(status: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            // Read the headers, then the body announced by Content-Length
+            loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request).to_string();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|l| l.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length || read == 0 {
+                        break;
+                    }
+                }
+                if read == 0 {
+                    break;
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (url, server)
+    }
+
+    fn sink(url: String) -> WebhookSink {
+        WebhookSink::new(WebhookConfig {
+            url,
+            method: "put".to_string(),
+            headers: HashMap::from([("Authorization".to_string(), "Bearer token".to_string())]),
+            content_type: "application/json".to_string(),
+            timeout_ms: 5000,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sends_body_headers_and_method() {
+        let (url, server) = serve_once("204 No Content").await;
+
+        sink(url)
+            .send(r#"{"event":"wifi_connected"}"#.to_string())
+            .await
+            .unwrap();
+
+        let request = server.await.unwrap();
+        let lowercase = request.to_lowercase();
+        assert!(request.starts_with("PUT /hook HTTP/1.1\r\n"));
+        assert!(lowercase.contains("content-type: application/json\r\n"));
+        assert!(lowercase.contains("authorization: bearer token\r\n"));
+        assert!(request.ends_with("\r\n\r\n{\"event\":\"wifi_connected\"}"));
+    }
+
+    #[tokio::test]
+    async fn reports_rejections_and_unreachable_endpoints() {
+        let (url, server) = serve_once("503 Service Unavailable").await;
+        let result = sink(url).send("{}".to_string()).await;
+        assert!(matches!(result, Err(SinkError::Rejected(503))));
+        server.await.unwrap();
+
+        // Nothing listens on the port once the listener is dropped
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+        let result = sink(url).send("{}".to_string()).await;
+        assert!(matches!(result, Err(SinkError::ConnectionFailure(_))));
+    }
+}
@@ -0,0 +1,139 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// A daemon event with the time it was raised, as templates see it
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateEvent {
+    pub event: String,
+    /// RFC 3339 time the event was raised
+    pub timestamp: String,
+    pub payload: Value,
+}
+
+impl TemplateEvent {
+    pub fn new(event: &str, payload: Value) -> Self {
+        TemplateEvent {
+            event: event.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            payload,
+        }
+    }
+}
+
+/// Fills the placeholders of a template with the event
+///
+/// Placeholders are `{{event}}`, `{{timestamp}}`, `{{payload}}` and paths
+/// into the payload like `{{payload.device.alias}}`. Strings are inserted as
+/// they are; append `|json` (`{{payload.ssid|json}}`) to insert them quoted
+/// and escaped. Unknown placeholders become empty.
+pub fn render_template(template: &str, event: &TemplateEvent) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let placeholder = rest[start + 2..start + end].trim();
+        rest = &rest[start + end + 2..];
+
+        let (path, as_json) = match placeholder.rsplit_once('|') {
+            Some((path, filter)) if filter.trim() == "json" => (path.trim(), true),
+            _ => (placeholder, false),
+        };
+        let value = match path {
+            "event" => Some(Value::from(event.event.clone())),
+            "timestamp" => Some(Value::from(event.timestamp.clone())),
+            _ => path
+                .strip_prefix("payload")
+                .filter(|fields| fields.is_empty() || fields.starts_with('.'))
+                .and_then(|fields| {
+                    fields
+                        .split('.')
+                        .filter(|field| !field.is_empty())
+                        .try_fold(&event.payload, |value, field| match value {
+                            Value::Array(items) => {
+                                field.parse::<usize>().ok().and_then(|i| items.get(i))
+                            }
+                            _ => value.get(field),
+                        })
+                        .cloned()
+                }),
+        };
+
+        match value {
+            Some(Value::String(text)) if !as_json => out.push_str(&text),
+            Some(value) => out.push_str(&value.to_string()),
+            None if as_json => out.push_str("null"),
+            None => {}
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn event() -> TemplateEvent {
+        TemplateEvent {
+            event: "wifi_connected".to_string(),
+            timestamp: "2026-10-19T08:00:00+00:00".to_string(),
+            payload: json!({
+                "ssid": "Caf\u{e9} \"Free\"",
+                "signal": 72,
+                "device": { "alias": "Headphones", "battery": null },
+                "targets": ["gateway", "dns"],
+            }),
+        }
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        let event = event();
+        let render = |template: &str| render_template(template, &event);
+
+        assert_eq!(render("wiblue/{{event}}"), "wiblue/wifi_connected");
+        assert_eq!(render("{{ timestamp }}"), "2026-10-19T08:00:00+00:00");
+        assert_eq!(
+            render("{{payload.ssid}} at {{payload.signal}}%"),
+            "Café \"Free\" at 72%"
+        );
+        assert_eq!(render("{{payload.device.alias}}"), "Headphones");
+        assert_eq!(render("{{payload.targets.1}}"), "dns");
+        assert_eq!(render("{{payload.targets}}"), r#"["gateway","dns"]"#);
+        assert_eq!(render("{{payload}}"), event.payload.to_string());
+    }
+
+    #[test]
+    fn renders_json_and_unknown_placeholders() {
+        let event = event();
+        let render = |template: &str| render_template(template, &event);
+
+        assert_eq!(
+            render(r#"{"ssid": {{payload.ssid|json}}}"#),
+            r#"{"ssid": "Café \"Free\""}"#
+        );
+        assert_eq!(render("{{ payload.signal | json }}"), "72");
+        assert_eq!(render("{{payload.missing|json}}"), "null");
+        assert_eq!(render("[{{payload.missing}}]"), "[]");
+        assert_eq!(render("[{{payloads}}{{payload.targets.x}}]"), "[]");
+        assert_eq!(render("{{payload.device.battery}}"), "null");
+    }
+
+    #[test]
+    fn keeps_unclosed_placeholders() {
+        let event = event();
+        assert_eq!(render_template("a {{event", &event), "a {{event");
+        assert_eq!(
+            render_template("{{event}} }} {{", &event),
+            "wifi_connected }} {{"
+        );
+    }
+}
@@ -0,0 +1,401 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use chrono::{Datelike, Local};
+use serde::{Deserialize, Serialize};
+use systemstat::{Platform, System};
+
+use super::events::EventBus;
+use crate::{
+    paths::{config_dir, data_dir},
+    wlan::{
+        get_networks::{get_access_points, get_active_network, ActiveNetwork},
+        network_stats::NetworkMonitor,
+    },
+};
+
+/// Seconds between two checks if `interval_secs` is not set
+const DEFAULT_INTERVAL_SECS: u64 = 30;
+
+/// Access points not seen for this long are forgotten, `wifi_ap_seen` is
+/// raised again when they return
+const SEEN_EXPIRY: Duration = Duration::from_secs(60 * 60);
+
+/// Set once the watcher runs
+static WATCHER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Cleared while background scans are paused, e.g. on battery
+static BACKGROUND_SCANS: AtomicBool = AtomicBool::new(true);
+
+/// Period a data quota applies to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaPeriod {
+    Daily,
+    Monthly,
+}
+
+/// Traffic limit of an interface, raises `quota_exceeded` once per period
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Quota {
+    pub interface: String,
+    /// Bytes sent and received together
+    pub limit_bytes: u64,
+    pub period: QuotaPeriod,
+}
+
+/// Settings of the watcher, stored in `watch.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchSettings {
+    pub quotas: Vec<Quota>,
+    /// Seconds between two checks of the connection, scan, power and quotas
+    pub interval_secs: Option<u64>,
+}
+
+impl WatchSettings {
+    fn path() -> PathBuf {
+        config_dir().join("watch.json")
+    }
+
+    /// Loads the settings, falling back to no quotas
+    pub fn load() -> Self {
+        std::fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Whether periodic scans of the daemon may run
+pub fn background_scans_enabled() -> bool {
+    BACKGROUND_SCANS.load(Ordering::SeqCst)
+}
+
+/// Pauses or resumes background scans
+///
+/// While paused the roaming assistant triggers no scans and the watcher stops
+/// polling scan results for `wifi_ap_seen`. The exporter is not affected, it
+/// only reads the results of scans NetworkManager runs on its own.
+pub fn set_background_scans(enabled: bool) {
+    BACKGROUND_SCANS.store(enabled, Ordering::SeqCst);
+}
+
+/// Payload of `wifi_ap_seen`
+#[derive(Debug, Clone, Serialize)]
+struct AccessPointSeen {
+    ssid: String,
+    bssid: String,
+    /// Signal quality in percent
+    signal: u8,
+    /// Frequency in MHz
+    frequency: u32,
+    /// Wi-Fi device that sees the access point
+    device: String,
+}
+
+/// Payload of `quota_exceeded`
+#[derive(Debug, Clone, Serialize)]
+struct QuotaExceeded {
+    interface: String,
+    period: QuotaPeriod,
+    limit_bytes: u64,
+    used_bytes: u64,
+}
+
+/// Payload of `power_changed`
+#[derive(Debug, Clone, Serialize)]
+pub struct PowerState {
+    pub on_battery: bool,
+}
+
+/// Traffic counted toward a quota in its current period
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct QuotaUsage {
+    /// Day or month of the year, see [`current_period`]
+    period: (i32, u32),
+    used_bytes: u64,
+    /// Whether `quota_exceeded` was raised in this period
+    exceeded: bool,
+}
+
+impl QuotaUsage {
+    /// Adds traffic, starting over if `period` is a new one
+    fn add(&mut self, period: (i32, u32), bytes: u64) {
+        if period != self.period {
+            *self = QuotaUsage {
+                period,
+                ..QuotaUsage::default()
+            };
+        }
+        self.used_bytes = self.used_bytes.saturating_add(bytes);
+    }
+}
+
+/// Usage of every quota, keyed by [`QuotaState::key`]
+type QuotaUsages = HashMap<String, QuotaUsage>;
+
+/// Path of the file the quota usage is kept in across restarts
+fn usage_path() -> PathBuf {
+    data_dir().join("quota_usage.json")
+}
+
+/// Reads the saved usage, nothing is counted yet if there is none
+fn load_usage(path: &Path) -> QuotaUsages {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_usage(path: &Path, usage: &QuotaUsages) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(usage)?)
+}
+
+/// Reads the traffic of one quota
+struct QuotaState {
+    quota: Quota,
+    monitor: Option<NetworkMonitor>,
+    /// Monitor total at the previous check, traffic since then is added
+    last_total: Option<u64>,
+}
+
+impl QuotaState {
+    fn new(quota: Quota) -> Self {
+        QuotaState {
+            quota,
+            monitor: None,
+            last_total: None,
+        }
+    }
+
+    /// Key of the saved usage, a quota per interface and period
+    fn key(&self) -> String {
+        let period = match self.quota.period {
+            QuotaPeriod::Daily => "daily",
+            QuotaPeriod::Monthly => "monthly",
+        };
+        format!("{}/{}", self.quota.interface, period)
+    }
+
+    /// Adds the traffic since the previous check to `usage`
+    ///
+    /// # Returns
+    /// `Some` with the usage once the limit is crossed, once per period
+    async fn check(&mut self, usage: &mut QuotaUsage) -> Option<QuotaExceeded> {
+        if self.monitor.is_none() {
+            self.monitor = NetworkMonitor::new(&self.quota.interface).ok();
+            self.last_total = None;
+        }
+        let monitor = self.monitor.as_mut()?;
+        let Some(stats) = monitor.get_stats().await else {
+            // The counters start over once the interface is back
+            if !monitor.interface_exists() {
+                self.monitor = None;
+            }
+            return None;
+        };
+        let total = stats.total_up.saturating_add(stats.total_down);
+        let added = self.last_total.map_or(0, |last| total.saturating_sub(last));
+        self.last_total = Some(total);
+
+        usage.add(current_period(self.quota.period), added);
+        if usage.exceeded || usage.used_bytes < self.quota.limit_bytes {
+            return None;
+        }
+        usage.exceeded = true;
+        Some(QuotaExceeded {
+            interface: self.quota.interface.clone(),
+            period: self.quota.period,
+            limit_bytes: self.quota.limit_bytes,
+            used_bytes: usage.used_bytes,
+        })
+    }
+}
+
+/// Day or month of the year, depending on the period
+fn current_period(period: QuotaPeriod) -> (i32, u32) {
+    let now = Local::now();
+    match period {
+        QuotaPeriod::Daily => (now.year(), now.ordinal()),
+        QuotaPeriod::Monthly => (now.year(), now.month()),
+    }
+}
+
+/// Whether the system runs on battery, `None` on machines without one
+pub fn on_battery() -> Option<bool> {
+    let system = System::new();
+    system.battery_life().ok()?;
+    system.on_ac_power().ok().map(|on_ac| !on_ac)
+}
+
+/// Starts [`watch`] with the settings of `watch.json`, once per daemon
+pub fn start_watcher(events: &EventBus) {
+    if WATCHER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let settings = WatchSettings::load();
+    tokio::spawn(watch(settings, events.clone()));
+}
+
+/// Publishes the events no monitor raises on its own
+///
+/// Polls the connected network, the scan results, the power source and the
+/// quotas and emits
+/// - `wifi_connected` with the [`ActiveNetwork`] when a network is joined
+///   or the device roams to another BSSID
+/// - `wifi_disconnected` with the network that was left
+/// - `wifi_ap_seen` for every BSSID not seen in the last hour; the access
+///   points of the first scan are taken as known. Skipped while background
+///   scans are paused
+/// - `power_changed` with the [`PowerState`] on the first reading and
+///   whenever the system switches between battery and AC
+/// - `quota_exceeded` once per period when an interface crosses its quota
+///
+/// Quota usage is kept in `quota_usage.json` across restarts, but traffic
+/// counts toward a quota only while the daemon runs.
+pub async fn watch(settings: WatchSettings, events: EventBus) {
+    let interval = Duration::from_secs(
+        settings
+            .interval_secs
+            .unwrap_or(DEFAULT_INTERVAL_SECS)
+            .max(1),
+    );
+    let mut quotas: Vec<QuotaState> = settings.quotas.into_iter().map(QuotaState::new).collect();
+    let usage_path = usage_path();
+    let mut usage = load_usage(&usage_path);
+    let mut active: Option<ActiveNetwork> = None;
+    let mut seen: Option<HashMap<String, Instant>> = None;
+    let mut power: Option<bool> = None;
+
+    loop {
+        let current = tokio::task::spawn_blocking(get_active_network)
+            .await
+            .ok()
+            .flatten();
+        match (&active, &current) {
+            (Some(old), Some(new)) if old.bssid == new.bssid => {}
+            (old, Some(new)) => {
+                if let Some(old) = old {
+                    events.emit("wifi_disconnected", old);
+                }
+                events.emit("wifi_connected", new);
+            }
+            (Some(old), None) => events.emit("wifi_disconnected", old),
+            (None, None) => {}
+        }
+        active = current;
+
+        let networks = match background_scans_enabled() {
+            true => tokio::task::spawn_blocking(|| get_access_points(false))
+                .await
+                .ok()
+                .and_then(Result::ok),
+            false => None,
+        };
+        if let Some(networks) = networks {
+            let now = Instant::now();
+            match &mut seen {
+                None => seen = Some(networks.into_iter().map(|n| (n.bssid, now)).collect()),
+                Some(seen) => {
+                    seen.retain(|_, last_seen| now.duration_since(*last_seen) < SEEN_EXPIRY);
+                    for network in networks {
+                        if seen.insert(network.bssid.clone(), now).is_none() {
+                            events.emit(
+                                "wifi_ap_seen",
+                                &AccessPointSeen {
+                                    ssid: network.ssid,
+                                    bssid: network.bssid,
+                                    signal: network.signal,
+                                    frequency: network.frequency,
+                                    device: network.device,
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        let on_battery = tokio::task::spawn_blocking(on_battery).await.ok().flatten();
+        if on_battery.is_some() && on_battery != power {
+            power = on_battery;
+            events.emit(
+                "power_changed",
+                &PowerState {
+                    on_battery: on_battery.unwrap_or_default(),
+                },
+            );
+        }
+
+        let saved = usage.clone();
+        for quota in &mut quotas {
+            let quota_usage = usage.entry(quota.key()).or_default();
+            if let Some(exceeded) = quota.check(quota_usage).await {
+                events.emit("quota_exceeded", &exceeded);
+            }
+        }
+        if usage != saved {
+            if let Err(e) = save_usage(&usage_path, &usage) {
+                eprintln!("Error saving quota usage: {:?}", e);
+            }
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_usage_and_starts_over_each_period() {
+        let mut usage = QuotaUsage::default();
+        usage.add((2026, 10), 500);
+        usage.add((2026, 10), 250);
+        usage.exceeded = true;
+        assert_eq!((usage.used_bytes, usage.exceeded), (750, true));
+
+        usage.add((2026, 11), 100);
+        assert_eq!(
+            usage,
+            QuotaUsage {
+                period: (2026, 11),
+                used_bytes: 100,
+                exceeded: false,
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_usage_across_restarts() {
+        let path = std::env::temp_dir()
+            .join(format!("wiblue-watcher-{}", std::process::id()))
+            .join("quota_usage.json");
+        let _ = std::fs::remove_file(&path);
+        assert!(load_usage(&path).is_empty());
+
+        let quota = QuotaState::new(Quota {
+            interface: "wlan0".to_string(),
+            limit_bytes: 1000,
+            period: QuotaPeriod::Monthly,
+        });
+        let mut usage = QuotaUsages::new();
+        usage.entry(quota.key()).or_default().add((2026, 10), 900);
+        save_usage(&path, &usage).unwrap();
+
+        let loaded = load_usage(&path);
+        assert_eq!(loaded, usage);
+        assert_eq!(loaded["wlan0/monthly"].used_bytes, 900);
+    }
+}
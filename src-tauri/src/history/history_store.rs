@@ -0,0 +1,252 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+
+use super::history_error::HistoryError;
+use crate::bluetooth::device_stats::BluetoothStats;
+use crate::paths::data_dir;
+use crate::wlan::network_stats::NetworkStats;
+use crate::wlan::speed_test::SpeedTestResult;
+
+/// Size above which the oldest records are dropped
+const MAX_FILE_BYTES: u64 = 8 * 1024 * 1024;
+/// Size of the blocks the file is read backwards in
+const READ_BLOCK_BYTES: u64 = 64 * 1024;
+
+/// Serializes appends and trimming within the process
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// A single measurement kept in the history
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", content = "data")]
+pub enum HistoryEntry {
+    SpeedTest(SpeedTestResult),
+    NetworkStats {
+        interface: String,
+        stats: NetworkStats,
+    },
+    BluetoothStats(BluetoothStats),
+}
+
+/// History entry together with the time it was recorded
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HistoryRecord {
+    pub timestamp: DateTime<Utc>,
+    pub entry: HistoryEntry,
+}
+
+/// Append-only history of measurements stored as JSON lines
+///
+/// Once the file grows past its size limit the oldest half is dropped.
+pub struct HistoryStore {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+impl HistoryStore {
+    /// Opens the history file in the wiblue data directory
+    pub fn open_default() -> Result<Self, HistoryError> {
+        Self::open(data_dir().join("history.jsonl"))
+    }
+
+    /// Opens (and creates the parent directory of) the given history file
+    pub fn open(path: PathBuf) -> Result<Self, HistoryError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                eprintln!("Error creating history directory: {:?}", e);
+                HistoryError::IoError
+            })?;
+        }
+
+        Ok(HistoryStore {
+            path,
+            max_bytes: MAX_FILE_BYTES,
+        })
+    }
+
+    /// Sets the size above which the oldest records are dropped
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Appends an entry timestamped with the current time
+    pub fn append(&self, entry: HistoryEntry) -> Result<HistoryRecord, HistoryError> {
+        let record = HistoryRecord {
+            timestamp: Utc::now(),
+            entry,
+        };
+        let line = serde_json::to_string(&record).map_err(|_| HistoryError::SerializationError)?;
+
+        let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|_| HistoryError::IoError)?;
+        writeln!(file, "{}", line).map_err(|_| HistoryError::IoError)?;
+
+        let size = file.metadata().map_err(|_| HistoryError::IoError)?.len();
+        if size > self.max_bytes {
+            self.trim(size)?;
+        }
+
+        Ok(record)
+    }
+
+    /// Keeps the newest half of the file, starting at a line boundary
+    fn trim(&self, size: u64) -> Result<(), HistoryError> {
+        let mut file = fs::File::open(&self.path).map_err(|_| HistoryError::IoError)?;
+        file.seek(SeekFrom::Start(size - self.max_bytes / 2))
+            .map_err(|_| HistoryError::IoError)?;
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail)
+            .map_err(|_| HistoryError::IoError)?;
+
+        let start = tail
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(tail.len(), |i| i + 1);
+        let temporary = self.path.with_extension("jsonl.tmp");
+        fs::write(&temporary, &tail[start..]).map_err(|_| HistoryError::IoError)?;
+        fs::rename(&temporary, &self.path).map_err(|e| {
+            eprintln!("Error trimming history: {:?}", e);
+            HistoryError::IoError
+        })
+    }
+
+    /// Reads the newest `limit` records accepted by `filter`, oldest first
+    ///
+    /// The file is read backwards, so only the part holding the requested
+    /// records is scanned. Lines that cannot be parsed (e.g. written by a
+    /// newer version) are skipped.
+    pub fn read(
+        &self,
+        limit: usize,
+        filter: impl Fn(&HistoryRecord) -> bool,
+    ) -> Result<Vec<HistoryRecord>, HistoryError> {
+        let mut file = match fs::File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(_) => return Err(HistoryError::IoError),
+        };
+        let mut position = file.metadata().map_err(|_| HistoryError::IoError)?.len();
+
+        let mut records = Vec::new();
+        // Start of a line that continues in the block read before
+        let mut partial = Vec::new();
+        while position > 0 && records.len() < limit {
+            let size = position.min(READ_BLOCK_BYTES);
+            position -= size;
+
+            let mut block = vec![0u8; size as usize];
+            file.seek(SeekFrom::Start(position))
+                .and_then(|_| file.read_exact(&mut block))
+                .map_err(|_| HistoryError::IoError)?;
+            block.extend_from_slice(&partial);
+
+            // The first line of a block may begin in the block before it
+            let split = match block.iter().position(|b| *b == b'\n') {
+                Some(i) if position > 0 => i + 1,
+                None if position > 0 => block.len(),
+                _ => 0,
+            };
+            for line in block[split..].rsplit(|b| *b == b'\n') {
+                if let Ok(record) = serde_json::from_slice::<HistoryRecord>(line) {
+                    if filter(&record) {
+                        records.push(record);
+                        if records.len() == limit {
+                            break;
+                        }
+                    }
+                }
+            }
+            block.truncate(split);
+            partial = block;
+        }
+
+        records.reverse();
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speed_test(n: usize) -> HistoryEntry {
+        HistoryEntry::SpeedTest(SpeedTestResult {
+            endpoint: format!("http://192.0.2.{}:5210", n % 250),
+            ssid: Some("Home".to_string()),
+            bssid: Some(format!("AA:BB:CC:DD:EE:{:02X}", n % 256)),
+            download_bps: n as f64,
+            upload_bps: n as f64,
+            idle_latency_ms: None,
+            download_latency_ms: None,
+            upload_latency_ms: None,
+        })
+    }
+
+    fn download(record: &HistoryRecord) -> usize {
+        match &record.entry {
+            HistoryEntry::SpeedTest(result) => result.download_bps as usize,
+            _ => unreachable!(),
+        }
+    }
+
+    fn store(name: &str, max_bytes: u64) -> HistoryStore {
+        let path = std::env::temp_dir()
+            .join(format!("wiblue-history-{}-{}", name, std::process::id()))
+            .join("history.jsonl");
+        let _ = fs::remove_file(&path);
+        HistoryStore::open(path).unwrap().with_max_bytes(max_bytes)
+    }
+
+    #[test]
+    fn reads_newest_records_across_blocks() {
+        let store = store("read", u64::MAX);
+        // About 300 bytes per line, several read blocks in total
+        for n in 0..1000 {
+            store.append(speed_test(n)).unwrap();
+        }
+
+        let newest = store.read(5, |_| true).unwrap();
+        assert_eq!(
+            newest.iter().map(download).collect::<Vec<_>>(),
+            [995, 996, 997, 998, 999]
+        );
+
+        let even = store
+            .read(usize::MAX, |r| download(r).is_multiple_of(2))
+            .unwrap();
+        assert_eq!(even.len(), 500);
+        assert_eq!(download(&even[0]), 0);
+        assert_eq!(download(&even[499]), 998);
+    }
+
+    #[test]
+    fn drops_oldest_records_past_the_limit() {
+        let store = store("trim", 16 * 1024);
+        for n in 0..500 {
+            store.append(speed_test(n)).unwrap();
+        }
+
+        assert!(fs::metadata(&store.path).unwrap().len() <= 16 * 1024);
+        let records = store.read(usize::MAX, |_| true).unwrap();
+        assert!(records.len() < 500);
+        assert_eq!(download(records.last().unwrap()), 499);
+        // Every remaining line is complete and the order is kept
+        assert!(records
+            .windows(2)
+            .all(|pair| download(&pair[1]) == download(&pair[0]) + 1));
+        assert!(fs::read_to_string(&store.path)
+            .unwrap()
+            .lines()
+            .all(|line| serde_json::from_str::<HistoryRecord>(line).is_ok()));
+    }
+}
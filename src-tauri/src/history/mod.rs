@@ -0,0 +1,2 @@
+pub mod history_error;
+pub mod history_store;
@@ -1,10 +1,18 @@
-use systemstat::NetworkStats;
+use daemon::client::DaemonClient;
+use daemon::events::Event;
+use daemon::protocol::{Reply, RpcError};
+use serde_json::{json, Value};
+use std::sync::{Arc, LazyLock, OnceLock};
 use tauri::{AppHandle, Emitter};
-use wlan::manager::WifiManager;
-use wlan::network_data::WifiNetwork;
-use wlan::network_stats::NetworkMonitor;
-use wlan::networkmanager_error::{StatsError, WifiConnectionError};
-mod wlan;
+use tauri_plugin_notification::NotificationExt;
+use tauri_plugin_opener::OpenerExt;
+pub mod automation;
+pub mod bluetooth;
+pub mod cli;
+pub mod daemon;
+pub mod history;
+pub mod paths;
+pub mod wlan;
 
 #[derive(serde::Serialize, serde::Deserialize)]
 struct JsonResponse {
@@ -25,99 +33,585 @@ impl JsonResponse {
     }
 }
 
-#[tauri::command]
-fn scan() -> String {
-    let networks = <WifiNetwork as WifiManager>::scan();
-    match networks {
-        Ok(net) => {
-            serde_json::to_string(&net).unwrap_or_else(|_| "Error serializing networks".to_string())
-        }
-        Err(_) => "Error getting networks 400".to_string(),
+/// Handle events of the daemon are forwarded to, set once the app is built
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+/// Connection to the daemon, replaced when the daemon restarts
+static DAEMON: LazyLock<tokio::sync::Mutex<Option<Arc<DaemonClient>>>> =
+    LazyLock::new(|| tokio::sync::Mutex::new(None));
+
+/// Returns the daemon connection, (re)connecting and subscribing to all events
+async fn daemon_client() -> Result<Arc<DaemonClient>, RpcError> {
+    let mut daemon = DAEMON.lock().await;
+    if let Some(client) = daemon.as_ref().filter(|c| !c.is_closed()) {
+        return Ok(client.clone());
     }
+
+    let client = DaemonClient::connect(forward_event).await.map_err(|e| {
+        eprintln!("Error connecting to daemon: {:?}", e);
+        RpcError::new(503, "Daemon unavailable")
+    })?;
+    client.call("events.subscribe", json!({})).await?;
+
+    let client = Arc::new(client);
+    *daemon = Some(client.clone());
+    Ok(client)
 }
 
-#[tauri::command]
-fn network_connect(bssid: String, password: String) -> Result<String, String> {
-    println!("bssid: {} \n password: {}", bssid.clone(), password.clone());
-    let temp_password: Option<&str> = if password.is_empty() {
-        None
-    } else {
-        Some(&password)
+/// Re-emits a daemon event to the webview
+fn forward_event(event: Event) {
+    let Some(app) = APP.get() else {
+        return;
     };
+    let _ = app.emit(&event.event, &event.payload);
 
-    let connection_status = <WifiNetwork as WifiManager>::connect(&bssid, temp_password);
-
-    match connection_status {
-        Ok(_) => Ok(JsonResponse::new("Connected Successfully", 200)),
-        Err(e) => match e {
-            WifiConnectionError::NoSuchNetwork => Err(JsonResponse::new("No network", 404)),
-            WifiConnectionError::NoPasswordProvided => {
-                Err(JsonResponse::new("No password provided", 502))
-            }
-            WifiConnectionError::WrongPassword => Err(JsonResponse::new("Wrong password", 401)),
-            WifiConnectionError::UnknownError | WifiConnectionError::AskingError => {
-                Err(JsonResponse::new("Unknown error", 500))
-            }
-        },
+    if event.event == "bluetooth_low_battery" {
+        let payload = &event.payload;
+        let alias = payload["device"]["alias"].as_str().unwrap_or_default();
+        let battery = payload["device"]["battery"]
+            .as_u64()
+            .or(payload["threshold"].as_u64())
+            .unwrap_or_default();
+        let result = app
+            .notification()
+            .builder()
+            .title(format!("{} battery low", alias))
+            .body(format!("Battery at {}%", battery))
+            .show();
+        if let Err(e) = result {
+            eprintln!("Error showing low battery notification: {:?}", e);
+        }
+    }
+
+    if event.event == "automation_notification" {
+        let payload = &event.payload;
+        let result = app
+            .notification()
+            .builder()
+            .title(payload["title"].as_str().unwrap_or_default())
+            .body(payload["body"].as_str().unwrap_or_default())
+            .show();
+        if let Err(e) = result {
+            eprintln!("Error showing automation notification: {:?}", e);
+        }
+    }
+}
+
+async fn call_daemon(method: &str, params: Value) -> Result<Reply, RpcError> {
+    daemon_client().await?.call(method, params).await
+}
+
+/// Calls a daemon method and wraps its outcome like every command response
+async fn call(method: &str, params: Value) -> Result<String, String> {
+    match call_daemon(method, params).await {
+        Ok(reply) => Ok(JsonResponse::new(&reply.message_text(), reply.status)),
+        Err(e) => Err(JsonResponse::new(&e.message, e.status())),
     }
 }
 
 #[tauri::command]
-async fn monitor_network_stats(app: AppHandle, interface: String) -> Result<String, String> {
-    // Start monitoring network stats
-    match network_stats_constat_monitoring(&interface, app).await {
-        Ok(_) => Ok(JsonResponse::new("Started monitoring", 200)),
-        Err(e) => {
-            eprintln!("Error monitoring network stats: {:?}", e);
-            Err(JsonResponse::new("Failed to start monitoring", 500))
-        }
+async fn scan() -> String {
+    match call_daemon("wifi.scan", json!({})).await {
+        Ok(reply) => reply.message_text(),
+        Err(_) => "Error getting networks 400".to_string(),
     }
 }
 
-async fn network_stats_constat_monitoring(
-    interface_name: &str,
-    app_handle: AppHandle,
-) -> Result<Option<NetworkStats>, StatsError> {
-    let mut monitor = match NetworkMonitor::new(interface_name) {
-        Ok(m) => m,
-        Err(e) => return Err(e),
-    };
+#[tauri::command]
+async fn channel_analysis() -> Result<String, String> {
+    call("wifi.channel_analysis", json!({})).await
+}
+
+#[tauri::command]
+async fn network_connect(bssid: String, password: String) -> Result<String, String> {
+    call(
+        "wifi.connect",
+        json!({ "bssid": bssid, "password": password }),
+    )
+    .await
+}
 
-    tokio::spawn(async move {
-        monitor
-            .monitor(10, move |stats| {
-                // Emit the stats to the frontend every second
-                let _ = app_handle.emit("network_stats", &stats);
-            })
-            .await;
+#[tauri::command]
+async fn network_disconnect(interface: Option<String>) -> Result<String, String> {
+    call("wifi.disconnect", json!({ "interface": interface })).await
+}
+
+#[tauri::command]
+async fn wifi_profiles() -> Result<String, String> {
+    call("wifi.profiles", json!({})).await
+}
+
+#[tauri::command]
+async fn wifi_delete_profile(profile: String) -> Result<String, String> {
+    call("wifi.delete_profile", json!({ "profile": profile })).await
+}
+
+#[tauri::command]
+async fn wifi_qr_code(profile: Option<String>) -> Result<String, String> {
+    call("wifi.qr_code", json!({ "profile": profile })).await
+}
+
+#[tauri::command]
+async fn wifi_qr_connect(text: Option<String>, image: Option<String>) -> Result<String, String> {
+    call("wifi.qr_connect", json!({ "text": text, "image": image })).await
+}
+
+#[tauri::command]
+async fn wifi_export_profiles(
+    profiles: Option<Vec<String>>,
+    passphrase: Option<String>,
+) -> Result<String, String> {
+    let params = json!({ "profiles": profiles, "passphrase": passphrase });
+    call("wifi.export_profiles", params).await
+}
+
+#[tauri::command]
+async fn wifi_import_preview(
+    path: String,
+    format: Option<String>,
+    passphrase: Option<String>,
+) -> Result<String, String> {
+    let params = json!({ "path": path, "format": format, "passphrase": passphrase });
+    call("wifi.import_preview", params).await
+}
+
+#[tauri::command]
+async fn wifi_import_profiles(
+    path: String,
+    format: Option<String>,
+    passphrase: Option<String>,
+    resolutions: Option<Value>,
+    default_resolution: Option<Value>,
+) -> Result<String, String> {
+    let params = json!({
+        "path": path,
+        "format": format,
+        "passphrase": passphrase,
+        "resolutions": resolutions,
+        "default_resolution": default_resolution,
     });
+    call("wifi.import_profiles", params).await
+}
 
-    Ok(None)
+#[tauri::command]
+async fn hotspot_start(config: Value) -> Result<String, String> {
+    call("wifi.hotspot_start", json!({ "config": config })).await
 }
 
 #[tauri::command]
-fn scan_interfaces() -> Result<String, String> {
-    match <WifiNetwork as WifiManager>::scan_interfaces() {
-        Ok(ifaces) => {
-            return Ok(JsonResponse::new(
-                &serde_json::to_string(&ifaces)
-                    .unwrap_or_else(|_| "Error serializing networks".to_string()),
-                200,
-            ))
-        }
-        Err(_) => return Err(JsonResponse::new("Error getting interfaces", 500)),
+async fn hotspot_stop() -> Result<String, String> {
+    call("wifi.hotspot_stop", json!({})).await
+}
+
+#[tauri::command]
+async fn hotspot_status() -> Result<String, String> {
+    call("wifi.hotspot_status", json!({})).await
+}
+
+#[tauri::command]
+async fn hotspot_support(interface: Option<String>) -> Result<String, String> {
+    call("wifi.ap_support", json!({ "interface": interface })).await
+}
+
+#[tauri::command]
+async fn monitor_network_stats(interface: String) -> Result<String, String> {
+    call("net.monitor_stats", json!({ "interface": interface })).await
+}
+
+#[tauri::command]
+async fn stop_network_stats_monitor(interface: String) -> Result<String, String> {
+    call("net.monitor_stats_stop", json!({ "interface": interface })).await
+}
+
+#[tauri::command]
+async fn monitor_connection_quality(config: Option<Value>) -> Result<String, String> {
+    call("net.monitor_quality", json!({ "config": config })).await
+}
+
+#[tauri::command]
+async fn stop_connection_quality_monitor() -> Result<String, String> {
+    call("net.monitor_quality_stop", json!({})).await
+}
+
+#[tauri::command]
+async fn dns_diagnostics(config: Option<Value>) -> Result<String, String> {
+    call("net.dns_diagnostics", json!({ "config": config })).await
+}
+
+#[tauri::command]
+async fn connectivity_check(config: Option<Value>) -> Result<String, String> {
+    call("net.connectivity", json!({ "config": config })).await
+}
+
+#[tauri::command]
+async fn monitor_connectivity_changes(config: Option<Value>) -> Result<String, String> {
+    call("net.monitor_connectivity", json!({ "config": config })).await
+}
+
+#[tauri::command]
+async fn stop_connectivity_monitor() -> Result<String, String> {
+    call("net.monitor_connectivity_stop", json!({})).await
+}
+
+/// Opens the login page of a captive portal in the browser of the user,
+/// the daemon detects the portal when no URL is given
+#[tauri::command]
+async fn open_captive_portal(app: AppHandle, url: Option<String>) -> Result<String, String> {
+    let url = match url {
+        Some(u) => u,
+        None => call_daemon("net.connectivity", json!({}))
+            .await
+            .map_err(|e| JsonResponse::new(&e.message, e.status()))?
+            .message
+            .get("portal_url")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| JsonResponse::new("No captive portal detected", 404))?,
     };
+
+    app.opener().open_url(url, None::<&str>).map_err(|e| {
+        eprintln!("Error opening captive portal: {:?}", e);
+        JsonResponse::new("Failed to open captive portal", 500)
+    })?;
+
+    Ok(JsonResponse::new("Opened captive portal", 200))
+}
+
+#[tauri::command]
+async fn speed_test(config: Value) -> Result<String, String> {
+    call("speed_test.run", json!({ "config": config })).await
+}
+
+#[tauri::command]
+async fn speed_test_history(bssid: Option<String>) -> Result<String, String> {
+    call("speed_test.history", json!({ "bssid": bssid })).await
+}
+
+#[tauri::command]
+async fn start_speed_test_server(lan: Option<bool>) -> Result<String, String> {
+    call("speed_test.start_server", json!({ "lan": lan })).await
+}
+
+#[tauri::command]
+async fn stop_speed_test_server() -> Result<String, String> {
+    call("speed_test.stop_server", json!({})).await
+}
+
+#[tauri::command]
+async fn scan_interfaces() -> Result<String, String> {
+    call("net.interfaces", json!({})).await
+}
+
+#[tauri::command]
+async fn scan_interface_details() -> Result<String, String> {
+    call("net.interface_details", json!({})).await
+}
+
+#[tauri::command]
+async fn bt_adapters() -> Result<String, String> {
+    call("bt.adapters", json!({})).await
+}
+
+#[tauri::command]
+async fn bt_set_adapter(adapter: String, setting: Value) -> Result<String, String> {
+    call(
+        "bt.set_adapter",
+        json!({ "adapter": adapter, "setting": setting }),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn bt_devices(adapter: Option<String>) -> Result<String, String> {
+    call("bt.devices", json!({ "adapter": adapter })).await
+}
+
+#[tauri::command]
+async fn bt_start_discovery(adapter: String, filter: Option<Value>) -> Result<String, String> {
+    call(
+        "bt.start_discovery",
+        json!({ "adapter": adapter, "filter": filter }),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn bt_stop_discovery(adapter: String) -> Result<String, String> {
+    call("bt.stop_discovery", json!({ "adapter": adapter })).await
+}
+
+#[tauri::command]
+async fn bt_beacon_scan(adapter: String, config: Option<Value>) -> Result<String, String> {
+    call(
+        "bt.beacon_scan",
+        json!({ "adapter": adapter, "config": config }),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn bt_beacon_stop(adapter: String) -> Result<String, String> {
+    call("bt.beacon_stop", json!({ "adapter": adapter })).await
+}
+
+#[tauri::command]
+async fn bt_beacons() -> Result<String, String> {
+    call("bt.beacons", json!({})).await
+}
+
+#[tauri::command]
+async fn bt_pair(device: String) -> Result<String, String> {
+    call("bt.pair", json!({ "device": device })).await
+}
+
+#[tauri::command]
+async fn bt_cancel_pair(device: String) -> Result<String, String> {
+    call("bt.cancel_pair", json!({ "device": device })).await
+}
+
+#[tauri::command]
+async fn bt_paired_devices(adapter: Option<String>) -> Result<String, String> {
+    call("bt.paired_devices", json!({ "adapter": adapter })).await
+}
+
+#[tauri::command]
+async fn bt_connect(device: String, profile: Option<String>) -> Result<String, String> {
+    call(
+        "bt.connect",
+        json!({ "device": device, "profile": profile }),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn bt_disconnect(device: String, profile: Option<String>) -> Result<String, String> {
+    call(
+        "bt.disconnect",
+        json!({ "device": device, "profile": profile }),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn bt_set_device(device: String, setting: Value) -> Result<String, String> {
+    call(
+        "bt.set_device",
+        json!({ "device": device, "setting": setting }),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn bt_remove_device(device: String) -> Result<String, String> {
+    call("bt.remove_device", json!({ "device": device })).await
+}
+
+#[tauri::command]
+async fn bt_gatt_services(device: String) -> Result<String, String> {
+    call("bt.gatt_services", json!({ "device": device })).await
+}
+
+#[tauri::command]
+async fn bt_gatt_read(path: String) -> Result<String, String> {
+    call("bt.gatt_read", json!({ "path": path })).await
+}
+
+#[tauri::command]
+async fn bt_gatt_write(
+    path: String,
+    value: Value,
+    write_type: Option<Value>,
+) -> Result<String, String> {
+    call(
+        "bt.gatt_write",
+        json!({ "path": path, "value": value, "write_type": write_type }),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn bt_gatt_subscribe(path: String) -> Result<String, String> {
+    call("bt.gatt_subscribe", json!({ "path": path })).await
+}
+
+#[tauri::command]
+async fn bt_gatt_unsubscribe(path: String) -> Result<String, String> {
+    call("bt.gatt_unsubscribe", json!({ "path": path })).await
+}
+
+#[tauri::command]
+async fn bt_audio_profiles(device: String) -> Result<String, String> {
+    call("bt.audio_profiles", json!({ "device": device })).await
+}
+
+#[tauri::command]
+async fn bt_set_audio_profile(device: String, profile: String) -> Result<String, String> {
+    call(
+        "bt.set_audio_profile",
+        json!({ "device": device, "profile": profile }),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn bt_pan_connect(device: String, role: Option<Value>) -> Result<String, String> {
+    call("bt.pan_connect", json!({ "device": device, "role": role })).await
+}
+
+#[tauri::command]
+async fn bt_pan_disconnect(device: String) -> Result<String, String> {
+    call("bt.pan_disconnect", json!({ "device": device })).await
+}
+
+#[tauri::command]
+async fn bt_pan_connections() -> Result<String, String> {
+    call("bt.pan_connections", json!({})).await
+}
+
+#[tauri::command]
+async fn bt_obex_send(device: String, file: String) -> Result<String, String> {
+    call("bt.obex_send", json!({ "device": device, "file": file })).await
+}
+
+#[tauri::command]
+async fn bt_obex_list(device: String, folder: Option<String>) -> Result<String, String> {
+    call(
+        "bt.obex_list",
+        json!({ "device": device, "folder": folder }),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn bt_obex_download(device: String, path: String) -> Result<String, String> {
+    call(
+        "bt.obex_download",
+        json!({ "device": device, "path": path }),
+    )
+    .await
+}
+
+#[tauri::command]
+async fn bt_obex_cancel(transfer: String) -> Result<String, String> {
+    call("bt.obex_cancel", json!({ "transfer": transfer })).await
+}
+
+#[tauri::command]
+async fn bt_obex_respond(id: u64, accept: bool) -> Result<String, String> {
+    call("bt.obex_respond", json!({ "id": id, "accept": accept })).await
+}
+
+#[tauri::command]
+async fn bt_obex_receive_dir() -> Result<String, String> {
+    call("bt.obex_receive_dir", json!({})).await
+}
+
+#[tauri::command]
+async fn bt_obex_set_receive_dir(dir: String) -> Result<String, String> {
+    call("bt.obex_set_receive_dir", json!({ "dir": dir })).await
+}
+
+#[tauri::command]
+async fn monitor_bluetooth_stats(config: Option<Value>) -> Result<String, String> {
+    call("bt.monitor_stats", json!({ "config": config })).await
+}
+
+#[tauri::command]
+async fn stop_bluetooth_stats_monitor() -> Result<String, String> {
+    call("bt.monitor_stats_stop", json!({})).await
+}
+
+#[tauri::command]
+async fn bt_agent_respond(id: u64, response: Value) -> Result<String, String> {
+    call(
+        "bt.agent_respond",
+        json!({ "id": id, "response": response }),
+    )
+    .await
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
+        .setup(|app| {
+            let _ = APP.set(app.handle().clone());
+
+            // Connect right away so agent requests and pushes reach the webview
+            tauri::async_runtime::spawn(async {
+                if let Err(e) = daemon_client().await {
+                    eprintln!("Error connecting to daemon: {}", e.message);
+                }
+            });
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             scan,
+            channel_analysis,
             network_connect,
+            network_disconnect,
+            wifi_profiles,
+            wifi_delete_profile,
+            wifi_qr_code,
+            wifi_qr_connect,
+            wifi_export_profiles,
+            wifi_import_preview,
+            wifi_import_profiles,
+            hotspot_start,
+            hotspot_stop,
+            hotspot_status,
+            hotspot_support,
             monitor_network_stats,
-            scan_interfaces
+            stop_network_stats_monitor,
+            monitor_connection_quality,
+            stop_connection_quality_monitor,
+            dns_diagnostics,
+            connectivity_check,
+            monitor_connectivity_changes,
+            stop_connectivity_monitor,
+            open_captive_portal,
+            speed_test,
+            speed_test_history,
+            start_speed_test_server,
+            stop_speed_test_server,
+            scan_interfaces,
+            scan_interface_details,
+            bt_adapters,
+            bt_set_adapter,
+            bt_devices,
+            bt_start_discovery,
+            bt_stop_discovery,
+            bt_beacon_scan,
+            bt_beacon_stop,
+            bt_beacons,
+            bt_pair,
+            bt_cancel_pair,
+            bt_agent_respond,
+            bt_paired_devices,
+            bt_connect,
+            bt_disconnect,
+            bt_set_device,
+            bt_remove_device,
+            monitor_bluetooth_stats,
+            stop_bluetooth_stats_monitor,
+            bt_gatt_services,
+            bt_gatt_read,
+            bt_gatt_write,
+            bt_gatt_subscribe,
+            bt_gatt_unsubscribe,
+            bt_audio_profiles,
+            bt_set_audio_profile,
+            bt_obex_send,
+            bt_obex_list,
+            bt_obex_download,
+            bt_obex_cancel,
+            bt_obex_respond,
+            bt_obex_receive_dir,
+            bt_obex_set_receive_dir,
+            bt_pan_connect,
+            bt_pan_disconnect,
+            bt_pan_connections
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
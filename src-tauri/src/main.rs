@@ -1,18 +1,12 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use wlan::get_interfaces::get_interfaces;
-
-pub mod wlan;
-
 fn main() {
-    let interfaces = match get_interfaces() {
-        Ok(iface) => iface,
-        Err(_) => return,
-    };
-
-    for i in interfaces {
-        println!("{}", i);
+    // A subcommand runs the headless command line interface instead of the GUI
+    let args: Vec<String> = std::env::args().collect();
+    if wiblue_lib::cli::wants_cli(&args) {
+        std::process::exit(wiblue_lib::cli::run());
     }
+
     wiblue_lib::run();
 }
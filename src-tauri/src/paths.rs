@@ -0,0 +1,35 @@
+use std::{env, path::PathBuf};
+
+/// Directory wiblue keeps its persistent data in
+///
+/// Follows the XDG base directory spec: `$XDG_DATA_HOME/wiblue`, falling back
+/// to `~/.local/share/wiblue`.
+pub fn data_dir() -> PathBuf {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// Directory wiblue keeps its settings in
+///
+/// `$XDG_CONFIG_HOME/wiblue`, falling back to `~/.config/wiblue`.
+pub fn config_dir() -> PathBuf {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+fn xdg_dir(variable: &str, fallback: &str) -> PathBuf {
+    let base = match env::var_os(variable) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME").unwrap_or_default()).join(fallback),
+    };
+    base.join("wiblue")
+}
+
+/// Directory wiblue keeps its sockets in
+///
+/// `$XDG_RUNTIME_DIR/wiblue`, falling back to the data directory when no
+/// runtime directory is set.
+pub fn runtime_dir() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join("wiblue"),
+        _ => data_dir(),
+    }
+}
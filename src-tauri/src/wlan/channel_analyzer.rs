@@ -0,0 +1,355 @@
+use super::network_data::WifiNetwork;
+
+/// Width of a 2.4 GHz channel in MHz, neighbours closer than this overlap
+const CHANNEL_WIDTH_2_4_GHZ: f64 = 20.0;
+
+/// Channels that do not overlap each other on 2.4 GHz
+const CANDIDATES_2_4_GHZ: [u8; 3] = [1, 6, 11];
+/// 20 MHz channels usable on 5 GHz in most regulatory domains
+const CANDIDATES_5_GHZ: [u8; 25] = [
+    36, 40, 44, 48, 52, 56, 60, 64, 100, 104, 108, 112, 116, 120, 124, 128, 132, 136, 140, 144,
+    149, 153, 157, 161, 165,
+];
+/// Preferred scanning channels (PSC) on 6 GHz
+const CANDIDATES_6_GHZ: [u8; 15] = [
+    5, 21, 37, 53, 69, 85, 101, 117, 133, 149, 165, 181, 197, 213, 229,
+];
+
+/// Number of recommended channels returned per band
+const RECOMMENDATIONS_PER_BAND: usize = 3;
+
+/// Frequency band of a Wi-Fi network
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum WifiBand {
+    /// 2.4 GHz
+    Ghz2_4,
+    /// 5 GHz
+    Ghz5,
+    /// 6 GHz
+    Ghz6,
+}
+
+impl WifiBand {
+    /// Determines the band from a frequency in MHz
+    pub fn from_frequency(frequency: u32) -> Option<Self> {
+        match frequency {
+            2400..=2500 => Some(WifiBand::Ghz2_4),
+            5150..=5925 => Some(WifiBand::Ghz5),
+            5926..=7125 => Some(WifiBand::Ghz6),
+            _ => None,
+        }
+    }
+
+    /// Center frequency of a channel in MHz
+    pub fn channel_frequency(&self, channel: u8) -> u32 {
+        match self {
+            WifiBand::Ghz2_4 if channel == 14 => 2484,
+            WifiBand::Ghz2_4 => 2407 + 5 * channel as u32,
+            WifiBand::Ghz5 => 5000 + 5 * channel as u32,
+            WifiBand::Ghz6 => 5950 + 5 * channel as u32,
+        }
+    }
+
+    fn candidates(&self) -> &'static [u8] {
+        match self {
+            WifiBand::Ghz2_4 => &CANDIDATES_2_4_GHZ,
+            WifiBand::Ghz5 => &CANDIDATES_5_GHZ,
+            WifiBand::Ghz6 => &CANDIDATES_6_GHZ,
+        }
+    }
+}
+
+/// Utilization of a single channel
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ChannelUsage {
+    pub channel: u8,
+    /// Center frequency in MHz
+    pub frequency: u32,
+    /// Networks using exactly this channel
+    pub network_count: usize,
+    /// Networks on neighbouring channels whose spectrum overlaps this one (2.4 GHz only)
+    pub overlapping_count: usize,
+    /// Sum of the overlap-weighted signal strengths of all interfering networks
+    pub interference: f64,
+    /// Estimated share of airtime used by other networks in percent
+    pub utilization: f64,
+    /// Whether the channel requires dynamic frequency selection (radar detection)
+    pub dfs: bool,
+}
+
+/// Analysis of all channels of one band
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BandAnalysis {
+    pub band: WifiBand,
+    /// Candidate channels ordered by channel number
+    pub channels: Vec<ChannelUsage>,
+    /// Least congested candidate channels, best first
+    pub recommended: Vec<u8>,
+}
+
+/// Channel analysis of a complete scan
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ChannelAnalysis {
+    pub bands: Vec<BandAnalysis>,
+}
+
+/// Groups scan results per band and channel and scores every candidate channel
+///
+/// On 2.4 GHz channels are 5 MHz apart but 20 MHz wide, so a network also
+/// interferes with up to three channels on each side; its weight falls off
+/// linearly with the distance between the center frequencies. On 5 and 6 GHz
+/// only networks on the same channel are counted.
+///
+/// # Arguments
+/// * `networks` - results of a scan
+///
+/// # Returns
+/// A `ChannelAnalysis` with one entry per band, including bands nobody uses
+pub fn analyze_channels(networks: &[WifiNetwork]) -> ChannelAnalysis {
+    let bands = [WifiBand::Ghz2_4, WifiBand::Ghz5, WifiBand::Ghz6]
+        .into_iter()
+        .map(|band| analyze_band(band, networks))
+        .collect();
+
+    ChannelAnalysis { bands }
+}
+
+fn analyze_band(band: WifiBand, networks: &[WifiNetwork]) -> BandAnalysis {
+    let band_networks: Vec<&WifiNetwork> = networks
+        .iter()
+        .filter(|n| WifiBand::from_frequency(n.frequency) == Some(band))
+        .collect();
+
+    // Channels somebody already uses are reported even if they are no candidates
+    let mut channel_numbers: Vec<u8> = band.candidates().to_vec();
+    for network in &band_networks {
+        let channel = network_channel(band, network);
+        if channel != 0 && !channel_numbers.contains(&channel) {
+            channel_numbers.push(channel);
+        }
+    }
+    channel_numbers.sort_unstable();
+
+    let channels: Vec<ChannelUsage> = channel_numbers
+        .into_iter()
+        .map(|channel| channel_usage(band, channel, &band_networks))
+        .collect();
+
+    let mut ranked: Vec<&ChannelUsage> = channels
+        .iter()
+        .filter(|c| band.candidates().contains(&c.channel))
+        .collect();
+    // Prefer channels without DFS when they are equally busy, DFS can force a channel switch
+    ranked.sort_by(|a, b| {
+        a.utilization
+            .total_cmp(&b.utilization)
+            .then(a.interference.total_cmp(&b.interference))
+            .then(a.dfs.cmp(&b.dfs))
+    });
+    let recommended = ranked
+        .into_iter()
+        .take(RECOMMENDATIONS_PER_BAND)
+        .map(|c| c.channel)
+        .collect();
+
+    BandAnalysis {
+        band,
+        channels,
+        recommended,
+    }
+}
+
+fn channel_usage(band: WifiBand, channel: u8, networks: &[&WifiNetwork]) -> ChannelUsage {
+    let frequency = band.channel_frequency(channel);
+
+    let mut network_count = 0;
+    let mut overlapping_count = 0;
+    let mut interference = 0.0;
+    let mut idle_probability = 1.0;
+
+    for network in networks {
+        let overlap = if network_channel(band, network) == channel {
+            network_count += 1;
+            1.0
+        } else if band == WifiBand::Ghz2_4 {
+            let distance = network.frequency.abs_diff(frequency) as f64;
+            let overlap = (1.0 - distance / CHANNEL_WIDTH_2_4_GHZ).max(0.0);
+            if overlap > 0.0 {
+                overlapping_count += 1;
+            }
+            overlap
+        } else {
+            0.0
+        };
+
+        let weight = overlap * signal_weight(network.signal_strength);
+        interference += weight;
+        idle_probability *= 1.0 - weight;
+    }
+
+    ChannelUsage {
+        channel,
+        frequency,
+        network_count,
+        overlapping_count,
+        interference,
+        utilization: (1.0 - idle_probability) * 100.0,
+        dfs: band == WifiBand::Ghz5 && (52..=144).contains(&channel),
+    }
+}
+
+/// Channel of a network, derived from its frequency if the scan did not report one
+fn network_channel(band: WifiBand, network: &WifiNetwork) -> u8 {
+    if network.channel != 0 {
+        return network.channel;
+    }
+
+    match band {
+        WifiBand::Ghz2_4 if network.frequency == 2484 => 14,
+        WifiBand::Ghz2_4 => (network.frequency.saturating_sub(2407) / 5) as u8,
+        WifiBand::Ghz5 => (network.frequency.saturating_sub(5000) / 5) as u8,
+        WifiBand::Ghz6 => (network.frequency.saturating_sub(5950) / 5) as u8,
+    }
+}
+
+/// Maps a signal strength to a weight between 0 and 1
+///
+/// `nmcli` reports signal quality in percent; negative values are treated as
+/// dBm and mapped linearly from -100 dBm (0) to -50 dBm (1).
+fn signal_weight(signal_strength: i32) -> f64 {
+    let percent = if signal_strength < 0 {
+        2 * (signal_strength + 100)
+    } else {
+        signal_strength
+    };
+    percent.clamp(0, 100) as f64 / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wlan::network_data::{NetworkMode, WifiSecurity};
+
+    fn network(frequency: u32, channel: u8, signal_strength: i32) -> WifiNetwork {
+        WifiNetwork {
+            ssid: format!("net-{}", frequency),
+            bssid: "AA:BB:CC:DD:EE:FF".to_string(),
+            signal_strength,
+            frequency,
+            channel,
+            security: WifiSecurity::Wpa2,
+            is_hidden: false,
+            speed: None,
+            network_mode: NetworkMode::Infra,
+            currently_used: false,
+        }
+    }
+
+    fn band(analysis: &ChannelAnalysis, band: WifiBand) -> &BandAnalysis {
+        analysis.bands.iter().find(|b| b.band == band).unwrap()
+    }
+
+    fn usage(band: &BandAnalysis, channel: u8) -> &ChannelUsage {
+        band.channels.iter().find(|c| c.channel == channel).unwrap()
+    }
+
+    #[test]
+    fn weights_overlap_on_2_4_ghz_by_distance() {
+        let analysis = analyze_channels(&[network(2422, 3, 100)]);
+        let band = band(&analysis, WifiBand::Ghz2_4);
+
+        // The used channel is listed next to the candidates
+        assert_eq!(
+            band.channels.iter().map(|c| c.channel).collect::<Vec<_>>(),
+            [1, 3, 6, 11]
+        );
+
+        let own = usage(band, 3);
+        assert_eq!((own.network_count, own.overlapping_count), (1, 0));
+        assert_eq!(own.utilization, 100.0);
+
+        // 10 MHz apart: half of the spectrum overlaps
+        let one = usage(band, 1);
+        assert_eq!((one.network_count, one.overlapping_count), (0, 1));
+        assert_eq!(one.interference, 0.5);
+        assert_eq!(one.utilization, 50.0);
+
+        // 15 MHz apart: a quarter overlaps
+        assert_eq!(usage(band, 6).interference, 0.25);
+
+        // 45 MHz apart: no overlap at all
+        let eleven = usage(band, 11);
+        assert_eq!((eleven.overlapping_count, eleven.interference), (0, 0.0));
+
+        assert_eq!(band.recommended, [11, 6, 1]);
+    }
+
+    #[test]
+    fn weights_signal_in_dbm_and_percent() {
+        assert_eq!(signal_weight(-50), 1.0);
+        assert_eq!(signal_weight(-75), 0.5);
+        assert_eq!(signal_weight(-110), 0.0);
+        assert_eq!(signal_weight(40), 0.4);
+
+        let analysis = analyze_channels(&[network(2437, 6, -75), network(2437, 6, -75)]);
+        let six = usage(band(&analysis, WifiBand::Ghz2_4), 6);
+        assert_eq!(six.network_count, 2);
+        assert_eq!(six.interference, 1.0);
+        // Two networks each busy half of the time leave the channel idle a quarter of it
+        assert_eq!(six.utilization, 75.0);
+    }
+
+    #[test]
+    fn prefers_channels_without_dfs_when_equally_free() {
+        let busy: Vec<WifiNetwork> = [36, 40, 44, 48]
+            .into_iter()
+            .map(|channel| network(5000 + 5 * channel as u32, channel, 100))
+            .collect();
+        let analysis = analyze_channels(&busy);
+        let band = band(&analysis, WifiBand::Ghz5);
+
+        assert!(usage(band, 52).dfs);
+        assert!(!usage(band, 149).dfs);
+        assert!(!usage(band, 48).dfs);
+        // 52 to 144 are just as free but need radar detection
+        assert_eq!(band.recommended, [149, 153, 157]);
+    }
+
+    #[test]
+    fn derives_channel_from_frequency() {
+        let analysis = analyze_channels(&[
+            network(2437, 0, 100),
+            network(2484, 0, 100),
+            network(5180, 0, 100),
+        ]);
+
+        let band_2_4 = band(&analysis, WifiBand::Ghz2_4);
+        assert_eq!(usage(band_2_4, 6).network_count, 1);
+        assert_eq!(usage(band_2_4, 14).frequency, 2484);
+        assert_eq!(usage(band_2_4, 14).network_count, 1);
+
+        assert_eq!(usage(band(&analysis, WifiBand::Ghz5), 36).network_count, 1);
+    }
+
+    #[test]
+    fn analyzes_6_ghz_without_overlap() {
+        assert_eq!(WifiBand::from_frequency(5925), Some(WifiBand::Ghz5));
+        assert_eq!(WifiBand::from_frequency(5975), Some(WifiBand::Ghz6));
+        assert_eq!(WifiBand::Ghz6.channel_frequency(5), 5975);
+
+        // Channel 7 is no preferred scanning channel but still reported
+        let analysis = analyze_channels(&[network(5975, 0, 100), network(5985, 7, 100)]);
+        let band = band(&analysis, WifiBand::Ghz6);
+
+        assert_eq!(usage(band, 5).network_count, 1);
+        assert_eq!(usage(band, 5).overlapping_count, 0);
+        assert_eq!(usage(band, 7).network_count, 1);
+        assert!(!usage(band, 5).dfs);
+        assert_eq!(band.recommended, [21, 37, 53]);
+        assert!(analysis
+            .bands
+            .iter()
+            .filter(|b| b.band != WifiBand::Ghz6)
+            .all(|b| b.channels.iter().all(|c| c.network_count == 0)));
+    }
+}
@@ -1,6 +1,6 @@
 use std::process::{Command, Output};
 
-use super::networkmanager_error::WifiConnectionError;
+use super::{get_networks::split_terse_line, networkmanager_error::WifiConnectionError};
 
 /// Connects to a Wi-Fi network using nmcli
 ///
@@ -33,6 +33,56 @@ pub fn connect_to_network(bssid: &str, password: Option<&str>) -> Result<(), Wif
     }
 }
 
+/// Connects to a network that does not broadcast its SSID
+///
+/// # Arguments
+/// * `ssid` - name of the network
+/// * `password` - Optional password for secured networks
+pub fn connect_to_hidden_network(
+    ssid: &str,
+    password: Option<&str>,
+) -> Result<(), WifiConnectionError> {
+    let mut args = vec!["dev", "wifi", "connect", ssid, "hidden", "yes"];
+    if let Some(pass) = password {
+        args.extend(["password", pass]);
+    }
+
+    let output = Command::new("nmcli")
+        .args(&args)
+        .output()
+        .map_err(|_| WifiConnectionError::UnknownError)?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let error = String::from_utf8_lossy(&output.stderr).to_string();
+        handle_connection_error(ssid, output, password, error)
+    }
+}
+
+/// Moves a device to one access point of a network it has a profile for
+///
+/// NetworkManager activates the saved profile of the SSID on the given
+/// BSSID without changing the profile, so this reassociates within the
+/// same network or switches to another known one.
+///
+/// # Arguments
+/// * `bssid` - access point to connect to
+/// * `interface` - Wi-Fi device to use
+pub fn connect_to_access_point(bssid: &str, interface: &str) -> Result<(), WifiConnectionError> {
+    let output = Command::new("nmcli")
+        .args(["device", "wifi", "connect", bssid, "ifname", interface])
+        .output()
+        .map_err(|_| WifiConnectionError::UnknownError)?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let error = String::from_utf8_lossy(&output.stderr).to_string();
+        handle_connection_error(bssid, output, None, error)
+    }
+}
+
 /// Handles connection errors and maps them to appropriate error types
 fn handle_connection_error(
     bssid: &str,
@@ -51,3 +101,53 @@ fn handle_connection_error(
         Err(WifiConnectionError::UnknownError)
     }
 }
+
+/// Disconnects a Wi-Fi device using nmcli
+///
+/// NetworkManager does not autoconnect the device again until asked to.
+///
+/// # Arguments
+/// * `interface` - device to disconnect, `None` picks the connected Wi-Fi device
+///
+/// # Returns
+/// - `Ok(())` if the device was disconnected
+/// - `Err(WifiConnectionError::NotConnected)` if no Wi-Fi device is connected
+pub fn disconnect_network(interface: Option<&str>) -> Result<(), WifiConnectionError> {
+    let interface = match interface {
+        Some(interface) => interface.to_string(),
+        None => connected_wifi_device()?.ok_or(WifiConnectionError::NotConnected)?,
+    };
+
+    let output = Command::new("nmcli")
+        .args(["device", "disconnect", &interface])
+        .output()
+        .map_err(|_| WifiConnectionError::UnknownError)?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let error = String::from_utf8_lossy(&output.stderr);
+    if error.contains("not active") || error.contains("not connected") {
+        Err(WifiConnectionError::NotConnected)
+    } else if error.contains("not found") {
+        Err(WifiConnectionError::NoSuchNetwork)
+    } else {
+        eprintln!("Disconnect error ({}): {}", interface, error);
+        Err(WifiConnectionError::UnknownError)
+    }
+}
+
+/// Finds the Wi-Fi device NetworkManager reports as connected
+fn connected_wifi_device() -> Result<Option<String>, WifiConnectionError> {
+    let output = Command::new("nmcli")
+        .args(["-t", "-f", "DEVICE,TYPE,STATE", "device"])
+        .output()
+        .map_err(|_| WifiConnectionError::UnknownError)?;
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(split_terse_line)
+        .find(|fields| fields.len() >= 3 && fields[1] == "wifi" && fields[2] == "connected")
+        .map(|fields| fields[0].clone()))
+}
@@ -0,0 +1,342 @@
+use std::{process::Stdio, time::Duration};
+
+use reqwest::{header::LOCATION, redirect::Policy, StatusCode, Url};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+    time,
+};
+
+use super::{networkmanager_error::ConnectivityError, ping_monitor::get_default_gateways};
+
+/// Result of a connectivity check
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub enum ConnectivityState {
+    /// The probe endpoint answered exactly as expected
+    Full,
+    /// There is a route to the local network but the probe endpoint is unreachable
+    Limited,
+    /// The probe got redirected or answered with foreign content (captive portal)
+    Portal,
+    /// No default route at all
+    None,
+}
+
+/// Endpoint used to probe the internet connection
+///
+/// Fields left out of a config take their default value.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ConnectivityConfig {
+    /// Plain HTTP URL, portals can only intercept unencrypted requests
+    pub probe_url: String,
+    /// Status code the endpoint answers with when nothing intercepts the request
+    pub expected_status: u16,
+    /// Body the endpoint answers with, `None` to skip the body comparison
+    pub expected_body: Option<String>,
+    /// Milliseconds to wait for the endpoint
+    pub timeout_ms: u64,
+}
+
+impl Default for ConnectivityConfig {
+    fn default() -> Self {
+        ConnectivityConfig {
+            probe_url: "http://connectivitycheck.gstatic.com/generate_204".to_string(),
+            expected_status: 204,
+            expected_body: None,
+            timeout_ms: 5000,
+        }
+    }
+}
+
+/// Outcome of a connectivity check
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ConnectivityReport {
+    pub state: ConnectivityState,
+    /// Login page of the captive portal, if one was detected
+    pub portal_url: Option<String>,
+    /// Status code the probe endpoint answered with
+    pub http_status: Option<u16>,
+}
+
+/// Probes the configured endpoint and classifies the connection
+///
+/// # Arguments
+/// * `config` - the endpoint to probe and the answer it is expected to give
+///
+/// # Returns
+/// - `Ok(ConnectivityReport)` with the detected state
+/// - `Err(ConnectivityError)` if the HTTP client cannot be created
+pub async fn check_connectivity(
+    config: &ConnectivityConfig,
+) -> Result<ConnectivityReport, ConnectivityError> {
+    let client = reqwest::Client::builder()
+        .redirect(Policy::none())
+        .timeout(Duration::from_millis(config.timeout_ms))
+        .build()
+        .map_err(|e| {
+            eprintln!("Error creating HTTP client: {:?}", e);
+            ConnectivityError::ClientCreationFailure
+        })?;
+
+    let response = match client.get(&config.probe_url).send().await {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Connectivity probe failed: {:?}", e);
+            let state = if get_default_gateways().is_empty() {
+                ConnectivityState::None
+            } else {
+                ConnectivityState::Limited
+            };
+            return Ok(ConnectivityReport {
+                state,
+                portal_url: None,
+                http_status: None,
+            });
+        }
+    };
+
+    let status = response.status();
+    if status.is_redirection() {
+        // Relative redirects like "/login" are relative to the probe URL
+        let portal_url = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|l| l.to_str().ok())
+            .and_then(|l| Url::parse(&config.probe_url).ok()?.join(l).ok())
+            .map(|url| url.to_string());
+        return Ok(ConnectivityReport {
+            state: ConnectivityState::Portal,
+            portal_url: portal_url.or_else(|| Some(config.probe_url.clone())),
+            http_status: Some(status.as_u16()),
+        });
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let expected_status = StatusCode::from_u16(config.expected_status).ok();
+    let body_matches = match &config.expected_body {
+        Some(expected) => body.trim() == expected.trim(),
+        None => true,
+    };
+
+    if Some(status) == expected_status && body_matches {
+        Ok(ConnectivityReport {
+            state: ConnectivityState::Full,
+            portal_url: None,
+            http_status: Some(status.as_u16()),
+        })
+    } else {
+        Ok(ConnectivityReport {
+            state: ConnectivityState::Portal,
+            portal_url: Some(find_portal_url(&body).unwrap_or_else(|| config.probe_url.clone())),
+            http_status: Some(status.as_u16()),
+        })
+    }
+}
+
+/// Looks for the login page in a portal answer that was not a redirect
+///
+/// Many portals answer with a small page that redirects through a
+/// `<meta http-equiv="refresh" content="0; url=...">` tag instead.
+fn find_portal_url(body: &str) -> Option<String> {
+    // ASCII lowercasing keeps the byte offsets of the original body
+    let lower = body.to_ascii_lowercase();
+    let start = lower.find("url=")? + "url=".len();
+    let rest = &body[start..];
+    let end = rest.find(['"', '\'', '>', ' ']).unwrap_or(rest.len());
+    let url = rest[..end].trim_matches(['"', '\'']);
+
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Some(url.to_string())
+    } else {
+        None
+    }
+}
+
+/// Re-checks connectivity every time NetworkManager reports a link change
+///
+/// The callback is only called when the state or the portal URL changed.
+///
+/// # Returns
+/// - `Err(ConnectivityError)` if `nmcli monitor` cannot be started or exits
+pub async fn monitor_connectivity(
+    config: ConnectivityConfig,
+    callback: impl Fn(ConnectivityReport),
+) -> Result<(), ConnectivityError> {
+    let mut child = Command::new("nmcli")
+        .arg("monitor")
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|_| ConnectivityError::CommandExecutionFailure)?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or(ConnectivityError::CommandExecutionFailure)?;
+    let mut lines = BufReader::new(stdout).lines();
+
+    let mut last = check_connectivity(&config).await?;
+    callback(last.clone());
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        // wlp1s0: connected
+        // Connectivity is now 'portal'
+        if !line.contains("connect") && !line.contains("Connectivity") {
+            continue;
+        }
+
+        // Give DHCP and DNS a moment to settle after the link came up
+        time::sleep(Duration::from_secs(2)).await;
+
+        let report = check_connectivity(&config).await?;
+        if report.state != last.state || report.portal_url != last.portal_url {
+            callback(report.clone());
+            last = report;
+        }
+    }
+
+    Err(ConnectivityError::CommandExecutionFailure)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Answers every request with `response`, returning the probe config
+    async fn serve(response: &'static str) -> ConnectivityConfig {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let probe_url = format!("http://{}/generate_204", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                // The probe is a GET without a body, the headers fit one read
+                let mut buffer = [0u8; 4096];
+                let _ = stream.read(&mut buffer).await;
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        ConnectivityConfig {
+            probe_url,
+            timeout_ms: 2000,
+            ..ConnectivityConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn expected_answer_is_full_connectivity() {
+        let config = serve("HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n").await;
+        let report = check_connectivity(&config).await.unwrap();
+
+        assert_eq!(report.state, ConnectivityState::Full);
+        assert_eq!(report.http_status, Some(204));
+        assert_eq!(report.portal_url, None);
+    }
+
+    #[tokio::test]
+    async fn relative_redirect_is_resolved_against_the_probe() {
+        let config = serve(
+            "HTTP/1.1 302 Found\r\nLocation: /login?next=1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        let report = check_connectivity(&config).await.unwrap();
+
+        let expected = config.probe_url.replace("/generate_204", "/login?next=1");
+        assert_eq!(report.state, ConnectivityState::Portal);
+        assert_eq!(report.http_status, Some(302));
+        assert_eq!(report.portal_url, Some(expected));
+    }
+
+    #[tokio::test]
+    async fn absolute_redirect_is_kept() {
+        let config = serve(
+            "HTTP/1.1 307 Temporary Redirect\r\nLocation: https://portal.example/auth\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        let report = check_connectivity(&config).await.unwrap();
+
+        assert_eq!(report.state, ConnectivityState::Portal);
+        assert_eq!(
+            report.portal_url.as_deref(),
+            Some("https://portal.example/auth")
+        );
+    }
+
+    #[tokio::test]
+    async fn foreign_content_is_a_portal() {
+        let config = serve(
+            "HTTP/1.1 200 OK\r\nContent-Length: 61\r\nConnection: close\r\n\r\n<meta http-equiv='refresh' content='0; url=http://10.0.0.1/'>",
+        )
+        .await;
+        let report = check_connectivity(&config).await.unwrap();
+
+        assert_eq!(report.state, ConnectivityState::Portal);
+        assert_eq!(report.http_status, Some(200));
+        assert_eq!(report.portal_url.as_deref(), Some("http://10.0.0.1/"));
+    }
+
+    #[tokio::test]
+    async fn body_is_compared_when_configured() {
+        let mut config =
+            serve("HTTP/1.1 200 OK\r\nContent-Length: 8\r\nConnection: close\r\n\r\nSuccess\n")
+                .await;
+        config.expected_status = 200;
+        config.expected_body = Some("Success".to_string());
+        assert_eq!(
+            check_connectivity(&config).await.unwrap().state,
+            ConnectivityState::Full
+        );
+
+        // Without a login link the probe URL is opened to reach the portal
+        config.expected_body = Some("Microsoft NCSI".to_string());
+        let report = check_connectivity(&config).await.unwrap();
+        assert_eq!(report.state, ConnectivityState::Portal);
+        assert_eq!(report.portal_url, Some(config.probe_url.clone()));
+    }
+
+    #[test]
+    fn partial_configs_use_the_defaults() {
+        let config: ConnectivityConfig = serde_json::from_str(
+            r#"{ "probe_url": "http://nmcheck.gnome.org/check_network_status.txt" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.probe_url,
+            "http://nmcheck.gnome.org/check_network_status.txt"
+        );
+        assert_eq!(config.expected_status, 204);
+        assert_eq!(config.timeout_ms, 5000);
+    }
+
+    #[test]
+    fn portal_url_from_meta_refresh() {
+        let body = r#"<html><head><META HTTP-EQUIV="refresh" CONTENT="0; URL=https://login.example/portal?x=1"></head></html>"#;
+        assert_eq!(
+            find_portal_url(body).as_deref(),
+            Some("https://login.example/portal?x=1")
+        );
+    }
+
+    #[test]
+    fn portal_url_after_non_ascii_text() {
+        // 'İ' grows from two to three bytes when lowercased with Unicode rules
+        let body =
+            "<p>İİİİ Wi-Fi</p><meta http-equiv='refresh' content='0; url=http://portal.example/'>";
+        assert_eq!(
+            find_portal_url(body).as_deref(),
+            Some("http://portal.example/")
+        );
+    }
+
+    #[test]
+    fn no_portal_url_without_absolute_link() {
+        assert_eq!(find_portal_url("<p>Welcome, İstanbul</p>"), None);
+        assert_eq!(find_portal_url("<meta content='0; url=/login'>"), None);
+    }
+}
@@ -0,0 +1,560 @@
+use std::{
+    net::{IpAddr, SocketAddr, SocketAddrV6},
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use rand::{distributions::Alphanumeric, Rng};
+use trust_dns_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
+    proto::op::ResponseCode,
+    TokioAsyncResolver,
+};
+
+use super::{networkmanager_error::DnsError, ping_monitor::link_scope};
+
+/// Transport used by an encrypted DNS endpoint
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum EncryptedDnsProtocol {
+    /// DNS-over-TLS (RFC 7858)
+    Tls,
+    /// DNS-over-HTTPS (RFC 8484)
+    Https,
+}
+
+/// Encrypted DNS server whose reachability should be tested
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct EncryptedDnsEndpoint {
+    pub address: IpAddr,
+    /// Name the server certificate is validated against
+    pub tls_name: String,
+    pub protocol: EncryptedDnsProtocol,
+    /// Defaults to 853 for TLS and 443 for HTTPS
+    pub port: Option<u16>,
+}
+
+/// What the diagnostics should test
+///
+/// Fields left out of a config take their default value.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DnsDiagnosticsConfig {
+    /// Domain resolved against every resolver
+    pub test_domain: String,
+    /// Resolvers tested on top of the ones configured on the interfaces
+    pub extra_resolvers: Vec<SocketAddr>,
+    /// Encrypted DNS servers tested for reachability
+    pub encrypted_endpoints: Vec<EncryptedDnsEndpoint>,
+    /// Milliseconds to wait for a single answer
+    pub timeout_ms: u64,
+}
+
+impl Default for DnsDiagnosticsConfig {
+    fn default() -> Self {
+        let cloudflare: IpAddr = [1, 1, 1, 1].into();
+        let google: IpAddr = [8, 8, 8, 8].into();
+
+        DnsDiagnosticsConfig {
+            test_domain: "example.com".to_string(),
+            extra_resolvers: Vec::new(),
+            encrypted_endpoints: vec![
+                EncryptedDnsEndpoint {
+                    address: cloudflare,
+                    tls_name: "cloudflare-dns.com".to_string(),
+                    protocol: EncryptedDnsProtocol::Tls,
+                    port: None,
+                },
+                EncryptedDnsEndpoint {
+                    address: cloudflare,
+                    tls_name: "cloudflare-dns.com".to_string(),
+                    protocol: EncryptedDnsProtocol::Https,
+                    port: None,
+                },
+                EncryptedDnsEndpoint {
+                    address: google,
+                    tls_name: "dns.google".to_string(),
+                    protocol: EncryptedDnsProtocol::Tls,
+                    port: None,
+                },
+                EncryptedDnsEndpoint {
+                    address: google,
+                    tls_name: "dns.google".to_string(),
+                    protocol: EncryptedDnsProtocol::Https,
+                    port: None,
+                },
+            ],
+            timeout_ms: 2000,
+        }
+    }
+}
+
+/// DNS servers configured on a single interface
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct InterfaceResolvers {
+    pub interface: String,
+    pub resolvers: Vec<IpAddr>,
+}
+
+/// Result of one timed lookup
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LookupTiming {
+    /// Record type that was queried (`A` or `AAAA`)
+    pub record_type: String,
+    /// Time until the answer arrived in ms, `None` if the resolver did not answer
+    pub duration_ms: Option<f64>,
+    pub addresses: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Overall verdict for a resolver
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub enum ResolverHealth {
+    /// Answers queries and reports NXDOMAIN honestly
+    Healthy,
+    /// Does not answer at all
+    Failing,
+    /// Answers queries for domains that do not exist (NXDOMAIN rewriting)
+    Hijacking,
+}
+
+/// Diagnostics for a single plain DNS resolver
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ResolverReport {
+    /// Interface the resolver is configured on, `None` for extra resolvers
+    pub interface: Option<String>,
+    pub address: SocketAddr,
+    pub a: LookupTiming,
+    pub aaaa: LookupTiming,
+    /// Addresses returned for a domain that must not exist
+    pub nxdomain_answers: Vec<String>,
+    pub health: ResolverHealth,
+}
+
+/// Reachability of a single encrypted DNS endpoint
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EncryptedDnsReport {
+    pub endpoint: EncryptedDnsEndpoint,
+    pub reachable: bool,
+    pub duration_ms: Option<f64>,
+    pub error: Option<String>,
+}
+
+/// Complete result of a diagnostics run
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DnsDiagnosticsReport {
+    pub interfaces: Vec<InterfaceResolvers>,
+    pub resolvers: Vec<ResolverReport>,
+    pub encrypted: Vec<EncryptedDnsReport>,
+}
+
+/// Runs the DNS diagnostics
+///
+/// Every resolver configured on an interface (and every extra resolver from
+/// the config) is queried for A and AAAA records of the test domain and for a
+/// random domain that does not exist, to detect NXDOMAIN rewriting.
+/// Encrypted endpoints are only checked for reachability.
+///
+/// # Returns
+/// - `Ok(DnsDiagnosticsReport)` with the results of every resolver
+/// - `Err(DnsError)` if there is nothing to test
+pub async fn run_diagnostics(
+    config: &DnsDiagnosticsConfig,
+) -> Result<DnsDiagnosticsReport, DnsError> {
+    let interfaces = get_interface_resolvers().unwrap_or_else(|e| {
+        eprintln!("Error reading interface resolvers: {:?}", e);
+        Vec::new()
+    });
+
+    let mut targets: Vec<(Option<String>, SocketAddr)> = Vec::new();
+    for iface in &interfaces {
+        for resolver in &iface.resolvers {
+            targets.push((
+                Some(iface.interface.clone()),
+                resolver_address(*resolver, &iface.interface),
+            ));
+        }
+    }
+    for resolver in &config.extra_resolvers {
+        targets.push((None, *resolver));
+    }
+
+    if targets.is_empty() && config.encrypted_endpoints.is_empty() {
+        return Err(DnsError::NoResolvers);
+    }
+
+    let resolvers = futures::future::join_all(
+        targets
+            .into_iter()
+            .map(|(interface, address)| test_resolver(config, interface, address)),
+    )
+    .await;
+
+    let encrypted = futures::future::join_all(
+        config
+            .encrypted_endpoints
+            .iter()
+            .map(|endpoint| test_encrypted_endpoint(config, endpoint)),
+    )
+    .await;
+
+    Ok(DnsDiagnosticsReport {
+        interfaces,
+        resolvers,
+        encrypted,
+    })
+}
+
+/// Times the lookups against a single plain resolver and classifies it
+async fn test_resolver(
+    config: &DnsDiagnosticsConfig,
+    interface: Option<String>,
+    address: SocketAddr,
+) -> ResolverReport {
+    let resolver = build_resolver(
+        NameServerConfigGroup::from_ips_clear(&[address.ip()], address.port(), true),
+        config.timeout_ms,
+    );
+
+    let a = timed_lookup(&resolver, &config.test_domain, "A").await;
+    let aaaa = timed_lookup(&resolver, &config.test_domain, "AAAA").await;
+
+    let bogus_domain = format!("wiblue-{}.com.", random_label());
+    let nxdomain = timed_lookup(&resolver, &bogus_domain, "A").await;
+
+    let health = if a.duration_ms.is_none() && aaaa.duration_ms.is_none() {
+        ResolverHealth::Failing
+    } else if !nxdomain.addresses.is_empty() {
+        ResolverHealth::Hijacking
+    } else {
+        ResolverHealth::Healthy
+    };
+
+    ResolverReport {
+        interface,
+        address,
+        a,
+        aaaa,
+        nxdomain_answers: nxdomain.addresses,
+        health,
+    }
+}
+
+/// Checks whether an encrypted endpoint answers a query for the test domain
+async fn test_encrypted_endpoint(
+    config: &DnsDiagnosticsConfig,
+    endpoint: &EncryptedDnsEndpoint,
+) -> EncryptedDnsReport {
+    let name_servers = match endpoint.protocol {
+        EncryptedDnsProtocol::Tls => NameServerConfigGroup::from_ips_tls(
+            &[endpoint.address],
+            endpoint.port.unwrap_or(853),
+            endpoint.tls_name.clone(),
+            true,
+        ),
+        EncryptedDnsProtocol::Https => NameServerConfigGroup::from_ips_https(
+            &[endpoint.address],
+            endpoint.port.unwrap_or(443),
+            endpoint.tls_name.clone(),
+            true,
+        ),
+    };
+    let resolver = build_resolver(name_servers, config.timeout_ms);
+
+    let lookup = timed_lookup(&resolver, &config.test_domain, "A").await;
+
+    EncryptedDnsReport {
+        endpoint: endpoint.clone(),
+        reachable: lookup.duration_ms.is_some(),
+        duration_ms: lookup.duration_ms,
+        error: lookup.error,
+    }
+}
+
+/// Builds a resolver that only talks to the given servers, without caching
+fn build_resolver(name_servers: NameServerConfigGroup, timeout_ms: u64) -> TokioAsyncResolver {
+    let mut opts = ResolverOpts::default();
+    opts.timeout = Duration::from_millis(timeout_ms);
+    opts.attempts = 1;
+    opts.cache_size = 0;
+    opts.use_hosts_file = false;
+
+    TokioAsyncResolver::tokio(ResolverConfig::from_parts(None, vec![], name_servers), opts)
+}
+
+/// Resolves `domain` and measures how long the resolver took to answer
+///
+/// A negative answer (NXDOMAIN or no records) still counts as an answer.
+async fn timed_lookup(
+    resolver: &TokioAsyncResolver,
+    domain: &str,
+    record_type: &str,
+) -> LookupTiming {
+    let start = Instant::now();
+    let result: Result<Vec<String>, ResolveError> = match record_type {
+        "AAAA" => resolver
+            .ipv6_lookup(domain)
+            .await
+            .map(|lookup| lookup.iter().map(|r| r.to_string()).collect()),
+        _ => resolver
+            .ipv4_lookup(domain)
+            .await
+            .map(|lookup| lookup.iter().map(|r| r.to_string()).collect()),
+    };
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+
+    match result {
+        Ok(addresses) => LookupTiming {
+            record_type: record_type.to_string(),
+            duration_ms: Some(elapsed),
+            addresses,
+            error: None,
+        },
+        Err(e) => {
+            let answered = match e.kind() {
+                ResolveErrorKind::NoRecordsFound { response_code, .. } => matches!(
+                    *response_code,
+                    ResponseCode::NXDomain | ResponseCode::NoError
+                ),
+                _ => false,
+            };
+            LookupTiming {
+                record_type: record_type.to_string(),
+                duration_ms: if answered { Some(elapsed) } else { None },
+                addresses: Vec::new(),
+                error: Some(e.to_string()),
+            }
+        }
+    }
+}
+
+/// Random lowercase label used for domains that must not exist
+fn random_label() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(|c| (c as char).to_ascii_lowercase())
+        .collect()
+}
+
+/// Reads the DNS servers NetworkManager configured on every device
+///
+/// # Returns
+/// - `Ok(Vec<InterfaceResolvers>)` with one entry per device that has resolvers
+/// - `Err(DnsError)` if `nmcli` fails
+pub fn get_interface_resolvers() -> Result<Vec<InterfaceResolvers>, DnsError> {
+    let output = Command::new("nmcli")
+        .args([
+            "-t",
+            "-f",
+            "GENERAL.DEVICE,IP4.DNS,IP6.DNS",
+            "device",
+            "show",
+        ])
+        .output()
+        .map_err(|_| DnsError::CommandExecutionFailure)?;
+
+    if !output.status.success() {
+        eprintln!(
+            "nmcli command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(DnsError::CommandExecutionFailure);
+    }
+
+    Ok(parse_interface_resolvers(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Port 53 of a resolver, link-local IPv6 resolvers are scoped to the
+/// interface they are configured on
+fn resolver_address(resolver: IpAddr, interface: &str) -> SocketAddr {
+    match (resolver, link_scope(&resolver, interface)) {
+        (IpAddr::V6(address), Some(scope_id)) => SocketAddrV6::new(address, 53, 0, scope_id).into(),
+        _ => SocketAddr::new(resolver, 53),
+    }
+}
+
+/// Parses the resolvers out of `nmcli -t device show` output
+fn parse_interface_resolvers(output: &str) -> Vec<InterfaceResolvers> {
+    // GENERAL.DEVICE:wlp1s0
+    // IP4.DNS[1]:192.168.1.1
+    // IP6.DNS[1]:fe80::1
+    let mut interfaces: Vec<InterfaceResolvers> = Vec::new();
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+
+        if key == "GENERAL.DEVICE" {
+            interfaces.push(InterfaceResolvers {
+                interface: value.to_string(),
+                resolvers: Vec::new(),
+            });
+        } else if key.starts_with("IP4.DNS") || key.starts_with("IP6.DNS") {
+            if let (Some(iface), Ok(address)) =
+                (interfaces.last_mut(), value.replace("\\:", ":").parse())
+            {
+                iface.resolvers.push(address);
+            }
+        }
+    }
+
+    interfaces.retain(|iface| !iface.resolvers.is_empty());
+    interfaces
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::UdpSocket;
+    use trust_dns_resolver::proto::{
+        op::{Message, MessageType},
+        rr::{rdata::A, RData, Record, RecordType},
+        serialize::binary::{BinDecodable, BinEncodable},
+    };
+
+    use super::*;
+
+    const KNOWN_DOMAIN: &str = "wiblue.test.";
+
+    /// Answers A queries for `KNOWN_DOMAIN`, or for every domain if
+    /// `hijack` is set, and NXDOMAIN otherwise
+    async fn stub_resolver(hijack: bool) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 512];
+            while let Ok((len, peer)) = socket.recv_from(&mut buffer).await {
+                let Ok(request) = Message::from_bytes(&buffer[..len]) else {
+                    continue;
+                };
+                let mut response = Message::new();
+                response
+                    .set_id(request.id())
+                    .set_message_type(MessageType::Response)
+                    .set_op_code(request.op_code())
+                    .set_recursion_desired(true)
+                    .set_recursion_available(true)
+                    .add_queries(request.queries().to_vec());
+
+                let query = &request.queries()[0];
+                let known = query.name().to_ascii() == KNOWN_DOMAIN;
+                if (known || hijack) && query.query_type() == RecordType::A {
+                    response.add_answer(Record::from_rdata(
+                        query.name().clone(),
+                        60,
+                        RData::A(A::new(192, 0, 2, 7)),
+                    ));
+                } else if !known {
+                    response.set_response_code(ResponseCode::NXDomain);
+                }
+                let _ = socket.send_to(&response.to_bytes().unwrap(), peer).await;
+            }
+        });
+
+        address
+    }
+
+    fn config(resolver: SocketAddr) -> DnsDiagnosticsConfig {
+        DnsDiagnosticsConfig {
+            test_domain: KNOWN_DOMAIN.to_string(),
+            extra_resolvers: vec![resolver],
+            encrypted_endpoints: Vec::new(),
+            timeout_ms: 500,
+        }
+    }
+
+    #[tokio::test]
+    async fn honest_resolver_is_healthy() {
+        let address = stub_resolver(false).await;
+        let report = test_resolver(&config(address), None, address).await;
+
+        assert_eq!(report.health, ResolverHealth::Healthy);
+        assert_eq!(report.a.addresses, vec!["192.0.2.7"]);
+        // No AAAA records is still an answer
+        assert!(report.aaaa.duration_ms.is_some());
+        assert!(report.aaaa.addresses.is_empty());
+        assert!(report.nxdomain_answers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn resolver_answering_for_missing_domains_is_hijacking() {
+        let address = stub_resolver(true).await;
+        let report = test_resolver(&config(address), None, address).await;
+
+        assert_eq!(report.health, ResolverHealth::Hijacking);
+        assert_eq!(report.nxdomain_answers, vec!["192.0.2.7"]);
+    }
+
+    #[tokio::test]
+    async fn silent_resolver_is_failing() {
+        // Bound but never answering
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+        let report = test_resolver(&config(address), None, address).await;
+
+        assert_eq!(report.health, ResolverHealth::Failing);
+        assert!(report.a.duration_ms.is_none());
+        assert!(report.a.error.is_some());
+    }
+
+    #[test]
+    fn resolvers_per_device() {
+        let interfaces = parse_interface_resolvers(
+            "GENERAL.DEVICE:wlp1s0\n\
+             IP4.DNS[1]:192.168.1.1\n\
+             IP4.DNS[2]:9.9.9.9\n\
+             IP6.DNS[1]:fe80\\:\\:1\n\
+             GENERAL.DEVICE:lo\n\
+             GENERAL.DEVICE:enp0s31f6\n\
+             IP4.DNS[1]:10.0.0.1\n",
+        );
+
+        assert_eq!(interfaces.len(), 2);
+        assert_eq!(interfaces[0].interface, "wlp1s0");
+        assert_eq!(
+            interfaces[0].resolvers,
+            vec![
+                "192.168.1.1".parse::<IpAddr>().unwrap(),
+                "9.9.9.9".parse().unwrap(),
+                "fe80::1".parse().unwrap(),
+            ]
+        );
+        assert_eq!(interfaces[1].interface, "enp0s31f6");
+    }
+
+    #[test]
+    fn link_local_resolvers_are_scoped() {
+        let link_local = resolver_address("fe80::1".parse().unwrap(), "3");
+        assert_eq!(link_local.to_string(), "[fe80::1%3]:53");
+
+        let global = resolver_address("2001:db8::53".parse().unwrap(), "3");
+        assert_eq!(global.to_string(), "[2001:db8::53]:53");
+        let v4 = resolver_address("192.168.1.1".parse().unwrap(), "3");
+        assert_eq!(v4.to_string(), "192.168.1.1:53");
+    }
+
+    #[test]
+    fn partial_configs_use_the_defaults() {
+        let config: DnsDiagnosticsConfig =
+            serde_json::from_str(r#"{ "test_domain": "example.org" }"#).unwrap();
+
+        assert_eq!(config.test_domain, "example.org");
+        assert_eq!(
+            config.timeout_ms,
+            DnsDiagnosticsConfig::default().timeout_ms
+        );
+        assert_eq!(config.encrypted_endpoints.len(), 4);
+    }
+
+    #[test]
+    fn random_labels_are_lowercase() {
+        let label = random_label();
+        assert_eq!(label.len(), 16);
+        assert!(label
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()));
+    }
+}
@@ -1,4 +1,8 @@
-use std::process::Command;
+use std::{fs, path::Path, process::Command};
+
+use serde::Serialize;
+
+const SYS_CLASS_NET: &str = "/sys/class/net";
 
 /// Retrieves a list of all available network interfaces on the system.
 ///
@@ -48,3 +52,86 @@ pub fn get_interfaces() -> Result<Vec<String>, ()> {
 
     Ok(interfaces)
 }
+
+/// Link type of a network interface
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum InterfaceKind {
+    Ethernet,
+    Wireless,
+    /// Bluetooth PAN link (`bnep`), e.g. tethering through a phone
+    Bluetooth,
+    Loopback,
+    /// Bridges, tunnels, VPNs and other software interfaces
+    Virtual,
+    Other,
+}
+
+/// A network interface together with its link type
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub kind: InterfaceKind,
+    /// Whether the interface is up
+    pub up: bool,
+    /// Remote device of a Bluetooth PAN link, filled in by the caller
+    pub bluetooth_device: Option<String>,
+}
+
+/// Determines the link type of an interface from `/sys/class/net`
+///
+/// # Arguments
+/// * `name` - interface name (e.g. `wlp1s0`, `bnep0`)
+pub fn interface_kind(name: &str) -> InterfaceKind {
+    let dir = Path::new(SYS_CLASS_NET).join(name);
+    let uevent = fs::read_to_string(dir.join("uevent")).unwrap_or_default();
+    let devtype = uevent
+        .lines()
+        .find_map(|line| line.strip_prefix("DEVTYPE="))
+        .unwrap_or_default();
+
+    if name == "lo" {
+        InterfaceKind::Loopback
+    } else if devtype == "bluetooth" || name.starts_with("bnep") {
+        InterfaceKind::Bluetooth
+    } else if devtype == "wlan" || dir.join("wireless").exists() || dir.join("phy80211").exists() {
+        InterfaceKind::Wireless
+    } else if !devtype.is_empty() || !dir.join("device").exists() {
+        InterfaceKind::Virtual
+    } else if fs::read_to_string(dir.join("type")).is_ok_and(|t| t.trim() == "1") {
+        InterfaceKind::Ethernet
+    } else {
+        InterfaceKind::Other
+    }
+}
+
+/// Lists all network interfaces with their link type
+///
+/// Unlike [`get_interfaces`] this reads `/sys/class/net` directly, so
+/// interfaces created on demand (like the `bnep` interface of a Bluetooth
+/// PAN connection) show up as soon as the kernel registers them.
+///
+/// # Returns
+/// - `Ok(Vec<NetworkInterface>)` sorted by name
+/// - `Err(())` if `/sys/class/net` cannot be read
+pub fn get_interface_details() -> Result<Vec<NetworkInterface>, ()> {
+    let entries = fs::read_dir(SYS_CLASS_NET).map_err(|e| {
+        eprintln!("Error reading {}: {:?}", SYS_CLASS_NET, e);
+    })?;
+
+    let mut interfaces: Vec<NetworkInterface> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let operstate = fs::read_to_string(entry.path().join("operstate")).unwrap_or_default();
+            NetworkInterface {
+                kind: interface_kind(&name),
+                up: matches!(operstate.trim(), "up" | "unknown"),
+                name,
+                bluetooth_device: None,
+            }
+        })
+        .collect();
+
+    interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(interfaces)
+}
@@ -15,7 +15,10 @@ pub fn get_networks() -> Result<Vec<WifiNetwork>, WifiManagerError> {
             "wifi",
         ])
         .output()
-        .expect("Failed to execute nmcli, recommended to download it");
+        .map_err(|e| {
+            eprintln!("Failed to execute nmcli, recommended to download it: {}", e);
+            WifiManagerError::CommandExecutionFailure
+        })?;
 
     if !output_without_ssid.status.success() {
         eprintln!(
@@ -28,7 +31,6 @@ pub fn get_networks() -> Result<Vec<WifiNetwork>, WifiManagerError> {
     let stdout_without_ssid = String::from_utf8_lossy(&output_without_ssid.stdout);
 
     for line in stdout_without_ssid.lines().skip(1) {
-        println!("{}", line);
         let fields: Vec<&str> = line.split_whitespace().collect();
 
         if fields.len() < 7 {
@@ -39,7 +41,8 @@ pub fn get_networks() -> Result<Vec<WifiNetwork>, WifiManagerError> {
         let bssid = fields[1].to_string();
         let signal_strength = fields[2].parse::<i32>().unwrap_or_default();
         let frequency = fields[3].parse::<u32>().unwrap_or_default();
-        let channel = fields[4].parse::<u8>().unwrap_or_default();
+        // FREQ is printed with its unit ("2437 MHz"), which shifts CHAN by one field
+        let channel = fields[5].parse::<u8>().unwrap_or_default();
         let security = match fields[6] {
             "OPEN" => WifiSecurity::Open,
             "WEP" => WifiSecurity::Wep,
@@ -94,7 +97,10 @@ pub fn get_networks() -> Result<Vec<WifiNetwork>, WifiManagerError> {
         let bssid_search_output = Command::new("nmcli")
             .args(["device", "wifi", "list", "bssid", &bssid])
             .output()
-            .expect("Failed to execute nmcli");
+            .map_err(|e| {
+                eprintln!("Failed to execute nmcli: {}", e);
+                WifiManagerError::CommandExecutionFailure
+            })?;
 
         if !bssid_search_output.status.success() {
             eprintln!(
@@ -146,6 +152,144 @@ pub fn get_networks() -> Result<Vec<WifiNetwork>, WifiManagerError> {
 
     Ok(wifi_networks)
 }
+/// SSID and BSSID of the network a device is currently connected to
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ActiveNetwork {
+    pub ssid: String,
+    pub bssid: String,
+    /// Signal quality in percent
+    #[serde(default)]
+    pub signal: u8,
+    /// Wi-Fi device connected to the network
+    #[serde(default)]
+    pub device: String,
+}
+
+/// Returns the Wi-Fi network that is currently in use, if any
+///
+/// Much cheaper than [`get_networks`], which queries every BSSID separately.
+pub fn get_active_network() -> Option<ActiveNetwork> {
+    let output = Command::new("nmcli")
+        .args([
+            "-t",
+            "-f",
+            "ACTIVE,SSID,BSSID,SIGNAL,DEVICE",
+            "device",
+            "wifi",
+            "list",
+            "--rescan",
+            "no",
+        ])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(split_terse_line)
+        .find(|fields| fields.len() >= 5 && fields[0] == "yes")
+        .map(|fields| ActiveNetwork {
+            ssid: fields[1].clone(),
+            bssid: fields[2].clone(),
+            signal: fields[3].parse().unwrap_or_default(),
+            device: fields[4].clone(),
+        })
+}
+
+/// An access point as NetworkManager last saw it
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct VisibleAccessPoint {
+    pub ssid: String,
+    pub bssid: String,
+    /// Signal quality in percent
+    pub signal: u8,
+    /// Frequency in MHz
+    pub frequency: u32,
+    /// Wi-Fi device that sees the access point
+    pub device: String,
+    /// Whether the device is connected to this access point
+    pub active: bool,
+}
+
+/// Lists the access points every Wi-Fi device sees, in one nmcli call
+///
+/// Unlike [`get_networks`] this skips the per-BSSID queries, so it is cheap
+/// enough to poll.
+///
+/// # Arguments
+/// * `rescan` - let NetworkManager scan first if its last scan is older than
+///   30 seconds, otherwise the results of the last scan are returned
+pub fn get_access_points(rescan: bool) -> Result<Vec<VisibleAccessPoint>, WifiManagerError> {
+    let output = Command::new("nmcli")
+        .args([
+            "-t",
+            "-f",
+            "ACTIVE,SSID,BSSID,SIGNAL,FREQ,DEVICE",
+            "device",
+            "wifi",
+            "list",
+            "--rescan",
+            if rescan { "auto" } else { "no" },
+        ])
+        .output()
+        .map_err(|_| WifiManagerError::CommandExecutionFailure)?;
+
+    if !output.status.success() {
+        eprintln!(
+            "nmcli command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(WifiManagerError::CommandExecutionFailure);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(split_terse_line)
+        .filter(|fields| fields.len() >= 6)
+        .map(|fields| VisibleAccessPoint {
+            ssid: fields[1].clone(),
+            bssid: fields[2].clone(),
+            signal: fields[3].parse().unwrap_or_default(),
+            // "5180 MHz"
+            frequency: fields[4]
+                .split_whitespace()
+                .next()
+                .and_then(|f| f.parse().ok())
+                .unwrap_or_default(),
+            device: fields[5].clone(),
+            active: fields[0] == "yes",
+        })
+        .collect())
+}
+
+/// Splits a line of `nmcli -t` output into its fields
+///
+/// Terse mode separates fields with `:` and escapes literal colons and
+/// backslashes inside values (e.g. in BSSIDs) with a backslash.
+pub fn split_terse_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            ':' => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+
+    fields
+}
+
 // Scans available Wi-Fi networks using `nmcli` and returns their details.
 
 // This function executes two `nmcli` commands:
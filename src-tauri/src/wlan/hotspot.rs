@@ -0,0 +1,774 @@
+use std::{collections::BTreeMap, process::Command};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    channel_analyzer::WifiBand, get_networks::split_terse_line, networkmanager_error::HotspotError,
+    profiles::get_profiles,
+};
+
+/// Name of the NetworkManager profile the hotspot runs on
+pub const HOTSPOT_PROFILE: &str = "wiblue-hotspot";
+
+/// Security of the hotspot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HotspotSecurity {
+    Open,
+    /// WPA2-Personal (PSK)
+    #[default]
+    Wpa2,
+    /// WPA3-Personal (SAE), older clients may not be able to join
+    Wpa3,
+}
+
+/// Settings of a hotspot to start
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HotspotConfig {
+    pub ssid: String,
+    /// Passphrase of 8 to 63 ASCII characters or, for WPA2 only, a PSK of 64
+    /// hex digits; not used for open hotspots
+    pub password: Option<String>,
+    #[serde(default)]
+    pub security: HotspotSecurity,
+    /// 2.4 or 5 GHz, derived from `channel` or left to NetworkManager if not set
+    pub band: Option<WifiBand>,
+    pub channel: Option<u8>,
+    /// Wi-Fi device to use, the first one if not set
+    pub interface: Option<String>,
+}
+
+/// A device connected to the hotspot
+///
+/// Traffic and signal come from the station list of the driver and are
+/// missing when `iw` is not installed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HotspotClient {
+    pub mac: String,
+    /// IPv4 address handed out by DHCP or seen in the neighbour table
+    pub ip: Option<String>,
+    /// Host name the client sent with its DHCP request
+    pub hostname: Option<String>,
+    /// Bytes received from the client
+    pub received_bytes: Option<u64>,
+    /// Bytes sent to the client
+    pub sent_bytes: Option<u64>,
+    /// Signal strength in dBm
+    pub signal: Option<i32>,
+    pub connected_secs: Option<u64>,
+}
+
+/// State of the hotspot profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HotspotStatus {
+    pub active: bool,
+    pub interface: Option<String>,
+    pub ssid: String,
+    pub security: HotspotSecurity,
+    pub band: Option<WifiBand>,
+    pub channel: Option<u8>,
+    /// Address of the hotspot on the shared network, e.g. `10.42.0.1/24`
+    pub address: Option<String>,
+    pub clients: Vec<HotspotClient>,
+}
+
+/// Whether a Wi-Fi adapter can run a hotspot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApSupport {
+    pub interface: String,
+    /// Wireless PHY of the interface, e.g. `phy0`
+    pub phy: String,
+    /// Whether the driver supports AP mode at all
+    pub ap_mode: bool,
+    /// Whether the adapter can stay connected to a network while it runs a
+    /// hotspot; otherwise starting the hotspot drops the connection
+    pub station_concurrency: bool,
+    /// Whether the hotspot has to use the channel of the connected network
+    pub single_channel: bool,
+    /// Interface combinations as reported by `iw`
+    pub combinations: Vec<String>,
+}
+
+/// Turns a Wi-Fi device into an access point sharing its IPv4 connection
+///
+/// Replaces the previous hotspot profile, so a running hotspot is restarted
+/// with the new settings. NetworkManager runs DHCP and NAT for the clients
+/// (`ipv4.method shared`).
+///
+/// # Returns
+/// - `Ok(HotspotStatus)` once the hotspot is up
+/// - `Err(HotspotError::InvalidConfig)` for a bad password, band or channel
+/// - `Err(HotspotError::NoWifiDevice)` if there is no Wi-Fi device to use
+/// - `Err(HotspotError::ApModeUnsupported)` if the driver cannot run an AP
+/// - `Err(HotspotError::CommandExecutionFailure)` if nmcli fails
+pub fn start_hotspot(config: &HotspotConfig) -> Result<HotspotStatus, HotspotError> {
+    let band = validate(config)?;
+    let interface = match &config.interface {
+        Some(interface) => interface.clone(),
+        None => wifi_device().ok_or(HotspotError::NoWifiDevice)?,
+    };
+    // Only refuse when iw positively says so, it is not always installed
+    if let Ok(support) = get_ap_support(Some(&interface)) {
+        if !support.ap_mode {
+            return Err(HotspotError::ApModeUnsupported);
+        }
+    }
+
+    if get_profiles(true).is_ok_and(|profiles| profiles.iter().any(|p| p.name == HOTSPOT_PROFILE)) {
+        run_nmcli(&["connection", "delete", "id", HOTSPOT_PROFILE])?;
+    }
+
+    let channel = config.channel.map(|c| c.to_string());
+    let password = config.password.as_deref().unwrap_or_default();
+    let mut args = vec![
+        "connection",
+        "add",
+        "type",
+        "wifi",
+        "ifname",
+        &interface,
+        "con-name",
+        HOTSPOT_PROFILE,
+        "autoconnect",
+        "no",
+        "ssid",
+        &config.ssid,
+        "802-11-wireless.mode",
+        "ap",
+        "ipv4.method",
+        "shared",
+        "ipv6.method",
+        "ignore",
+    ];
+    if let Some(band) = band {
+        args.extend(["802-11-wireless.band", nm_band(band)]);
+    }
+    if let Some(channel) = &channel {
+        args.extend(["802-11-wireless.channel", channel]);
+    }
+    match config.security {
+        HotspotSecurity::Open => {}
+        HotspotSecurity::Wpa2 => args.extend([
+            "wifi-sec.key-mgmt",
+            "wpa-psk",
+            "wifi-sec.proto",
+            "rsn",
+            "wifi-sec.psk",
+            password,
+        ]),
+        // SAE requires management frame protection
+        HotspotSecurity::Wpa3 => args.extend([
+            "wifi-sec.key-mgmt",
+            "sae",
+            "wifi-sec.pmf",
+            "required",
+            "wifi-sec.psk",
+            password,
+        ]),
+    }
+
+    run_nmcli(&args)?;
+    run_nmcli(&["connection", "up", "id", HOTSPOT_PROFILE])?;
+
+    get_hotspot_status()?.ok_or(HotspotError::CommandExecutionFailure)
+}
+
+/// Stops the hotspot, the profile is kept for [`get_hotspot_status`]
+///
+/// # Returns
+/// - `Ok(())` if the hotspot was stopped
+/// - `Err(HotspotError::NotRunning)` if it was not active
+pub fn stop_hotspot() -> Result<(), HotspotError> {
+    let active = get_profiles(true)
+        .map_err(|_| HotspotError::CommandExecutionFailure)?
+        .iter()
+        .any(|p| p.name == HOTSPOT_PROFILE && p.active);
+    if !active {
+        return Err(HotspotError::NotRunning);
+    }
+
+    run_nmcli(&["connection", "down", "id", HOTSPOT_PROFILE])
+}
+
+/// Reads the hotspot profile and, while it is active, its clients
+///
+/// # Returns
+/// - `Ok(Some(HotspotStatus))` if a hotspot was set up before
+/// - `Ok(None)` if there is no hotspot profile
+pub fn get_hotspot_status() -> Result<Option<HotspotStatus>, HotspotError> {
+    let profiles = get_profiles(true).map_err(|_| HotspotError::CommandExecutionFailure)?;
+    let Some(profile) = profiles.into_iter().find(|p| p.name == HOTSPOT_PROFILE) else {
+        return Ok(None);
+    };
+
+    let output = Command::new("nmcli")
+        .args([
+            "-t",
+            "-f",
+            "802-11-wireless.ssid,802-11-wireless.band,802-11-wireless.channel,802-11-wireless-security.key-mgmt",
+            "connection",
+            "show",
+            "uuid",
+            &profile.uuid,
+        ])
+        .output()
+        .map_err(|_| HotspotError::CommandExecutionFailure)?;
+    if !output.status.success() {
+        return Err(HotspotError::CommandExecutionFailure);
+    }
+
+    let settings = parse_settings(&String::from_utf8_lossy(&output.stdout));
+    let setting = |name: &str| settings.get(name).cloned().unwrap_or_default();
+
+    let interface = profile.device.clone();
+    let (address, clients) = match (&interface, profile.active) {
+        (Some(interface), true) => (interface_address(interface), get_clients(interface)),
+        _ => (None, Vec::new()),
+    };
+
+    Ok(Some(HotspotStatus {
+        active: profile.active,
+        interface,
+        ssid: setting("802-11-wireless.ssid"),
+        security: match setting("802-11-wireless-security.key-mgmt").as_str() {
+            "sae" => HotspotSecurity::Wpa3,
+            "" => HotspotSecurity::Open,
+            _ => HotspotSecurity::Wpa2,
+        },
+        band: match setting("802-11-wireless.band").as_str() {
+            "a" => Some(WifiBand::Ghz5),
+            "bg" => Some(WifiBand::Ghz2_4),
+            _ => None,
+        },
+        channel: setting("802-11-wireless.channel")
+            .parse()
+            .ok()
+            .filter(|c| *c != 0),
+        address,
+        clients,
+    }))
+}
+
+/// Parses the settings printed by `nmcli -t -f <settings> connection show`
+///
+/// e.g. `802-11-wireless.ssid:Cafe\:Bar`, colons in values are escaped
+fn parse_settings(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .map(split_terse_line)
+        .filter_map(|mut fields| match fields.len() {
+            2 => {
+                let value = fields.pop()?;
+                Some((fields.pop()?, value))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Lists the clients of a hotspot running on a device
+///
+/// Combines the station list of the driver (traffic and signal), the
+/// neighbour table and the DHCP leases of NetworkManager's dnsmasq (address
+/// and host name). Without `iw` every reachable neighbour counts as client.
+pub fn get_clients(interface: &str) -> Vec<HotspotClient> {
+    let mut clients: BTreeMap<String, HotspotClient> = BTreeMap::new();
+
+    let stations = command_output("iw", &["dev", interface, "station", "dump"]);
+    if let Some(stations) = &stations {
+        let mut current: Option<&mut HotspotClient> = None;
+        for line in stations.lines() {
+            // Station aa:bb:cc:dd:ee:ff (on wlan0)
+            if let Some(rest) = line.strip_prefix("Station ") {
+                let mac = rest.split_whitespace().next().unwrap_or_default();
+                let mac = mac.to_lowercase();
+                current = Some(clients.entry(mac.clone()).or_insert(HotspotClient {
+                    mac,
+                    ..HotspotClient::default()
+                }));
+                continue;
+            }
+            let (Some(client), Some((name, value))) =
+                (current.as_deref_mut(), line.split_once(':'))
+            else {
+                continue;
+            };
+            // "signal:  	-44 [-44, -46] dBm", "connected time:	120 seconds"
+            let number = value.split_whitespace().next().unwrap_or_default();
+            match name.trim() {
+                "rx bytes" => client.received_bytes = number.parse().ok(),
+                "tx bytes" => client.sent_bytes = number.parse().ok(),
+                "signal" => client.signal = number.parse().ok(),
+                "connected time" => client.connected_secs = number.parse().ok(),
+                _ => {}
+            }
+        }
+    }
+
+    // 10.42.0.23 lladdr aa:bb:cc:dd:ee:ff REACHABLE
+    let neighbours = command_output("ip", &["-4", "neigh", "show", "dev", interface]);
+    for line in neighbours.unwrap_or_default().lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let Some(mac) = fields
+            .iter()
+            .position(|f| *f == "lladdr")
+            .and_then(|pos| fields.get(pos + 1))
+        else {
+            continue;
+        };
+        if matches!(fields.last(), Some(&"FAILED") | Some(&"INCOMPLETE")) {
+            continue;
+        }
+        let mac = mac.to_lowercase();
+        let client = match (&stations, clients.get_mut(&mac)) {
+            (_, Some(client)) => client,
+            // The station list is complete, neighbours not in it have left
+            (Some(_), None) => continue,
+            (None, None) => clients.entry(mac.clone()).or_insert(HotspotClient {
+                mac,
+                ..HotspotClient::default()
+            }),
+        };
+        client.ip = fields.first().map(|ip| ip.to_string());
+    }
+
+    // 1700000000 aa:bb:cc:dd:ee:ff 10.42.0.23 phone 01:aa:bb:cc:dd:ee:ff
+    let leases = std::fs::read_to_string(format!(
+        "/var/lib/NetworkManager/dnsmasq-{}.leases",
+        interface
+    ));
+    for line in leases.unwrap_or_default().lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 {
+            continue;
+        }
+        if let Some(client) = clients.get_mut(&fields[1].to_lowercase()) {
+            client.ip.get_or_insert_with(|| fields[2].to_string());
+            client.hostname = Some(fields[3].to_string()).filter(|h| h != "*");
+        }
+    }
+
+    clients.into_values().collect()
+}
+
+/// Reads from `iw` whether an adapter supports AP mode and whether it can
+/// run an AP next to a station connection
+///
+/// # Arguments
+/// * `interface` - Wi-Fi device, the first one if not set
+///
+/// # Returns
+/// - `Ok(ApSupport)` with the capabilities of the adapter
+/// - `Err(HotspotError::NoWifiDevice)` if the device is not a Wi-Fi device
+/// - `Err(HotspotError::CommandExecutionFailure)` if `iw` is missing
+pub fn get_ap_support(interface: Option<&str>) -> Result<ApSupport, HotspotError> {
+    let interface = match interface {
+        Some(interface) => interface.to_string(),
+        None => wifi_device().ok_or(HotspotError::NoWifiDevice)?,
+    };
+
+    // "	wiphy 0"
+    let info = command_output("iw", &["dev", &interface, "info"])
+        .ok_or(HotspotError::CommandExecutionFailure)?;
+    let phy = info
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("wiphy "))
+        .map(|index| format!("phy{}", index.trim()))
+        .ok_or(HotspotError::NoWifiDevice)?;
+    let phy_info = command_output("iw", &["phy", &phy, "info"])
+        .ok_or(HotspotError::CommandExecutionFailure)?;
+
+    let modes = section(&phy_info, "Supported interface modes:");
+    let combinations: Vec<String> = section(&phy_info, "valid interface combinations:");
+    let concurrent: Vec<(bool, Option<u32>)> = combinations
+        .iter()
+        .map(|c| parse_combination(c))
+        .filter(|(allows_both, _)| *allows_both)
+        .collect();
+
+    Ok(ApSupport {
+        interface,
+        phy,
+        ap_mode: modes.iter().any(|mode| mode == "AP"),
+        station_concurrency: !concurrent.is_empty(),
+        single_channel: !concurrent.is_empty()
+            && concurrent.iter().all(|(_, channels)| *channels == Some(1)),
+        combinations,
+    })
+}
+
+/// Checks the password, band and channel, returns the band to configure
+fn validate(config: &HotspotConfig) -> Result<Option<WifiBand>, HotspotError> {
+    let invalid = |message: &str| Err(HotspotError::InvalidConfig(message.to_string()));
+
+    if config.ssid.is_empty() || config.ssid.len() > 32 {
+        return invalid("The SSID must have 1 to 32 bytes");
+    }
+    let password = config.password.as_deref();
+    match config.security {
+        HotspotSecurity::Open => {}
+        HotspotSecurity::Wpa2 if !password.is_some_and(|p| valid_password(p, true)) => {
+            return invalid("The password must have 8 to 63 ASCII characters or 64 hex digits");
+        }
+        HotspotSecurity::Wpa3 if !password.is_some_and(|p| valid_password(p, false)) => {
+            return invalid("The password must have 8 to 63 ASCII characters");
+        }
+        _ => {}
+    }
+
+    let band = match (config.band, config.channel) {
+        (band, None) => band,
+        (None, Some(channel)) if channel <= 14 => Some(WifiBand::Ghz2_4),
+        (None, Some(_)) => Some(WifiBand::Ghz5),
+        (Some(band), Some(_)) => Some(band),
+    };
+    match (band, config.channel) {
+        (Some(WifiBand::Ghz6), _) => invalid("NetworkManager cannot run a hotspot on 6 GHz"),
+        (Some(WifiBand::Ghz2_4), Some(channel)) if !(1..=14).contains(&channel) => {
+            invalid("2.4 GHz channels are 1 to 14")
+        }
+        (Some(WifiBand::Ghz5), Some(channel)) if !(32..=177).contains(&channel) => {
+            invalid("5 GHz channels are 32 to 177")
+        }
+        _ => Ok(band),
+    }
+}
+
+/// Whether a password is a WPA passphrase (8 to 63 printable ASCII
+/// characters) or, if `allow_psk`, a raw PSK (64 hex digits)
+///
+/// SAE (WPA3) derives its keys from the passphrase itself, so there is no raw
+/// PSK to give.
+fn valid_password(password: &str, allow_psk: bool) -> bool {
+    match password.len() {
+        8..=63 => password.bytes().all(|b| (b' '..=b'~').contains(&b)),
+        64 => allow_psk && password.bytes().all(|b| b.is_ascii_hexdigit()),
+        _ => false,
+    }
+}
+
+/// Value of `802-11-wireless.band` for a band
+fn nm_band(band: WifiBand) -> &'static str {
+    match band {
+        WifiBand::Ghz2_4 => "bg",
+        WifiBand::Ghz5 | WifiBand::Ghz6 => "a",
+    }
+}
+
+/// Runs nmcli, logging its error output on failure
+fn run_nmcli(args: &[&str]) -> Result<(), HotspotError> {
+    let output = Command::new("nmcli")
+        .args(args)
+        .output()
+        .map_err(|_| HotspotError::CommandExecutionFailure)?;
+
+    if !output.status.success() {
+        eprintln!(
+            "nmcli command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(HotspotError::CommandExecutionFailure);
+    }
+
+    Ok(())
+}
+
+/// Standard output of a command, `None` if it is missing or fails
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+
+    match output.status.success() {
+        true => Some(String::from_utf8_lossy(&output.stdout).to_string()),
+        false => None,
+    }
+}
+
+/// First Wi-Fi device NetworkManager manages
+fn wifi_device() -> Option<String> {
+    let output = command_output("nmcli", &["-t", "-f", "DEVICE,TYPE,STATE", "device"])?;
+
+    output
+        .lines()
+        .map(split_terse_line)
+        .find(|fields| fields.len() >= 3 && fields[1] == "wifi" && fields[2] != "unavailable")
+        .map(|fields| fields[0].clone())
+}
+
+/// IPv4 address of a device with its prefix length
+fn interface_address(interface: &str) -> Option<String> {
+    // 3: wlan0    inet 10.42.0.1/24 brd 10.42.0.255 scope global wlan0
+    let output = command_output("ip", &["-4", "-o", "addr", "show", "dev", interface])?;
+    let fields: Vec<&str> = output.split_whitespace().collect();
+    let pos = fields.iter().position(|f| *f == "inet")?;
+
+    fields.get(pos + 1).map(|a| a.to_string())
+}
+
+/// Lines of an indented `iw` list following its header, without the bullets
+///
+/// Entries spanning several lines, like interface combinations, are joined;
+/// blank lines between entries are skipped.
+fn section(output: &str, header: &str) -> Vec<String> {
+    let mut entries: Vec<String> = Vec::new();
+    let mut lines = output.lines().skip_while(|line| line.trim() != header);
+    lines.next();
+
+    for line in lines {
+        let line = line.trim();
+        if let Some(entry) = line.strip_prefix("* ") {
+            entries.push(entry.trim().to_string());
+        } else if line.starts_with("total") || line.starts_with("#{") {
+            match entries.last_mut() {
+                Some(entry) => {
+                    entry.push(' ');
+                    entry.push_str(line);
+                }
+                None => break,
+            }
+        } else if !line.is_empty() {
+            break;
+        }
+    }
+
+    entries
+}
+
+/// Parses `#{ managed } <= 1, #{ AP, P2P-client } <= 1, total <= 2, #channels <= 1`
+///
+/// # Returns
+/// Whether a station and an AP can exist at once, and the channel limit
+fn parse_combination(combination: &str) -> (bool, Option<u32>) {
+    let limit = |text: &str| -> Option<u32> {
+        text.split("<=")
+            .nth(1)?
+            .trim()
+            .trim_end_matches(',')
+            .split(|c: char| !c.is_ascii_digit())
+            .next()?
+            .parse()
+            .ok()
+    };
+
+    let mut station = 0;
+    let mut ap = 0;
+    let mut shared = 0;
+    let mut rest = combination;
+    while let Some(start) = rest.find("#{") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let types: Vec<&str> = rest[start + 2..start + end]
+            .split(',')
+            .map(str::trim)
+            .collect();
+        rest = &rest[start + end + 1..];
+        let count = limit(rest).unwrap_or_default();
+        match (types.contains(&"managed"), types.contains(&"AP")) {
+            (true, true) => shared += count,
+            (true, false) => station += count,
+            (false, true) => ap += count,
+            (false, false) => {}
+        }
+    }
+
+    let total = combination
+        .split(", ")
+        .find(|part| part.trim().starts_with("total"))
+        .and_then(limit)
+        .unwrap_or(u32::MAX);
+    let channels = combination
+        .split(", ")
+        .find(|part| part.trim().starts_with("#channels"))
+        .and_then(limit);
+    let both = matches!(
+        (station, ap, shared),
+        (_, _, 2..) | (1.., 1.., _) | (1.., _, 1..) | (_, 1.., 1..)
+    );
+
+    (both && total >= 2, channels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `iw phy phy0 info` of an Intel Wireless 7260, abridged
+    const IWLWIFI: &str = "Wiphy phy0
+	max # scan SSIDs: 20
+	Supported interface modes:
+		 * IBSS
+		 * managed
+		 * AP
+		 * AP/VLAN
+		 * monitor
+		 * P2P-client
+		 * P2P-GO
+		 * P2P-device
+	Band 1:
+		Capabilities: 0x11ef
+	software interface modes (can always be added):
+		 * AP/VLAN
+		 * monitor
+	valid interface combinations:
+		 * #{ managed } <= 1, #{ AP, P2P-client, P2P-GO } <= 1, #{ P2P-device } <= 1,
+		   total <= 3, #channels <= 1
+	HT Capability overrides:
+		 * MCS: ff ff ff ff ff ff ff ff ff ff
+";
+
+    /// `iw phy phy1 info` of an Atheros AR9462 (ath9k), abridged
+    const ATH9K: &str = "Wiphy phy1
+	Supported interface modes:
+		 * IBSS
+		 * managed
+		 * AP
+		 * AP/VLAN
+		 * WDS
+		 * monitor
+		 * mesh point
+	Band 1:
+		Capabilities: 0x11ee
+	valid interface combinations:
+		 * #{ managed } <= 2048, #{ AP, mesh point } <= 8, #{ P2P-client, P2P-GO } <= 1, #{ IBSS } <= 1,
+		   total <= 2048, #channels <= 1, STA/AP BI must match, radar detect widths: { 20 MHz (no HT), 20 MHz, 40 MHz }
+
+		 * #{ WDS } <= 2048,
+		   total <= 2048, #channels <= 1, STA/AP BI must match
+		 * #{ IBSS, AP, mesh point } <= 1,
+		   total <= 1, #channels <= 1, STA/AP BI must match
+	HT Capability overrides:
+		 * MCS: ff ff ff ff ff ff ff ff ff ff
+";
+
+    fn config(password: Option<&str>, security: HotspotSecurity) -> HotspotConfig {
+        HotspotConfig {
+            ssid: "Hotspot".to_string(),
+            password: password.map(str::to_string),
+            security,
+            band: None,
+            channel: None,
+            interface: None,
+        }
+    }
+
+    #[test]
+    fn reads_modes_and_combinations() {
+        assert_eq!(
+            section(IWLWIFI, "Supported interface modes:"),
+            [
+                "IBSS",
+                "managed",
+                "AP",
+                "AP/VLAN",
+                "monitor",
+                "P2P-client",
+                "P2P-GO",
+                "P2P-device"
+            ]
+        );
+        assert_eq!(
+            section(IWLWIFI, "valid interface combinations:"),
+            ["#{ managed } <= 1, #{ AP, P2P-client, P2P-GO } <= 1, #{ P2P-device } <= 1, total <= 3, #channels <= 1"]
+        );
+        assert!(section(IWLWIFI, "missing header:").is_empty());
+    }
+
+    #[test]
+    fn reads_combinations_separated_by_blank_lines() {
+        let combinations = section(ATH9K, "valid interface combinations:");
+        assert_eq!(combinations.len(), 3);
+        assert!(combinations[0].starts_with("#{ managed } <= 2048, #{ AP, mesh point } <= 8"));
+        assert!(combinations[0].ends_with("40 MHz }"));
+        assert_eq!(
+            combinations[2],
+            "#{ IBSS, AP, mesh point } <= 1, total <= 1, #channels <= 1, STA/AP BI must match"
+        );
+    }
+
+    #[test]
+    fn parses_single_channel_combination() {
+        let combinations = section(IWLWIFI, "valid interface combinations:");
+        assert_eq!(parse_combination(&combinations[0]), (true, Some(1)));
+        assert_eq!(
+            parse_combination(
+                "#{ managed } <= 1, #{ AP, P2P-client, P2P-GO } <= 1, total <= 2, #channels <= 2"
+            ),
+            (true, Some(2))
+        );
+    }
+
+    #[test]
+    fn parses_multi_group_combinations() {
+        let parsed: Vec<(bool, Option<u32>)> = section(ATH9K, "valid interface combinations:")
+            .iter()
+            .map(|c| parse_combination(c))
+            .collect();
+        // Station and AP together, WDS only, and an AP that must be alone
+        assert_eq!(
+            parsed,
+            [(true, Some(1)), (false, Some(1)), (false, Some(1))]
+        );
+
+        // Two interfaces of a shared group, but only one at a time
+        assert_eq!(
+            parse_combination("#{ managed, AP } <= 2, total <= 1"),
+            (false, None)
+        );
+        assert_eq!(
+            parse_combination("#{ managed, AP } <= 2, total <= 2"),
+            (true, None)
+        );
+        assert_eq!(
+            parse_combination("#{ managed } <= 1, #{ IBSS } <= 1"),
+            (false, None)
+        );
+    }
+
+    #[test]
+    fn accepts_passphrases_and_hex_psks() {
+        let valid =
+            |password: &str| validate(&config(Some(password), HotspotSecurity::Wpa2)).is_ok();
+        assert!(valid("12345678"));
+        assert!(valid("pass phrase with spaces ~!"));
+        assert!(valid(&"a".repeat(63)));
+        assert!(valid(&"0123456789abcdefABCDEF".repeat(3)[..64]));
+
+        assert!(!valid("1234567"));
+        assert!(!valid(&"g".repeat(64)));
+        assert!(!valid(&"0".repeat(65)));
+        // Non-ASCII and control characters are not allowed in a passphrase
+        assert!(!valid("pässwörd"));
+        assert!(!valid("password\n"));
+        // Eight characters, but more than 63 bytes
+        assert!(!valid(&"é".repeat(40)));
+    }
+
+    #[test]
+    fn accepts_hex_psks_for_wpa2_only() {
+        let psk = "0123456789abcdef".repeat(4);
+        assert!(validate(&config(Some(&psk), HotspotSecurity::Wpa2)).is_ok());
+        assert!(validate(&config(Some(&psk), HotspotSecurity::Wpa3)).is_err());
+        assert!(validate(&config(Some("12345678"), HotspotSecurity::Wpa3)).is_ok());
+    }
+
+    #[test]
+    fn parses_escaped_settings() {
+        let settings = parse_settings(
+            "802-11-wireless.ssid:Cafe\\:Bar\n802-11-wireless.band:a\n802-11-wireless.channel:0\n802-11-wireless-security.key-mgmt:sae\n",
+        );
+        assert_eq!(settings["802-11-wireless.ssid"], "Cafe:Bar");
+        assert_eq!(settings["802-11-wireless.band"], "a");
+        assert_eq!(settings["802-11-wireless-security.key-mgmt"], "sae");
+
+        // Unset settings are printed with an empty value
+        let settings = parse_settings("802-11-wireless-security.key-mgmt:\n");
+        assert_eq!(settings["802-11-wireless-security.key-mgmt"], "");
+    }
+
+    #[test]
+    fn requires_a_password_unless_open() {
+        assert!(validate(&config(None, HotspotSecurity::Wpa3)).is_err());
+        assert!(validate(&config(None, HotspotSecurity::Open)).is_ok());
+        assert!(validate(&config(Some("short"), HotspotSecurity::Open)).is_ok());
+    }
+}
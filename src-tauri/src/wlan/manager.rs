@@ -1,7 +1,8 @@
 use super::{
     network_data::WifiNetwork,
     network_stats::{NetworkMonitor, NetworkStats},
-    networkmanager_error::{StatsError, WifiConnectionError, WifiManagerError},
+    networkmanager_error::{ProfileError, StatsError, WifiConnectionError, WifiManagerError},
+    profiles::SavedProfile,
 };
 use tauri::{AppHandle, Emitter};
 
@@ -25,6 +26,33 @@ pub trait WifiManager {
     /// - `Err(WifiConnectionError)` if connection fails
     fn connect(bssid: &str, password: Option<&str>) -> Result<(), WifiConnectionError>;
 
+    /// Disconnects a Wi-Fi device
+    ///
+    /// # Arguments
+    /// * `interface` - device to disconnect, `None` picks the connected one
+    ///
+    /// # Returns
+    /// - `Ok(())` if the device was disconnected
+    /// - `Err(WifiConnectionError)` if nothing was connected or nmcli fails
+    fn disconnect(interface: Option<&str>) -> Result<(), WifiConnectionError>;
+
+    /// Lists the saved Wi-Fi profiles
+    ///
+    /// # Returns
+    /// - `Ok(Vec<SavedProfile>)` with every saved Wi-Fi profile
+    /// - `Err(ProfileError)` if nmcli fails
+    fn profiles() -> Result<Vec<SavedProfile>, ProfileError>;
+
+    /// Deletes a saved profile
+    ///
+    /// # Arguments
+    /// * `profile` - name or UUID of the profile
+    ///
+    /// # Returns
+    /// - `Ok(())` if the profile was deleted
+    /// - `Err(ProfileError)` if there is no such profile or nmcli fails
+    fn delete_profile(profile: &str) -> Result<(), ProfileError>;
+
     /// Gets network interface statistics
     ///
     /// # Arguments
@@ -62,6 +90,18 @@ impl WifiManager for WifiNetwork {
         super::connect_network::connect_to_network(bssid, password)
     }
 
+    fn disconnect(interface: Option<&str>) -> Result<(), WifiConnectionError> {
+        super::connect_network::disconnect_network(interface)
+    }
+
+    fn profiles() -> Result<Vec<SavedProfile>, ProfileError> {
+        super::profiles::get_profiles(true)
+    }
+
+    fn delete_profile(profile: &str) -> Result<(), ProfileError> {
+        super::profiles::delete_profile(profile)
+    }
+
     async fn network_stats_onetime(
         interface_name: &str,
     ) -> Result<Option<NetworkStats>, StatsError> {
@@ -1,7 +1,19 @@
+pub mod channel_analyzer;
 pub mod connect_network;
+pub mod connectivity;
+pub mod dns_diagnostics;
 pub mod get_interfaces;
 pub mod get_networks;
+pub mod hotspot;
 pub mod manager;
 pub mod network_data;
 pub mod network_stats;
 pub mod networkmanager_error;
+pub mod ping_monitor;
+pub mod profile_exchange;
+pub mod profile_formats;
+pub mod profiles;
+pub mod roaming;
+pub mod speed_test;
+pub mod speed_test_server;
+pub mod wifi_qr;
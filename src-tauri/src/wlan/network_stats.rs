@@ -6,7 +6,7 @@ use tokio::time;
 use super::networkmanager_error::StatsError;
 
 /// Network statistics including bytes, speeds, and totals
-#[derive(Clone, serde::Serialize, Debug)]
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
 pub struct NetworkStats {
     pub bytes_up: u64,
     pub bytes_down: u64,
@@ -30,10 +30,7 @@ impl NetworkMonitor {
     pub fn new(interface: &str) -> Result<Self, StatsError> {
         let sys = System::new();
         let interfaces = match sys.networks() {
-            Ok(i) => {
-                println!("Available network interfaces: {:?}", i); // Log available interfaces
-                i
-            }
+            Ok(i) => i,
             Err(e) => {
                 eprintln!("Error checking interfaces {:?}", e);
                 return Err(StatsError::InterfaceValidationError);
@@ -90,7 +87,6 @@ impl NetworkMonitor {
                     total_up: self.total_up,
                     total_down: self.total_down,
                 };
-                println!("Calculated stats: {:?}", stats); // Log the calculated stats
                 Some(stats)
             }
             Err(e) => {
@@ -100,11 +96,34 @@ impl NetworkMonitor {
         }
     }
 
+    /// Name of the monitored interface
+    pub fn interface(&self) -> &str {
+        &self.interface
+    }
+
+    /// Checks whether the monitored interface still exists
+    ///
+    /// On-demand interfaces like the `bnep` interface of a Bluetooth PAN
+    /// connection are removed when the link goes down.
+    pub fn interface_exists(&self) -> bool {
+        match self.sys.networks() {
+            Ok(interfaces) => interfaces.contains_key(&self.interface),
+            Err(_) => true,
+        }
+    }
+
     /// Continuously monitors network usage with a given interval
+    ///
+    /// Stops once the interface disappears.
     pub async fn monitor(&mut self, interval_secs: u64, callback: impl Fn(NetworkStats)) {
         loop {
-            if let Some(stats) = self.get_stats().await {
-                callback(stats);
+            match self.get_stats().await {
+                Some(stats) => callback(stats),
+                None if !self.interface_exists() => {
+                    eprintln!("Interface '{}' removed, stopping monitor", self.interface);
+                    return;
+                }
+                None => {}
             }
             time::sleep(Duration::from_secs(interval_secs)).await;
         }
@@ -11,6 +11,13 @@ pub enum WifiConnectionError {
     NoSuchNetwork,
     UnknownError,
     AskingError,
+    NotConnected,
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+    CommandExecutionFailure,
+    NoSuchProfile,
 }
 
 #[derive(Debug)]
@@ -20,3 +27,75 @@ pub enum StatsError {
     UnknownError,
     NoAppInContext,
 }
+
+#[derive(Debug)]
+pub enum PingError {
+    SocketCreationFailure,
+    TargetResolutionFailure,
+    NoTargets,
+}
+
+#[derive(Debug)]
+pub enum DnsError {
+    CommandExecutionFailure,
+    NoResolvers,
+}
+
+#[derive(Debug)]
+pub enum ConnectivityError {
+    ClientCreationFailure,
+    CommandExecutionFailure,
+    NoPortalDetected,
+    OpenerFailure,
+}
+
+#[derive(Debug)]
+pub enum SpeedTestError {
+    InvalidEndpoint,
+    ConnectionFailure,
+    TransferFailure,
+    ServerBindFailure,
+}
+
+#[derive(Debug)]
+pub enum RoamingError {
+    InProgress,
+    NotRunning,
+    CommandExecutionFailure,
+}
+
+#[derive(Debug)]
+pub enum HotspotError {
+    CommandExecutionFailure,
+    NoWifiDevice,
+    /// The settings cannot work, with the reason
+    InvalidConfig(String),
+    ApModeUnsupported,
+    NotRunning,
+}
+
+#[derive(Debug)]
+pub enum QrError {
+    /// The text is not a `WIFI:` URI, with the reason
+    InvalidUri(String),
+    /// Enterprise (EAP) networks cannot be shared as QR code
+    UnsupportedSecurity,
+    NoSuchProfile,
+    NotConnected,
+    NoQrCode,
+    EncodingFailure,
+    CommandExecutionFailure,
+}
+
+#[derive(Debug)]
+pub enum ExchangeError {
+    /// The file or directory could not be read
+    IoError,
+    UnknownFormat,
+    /// The file is not valid in its format, with the reason
+    InvalidFile(String),
+    WrongPassphrase,
+    EncryptionFailure,
+    NoSuchProfile,
+    CommandExecutionFailure,
+}
@@ -0,0 +1,552 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{IpAddr, Ipv6Addr},
+    process::Command,
+    time::Duration,
+};
+
+use rand::random;
+use surge_ping::{Client, Config, PingIdentifier, PingSequence, ICMP};
+use tokio::time;
+
+use super::{dns_diagnostics::get_interface_resolvers, networkmanager_error::PingError};
+
+/// Payload sent with every echo request
+const PING_PAYLOAD: [u8; 56] = [0; 56];
+
+/// Where a ping target came from
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum PingTargetKind {
+    /// Default gateway of the system
+    Gateway,
+    /// DNS server configured by NetworkManager
+    Dns,
+    /// Host provided by the user
+    Custom,
+}
+
+/// Single host the monitor pings on every tick
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PingTarget {
+    /// Host as given by the user (or the address itself for discovered targets)
+    pub host: String,
+    /// Resolved address
+    pub address: IpAddr,
+    /// Origin of the target
+    pub kind: PingTargetKind,
+    /// Index of the interface link-local IPv6 addresses are reached through
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope_id: Option<u32>,
+}
+
+/// Which targets the monitor should ping and how often
+///
+/// Fields left out of a config take their default value.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PingMonitorConfig {
+    /// Ping the default gateway
+    pub include_gateway: bool,
+    /// Ping the DNS servers of the active connections
+    pub include_dns: bool,
+    /// Additional hosts (names or addresses)
+    pub custom_hosts: Vec<String>,
+    /// Seconds between two rounds of pings
+    pub interval_secs: u64,
+    /// Number of samples kept per target for the statistics
+    pub window_size: usize,
+    /// Milliseconds to wait for an echo reply
+    pub timeout_ms: u64,
+}
+
+impl Default for PingMonitorConfig {
+    fn default() -> Self {
+        PingMonitorConfig {
+            include_gateway: true,
+            include_dns: true,
+            custom_hosts: Vec::new(),
+            interval_secs: 1,
+            window_size: 60,
+            timeout_ms: 1000,
+        }
+    }
+}
+
+/// Connection quality of a single target computed over the sliding window
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PingStats {
+    pub target: PingTarget,
+    /// Round trip time of the last probe in ms, `None` if it was lost
+    pub last_rtt: Option<f64>,
+    pub rtt_min: Option<f64>, // in ms
+    pub rtt_avg: Option<f64>, // in ms
+    pub rtt_max: Option<f64>, // in ms
+    /// Mean difference between consecutive round trip times in ms
+    pub jitter: Option<f64>,
+    /// Lost probes in percent of `samples`
+    pub packet_loss: f64,
+    /// Number of probes in the window
+    pub samples: usize,
+}
+
+/// Pings a set of targets on an interval and keeps a sliding window of results
+pub struct PingMonitor {
+    config: PingMonitorConfig,
+    targets: Vec<PingTarget>,
+    client_v4: Option<Client>,
+    client_v6: Option<Client>,
+    identifier: PingIdentifier,
+    sequence: u16,
+    windows: HashMap<IpAddr, VecDeque<Option<Duration>>>,
+}
+
+impl PingMonitor {
+    /// Creates a new PingMonitor, resolving every configured target up front
+    ///
+    /// Sockets are only opened for the address families of the targets. If
+    /// one family is unavailable (e.g. IPv6 is disabled) its targets are
+    /// skipped and the others are still pinged.
+    pub async fn new(config: PingMonitorConfig) -> Result<Self, PingError> {
+        let mut targets = resolve_targets(&config).await?;
+
+        let client_v4 = open_client(&targets, false);
+        let client_v6 = open_client(&targets, true);
+        targets.retain(|target| match target.address {
+            IpAddr::V4(_) => client_v4.is_some(),
+            IpAddr::V6(_) => client_v6.is_some(),
+        });
+        if targets.is_empty() {
+            return Err(PingError::SocketCreationFailure);
+        }
+
+        Ok(PingMonitor {
+            config,
+            targets,
+            client_v4,
+            client_v6,
+            identifier: PingIdentifier(random()),
+            sequence: 0,
+            windows: HashMap::new(),
+        })
+    }
+
+    /// Sends one probe to every target and returns the updated statistics
+    pub async fn probe(&mut self) -> Vec<PingStats> {
+        let sequence = PingSequence(self.sequence);
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let probes = self.targets.iter().map(|target| {
+            let client = match target.address {
+                IpAddr::V4(_) => &self.client_v4,
+                IpAddr::V6(_) => &self.client_v6,
+            };
+            let identifier = self.identifier;
+            async move {
+                // Targets of an unavailable family were dropped in `new`
+                let mut pinger = client.as_ref()?.pinger(target.address, identifier).await;
+                pinger.timeout(timeout);
+                if let Some(scope_id) = target.scope_id {
+                    pinger.scope_id(scope_id);
+                }
+                match pinger.ping(sequence, &PING_PAYLOAD).await {
+                    Ok((_, rtt)) => Some(rtt),
+                    Err(e) => {
+                        eprintln!("Ping to {} failed: {:?}", target.host, e);
+                        None
+                    }
+                }
+            }
+        });
+        let results = futures::future::join_all(probes).await;
+
+        let window_size = self.config.window_size.max(1);
+        let mut stats = Vec::with_capacity(self.targets.len());
+        for (target, rtt) in self.targets.iter().zip(results) {
+            let window = self.windows.entry(target.address).or_default();
+            window.push_back(rtt);
+            while window.len() > window_size {
+                window.pop_front();
+            }
+            stats.push(compute_stats(target.clone(), window));
+        }
+
+        stats
+    }
+
+    /// Continuously pings the targets with the configured interval
+    pub async fn monitor(&mut self, callback: impl Fn(Vec<PingStats>)) {
+        let mut interval = time::interval(Duration::from_secs(self.config.interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            callback(self.probe().await);
+        }
+    }
+}
+
+/// Opens the ICMP socket of one address family if any target needs it
+fn open_client(targets: &[PingTarget], v6: bool) -> Option<Client> {
+    let needed: Vec<&str> = targets
+        .iter()
+        .filter(|target| target.address.is_ipv6() == v6)
+        .map(|target| target.host.as_str())
+        .collect();
+    if needed.is_empty() {
+        return None;
+    }
+
+    let (kind, name) = match v6 {
+        true => (ICMP::V6, "ICMPv6"),
+        false => (ICMP::V4, "ICMP"),
+    };
+    Client::new(&Config::builder().kind(kind).build())
+        .map_err(|e| {
+            eprintln!(
+                "Error creating {} socket, skipping {}: {:?}",
+                name,
+                needed.join(", "),
+                e
+            );
+        })
+        .ok()
+}
+
+/// Computes min/avg/max, jitter and loss of a window of round trip times
+fn compute_stats(target: PingTarget, window: &VecDeque<Option<Duration>>) -> PingStats {
+    let rtts: Vec<f64> = window
+        .iter()
+        .flatten()
+        .map(|rtt| rtt.as_secs_f64() * 1000.0)
+        .collect();
+
+    let samples = window.len();
+    let lost = samples - rtts.len();
+    let packet_loss = if samples == 0 {
+        0.0
+    } else {
+        lost as f64 / samples as f64 * 100.0
+    };
+
+    let (rtt_min, rtt_avg, rtt_max) = if rtts.is_empty() {
+        (None, None, None)
+    } else {
+        let min = rtts.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = rtts.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let avg = rtts.iter().sum::<f64>() / rtts.len() as f64;
+        (Some(min), Some(avg), Some(max))
+    };
+
+    let jitter = if rtts.len() < 2 {
+        None
+    } else {
+        let diffs: f64 = rtts.windows(2).map(|w| (w[1] - w[0]).abs()).sum();
+        Some(diffs / (rtts.len() - 1) as f64)
+    };
+
+    PingStats {
+        target,
+        last_rtt: window
+            .back()
+            .copied()
+            .flatten()
+            .map(|rtt| rtt.as_secs_f64() * 1000.0),
+        rtt_min,
+        rtt_avg,
+        rtt_max,
+        jitter,
+        packet_loss,
+        samples,
+    }
+}
+
+/// Builds the list of targets from the config, skipping duplicates
+async fn resolve_targets(config: &PingMonitorConfig) -> Result<Vec<PingTarget>, PingError> {
+    let mut targets: Vec<PingTarget> = Vec::new();
+
+    if config.include_gateway {
+        for (address, device) in get_default_routes() {
+            targets.push(PingTarget {
+                host: address.to_string(),
+                address,
+                kind: PingTargetKind::Gateway,
+                scope_id: device.as_deref().and_then(|d| link_scope(&address, d)),
+            });
+        }
+    }
+
+    if config.include_dns {
+        for address in get_dns_servers() {
+            targets.push(PingTarget {
+                host: address.to_string(),
+                address,
+                kind: PingTargetKind::Dns,
+                scope_id: None,
+            });
+        }
+    }
+
+    for host in &config.custom_hosts {
+        // Link-local addresses name their interface, e.g. "fe80::1%wlan0"
+        if let Some((address, zone)) = parse_scoped_address(host) {
+            targets.push(PingTarget {
+                host: host.clone(),
+                address,
+                kind: PingTargetKind::Custom,
+                scope_id: zone.and_then(|zone| link_scope(&address, zone)),
+            });
+            continue;
+        }
+        let address = match tokio::net::lookup_host((host.as_str(), 0)).await {
+            Ok(mut addrs) => match addrs.next() {
+                Some(addr) => addr.ip(),
+                None => return Err(PingError::TargetResolutionFailure),
+            },
+            Err(e) => {
+                eprintln!("Error resolving '{}': {:?}", host, e);
+                return Err(PingError::TargetResolutionFailure);
+            }
+        };
+        targets.push(PingTarget {
+            host: host.clone(),
+            address,
+            kind: PingTargetKind::Custom,
+            scope_id: None,
+        });
+    }
+
+    let mut seen = Vec::new();
+    targets.retain(|t| {
+        if seen.contains(&t.address) {
+            false
+        } else {
+            seen.push(t.address);
+            true
+        }
+    });
+
+    if targets.is_empty() {
+        return Err(PingError::NoTargets);
+    }
+
+    Ok(targets)
+}
+
+/// Reads the default gateways from `ip route`
+pub fn get_default_gateways() -> Vec<IpAddr> {
+    get_default_routes()
+        .into_iter()
+        .map(|(address, _)| address)
+        .collect()
+}
+
+/// Default gateways with the device they are reached through
+fn get_default_routes() -> Vec<(IpAddr, Option<String>)> {
+    let mut routes = Vec::new();
+
+    for family in ["-4", "-6"] {
+        let output = match Command::new("ip")
+            .args([family, "route", "show", "default"])
+            .output()
+        {
+            Ok(o) if o.status.success() => o,
+            _ => continue,
+        };
+        routes.extend(parse_default_routes(&String::from_utf8_lossy(
+            &output.stdout,
+        )));
+    }
+
+    routes
+}
+
+/// Parses `ip route show default` output
+fn parse_default_routes(output: &str) -> Vec<(IpAddr, Option<String>)> {
+    // default via 192.168.1.1 dev wlp1s0 proto dhcp metric 600
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let value = |name: &str| {
+                let pos = fields.iter().position(|f| *f == name)?;
+                fields.get(pos + 1).copied()
+            };
+            let address = value("via")?.parse().ok()?;
+            Some((address, value("dev").map(str::to_string)))
+        })
+        .collect()
+}
+
+/// Splits an address with an optional zone, `None` if it is not an address
+fn parse_scoped_address(host: &str) -> Option<(IpAddr, Option<&str>)> {
+    let (address, zone) = match host.split_once('%') {
+        Some((address, zone)) => (address, Some(zone)),
+        None => (host, None),
+    };
+    Some((address.parse().ok()?, zone))
+}
+
+/// Interface index for link-local IPv6 addresses, which are ambiguous
+/// without one
+///
+/// # Arguments
+/// * `zone` - interface name or index the address is reached through
+pub(super) fn link_scope(address: &IpAddr, zone: &str) -> Option<u32> {
+    let IpAddr::V6(address) = address else {
+        return None;
+    };
+    if !is_link_local(address) {
+        return None;
+    }
+
+    zone.parse().ok().or_else(|| {
+        std::fs::read_to_string(format!("/sys/class/net/{}/ifindex", zone))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    })
+}
+
+fn is_link_local(address: &Ipv6Addr) -> bool {
+    address.segments()[0] & 0xffc0 == 0xfe80
+}
+
+/// Collects the DNS servers of all devices without duplicates
+pub fn get_dns_servers() -> Vec<IpAddr> {
+    let mut servers: Vec<IpAddr> = Vec::new();
+    for iface in get_interface_resolvers().unwrap_or_default() {
+        for resolver in iface.resolvers {
+            if !servers.contains(&resolver) {
+                servers.push(resolver);
+            }
+        }
+    }
+    servers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target() -> PingTarget {
+        PingTarget {
+            host: "192.0.2.1".to_string(),
+            address: "192.0.2.1".parse().unwrap(),
+            kind: PingTargetKind::Custom,
+            scope_id: None,
+        }
+    }
+
+    fn window(rtts: &[Option<u64>]) -> VecDeque<Option<Duration>> {
+        rtts.iter()
+            .map(|rtt| rtt.map(Duration::from_millis))
+            .collect()
+    }
+
+    #[test]
+    fn stats_of_a_window_with_losses() {
+        let stats = compute_stats(
+            target(),
+            &window(&[Some(10), None, Some(30), Some(20), None]),
+        );
+
+        assert_eq!(stats.samples, 5);
+        assert_eq!(stats.packet_loss, 40.0);
+        assert_eq!(stats.rtt_min, Some(10.0));
+        assert_eq!(stats.rtt_max, Some(30.0));
+        assert_eq!(stats.rtt_avg, Some(20.0));
+        // |30 - 10| and |20 - 30| over the received probes
+        assert_eq!(stats.jitter, Some(15.0));
+        assert_eq!(stats.last_rtt, None);
+    }
+
+    #[test]
+    fn stats_of_a_single_probe_have_no_jitter() {
+        let stats = compute_stats(target(), &window(&[Some(12)]));
+
+        assert_eq!(stats.packet_loss, 0.0);
+        assert_eq!(stats.last_rtt, Some(12.0));
+        assert_eq!(stats.jitter, None);
+    }
+
+    #[test]
+    fn stats_of_a_lost_or_empty_window() {
+        let lost = compute_stats(target(), &window(&[None, None]));
+        assert_eq!(lost.packet_loss, 100.0);
+        assert_eq!(lost.rtt_avg, None);
+
+        let empty = compute_stats(target(), &VecDeque::new());
+        assert_eq!(empty.packet_loss, 0.0);
+        assert_eq!(empty.samples, 0);
+    }
+
+    #[test]
+    fn default_routes_with_devices() {
+        let routes = parse_default_routes(
+            "default via 192.168.1.1 dev wlp1s0 proto dhcp metric 600\n\
+             default via fe80::1 dev wlp1s0 proto ra metric 600 pref medium\n\
+             default dev wg0 scope link\n",
+        );
+
+        assert_eq!(
+            routes,
+            vec![
+                ("192.168.1.1".parse().unwrap(), Some("wlp1s0".to_string())),
+                ("fe80::1".parse().unwrap(), Some("wlp1s0".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn scope_only_for_link_local_addresses() {
+        let link_local = "fe80::1".parse().unwrap();
+        assert_eq!(link_scope(&link_local, "3"), Some(3));
+        assert_eq!(link_scope(&"2001:db8::1".parse().unwrap(), "3"), None);
+        assert_eq!(link_scope(&"192.0.2.1".parse().unwrap(), "3"), None);
+
+        assert_eq!(
+            parse_scoped_address("fe80::1%wlan0"),
+            Some((link_local, Some("wlan0")))
+        );
+        assert_eq!(parse_scoped_address("example.org"), None);
+    }
+
+    #[test]
+    fn partial_configs_use_the_defaults() {
+        let config: PingMonitorConfig =
+            serde_json::from_str(r#"{ "custom_hosts": ["192.0.2.1"], "interval_secs": 5 }"#)
+                .unwrap();
+
+        assert_eq!(config.custom_hosts, ["192.0.2.1"]);
+        assert_eq!(config.interval_secs, 5);
+        assert!(config.include_gateway);
+        assert_eq!(config.window_size, 60);
+    }
+
+    #[tokio::test]
+    async fn pings_the_loopback_address() {
+        let config = PingMonitorConfig {
+            include_gateway: false,
+            include_dns: false,
+            custom_hosts: vec!["127.0.0.1".to_string()],
+            ..PingMonitorConfig::default()
+        };
+        // Needs a raw socket or an unprivileged ICMP socket (ping_group_range)
+        let mut monitor = match PingMonitor::new(config).await {
+            Ok(monitor) => monitor,
+            Err(e) => {
+                eprintln!("ICMP sockets not available, skipping: {:?}", e);
+                return;
+            }
+        };
+
+        monitor.probe().await;
+        let stats = monitor.probe().await;
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].target.kind, PingTargetKind::Custom);
+        assert_eq!(stats[0].samples, 2);
+        assert_eq!(stats[0].packet_loss, 0.0);
+        assert!(stats[0].last_rtt.is_some());
+        assert!(stats[0].rtt_min <= stats[0].rtt_max);
+    }
+}
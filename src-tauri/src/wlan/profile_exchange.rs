@@ -0,0 +1,1001 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    process::Command,
+};
+
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    get_networks::split_terse_line,
+    networkmanager_error::ExchangeError,
+    profile_formats::{detect_format, parse_profiles, ProfileFormat},
+    profiles::{delete_profile, get_profile_ssid, get_profiles, SavedProfile},
+};
+
+/// Value of [`ProfileBundle::format`]
+pub const BUNDLE_FORMAT: &str = "wiblue-profiles";
+
+/// Latest bundle version, newer bundles are rejected
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Highest Argon2 memory cost accepted from a bundle, 1 GiB
+const MAX_MEMORY_KIB: u32 = 1 << 20;
+/// Highest Argon2 time cost accepted from a bundle
+const MAX_ITERATIONS: u32 = 16;
+/// Highest Argon2 lane count accepted from a bundle
+const MAX_PARALLELISM: u32 = 16;
+
+/// Security of a Wi-Fi profile, independent of the format it came from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileSecurity {
+    Open,
+    Wep,
+    /// WPA/WPA2 personal
+    WpaPsk,
+    /// WPA3 personal
+    Sae,
+    /// WPA/WPA2/WPA3 enterprise (802.1X)
+    Enterprise,
+}
+
+/// 802.1X settings of an enterprise profile
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EapSettings {
+    /// EAP method in NetworkManager spelling, e.g. `peap`, `ttls`, `tls`
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anonymous_identity: Option<String>,
+    /// Inner authentication, e.g. `mschapv2`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phase2: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Path of the CA certificate, it is not copied into bundles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+}
+
+/// A Wi-Fi profile in the portable form every format is converted to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortableProfile {
+    pub name: String,
+    pub ssid: String,
+    pub security: ProfileSecurity,
+    #[serde(default = "autoconnect_default")]
+    pub autoconnect: bool,
+    /// Whether the network does not broadcast its SSID
+    #[serde(default)]
+    pub hidden: bool,
+    /// Passphrase, PSK or WEP key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eap: Option<EapSettings>,
+}
+
+fn autoconnect_default() -> bool {
+    true
+}
+
+/// The secrets of a profile, stored encrypted in bundles
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ProfileSecrets {
+    password: Option<String>,
+    eap_password: Option<String>,
+}
+
+impl PortableProfile {
+    /// Whether the profile carries a password
+    pub fn has_secrets(&self) -> bool {
+        self.password.is_some() || self.eap.as_ref().is_some_and(|eap| eap.password.is_some())
+    }
+
+    /// The profile without its passwords
+    pub fn without_secrets(&self) -> PortableProfile {
+        let mut profile = self.clone();
+        profile.password = None;
+        if let Some(eap) = &mut profile.eap {
+            eap.password = None;
+        }
+        profile
+    }
+
+    fn secrets(&self) -> ProfileSecrets {
+        ProfileSecrets {
+            password: self.password.clone(),
+            eap_password: self.eap.as_ref().and_then(|eap| eap.password.clone()),
+        }
+    }
+
+    fn set_secrets(&mut self, secrets: ProfileSecrets) {
+        self.password = secrets.password.or(self.password.take());
+        if let Some(eap) = &mut self.eap {
+            eap.password = secrets.eap_password.or(eap.password.take());
+        }
+    }
+}
+
+/// Passwords of the profiles of a bundle, encrypted with AES-256-GCM under a
+/// key derived from a passphrase with Argon2id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedSecrets {
+    /// Always `argon2id`
+    pub kdf: String,
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    /// Base64 encoded
+    pub salt: String,
+    /// Base64 encoded
+    pub nonce: String,
+    /// Base64 encoded JSON list of the secrets, in the order of the profiles
+    pub ciphertext: String,
+}
+
+/// Portable JSON file with exported Wi-Fi profiles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileBundle {
+    /// Always [`BUNDLE_FORMAT`]
+    pub format: String,
+    pub version: u32,
+    /// RFC 3339 time of the export
+    pub created: String,
+    /// The profiles, without passwords if `secrets` is set
+    pub profiles: Vec<PortableProfile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secrets: Option<EncryptedSecrets>,
+}
+
+/// Binds the ciphertext to the bundle format
+fn associated_data() -> Vec<u8> {
+    format!("{}/{}", BUNDLE_FORMAT, BUNDLE_VERSION).into_bytes()
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+) -> Result<[u8; 32], ExchangeError> {
+    let params = Params::new(memory_kib, iterations, parallelism, Some(32))
+        .map_err(|e| ExchangeError::InvalidFile(format!("Invalid key parameters: {}", e)))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|_| ExchangeError::EncryptionFailure)?;
+    Ok(key)
+}
+
+fn encrypt_secrets(
+    secrets: &[ProfileSecrets],
+    passphrase: &str,
+) -> Result<EncryptedSecrets, ExchangeError> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(
+        passphrase,
+        &salt,
+        Params::DEFAULT_M_COST,
+        Params::DEFAULT_T_COST,
+        Params::DEFAULT_P_COST,
+    )?;
+
+    let plaintext = serde_json::to_vec(secrets).map_err(|_| ExchangeError::EncryptionFailure)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let aad = associated_data();
+    let ciphertext = Aes256Gcm::new(&key.into())
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: &plaintext,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| ExchangeError::EncryptionFailure)?;
+
+    Ok(EncryptedSecrets {
+        kdf: "argon2id".to_string(),
+        memory_kib: Params::DEFAULT_M_COST,
+        iterations: Params::DEFAULT_T_COST,
+        parallelism: Params::DEFAULT_P_COST,
+        salt: STANDARD.encode(salt),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    })
+}
+
+fn decrypt_secrets(
+    encrypted: &EncryptedSecrets,
+    passphrase: &str,
+) -> Result<Vec<ProfileSecrets>, ExchangeError> {
+    if encrypted.kdf != "argon2id" {
+        return Err(ExchangeError::InvalidFile(format!(
+            "Unknown key derivation {}",
+            encrypted.kdf
+        )));
+    }
+    if encrypted.memory_kib > MAX_MEMORY_KIB {
+        return Err(ExchangeError::InvalidFile(
+            "Key derivation needs too much memory".to_string(),
+        ));
+    }
+    if encrypted.iterations > MAX_ITERATIONS || encrypted.parallelism > MAX_PARALLELISM {
+        return Err(ExchangeError::InvalidFile(
+            "Key derivation takes too long".to_string(),
+        ));
+    }
+    let decode = |value: &str| {
+        STANDARD
+            .decode(value)
+            .map_err(|_| ExchangeError::InvalidFile("Invalid base64 in secrets".to_string()))
+    };
+    let salt = decode(&encrypted.salt)?;
+    let nonce = decode(&encrypted.nonce)?;
+    if nonce.len() != 12 {
+        return Err(ExchangeError::InvalidFile("Invalid nonce".to_string()));
+    }
+
+    let key = derive_key(
+        passphrase,
+        &salt,
+        encrypted.memory_kib,
+        encrypted.iterations,
+        encrypted.parallelism,
+    )?;
+    let aad = associated_data();
+    // Authentication fails for a wrong passphrase as well as a changed file
+    let plaintext = Aes256Gcm::new(&key.into())
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &decode(&encrypted.ciphertext)?,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| ExchangeError::WrongPassphrase)?;
+
+    serde_json::from_slice(&plaintext)
+        .map_err(|_| ExchangeError::InvalidFile("Invalid secrets".to_string()))
+}
+
+/// Reads a saved Wi-Fi profile from NetworkManager
+///
+/// # Returns
+/// - `Ok(None)` for profiles that cannot be exported, e.g. hotspots
+fn read_saved_profile(
+    profile: &SavedProfile,
+    secrets: bool,
+    warnings: &mut Vec<String>,
+) -> Result<Option<PortableProfile>, ExchangeError> {
+    let mut command = Command::new("nmcli");
+    if secrets {
+        command.arg("--show-secrets");
+    }
+    let output = command
+        .args([
+            "-g",
+            "connection.autoconnect,802-11-wireless.ssid,802-11-wireless.hidden,802-11-wireless.mode,802-11-wireless-security.key-mgmt,802-11-wireless-security.psk,802-11-wireless-security.wep-key0,802-1x.eap,802-1x.identity,802-1x.anonymous-identity,802-1x.phase2-auth,802-1x.password,802-1x.ca-cert",
+            "connection",
+            "show",
+            "uuid",
+            &profile.uuid,
+        ])
+        .output()
+        .map_err(|_| ExchangeError::CommandExecutionFailure)?;
+    if !output.status.success() {
+        eprintln!(
+            "nmcli command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(ExchangeError::CommandExecutionFailure);
+    }
+
+    // One value per line, colons escaped
+    let values: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| split_terse_line(line).join(":"))
+        .collect();
+    let value = |index: usize| values.get(index).cloned().filter(|v| !v.is_empty());
+
+    if value(3).as_deref() == Some("ap") {
+        warnings.push(format!("{}: access point profile, skipped", profile.name));
+        return Ok(None);
+    }
+    let security = match value(4).as_deref() {
+        None | Some("owe") => ProfileSecurity::Open,
+        Some("none") => ProfileSecurity::Wep,
+        Some("wpa-psk") => ProfileSecurity::WpaPsk,
+        Some("sae") => ProfileSecurity::Sae,
+        Some(_) => ProfileSecurity::Enterprise,
+    };
+    let password = match security {
+        ProfileSecurity::WpaPsk | ProfileSecurity::Sae => value(5),
+        ProfileSecurity::Wep => value(6),
+        _ => None,
+    };
+    let eap = (security == ProfileSecurity::Enterprise).then(|| EapSettings {
+        method: value(7)
+            .and_then(|m| m.split(',').next().map(str::to_string))
+            .unwrap_or_else(|| "peap".to_string()),
+        identity: value(8),
+        anonymous_identity: value(9),
+        phase2: value(10),
+        password: value(11),
+        ca_cert: value(12).map(|c| c.strip_prefix("file://").unwrap_or(&c).to_string()),
+    });
+
+    Ok(Some(PortableProfile {
+        name: profile.name.clone(),
+        ssid: value(1).unwrap_or_default(),
+        security,
+        autoconnect: value(0).as_deref() != Some("no"),
+        hidden: value(2).as_deref() == Some("yes"),
+        password,
+        eap,
+    }))
+}
+
+/// Exports saved Wi-Fi profiles to a bundle
+///
+/// Passwords are only exported with a passphrase, they are encrypted with
+/// it and left out of the readable part of the bundle.
+///
+/// # Arguments
+/// * `profiles` - names or UUIDs of the profiles, all Wi-Fi profiles if empty
+/// * `passphrase` - export the passwords encrypted with this passphrase
+/// * `warnings` - receives the profiles that were left out
+///
+/// # Returns
+/// - `Ok(ProfileBundle)` ready to be written as JSON
+/// - `Err(ExchangeError::NoSuchProfile)` if a requested profile does not exist
+pub fn export_profiles(
+    profiles: &[String],
+    passphrase: Option<&str>,
+    warnings: &mut Vec<String>,
+) -> Result<ProfileBundle, ExchangeError> {
+    let saved = get_profiles(true).map_err(|_| ExchangeError::CommandExecutionFailure)?;
+    let selected: Vec<&SavedProfile> = match profiles.is_empty() {
+        true => saved.iter().collect(),
+        false => profiles
+            .iter()
+            .map(|wanted| {
+                saved
+                    .iter()
+                    .find(|p| &p.uuid == wanted || &p.name == wanted)
+                    .ok_or(ExchangeError::NoSuchProfile)
+            })
+            .collect::<Result<_, _>>()?,
+    };
+
+    let mut exported = Vec::new();
+    for profile in selected {
+        if let Some(profile) = read_saved_profile(profile, passphrase.is_some(), warnings)? {
+            exported.push(profile);
+        }
+    }
+
+    let secrets = match passphrase {
+        Some(passphrase) => {
+            let secrets: Vec<ProfileSecrets> = exported.iter().map(|p| p.secrets()).collect();
+            Some(encrypt_secrets(&secrets, passphrase)?)
+        }
+        None => None,
+    };
+
+    Ok(ProfileBundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        created: chrono::Utc::now().to_rfc3339(),
+        profiles: exported
+            .iter()
+            .map(PortableProfile::without_secrets)
+            .collect(),
+        secrets,
+    })
+}
+
+/// Reads the profiles of a bundle, decrypting the passwords if a
+/// passphrase is given
+fn read_bundle(
+    content: &str,
+    passphrase: Option<&str>,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<PortableProfile>, ExchangeError> {
+    let bundle: ProfileBundle =
+        serde_json::from_str(content).map_err(|e| ExchangeError::InvalidFile(e.to_string()))?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err(ExchangeError::InvalidFile(format!(
+            "Not a profile bundle: {}",
+            bundle.format
+        )));
+    }
+    if bundle.version > BUNDLE_VERSION {
+        return Err(ExchangeError::InvalidFile(format!(
+            "Bundle version {} is not supported",
+            bundle.version
+        )));
+    }
+
+    let mut profiles = bundle.profiles;
+    match (&bundle.secrets, passphrase) {
+        (Some(encrypted), Some(passphrase)) => {
+            let secrets = decrypt_secrets(encrypted, passphrase)?;
+            if secrets.len() != profiles.len() {
+                return Err(ExchangeError::InvalidFile(
+                    "Secrets do not match the profiles".to_string(),
+                ));
+            }
+            for (profile, secrets) in profiles.iter_mut().zip(secrets) {
+                profile.set_secrets(secrets);
+            }
+        }
+        (Some(_), None) => warnings
+            .push("The passwords are encrypted, give the passphrase to import them".to_string()),
+        (None, _) => {}
+    }
+
+    Ok(profiles)
+}
+
+/// A profile read from a file
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedProfile {
+    /// Name of the file the profile is from
+    pub source: String,
+    pub format: ProfileFormat,
+    pub profile: PortableProfile,
+}
+
+/// Profiles read by [`read_profiles`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportSource {
+    pub profiles: Vec<ImportedProfile>,
+    /// What was left out, one line each
+    pub warnings: Vec<String>,
+}
+
+fn read_file(
+    path: &Path,
+    format: Option<ProfileFormat>,
+    passphrase: Option<&str>,
+    source: &mut ImportSource,
+) -> Result<(), ExchangeError> {
+    let content = std::fs::read_to_string(path).map_err(|_| ExchangeError::IoError)?;
+    let format = format
+        .or_else(|| detect_format(path, &content))
+        .ok_or(ExchangeError::UnknownFormat)?;
+    let profiles = match format {
+        ProfileFormat::Bundle => read_bundle(&content, passphrase, &mut source.warnings)?,
+        _ => parse_profiles(format, path, &content, &mut source.warnings)?,
+    };
+
+    let file = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    source
+        .profiles
+        .extend(profiles.into_iter().map(|profile| ImportedProfile {
+            source: file.clone(),
+            format,
+            profile,
+        }));
+    Ok(())
+}
+
+/// Reads the profiles of a file, or of every file in a directory
+///
+/// Files in a directory that cannot be read are reported as warnings, so a
+/// whole `/var/lib/iwd` or `system-connections` directory can be imported.
+///
+/// # Arguments
+/// * `path` - file or directory
+/// * `format` - format of the files, detected from each file if not set
+/// * `passphrase` - decrypts the passwords of bundles
+///
+/// # Returns
+/// - `Ok(ImportSource)` with the profiles and warnings
+/// - `Err(ExchangeError::UnknownFormat)` if the format of a single file
+///   could not be detected
+/// - `Err(ExchangeError::WrongPassphrase)` if a bundle cannot be decrypted
+pub fn read_profiles(
+    path: &Path,
+    format: Option<ProfileFormat>,
+    passphrase: Option<&str>,
+) -> Result<ImportSource, ExchangeError> {
+    let mut source = ImportSource::default();
+    if !path.is_dir() {
+        read_file(path, format, passphrase, &mut source)?;
+        return Ok(source);
+    }
+
+    let mut files: Vec<_> = std::fs::read_dir(path)
+        .map_err(|_| ExchangeError::IoError)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+    for file in files {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        match read_file(&file, format, passphrase, &mut source) {
+            // Directories like system-connections hold other files too
+            Ok(()) | Err(ExchangeError::UnknownFormat) => {}
+            Err(ExchangeError::WrongPassphrase) => return Err(ExchangeError::WrongPassphrase),
+            Err(ExchangeError::InvalidFile(reason)) => {
+                source.warnings.push(format!("{}: {}", name, reason))
+            }
+            Err(e) => source.warnings.push(format!("{}: {:?}", name, e)),
+        }
+    }
+
+    Ok(source)
+}
+
+/// A saved profile an imported one collides with
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileConflict {
+    pub name: String,
+    pub uuid: String,
+    pub same_name: bool,
+    pub same_ssid: bool,
+}
+
+/// How to import a profile that collides with a saved one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    /// Leave the profile out
+    Skip,
+    /// Delete the saved profile with the same name, or else the only one with
+    /// the same SSID, first
+    Replace,
+    /// Import next to the saved profiles under a free name
+    KeepBoth,
+    /// Import under the given name
+    Rename(String),
+}
+
+/// What an import would do with a profile
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportPreview {
+    pub source: String,
+    pub format: ProfileFormat,
+    /// The profile without its passwords
+    pub profile: PortableProfile,
+    pub has_secrets: bool,
+    /// Saved profiles with the same name or SSID
+    pub conflicts: Vec<ProfileConflict>,
+    /// The saved profile [`ConflictResolution::Replace`] would delete, `None`
+    /// without conflicts or if several saved profiles share the SSID
+    pub replaces: Option<ProfileConflict>,
+}
+
+/// Saved Wi-Fi profiles with their SSIDs
+fn saved_profiles() -> Result<Vec<(SavedProfile, Option<String>)>, ExchangeError> {
+    get_profiles(true)
+        .and_then(|profiles| {
+            profiles
+                .into_iter()
+                .map(|profile| {
+                    let ssid = get_profile_ssid(&profile.uuid)?;
+                    Ok((profile, ssid))
+                })
+                .collect()
+        })
+        .map_err(|_| ExchangeError::CommandExecutionFailure)
+}
+
+fn find_conflicts(
+    profile: &PortableProfile,
+    saved: &[(SavedProfile, Option<String>)],
+) -> Vec<ProfileConflict> {
+    saved
+        .iter()
+        .map(|(existing, ssid)| ProfileConflict {
+            name: existing.name.clone(),
+            uuid: existing.uuid.clone(),
+            same_name: existing.name == profile.name,
+            same_ssid: ssid.as_deref() == Some(profile.ssid.as_str()),
+        })
+        .filter(|conflict| conflict.same_name || conflict.same_ssid)
+        .collect()
+}
+
+/// The conflict [`ConflictResolution::Replace`] deletes: the saved profile
+/// with the same name, or else the only one with the same SSID
+fn replaced_profile(conflicts: &[ProfileConflict]) -> Option<&ProfileConflict> {
+    conflicts
+        .iter()
+        .find(|conflict| conflict.same_name)
+        .or(match conflicts {
+            [only] => Some(only),
+            _ => None,
+        })
+}
+
+/// Compares imported profiles with the saved ones without changing anything
+pub fn preview_import(source: &ImportSource) -> Result<Vec<ImportPreview>, ExchangeError> {
+    let saved = saved_profiles()?;
+
+    Ok(source
+        .profiles
+        .iter()
+        .map(|imported| {
+            let conflicts = find_conflicts(&imported.profile, &saved);
+            ImportPreview {
+                source: imported.source.clone(),
+                format: imported.format,
+                profile: imported.profile.without_secrets(),
+                has_secrets: imported.profile.has_secrets(),
+                replaces: replaced_profile(&conflicts).cloned(),
+                conflicts,
+            }
+        })
+        .collect())
+}
+
+/// What happened to an imported profile
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportOutcome {
+    Created,
+    Replaced,
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportResult {
+    /// Name in the file
+    pub name: String,
+    /// Name the profile was saved under
+    pub saved_as: Option<String>,
+    pub ssid: String,
+    pub outcome: ImportOutcome,
+    /// Why the profile could not be saved
+    pub error: Option<String>,
+}
+
+/// Saves imported profiles in NetworkManager
+///
+/// Profiles without conflicts are created unless their resolution is
+/// [`ConflictResolution::Skip`]; the others follow their resolution, or the
+/// default one. A failed profile does not stop the following ones.
+///
+/// # Arguments
+/// * `profiles` - profiles from [`read_profiles`]
+/// * `resolutions` - resolution per profile name in the file
+/// * `default` - resolution of conflicts not listed in `resolutions`
+pub fn apply_import(
+    profiles: &[ImportedProfile],
+    resolutions: &HashMap<String, ConflictResolution>,
+    default: &ConflictResolution,
+) -> Result<Vec<ImportResult>, ExchangeError> {
+    let saved = saved_profiles()?;
+    let mut taken: HashSet<String> = saved.iter().map(|(p, _)| p.name.clone()).collect();
+    let mut results = Vec::new();
+
+    for imported in profiles {
+        let profile = &imported.profile;
+        let conflicts = find_conflicts(profile, &saved);
+        let resolution = match (resolutions.get(&profile.name), conflicts.is_empty()) {
+            (Some(resolution), _) => Some(resolution),
+            (None, true) => None,
+            (None, false) => Some(default),
+        };
+
+        let name = match resolution {
+            Some(ConflictResolution::Skip) => {
+                results.push(ImportResult {
+                    name: profile.name.clone(),
+                    saved_as: None,
+                    ssid: profile.ssid.clone(),
+                    outcome: ImportOutcome::Skipped,
+                    error: None,
+                });
+                continue;
+            }
+            Some(ConflictResolution::KeepBoth) => free_name(&profile.name, &taken),
+            Some(ConflictResolution::Rename(name)) => name.clone(),
+            Some(ConflictResolution::Replace) | None => profile.name.clone(),
+        };
+
+        let replace = resolution == Some(&ConflictResolution::Replace) && !conflicts.is_empty();
+        let deleted = match replace {
+            true => match replaced_profile(&conflicts) {
+                Some(conflict) => delete_profile(&conflict.uuid)
+                    .map_err(|e| format!("Error deleting the saved profile: {:?}", e)),
+                None => Err(format!(
+                    "Several saved profiles use the SSID {}, rename the one to replace",
+                    profile.ssid
+                )),
+            },
+            false => Ok(()),
+        };
+        let error = deleted.and_then(|()| add_profile(profile, &name)).err();
+
+        if error.is_none() {
+            taken.insert(name.clone());
+        }
+        results.push(ImportResult {
+            name: profile.name.clone(),
+            saved_as: error.is_none().then_some(name),
+            ssid: profile.ssid.clone(),
+            outcome: match (&error, replace) {
+                (Some(_), _) => ImportOutcome::Failed,
+                (None, true) => ImportOutcome::Replaced,
+                (None, false) => ImportOutcome::Created,
+            },
+            error,
+        });
+    }
+
+    Ok(results)
+}
+
+/// `name`, or `name 2`, `name 3`... whichever is not taken
+fn free_name(name: &str, taken: &HashSet<String>) -> String {
+    (1..)
+        .map(|n| match n {
+            1 => name.to_string(),
+            n => format!("{} {}", name, n),
+        })
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap_or_default()
+}
+
+/// Value of `wep-key-type` for a WEP key: `1` for keys of 5 or 13 ASCII
+/// characters or 10 or 26 hex digits, `2` for passphrases that are hashed
+/// into a key
+fn wep_key_type(key: &str) -> &'static str {
+    let hex = key.bytes().all(|b| b.is_ascii_hexdigit());
+    match key.len() {
+        5 | 13 if key.is_ascii() => "1",
+        10 | 26 if hex => "1",
+        _ => "2",
+    }
+}
+
+/// Creates a NetworkManager profile, failures return the nmcli message
+fn add_profile(profile: &PortableProfile, name: &str) -> Result<(), String> {
+    let yes_no = |value: bool| if value { "yes" } else { "no" };
+    let mut args: Vec<&str> = vec![
+        "connection",
+        "add",
+        "type",
+        "wifi",
+        "con-name",
+        name,
+        "ssid",
+        &profile.ssid,
+        "connection.autoconnect",
+        yes_no(profile.autoconnect),
+        "802-11-wireless.hidden",
+        yes_no(profile.hidden),
+    ];
+
+    match profile.security {
+        ProfileSecurity::Open => {}
+        ProfileSecurity::Wep => {
+            args.extend(["wifi-sec.key-mgmt", "none"]);
+            if let Some(key) = &profile.password {
+                args.extend(["wifi-sec.wep-key-type", wep_key_type(key)]);
+                args.extend(["wifi-sec.wep-key0", key]);
+            }
+        }
+        ProfileSecurity::WpaPsk | ProfileSecurity::Sae => {
+            let key_mgmt = match profile.security {
+                ProfileSecurity::Sae => "sae",
+                _ => "wpa-psk",
+            };
+            args.extend(["wifi-sec.key-mgmt", key_mgmt]);
+            if let Some(password) = &profile.password {
+                args.extend(["wifi-sec.psk", password]);
+            }
+        }
+        ProfileSecurity::Enterprise => {
+            let eap = profile
+                .eap
+                .as_ref()
+                .ok_or_else(|| "The 802.1X settings are missing".to_string())?;
+            args.extend(["wifi-sec.key-mgmt", "wpa-eap", "802-1x.eap", &eap.method]);
+            let optional = [
+                ("802-1x.identity", &eap.identity),
+                ("802-1x.anonymous-identity", &eap.anonymous_identity),
+                ("802-1x.phase2-auth", &eap.phase2),
+                ("802-1x.password", &eap.password),
+                ("802-1x.ca-cert", &eap.ca_cert),
+            ];
+            for (setting, value) in optional {
+                if let Some(value) = value {
+                    args.extend([setting, value]);
+                }
+            }
+        }
+    }
+
+    let output = Command::new("nmcli")
+        .args(&args)
+        .output()
+        .map_err(|e| format!("Error running nmcli: {}", e))?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(name: &str, password: Option<&str>) -> PortableProfile {
+        PortableProfile {
+            name: name.to_string(),
+            ssid: name.to_string(),
+            security: ProfileSecurity::WpaPsk,
+            autoconnect: true,
+            hidden: false,
+            password: password.map(str::to_string),
+            eap: None,
+        }
+    }
+
+    /// A bundle of `profiles` whose secrets are encrypted with `passphrase`
+    fn bundle(
+        profiles: &[PortableProfile],
+        secrets: &[PortableProfile],
+        passphrase: &str,
+    ) -> String {
+        let secrets: Vec<ProfileSecrets> = secrets.iter().map(PortableProfile::secrets).collect();
+        let bundle = ProfileBundle {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            created: "2026-10-19T12:00:00+00:00".to_string(),
+            profiles: profiles
+                .iter()
+                .map(PortableProfile::without_secrets)
+                .collect(),
+            secrets: Some(encrypt_secrets(&secrets, passphrase).unwrap()),
+        };
+        serde_json::to_string(&bundle).unwrap()
+    }
+
+    fn conflict(name: &str, same_name: bool, same_ssid: bool) -> ProfileConflict {
+        ProfileConflict {
+            name: name.to_string(),
+            uuid: format!("uuid-{}", name),
+            same_name,
+            same_ssid,
+        }
+    }
+
+    #[test]
+    fn decrypts_the_secrets_of_a_bundle() {
+        let mut enterprise = profile("Campus", None);
+        enterprise.security = ProfileSecurity::Enterprise;
+        enterprise.eap = Some(EapSettings {
+            method: "peap".to_string(),
+            identity: Some("alice".to_string()),
+            anonymous_identity: None,
+            phase2: Some("mschapv2".to_string()),
+            password: Some("inner secret".to_string()),
+            ca_cert: None,
+        });
+        let profiles = [profile("Home", Some("home password")), enterprise];
+        let content = bundle(&profiles, &profiles, "correct horse");
+        assert!(!content.contains("home password"));
+        assert!(!content.contains("inner secret"));
+
+        let mut warnings = Vec::new();
+        let read = read_bundle(&content, Some("correct horse"), &mut warnings).unwrap();
+        assert_eq!(read, profiles);
+        assert!(warnings.is_empty());
+
+        // Without the passphrase the profiles are read without passwords
+        let read = read_bundle(&content, None, &mut warnings).unwrap();
+        assert!(read.iter().all(|p| !p.has_secrets()));
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn rejects_a_wrong_passphrase() {
+        let profiles = [profile("Home", Some("home password"))];
+        let content = bundle(&profiles, &profiles, "correct horse");
+        let result = read_bundle(&content, Some("battery staple"), &mut Vec::new());
+        assert!(matches!(result, Err(ExchangeError::WrongPassphrase)));
+    }
+
+    #[test]
+    fn rejects_excessive_key_derivation_costs() {
+        let profiles = [profile("Home", Some("home password"))];
+        let content = bundle(&profiles, &profiles, "correct horse");
+        for (cost, value) in [
+            ("memory_kib", MAX_MEMORY_KIB + 1),
+            ("iterations", u32::MAX),
+            ("parallelism", MAX_PARALLELISM + 1),
+        ] {
+            let mut tampered: serde_json::Value = serde_json::from_str(&content).unwrap();
+            tampered["secrets"][cost] = value.into();
+            let result = read_bundle(
+                &tampered.to_string(),
+                Some("correct horse"),
+                &mut Vec::new(),
+            );
+            assert!(
+                matches!(result, Err(ExchangeError::InvalidFile(_))),
+                "{}",
+                cost
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_secrets_of_other_profiles() {
+        let profiles = [
+            profile("Home", Some("home")),
+            profile("Office", Some("office")),
+        ];
+        let content = bundle(&profiles, &profiles[..1], "correct horse");
+        let result = read_bundle(&content, Some("correct horse"), &mut Vec::new());
+        assert!(matches!(result, Err(ExchangeError::InvalidFile(_))));
+    }
+
+    #[test]
+    fn rejects_foreign_and_newer_bundles() {
+        let newer = r#"{"format":"wiblue-profiles","version":2,"created":"","profiles":[]}"#;
+        let foreign = r#"{"format":"other","version":1,"created":"","profiles":[]}"#;
+        for content in [newer, foreign, "{"] {
+            let result = read_bundle(content, None, &mut Vec::new());
+            assert!(matches!(result, Err(ExchangeError::InvalidFile(_))));
+        }
+    }
+
+    #[test]
+    fn replaces_a_single_saved_profile() {
+        let by_name = [
+            conflict("Other", false, true),
+            conflict("Home", true, false),
+        ];
+        assert_eq!(replaced_profile(&by_name).unwrap().name, "Home");
+
+        let by_ssid = [conflict("Home (old)", false, true)];
+        assert_eq!(replaced_profile(&by_ssid).unwrap().name, "Home (old)");
+
+        let ambiguous = [
+            conflict("Home 1", false, true),
+            conflict("Home 2", false, true),
+        ];
+        assert!(replaced_profile(&ambiguous).is_none());
+        assert!(replaced_profile(&[]).is_none());
+    }
+
+    #[test]
+    fn tells_wep_keys_from_passphrases() {
+        assert_eq!(wep_key_type("abcde"), "1");
+        assert_eq!(wep_key_type("0123456789abc"), "1");
+        assert_eq!(wep_key_type("0123456789"), "1");
+        assert_eq!(wep_key_type("0123456789ABCDEF0123456789"), "1");
+        assert_eq!(wep_key_type("my wep passphrase"), "2");
+        assert_eq!(wep_key_type("abcdefghij"), "2");
+        assert_eq!(wep_key_type("abcd"), "2");
+    }
+
+    #[test]
+    fn finds_a_free_name() {
+        let taken = HashSet::from(["Home".to_string(), "Home 2".to_string()]);
+        assert_eq!(free_name("Home", &taken), "Home 3");
+        assert_eq!(free_name("Office", &taken), "Office");
+    }
+}
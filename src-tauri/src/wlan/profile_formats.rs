@@ -0,0 +1,813 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    networkmanager_error::ExchangeError,
+    profile_exchange::{EapSettings, PortableProfile, ProfileSecurity},
+};
+
+/// File formats Wi-Fi profiles can be imported from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileFormat {
+    /// Portable JSON bundle written by the export
+    Bundle,
+    /// NetworkManager keyfile (`.nmconnection`)
+    Keyfile,
+    /// `network={...}` blocks of a `wpa_supplicant.conf`
+    WpaSupplicant,
+    /// iwd network file (`.psk`, `.open`, `.8021x`)
+    Iwd,
+    /// Windows `netsh wlan export profile` XML
+    Netsh,
+}
+
+/// Guesses the format of a file from its extension, then its content
+pub fn detect_format(path: &Path, content: &str) -> Option<ProfileFormat> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "json" => return Some(ProfileFormat::Bundle),
+        "nmconnection" => return Some(ProfileFormat::Keyfile),
+        "psk" | "open" | "8021x" => return Some(ProfileFormat::Iwd),
+        "xml" => return Some(ProfileFormat::Netsh),
+        _ => {}
+    }
+
+    let content = content.trim_start();
+    if content.starts_with('{') {
+        Some(ProfileFormat::Bundle)
+    } else if content.starts_with("<?xml") || content.starts_with("<WLANProfile") {
+        Some(ProfileFormat::Netsh)
+    } else if content.contains("network={") {
+        Some(ProfileFormat::WpaSupplicant)
+    } else if content.contains("[connection]") {
+        Some(ProfileFormat::Keyfile)
+    } else {
+        None
+    }
+}
+
+/// Reads the profiles of a file in one of the foreign formats
+///
+/// Bundles are read by [`super::profile_exchange::read_profiles`] as they
+/// may need a passphrase.
+///
+/// # Arguments
+/// * `format` - format of the file, not [`ProfileFormat::Bundle`]
+/// * `path` - the file, iwd encodes the SSID in its name
+/// * `content` - content of the file
+/// * `warnings` - receives what could not be imported
+pub fn parse_profiles(
+    format: ProfileFormat,
+    path: &Path,
+    content: &str,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<PortableProfile>, ExchangeError> {
+    let file = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut warn = |message: String| warnings.push(format!("{}: {}", file, message));
+
+    match format {
+        ProfileFormat::Keyfile => Ok(parse_keyfile(content, &mut warn)?.into_iter().collect()),
+        ProfileFormat::WpaSupplicant => Ok(parse_wpa_supplicant(content, &mut warn)),
+        ProfileFormat::Iwd => Ok(vec![parse_iwd(path, content, &mut warn)?]),
+        ProfileFormat::Netsh => Ok(parse_netsh(content, &mut warn)?.into_iter().collect()),
+        ProfileFormat::Bundle => Err(ExchangeError::InvalidFile(
+            "Bundles are not read here".to_string(),
+        )),
+    }
+}
+
+/// Sections of an INI file as used by NetworkManager and iwd
+fn parse_ini(content: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut section = String::new();
+
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim().to_string();
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            sections
+                .entry(section.clone())
+                .or_default()
+                .insert(key.trim().to_string(), unescape_ini(value.trim()));
+        }
+    }
+
+    sections
+}
+
+/// Resolves the escapes of GLib key files (`\s`, `\n`, `\t`, `\\`)
+fn unescape_ini(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => result.push(' '),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// SSIDs are bytes, non UTF-8 ones cannot be passed to nmcli as text
+fn ssid_from_bytes(bytes: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(bytes).map_err(|_| "SSID is not valid UTF-8".to_string())
+}
+
+fn parse_bool(value: Option<&String>, default: bool) -> bool {
+    match value.map(|v| v.to_lowercase()).as_deref() {
+        Some("true" | "yes" | "1") => true,
+        Some("false" | "no" | "0") => false,
+        _ => default,
+    }
+}
+
+/// Strips the `file://` prefix NetworkManager stores certificate paths with
+fn certificate_path(value: Option<&String>) -> Option<String> {
+    value
+        .map(|v| v.strip_prefix("file://").unwrap_or(v).to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Reads a NetworkManager keyfile, `None` if it is not a Wi-Fi client profile
+fn parse_keyfile(
+    content: &str,
+    warn: &mut impl FnMut(String),
+) -> Result<Option<PortableProfile>, ExchangeError> {
+    let sections = parse_ini(content);
+    let empty = HashMap::new();
+    let connection = sections
+        .get("connection")
+        .ok_or_else(|| ExchangeError::InvalidFile("No [connection] section".to_string()))?;
+    let wifi = sections.get("wifi").unwrap_or(&empty);
+    let security = sections.get("wifi-security").unwrap_or(&empty);
+    let eap = sections.get("802-1x").unwrap_or(&empty);
+
+    let name = connection.get("id").cloned().unwrap_or_default();
+    if !matches!(
+        connection.get("type").map(String::as_str),
+        Some("wifi" | "802-11-wireless")
+    ) {
+        warn(format!("{} is not a Wi-Fi profile, skipped", name));
+        return Ok(None);
+    }
+    if wifi.get("mode").is_some_and(|mode| mode == "ap") {
+        warn(format!("{} is an access point profile, skipped", name));
+        return Ok(None);
+    }
+
+    // Either text or a list of bytes ("72;111;109;101;")
+    let raw_ssid = wifi.get("ssid").cloned().unwrap_or_default();
+    let bytes: Option<Vec<u8>> = raw_ssid
+        .strip_suffix(';')
+        .and_then(|list| list.split(';').map(|b| b.parse().ok()).collect());
+    let ssid = match bytes {
+        Some(bytes) => ssid_from_bytes(bytes).map_err(ExchangeError::InvalidFile)?,
+        None => raw_ssid,
+    };
+    if ssid.is_empty() {
+        return Err(ExchangeError::InvalidFile(format!("{} has no SSID", name)));
+    }
+
+    let (security, password) = match security.get("key-mgmt").map(String::as_str) {
+        None | Some("owe") => (ProfileSecurity::Open, None),
+        Some("none") => {
+            let index = security.get("wep-tx-keyidx").map_or("0", String::as_str);
+            (
+                ProfileSecurity::Wep,
+                security.get(&format!("wep-key{}", index)).cloned(),
+            )
+        }
+        Some("wpa-psk") => (ProfileSecurity::WpaPsk, security.get("psk").cloned()),
+        Some("sae") => (ProfileSecurity::Sae, security.get("psk").cloned()),
+        Some("wpa-eap" | "wpa-eap-suite-b-192" | "ieee8021x") => {
+            (ProfileSecurity::Enterprise, None)
+        }
+        Some(other) => {
+            warn(format!(
+                "{} uses unsupported key management {}",
+                name, other
+            ));
+            return Ok(None);
+        }
+    };
+    if matches!(security, ProfileSecurity::WpaPsk | ProfileSecurity::Sae) && password.is_none() {
+        warn(format!("{} does not store its password", name));
+    }
+
+    let eap = (security == ProfileSecurity::Enterprise).then(|| EapSettings {
+        method: eap
+            .get("eap")
+            .and_then(|methods| methods.split(';').next())
+            .unwrap_or("peap")
+            .to_string(),
+        identity: eap.get("identity").cloned(),
+        anonymous_identity: eap.get("anonymous-identity").cloned(),
+        phase2: eap
+            .get("phase2-auth")
+            .or_else(|| eap.get("phase2-autheap"))
+            .cloned(),
+        password: eap.get("password").cloned(),
+        ca_cert: certificate_path(eap.get("ca-cert")),
+    });
+
+    Ok(Some(PortableProfile {
+        name: if name.is_empty() { ssid.clone() } else { name },
+        ssid,
+        security,
+        autoconnect: parse_bool(connection.get("autoconnect"), true),
+        hidden: parse_bool(wifi.get("hidden"), false),
+        password,
+        eap,
+    }))
+}
+
+/// Reads the `network={...}` blocks of a `wpa_supplicant.conf`
+fn parse_wpa_supplicant(content: &str, warn: &mut impl FnMut(String)) -> Vec<PortableProfile> {
+    let mut profiles = Vec::new();
+    let mut block: Option<HashMap<String, String>> = None;
+
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match &mut block {
+            None if line.starts_with("network") && line.ends_with('{') => {
+                block = Some(HashMap::new());
+            }
+            None => {}
+            Some(_) if line == "}" => {
+                if let Some(profile) = wpa_network(&block.take().unwrap_or_default(), warn) {
+                    profiles.push(profile);
+                }
+            }
+            Some(fields) => {
+                if let Some((key, value)) = line.split_once('=') {
+                    fields.insert(key.trim().to_string(), value.trim().to_string());
+                }
+            }
+        }
+    }
+
+    profiles
+}
+
+/// Quoted values are text, unquoted ones hex (SSIDs) or raw keys (PSKs)
+fn wpa_value(fields: &HashMap<String, String>, key: &str) -> Option<String> {
+    let value = fields.get(key)?;
+    Some(
+        value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value)
+            .to_string(),
+    )
+}
+
+fn wpa_network(
+    fields: &HashMap<String, String>,
+    warn: &mut impl FnMut(String),
+) -> Option<PortableProfile> {
+    let raw_ssid = fields.get("ssid")?;
+    let ssid = match raw_ssid.starts_with('"') {
+        true => wpa_value(fields, "ssid")?,
+        false => match decode_hex(raw_ssid).map(ssid_from_bytes) {
+            Some(Ok(ssid)) => ssid,
+            _ => {
+                warn(format!("Network with SSID {} skipped, not text", raw_ssid));
+                return None;
+            }
+        },
+    };
+
+    // wpa_supplicant defaults to "WPA-PSK WPA-EAP"
+    let key_mgmt = fields
+        .get("key_mgmt")
+        .map(|k| k.to_uppercase())
+        .unwrap_or_else(|| "WPA-PSK WPA-EAP".to_string());
+    let methods: Vec<&str> = key_mgmt.split_whitespace().collect();
+    let security = if methods
+        .iter()
+        .any(|m| m.contains("EAP") || *m == "IEEE8021X")
+    {
+        match fields.contains_key("eap") || fields.contains_key("identity") {
+            true => ProfileSecurity::Enterprise,
+            false if fields.contains_key("psk") => ProfileSecurity::WpaPsk,
+            false => ProfileSecurity::Enterprise,
+        }
+    } else if methods.iter().any(|m| m.starts_with("WPA-PSK")) {
+        // Transition networks accept WPA2 too
+        ProfileSecurity::WpaPsk
+    } else if methods.contains(&"SAE") {
+        ProfileSecurity::Sae
+    } else if fields.keys().any(|k| k.starts_with("wep_key")) {
+        ProfileSecurity::Wep
+    } else {
+        ProfileSecurity::Open
+    };
+
+    let password = match security {
+        ProfileSecurity::WpaPsk | ProfileSecurity::Sae => {
+            wpa_value(fields, "psk").or_else(|| wpa_value(fields, "sae_password"))
+        }
+        ProfileSecurity::Wep => {
+            let index = fields.get("wep_tx_keyidx").map_or("0", String::as_str);
+            wpa_value(fields, &format!("wep_key{}", index))
+        }
+        _ => None,
+    };
+    if matches!(security, ProfileSecurity::WpaPsk | ProfileSecurity::Sae) && password.is_none() {
+        warn(format!("{} has no password", ssid));
+    }
+
+    let eap = (security == ProfileSecurity::Enterprise).then(|| EapSettings {
+        method: fields
+            .get("eap")
+            .and_then(|m| m.split_whitespace().next())
+            .unwrap_or("PEAP")
+            .to_lowercase(),
+        identity: wpa_value(fields, "identity"),
+        anonymous_identity: wpa_value(fields, "anonymous_identity"),
+        // "auth=MSCHAPV2" or "autheap=MSCHAPV2"
+        phase2: wpa_value(fields, "phase2")
+            .and_then(|p| p.split_once('=').map(|(_, method)| method.to_lowercase())),
+        password: wpa_value(fields, "password"),
+        ca_cert: wpa_value(fields, "ca_cert"),
+    });
+
+    Some(PortableProfile {
+        name: wpa_value(fields, "id_str").unwrap_or_else(|| ssid.clone()),
+        ssid,
+        security,
+        autoconnect: fields.get("disabled").map(String::as_str) != Some("1"),
+        hidden: fields.get("scan_ssid").map(String::as_str) == Some("1"),
+        password,
+        eap,
+    })
+}
+
+/// Reads an iwd network file, the SSID is its name, hex encoded after `=`
+/// if it is not plain text
+fn parse_iwd(
+    path: &Path,
+    content: &str,
+    warn: &mut impl FnMut(String),
+) -> Result<PortableProfile, ExchangeError> {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ssid = match stem.strip_prefix('=') {
+        Some(hex) => decode_hex(hex)
+            .ok_or_else(|| ExchangeError::InvalidFile("Invalid hex SSID".to_string()))
+            .and_then(|bytes| ssid_from_bytes(bytes).map_err(ExchangeError::InvalidFile))?,
+        None => stem,
+    };
+    if ssid.is_empty() {
+        return Err(ExchangeError::InvalidFile(
+            "No SSID in the file name".to_string(),
+        ));
+    }
+
+    let sections = parse_ini(content);
+    let empty = HashMap::new();
+    let settings = sections.get("Settings").unwrap_or(&empty);
+    let security_section = sections.get("Security").unwrap_or(&empty);
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let (security, password, eap) = match extension.as_str() {
+        "open" => (ProfileSecurity::Open, None, None),
+        "psk" => {
+            let password = security_section
+                .get("Passphrase")
+                .or_else(|| security_section.get("PreSharedKey"))
+                .cloned();
+            if password.is_none() {
+                warn(format!("{} has no passphrase", ssid));
+            }
+            (ProfileSecurity::WpaPsk, password, None)
+        }
+        "8021x" => {
+            let value = |key: &str| security_section.get(key).cloned();
+            let method = value("EAP-Method").unwrap_or_else(|| "PEAP".to_string());
+            let inner = |key: &str| value(&format!("EAP-{}-Phase2-{}", method, key));
+            // The outer identity is anonymous if there is an inner one
+            let (identity, anonymous_identity) = match inner("Identity") {
+                Some(identity) => (Some(identity), value("EAP-Identity")),
+                None => (value("EAP-Identity"), None),
+            };
+            let eap = EapSettings {
+                method: method.to_lowercase(),
+                identity,
+                anonymous_identity,
+                phase2: inner("Method").map(|m| m.to_lowercase()),
+                password: inner("Password").or_else(|| value("EAP-Password")),
+                ca_cert: value(&format!("EAP-{}-CACert", method)).or_else(|| value("EAP-CACert")),
+            };
+            (ProfileSecurity::Enterprise, None, Some(eap))
+        }
+        other => {
+            return Err(ExchangeError::InvalidFile(format!(
+                "Unknown iwd network type {}",
+                other
+            )))
+        }
+    };
+
+    Ok(PortableProfile {
+        name: ssid.clone(),
+        ssid,
+        security,
+        autoconnect: parse_bool(settings.get("AutoConnect"), true),
+        hidden: parse_bool(settings.get("Hidden"), false),
+        password,
+        eap,
+    })
+}
+
+/// Reads a profile exported with `netsh wlan export profile`
+///
+/// Keys are only usable if they were exported with `key=clear`, otherwise
+/// Windows encrypted them for the exporting machine.
+fn parse_netsh(
+    content: &str,
+    warn: &mut impl FnMut(String),
+) -> Result<Option<PortableProfile>, ExchangeError> {
+    let document = roxmltree::Document::parse(content)
+        .map_err(|e| ExchangeError::InvalidFile(e.to_string()))?;
+    let root = document.root_element();
+    let text = |path: &[&str]| -> Option<String> {
+        let mut node = root;
+        for name in path {
+            node = node
+                .children()
+                .find(|child| child.tag_name().name() == *name)?;
+        }
+        node.text().map(|t| t.trim().to_string())
+    };
+
+    let name = text(&["name"]).unwrap_or_default();
+    let ssid = match text(&["SSIDConfig", "SSID", "name"]) {
+        Some(ssid) => ssid,
+        None => text(&["SSIDConfig", "SSID", "hex"])
+            .and_then(|hex| decode_hex(&hex))
+            .map(ssid_from_bytes)
+            .transpose()
+            .map_err(ExchangeError::InvalidFile)?
+            .ok_or_else(|| ExchangeError::InvalidFile(format!("{} has no SSID", name)))?,
+    };
+
+    let security_path = ["MSM", "security", "authEncryption"];
+    let auth = text(&[security_path.as_slice(), &["authentication"]].concat())
+        .unwrap_or_else(|| "open".to_string());
+    let encryption =
+        text(&[security_path.as_slice(), &["encryption"]].concat()).unwrap_or_default();
+    let security = match auth.to_uppercase().as_str() {
+        "OPEN" | "SHARED" if encryption.eq_ignore_ascii_case("WEP") => ProfileSecurity::Wep,
+        "OPEN" | "OWE" => ProfileSecurity::Open,
+        "WPAPSK" | "WPA2PSK" => ProfileSecurity::WpaPsk,
+        "WPA3SAE" => ProfileSecurity::Sae,
+        _ => {
+            warn(format!(
+                "{} uses {} authentication, its settings cannot be read from the export",
+                name, auth
+            ));
+            return Ok(None);
+        }
+    };
+
+    let key_path = ["MSM", "security", "sharedKey"];
+    let protected = text(&[key_path.as_slice(), &["protected"]].concat())
+        .is_some_and(|p| p.eq_ignore_ascii_case("true"));
+    let password = match text(&[key_path.as_slice(), &["keyMaterial"]].concat()) {
+        Some(_) if protected => {
+            warn(format!(
+                "The key of {} is encrypted by Windows, export it with key=clear",
+                name
+            ));
+            None
+        }
+        key => key,
+    };
+
+    Ok(Some(PortableProfile {
+        name: if name.is_empty() { ssid.clone() } else { name },
+        ssid,
+        security,
+        autoconnect: text(&["connectionMode"]).as_deref() != Some("manual"),
+        hidden: text(&["SSIDConfig", "nonBroadcast"]).as_deref() == Some("true"),
+        password,
+        eap: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(
+        format: ProfileFormat,
+        file: &str,
+        content: &str,
+    ) -> (Vec<PortableProfile>, Vec<String>) {
+        let mut warnings = Vec::new();
+        let profiles = parse_profiles(format, Path::new(file), content, &mut warnings).unwrap();
+        (profiles, warnings)
+    }
+
+    const KEYFILE: &str = "[connection]
+id=Home Network
+uuid=3b1f0b4e-8f0c-4d7a-9a55-2f5a0b6c1d2e
+type=wifi
+autoconnect=false
+
+[wifi]
+mode=infrastructure
+ssid=Home\\sNetwork
+hidden=true
+
+[wifi-security]
+auth-alg=open
+key-mgmt=wpa-psk
+psk=secret\\spassword
+
+[ipv4]
+method=auto
+";
+
+    const KEYFILE_EAP: &str = "[connection]
+id=eduroam
+type=802-11-wireless
+
+[wifi]
+ssid=101;100;117;114;111;97;109;
+
+[wifi-security]
+key-mgmt=wpa-eap
+
+[802-1x]
+eap=peap;
+identity=alice@example.edu
+anonymous-identity=anonymous@example.edu
+phase2-auth=mschapv2
+password=inner
+ca-cert=file:///etc/ssl/certs/ca.pem
+";
+
+    const WPA_SUPPLICANT: &str = r#"ctrl_interface=DIR=/var/run/wpa_supplicant GROUP=netdev
+update_config=1
+
+network={
+	ssid="Home"
+	psk="home password"
+	scan_ssid=1
+}
+
+network={
+	ssid=4f6666696365
+	key_mgmt=WPA-EAP
+	eap=PEAP
+	identity="bob"
+	password="inner"
+	phase2="auth=MSCHAPV2"
+	disabled=1
+}
+
+network={
+	ssid="Cafe"
+	key_mgmt=NONE
+}
+
+network={
+	ssid="Old"
+	key_mgmt=NONE
+	wep_key0="abcde"
+	wep_tx_keyidx=0
+}
+"#;
+
+    const NETSH: &str = r#"<?xml version="1.0"?>
+<WLANProfile xmlns="http://www.microsoft.com/networking/WLAN/profile/v1">
+	<name>Home</name>
+	<SSIDConfig>
+		<SSID>
+			<hex>486F6D65</hex>
+			<name>Home</name>
+		</SSID>
+		<nonBroadcast>true</nonBroadcast>
+	</SSIDConfig>
+	<connectionType>ESS</connectionType>
+	<connectionMode>manual</connectionMode>
+	<MSM>
+		<security>
+			<authEncryption>
+				<authentication>WPA2PSK</authentication>
+				<encryption>AES</encryption>
+				<useOneX>false</useOneX>
+			</authEncryption>
+			<sharedKey>
+				<keyType>passPhrase</keyType>
+				<protected>false</protected>
+				<keyMaterial>home password</keyMaterial>
+			</sharedKey>
+		</security>
+	</MSM>
+</WLANProfile>
+"#;
+
+    #[test]
+    fn detects_formats() {
+        let detect = |file: &str, content: &str| detect_format(Path::new(file), content);
+        assert_eq!(
+            detect("Home.nmconnection", ""),
+            Some(ProfileFormat::Keyfile)
+        );
+        assert_eq!(detect("Home.psk", ""), Some(ProfileFormat::Iwd));
+        assert_eq!(detect("profiles.json", ""), Some(ProfileFormat::Bundle));
+        assert_eq!(detect("Wi-Fi-Home.xml", ""), Some(ProfileFormat::Netsh));
+        assert_eq!(detect("Home", KEYFILE), Some(ProfileFormat::Keyfile));
+        assert_eq!(
+            detect("wpa_supplicant.conf", WPA_SUPPLICANT),
+            Some(ProfileFormat::WpaSupplicant)
+        );
+        assert_eq!(detect("export", NETSH), Some(ProfileFormat::Netsh));
+        assert_eq!(detect("notes.txt", "hello"), None);
+    }
+
+    #[test]
+    fn reads_keyfiles() {
+        let (profiles, warnings) = parse(ProfileFormat::Keyfile, "Home.nmconnection", KEYFILE);
+        assert!(warnings.is_empty());
+        assert_eq!(
+            profiles,
+            [PortableProfile {
+                name: "Home Network".to_string(),
+                ssid: "Home Network".to_string(),
+                security: ProfileSecurity::WpaPsk,
+                autoconnect: false,
+                hidden: true,
+                password: Some("secret password".to_string()),
+                eap: None,
+            }]
+        );
+
+        let (profiles, _) = parse(ProfileFormat::Keyfile, "eduroam.nmconnection", KEYFILE_EAP);
+        assert_eq!(profiles[0].ssid, "eduroam");
+        assert_eq!(profiles[0].security, ProfileSecurity::Enterprise);
+        let eap = profiles[0].eap.as_ref().unwrap();
+        assert_eq!(eap.method, "peap");
+        assert_eq!(eap.identity.as_deref(), Some("alice@example.edu"));
+        assert_eq!(eap.phase2.as_deref(), Some("mschapv2"));
+        assert_eq!(eap.password.as_deref(), Some("inner"));
+        assert_eq!(eap.ca_cert.as_deref(), Some("/etc/ssl/certs/ca.pem"));
+    }
+
+    #[test]
+    fn skips_keyfiles_of_other_connections() {
+        let ethernet = "[connection]\nid=Wired\ntype=ethernet\n";
+        let (profiles, warnings) = parse(ProfileFormat::Keyfile, "Wired.nmconnection", ethernet);
+        assert!(profiles.is_empty());
+        assert_eq!(warnings.len(), 1);
+
+        let hotspot = KEYFILE.replace("mode=infrastructure", "mode=ap");
+        let (profiles, warnings) = parse(ProfileFormat::Keyfile, "Hotspot.nmconnection", &hotspot);
+        assert!(profiles.is_empty());
+        assert_eq!(warnings.len(), 1);
+
+        let mut warnings = Vec::new();
+        let invalid = parse_profiles(
+            ProfileFormat::Keyfile,
+            Path::new("x"),
+            "[wifi]\n",
+            &mut warnings,
+        );
+        assert!(matches!(invalid, Err(ExchangeError::InvalidFile(_))));
+    }
+
+    #[test]
+    fn reads_wpa_supplicant_networks() {
+        let (profiles, warnings) = parse(
+            ProfileFormat::WpaSupplicant,
+            "wpa_supplicant.conf",
+            WPA_SUPPLICANT,
+        );
+        assert!(warnings.is_empty());
+        let summary: Vec<(&str, ProfileSecurity, Option<&str>)> = profiles
+            .iter()
+            .map(|p| (p.ssid.as_str(), p.security, p.password.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Home", ProfileSecurity::WpaPsk, Some("home password")),
+                ("Office", ProfileSecurity::Enterprise, None),
+                ("Cafe", ProfileSecurity::Open, None),
+                ("Old", ProfileSecurity::Wep, Some("abcde")),
+            ]
+        );
+        assert!(profiles[0].hidden);
+        assert!(!profiles[1].autoconnect);
+        let eap = profiles[1].eap.as_ref().unwrap();
+        assert_eq!(eap.method, "peap");
+        assert_eq!(eap.identity.as_deref(), Some("bob"));
+        assert_eq!(eap.phase2.as_deref(), Some("mschapv2"));
+    }
+
+    #[test]
+    fn reads_iwd_networks() {
+        let psk = "[Security]\nPassphrase=home password\n\n[Settings]\nAutoConnect=false\n";
+        let (profiles, _) = parse(ProfileFormat::Iwd, "Home.psk", psk);
+        assert_eq!(profiles[0].ssid, "Home");
+        assert_eq!(profiles[0].security, ProfileSecurity::WpaPsk);
+        assert_eq!(profiles[0].password.as_deref(), Some("home password"));
+        assert!(!profiles[0].autoconnect);
+
+        // SSIDs that are not plain file names are hex encoded
+        let (profiles, _) = parse(ProfileFormat::Iwd, "=4361666520f09f8d95.open", "");
+        assert_eq!(profiles[0].ssid, "Cafe 🍕");
+        assert_eq!(profiles[0].security, ProfileSecurity::Open);
+
+        let eap = "[Security]
+EAP-Method=PEAP
+EAP-Identity=anonymous@example.edu
+EAP-PEAP-CACert=/etc/ssl/certs/ca.pem
+EAP-PEAP-Phase2-Method=MSCHAPV2
+EAP-PEAP-Phase2-Identity=alice@example.edu
+EAP-PEAP-Phase2-Password=inner
+";
+        let (profiles, _) = parse(ProfileFormat::Iwd, "eduroam.8021x", eap);
+        assert_eq!(
+            profiles[0].eap,
+            Some(EapSettings {
+                method: "peap".to_string(),
+                identity: Some("alice@example.edu".to_string()),
+                anonymous_identity: Some("anonymous@example.edu".to_string()),
+                phase2: Some("mschapv2".to_string()),
+                password: Some("inner".to_string()),
+                ca_cert: Some("/etc/ssl/certs/ca.pem".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn reads_netsh_exports() {
+        let (profiles, warnings) = parse(ProfileFormat::Netsh, "Wi-Fi-Home.xml", NETSH);
+        assert!(warnings.is_empty());
+        assert_eq!(
+            profiles,
+            [PortableProfile {
+                name: "Home".to_string(),
+                ssid: "Home".to_string(),
+                security: ProfileSecurity::WpaPsk,
+                autoconnect: false,
+                hidden: true,
+                password: Some("home password".to_string()),
+                eap: None,
+            }]
+        );
+
+        // Keys encrypted by Windows cannot be used
+        let protected = NETSH.replace("<protected>false", "<protected>true");
+        let (profiles, warnings) = parse(ProfileFormat::Netsh, "Wi-Fi-Home.xml", &protected);
+        assert_eq!(profiles[0].password, None);
+        assert_eq!(warnings.len(), 1);
+
+        let enterprise = NETSH.replace("WPA2PSK", "WPA2");
+        let (profiles, warnings) = parse(ProfileFormat::Netsh, "Wi-Fi-Home.xml", &enterprise);
+        assert!(profiles.is_empty());
+        assert_eq!(warnings.len(), 1);
+    }
+}
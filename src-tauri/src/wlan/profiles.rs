@@ -0,0 +1,207 @@
+use std::{collections::HashSet, process::Command};
+
+use serde::Serialize;
+
+use super::{get_networks::split_terse_line, networkmanager_error::ProfileError};
+
+/// Connection type NetworkManager uses for Wi-Fi profiles
+pub const WIFI_CONNECTION_TYPE: &str = "802-11-wireless";
+
+/// A connection profile saved in NetworkManager
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedProfile {
+    /// Profile name, for Wi-Fi usually the SSID
+    pub name: String,
+    pub uuid: String,
+    /// NetworkManager connection type (e.g. `802-11-wireless`, `802-3-ethernet`)
+    pub connection_type: String,
+    /// Device the profile is active on, if any
+    pub device: Option<String>,
+    pub active: bool,
+    /// Whether NetworkManager connects automatically when the network is in range
+    pub autoconnect: bool,
+}
+
+/// Lists the connection profiles saved in NetworkManager
+///
+/// # Arguments
+/// * `wifi_only` - only return Wi-Fi profiles
+///
+/// # Returns
+/// - `Ok(Vec<SavedProfile>)` active profiles first, then by name
+/// - `Err(ProfileError)` if nmcli fails
+pub fn get_profiles(wifi_only: bool) -> Result<Vec<SavedProfile>, ProfileError> {
+    let output = Command::new("nmcli")
+        .args([
+            "-t",
+            "-f",
+            "NAME,UUID,TYPE,DEVICE,ACTIVE,AUTOCONNECT",
+            "connection",
+            "show",
+        ])
+        .output()
+        .map_err(|_| ProfileError::CommandExecutionFailure)?;
+
+    if !output.status.success() {
+        eprintln!(
+            "nmcli command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(ProfileError::CommandExecutionFailure);
+    }
+
+    let mut profiles: Vec<SavedProfile> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(split_terse_line)
+        .filter(|fields| fields.len() >= 6)
+        .map(|fields| SavedProfile {
+            name: fields[0].clone(),
+            uuid: fields[1].clone(),
+            connection_type: fields[2].clone(),
+            device: Some(fields[3].clone()).filter(|d| !d.is_empty() && d != "--"),
+            active: fields[4] == "yes",
+            autoconnect: fields[5] == "yes",
+        })
+        .filter(|profile| !wifi_only || profile.connection_type == WIFI_CONNECTION_TYPE)
+        .collect();
+
+    profiles.sort_by(|a, b| b.active.cmp(&a.active).then(a.name.cmp(&b.name)));
+    Ok(profiles)
+}
+
+/// SSIDs of the saved Wi-Fi profiles, the networks that can be joined
+/// without asking for a password
+///
+/// # Returns
+/// - `Ok(HashSet<String>)` with the SSIDs, profile names can differ from them
+/// - `Err(ProfileError)` if nmcli fails
+pub fn get_profile_ssids() -> Result<HashSet<String>, ProfileError> {
+    let mut ssids = HashSet::new();
+
+    for profile in get_profiles(true)? {
+        if let Some(ssid) = get_profile_ssid(&profile.uuid)? {
+            ssids.insert(ssid);
+        }
+    }
+
+    Ok(ssids)
+}
+
+/// SSID of a saved Wi-Fi profile, `None` if it has none or is gone
+pub fn get_profile_ssid(uuid: &str) -> Result<Option<String>, ProfileError> {
+    let output = Command::new("nmcli")
+        .args([
+            "-g",
+            "802-11-wireless.ssid",
+            "connection",
+            "show",
+            "uuid",
+            uuid,
+        ])
+        .output()
+        .map_err(|_| ProfileError::CommandExecutionFailure)?;
+
+    let ssid = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(Some(ssid).filter(|ssid| output.status.success() && !ssid.is_empty()))
+}
+
+/// Finds a profile by name or UUID, the first one if names are not unique
+fn find_profile(profile: &str) -> Result<SavedProfile, ProfileError> {
+    get_profiles(false)?
+        .into_iter()
+        .find(|p| p.uuid == profile || p.name == profile)
+        .ok_or(ProfileError::NoSuchProfile)
+}
+
+/// Resolves a profile name or UUID to the UUID, names are not unique
+fn profile_uuid(profile: &str) -> Result<String, ProfileError> {
+    find_profile(profile).map(|p| p.uuid)
+}
+
+/// Runs `nmcli connection <args>`
+fn run_connection_command(args: &[&str]) -> Result<(), ProfileError> {
+    let output = Command::new("nmcli")
+        .arg("connection")
+        .args(args)
+        .output()
+        .map_err(|_| ProfileError::CommandExecutionFailure)?;
+
+    if !output.status.success() {
+        eprintln!(
+            "nmcli command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(ProfileError::CommandExecutionFailure);
+    }
+
+    Ok(())
+}
+
+/// Deletes a saved connection profile
+///
+/// # Arguments
+/// * `profile` - name or UUID of the profile
+///
+/// # Returns
+/// - `Ok(())` if the profile was deleted
+/// - `Err(ProfileError::NoSuchProfile)` if no profile has that name or UUID
+pub fn delete_profile(profile: &str) -> Result<(), ProfileError> {
+    let uuid = profile_uuid(profile)?;
+    run_connection_command(&["delete", "uuid", &uuid])
+}
+
+/// Activates a saved profile of any type, e.g. a VPN
+///
+/// # Arguments
+/// * `profile` - name or UUID of the profile
+///
+/// # Returns
+/// - `Ok(())` once NetworkManager activated the profile
+/// - `Err(ProfileError::NoSuchProfile)` if no profile has that name or UUID
+pub fn activate_profile(profile: &str) -> Result<(), ProfileError> {
+    let uuid = profile_uuid(profile)?;
+    run_connection_command(&["up", "uuid", &uuid])
+}
+
+/// Deactivates an active profile
+///
+/// # Arguments
+/// * `profile` - name or UUID of the profile
+pub fn deactivate_profile(profile: &str) -> Result<(), ProfileError> {
+    let uuid = profile_uuid(profile)?;
+    run_connection_command(&["down", "uuid", &uuid])
+}
+
+/// Changes settings of a saved profile
+///
+/// Settings use the nmcli names, e.g. `ipv4.dns` or
+/// `connection.autoconnect`. The settings of an active profile are applied
+/// to its device right away, by activating the profile again if the device
+/// cannot take them over while connected.
+///
+/// # Arguments
+/// * `profile` - name or UUID of the profile
+/// * `options` - pairs of setting name and value
+pub fn modify_profile(profile: &str, options: &[(String, String)]) -> Result<(), ProfileError> {
+    let profile = find_profile(profile)?;
+    let mut args = vec!["modify", "uuid", profile.uuid.as_str()];
+    for (name, value) in options {
+        args.push(name);
+        args.push(value);
+    }
+    run_connection_command(&args)?;
+
+    if !profile.active {
+        return Ok(());
+    }
+    let reapplied = profile.device.as_deref().is_some_and(|device| {
+        Command::new("nmcli")
+            .args(["device", "reapply", device])
+            .output()
+            .is_ok_and(|output| output.status.success())
+    });
+    match reapplied {
+        true => Ok(()),
+        false => run_connection_command(&["up", "uuid", &profile.uuid]),
+    }
+}
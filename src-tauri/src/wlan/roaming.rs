@@ -0,0 +1,482 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io,
+    path::PathBuf,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use super::{
+    connect_network::connect_to_access_point,
+    get_networks::{get_access_points, VisibleAccessPoint},
+    networkmanager_error::RoamingError,
+    profiles::get_profile_ssids,
+};
+use crate::paths::config_dir;
+
+/// Decisions kept for [`recent_decisions`]
+const MAX_DECISIONS: usize = 50;
+
+/// Stops the running roaming assistant when dropped or sent to
+static STOP: LazyLock<Mutex<Option<oneshot::Sender<()>>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Latest decisions, oldest first
+static DECISIONS: LazyLock<Mutex<VecDeque<RoamingDecision>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::new()));
+
+/// When the roaming assistant moves a device to another access point,
+/// stored in `roaming.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoamingPolicy {
+    /// Run the assistant while the daemon runs
+    pub enabled: bool,
+    /// Signal quality in percent below which the current access point is weak
+    pub threshold: u8,
+    /// Seconds the signal has to stay weak before the assistant acts
+    pub weak_secs: u64,
+    /// Percentage points a candidate has to be stronger than the current
+    /// access point
+    pub hysteresis: u8,
+    /// SSIDs to prefer, highest priority first; only networks with a saved
+    /// profile are switched to
+    pub preferred_ssids: Vec<String>,
+    /// Seconds between two readings of the scan results
+    pub interval_secs: u64,
+    /// Seconds to wait after a roam before acting again
+    pub cooldown_secs: u64,
+}
+
+impl Default for RoamingPolicy {
+    fn default() -> Self {
+        RoamingPolicy {
+            enabled: false,
+            threshold: 40,
+            weak_secs: 15,
+            hysteresis: 10,
+            preferred_ssids: Vec::new(),
+            interval_secs: 5,
+            cooldown_secs: 60,
+        }
+    }
+}
+
+impl RoamingPolicy {
+    fn path() -> PathBuf {
+        config_dir().join("roaming.json")
+    }
+
+    /// Loads the policy, falling back to the defaults
+    pub fn load() -> Self {
+        std::fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the policy to the wiblue config directory
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)
+    }
+}
+
+/// What the assistant did about a weak access point
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoamingAction {
+    /// Kept the current access point
+    Stay,
+    /// Moved to a stronger access point of the same network
+    Reassociate,
+    /// Switched to a network with a higher priority
+    Reconnect,
+}
+
+/// A decision of the roaming assistant
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoamingDecision {
+    /// RFC 3339 time of the decision
+    pub timestamp: String,
+    pub interface: String,
+    pub ssid: String,
+    pub bssid: String,
+    /// Smoothed signal quality of the current access point in percent
+    pub signal: u8,
+    pub action: RoamingAction,
+    /// Access point moved to, with its smoothed signal
+    pub target: Option<VisibleAccessPoint>,
+    pub reason: String,
+    /// Why moving to the target failed, `None` if it succeeded
+    pub error: Option<String>,
+}
+
+/// Whether the roaming assistant runs
+pub fn roaming_active() -> bool {
+    STOP.lock().unwrap().is_some()
+}
+
+/// Returns the latest decisions of the roaming assistant, oldest first
+pub fn recent_decisions() -> Vec<RoamingDecision> {
+    DECISIONS.lock().unwrap().iter().cloned().collect()
+}
+
+/// Stops the assistant started with [`run_roaming`]
+///
+/// # Returns
+/// - `Ok(())` if the assistant was running
+/// - `Err(RoamingError::NotRunning)` otherwise
+pub fn stop_roaming() -> Result<(), RoamingError> {
+    STOP.lock()
+        .unwrap()
+        .take()
+        .map(|_| ())
+        .ok_or(RoamingError::NotRunning)
+}
+
+/// Moves the connected device to a better access point while its signal
+/// stays weak
+///
+/// Reads the scan results every `interval_secs` and smooths the signal of
+/// every access point over the readings. Once the connected access point
+/// has been below `threshold` for `weak_secs`, the device switches to the
+/// strongest preferred network ranked above the current one, or else
+/// reassociates with the strongest access point of the same SSID; either
+/// only if it is `hysteresis` points stronger. The callback receives every
+/// decision, including the ones to stay.
+///
+/// # Arguments
+/// * `policy` - thresholds and preferred networks
+/// * `may_scan` - whether the assistant may trigger scans, otherwise only
+///   the results of the scans NetworkManager runs on its own are used
+/// * `callback` - called with each decision
+///
+/// # Returns
+/// - `Ok(())` once [`stop_roaming`] was called
+/// - `Err(RoamingError::InProgress)` if the assistant already runs
+pub async fn run_roaming(
+    policy: RoamingPolicy,
+    may_scan: impl Fn() -> bool,
+    callback: impl Fn(RoamingDecision),
+) -> Result<(), RoamingError> {
+    let (stop, mut stopped) = oneshot::channel();
+    {
+        let mut running = STOP.lock().unwrap();
+        if running.is_some() {
+            return Err(RoamingError::InProgress);
+        }
+        *running = Some(stop);
+    }
+
+    let interval = Duration::from_secs(policy.interval_secs.max(1));
+    let weak_for = Duration::from_secs(policy.weak_secs);
+    let cooldown = Duration::from_secs(policy.cooldown_secs);
+    let mut signals: HashMap<String, f64> = HashMap::new();
+    let mut weak_since: Option<Instant> = None;
+    let mut last_roam: Option<Instant> = None;
+
+    loop {
+        tokio::select! {
+            _ = &mut stopped => return Ok(()),
+            _ = tokio::time::sleep(interval) => {}
+        }
+
+        let rescan = weak_since.is_some() && may_scan();
+        let Ok(Ok(access_points)) =
+            tokio::task::spawn_blocking(move || get_access_points(rescan)).await
+        else {
+            continue;
+        };
+
+        let Some(current) = access_points.iter().find(|ap| ap.active).cloned() else {
+            signals.clear();
+            weak_since = None;
+            continue;
+        };
+
+        // Exponential average, a single bad reading should not trigger a roam
+        signals.retain(|bssid, _| access_points.iter().any(|ap| &ap.bssid == bssid));
+        let candidates: Vec<VisibleAccessPoint> = access_points
+            .into_iter()
+            .filter(|ap| ap.device == current.device)
+            .map(|mut ap| {
+                let signal = signals
+                    .entry(ap.bssid.clone())
+                    .and_modify(|s| *s = (*s + ap.signal as f64) / 2.0)
+                    .or_insert(ap.signal as f64);
+                ap.signal = signal.round() as u8;
+                ap
+            })
+            .collect();
+        let signal = candidates
+            .iter()
+            .find(|ap| ap.bssid == current.bssid)
+            .map(|ap| ap.signal)
+            .unwrap_or(current.signal);
+
+        if !is_weak(signal, &policy) {
+            weak_since = None;
+            continue;
+        }
+        let now = Instant::now();
+        if now.duration_since(*weak_since.get_or_insert(now)) < weak_for {
+            continue;
+        }
+        // Decide again after the signal stayed weak for another `weak_secs`
+        weak_since = Some(now);
+
+        let (action, target, reason) = match last_roam {
+            Some(roamed) if now.duration_since(roamed) < cooldown => (
+                RoamingAction::Stay,
+                None,
+                format!(
+                    "Signal at {}%, last roam was {}s ago",
+                    signal,
+                    now.duration_since(roamed).as_secs()
+                ),
+            ),
+            _ => {
+                let known = match needs_profiles(&current, &policy) {
+                    true => tokio::task::spawn_blocking(get_profile_ssids)
+                        .await
+                        .ok()
+                        .and_then(Result::ok)
+                        .unwrap_or_default(),
+                    false => HashSet::new(),
+                };
+                choose_target(&current, signal, &candidates, &known, &policy)
+            }
+        };
+
+        let mut decision = RoamingDecision {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            interface: current.device.clone(),
+            ssid: current.ssid.clone(),
+            bssid: current.bssid.clone(),
+            signal,
+            action,
+            target,
+            reason,
+            error: None,
+        };
+        if let Some(target) = decision.target.clone() {
+            last_roam = Some(now);
+            weak_since = None;
+            let interface = current.device.clone();
+            decision.error = match tokio::task::spawn_blocking(move || {
+                connect_to_access_point(&target.bssid, &interface)
+            })
+            .await
+            {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(format!("{:?}", e)),
+                Err(_) => Some("Connecting panicked".to_string()),
+            };
+        }
+
+        {
+            let mut decisions = DECISIONS.lock().unwrap();
+            if decisions.len() == MAX_DECISIONS {
+                decisions.pop_front();
+            }
+            decisions.push_back(decision.clone());
+        }
+        callback(decision);
+    }
+}
+
+/// Whether the signal is below the threshold of the policy
+fn is_weak(signal: u8, policy: &RoamingPolicy) -> bool {
+    signal < policy.threshold
+}
+
+/// Position of an SSID in the preferred networks, `None` if it is not listed
+fn priority(policy: &RoamingPolicy, ssid: &str) -> Option<usize> {
+    policy.preferred_ssids.iter().position(|s| s == ssid)
+}
+
+/// Whether a preferred network ranks above the current one
+fn needs_profiles(current: &VisibleAccessPoint, policy: &RoamingPolicy) -> bool {
+    priority(policy, &current.ssid) != Some(0) && !policy.preferred_ssids.is_empty()
+}
+
+/// Picks the access point to move to, higher priority networks first
+fn choose_target(
+    current: &VisibleAccessPoint,
+    signal: u8,
+    candidates: &[VisibleAccessPoint],
+    known: &HashSet<String>,
+    policy: &RoamingPolicy,
+) -> (RoamingAction, Option<VisibleAccessPoint>, String) {
+    let required = signal.saturating_add(policy.hysteresis);
+    let current_priority = priority(policy, &current.ssid).unwrap_or(usize::MAX);
+
+    let preferred = candidates
+        .iter()
+        .filter(|ap| ap.ssid != current.ssid && ap.signal >= required && known.contains(&ap.ssid))
+        .filter_map(|ap| Some((priority(policy, &ap.ssid)?, ap)))
+        .filter(|(rank, _)| *rank < current_priority)
+        .min_by(|(a_rank, a), (b_rank, b)| a_rank.cmp(b_rank).then(b.signal.cmp(&a.signal)));
+    if let Some((_, ap)) = preferred {
+        return (
+            RoamingAction::Reconnect,
+            Some(ap.clone()),
+            format!(
+                "Signal at {}%, preferred network {} has {}%",
+                signal, ap.ssid, ap.signal
+            ),
+        );
+    }
+
+    let same_network = candidates
+        .iter()
+        .filter(|ap| ap.ssid == current.ssid && ap.bssid != current.bssid)
+        .filter(|ap| ap.signal >= required)
+        .max_by_key(|ap| ap.signal);
+    match same_network {
+        Some(ap) => (
+            RoamingAction::Reassociate,
+            Some(ap.clone()),
+            format!(
+                "Signal at {}%, access point {} has {}%",
+                signal, ap.bssid, ap.signal
+            ),
+        ),
+        None => (
+            RoamingAction::Stay,
+            None,
+            format!(
+                "Signal at {}%, no known access point has {}% or more",
+                signal, required
+            ),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ap(ssid: &str, bssid: &str, signal: u8) -> VisibleAccessPoint {
+        VisibleAccessPoint {
+            ssid: ssid.to_string(),
+            bssid: bssid.to_string(),
+            signal,
+            frequency: 2412,
+            device: "wlan0".to_string(),
+            active: false,
+        }
+    }
+
+    fn policy(preferred: &[&str]) -> RoamingPolicy {
+        RoamingPolicy {
+            threshold: 40,
+            hysteresis: 10,
+            preferred_ssids: preferred.iter().map(|s| s.to_string()).collect(),
+            ..RoamingPolicy::default()
+        }
+    }
+
+    fn known(ssids: &[&str]) -> HashSet<String> {
+        ssids.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn weak_below_the_threshold_only() {
+        let policy = policy(&[]);
+        assert!(is_weak(39, &policy));
+        assert!(!is_weak(40, &policy));
+        assert!(!is_weak(41, &policy));
+    }
+
+    #[test]
+    fn reassociates_at_the_hysteresis_boundary() {
+        let current = ap("Home", "00:00:00:00:00:01", 30);
+        let policy = policy(&[]);
+
+        // 39% is 9 points above the current signal, one short of the hysteresis
+        let below = [current.clone(), ap("Home", "00:00:00:00:00:02", 39)];
+        let (action, target, _) = choose_target(&current, 30, &below, &known(&[]), &policy);
+        assert_eq!(
+            (action, target.map(|ap| ap.bssid)),
+            (RoamingAction::Stay, None)
+        );
+
+        let at = [
+            current.clone(),
+            ap("Home", "00:00:00:00:00:02", 40),
+            ap("Home", "00:00:00:00:00:03", 45),
+        ];
+        let (action, target, _) = choose_target(&current, 30, &at, &known(&[]), &policy);
+        assert_eq!(action, RoamingAction::Reassociate);
+        assert_eq!(target.unwrap().bssid, "00:00:00:00:00:03");
+    }
+
+    #[test]
+    fn stays_without_a_better_access_point() {
+        let current = ap("Home", "00:00:00:00:00:01", 35);
+        let candidates = [
+            current.clone(),
+            // Stronger, but of a network without a saved profile
+            ap("Cafe", "00:00:00:00:00:02", 90),
+            // Strong enough, but not preferred
+            ap("Neighbour", "00:00:00:00:00:03", 80),
+            ap("Home", "00:00:00:00:00:04", 44),
+        ];
+
+        let (action, target, reason) = choose_target(
+            &current,
+            35,
+            &candidates,
+            &known(&["Neighbour"]),
+            &policy(&["Cafe"]),
+        );
+        assert_eq!(action, RoamingAction::Stay);
+        assert!(target.is_none());
+        assert_eq!(
+            reason,
+            "Signal at 35%, no known access point has 45% or more"
+        );
+
+        // Nothing else in range
+        let (action, _, _) = choose_target(
+            &current,
+            35,
+            std::slice::from_ref(&current),
+            &known(&[]),
+            &policy(&[]),
+        );
+        assert_eq!(action, RoamingAction::Stay);
+    }
+
+    #[test]
+    fn prefers_higher_priority_networks() {
+        let current = ap("Guest", "00:00:00:00:00:01", 20);
+        let policy = policy(&["Work", "Home", "Guest"]);
+        let known = known(&["Work", "Home", "Guest"]);
+        let candidates = [
+            current.clone(),
+            ap("Guest", "00:00:00:00:00:02", 90),
+            ap("Home", "00:00:00:00:00:03", 80),
+            ap("Work", "00:00:00:00:00:04", 29),
+            ap("Work", "00:00:00:00:00:05", 30),
+        ];
+
+        let (action, target, _) = choose_target(&current, 20, &candidates, &known, &policy);
+        assert_eq!(action, RoamingAction::Reconnect);
+        assert_eq!(target.unwrap().bssid, "00:00:00:00:00:05");
+
+        // A lower priority network is not switched to, however strong
+        let current = ap("Work", "00:00:00:00:00:04", 20);
+        let (action, target, _) = choose_target(&current, 20, &candidates, &known, &policy);
+        assert_eq!(action, RoamingAction::Reassociate);
+        assert_eq!(target.unwrap().bssid, "00:00:00:00:00:05");
+    }
+}
@@ -0,0 +1,489 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use reqwest::Url;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time,
+};
+
+use super::{get_networks::get_active_network, networkmanager_error::SpeedTestError};
+
+/// Port the bundled speed test server listens on by default
+pub const DEFAULT_SERVER_PORT: u16 = 5210;
+
+/// Raw TCP command asking the server to send data
+pub const TCP_DOWNLOAD_COMMAND: u8 = b'D';
+/// Raw TCP command announcing that the client sends data
+pub const TCP_UPLOAD_COMMAND: u8 = b'U';
+
+/// Bytes requested per HTTP download, the transfer is cut off after the test duration
+const HTTP_DOWNLOAD_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+/// Size of a single HTTP upload request, small enough to finish in time on slow links
+const HTTP_UPLOAD_CHUNK: usize = 256 * 1024;
+/// Buffer size used for raw TCP transfers
+const TCP_BUFFER_SIZE: usize = 64 * 1024;
+/// Minimal time between two progress callbacks
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Server the speed test runs against
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum SpeedTestEndpoint {
+    /// Base URL of an HTTP server offering `/download?bytes=N` and `/upload`
+    Http { url: String },
+    /// `host:port` of a server speaking the raw TCP protocol
+    Tcp { address: String },
+}
+
+impl SpeedTestEndpoint {
+    /// `host:port` used for the TCP connect latency probes
+    fn probe_address(&self) -> Result<String, SpeedTestError> {
+        match self {
+            SpeedTestEndpoint::Tcp { address } => Ok(address.clone()),
+            SpeedTestEndpoint::Http { url } => {
+                let url = Url::parse(url).map_err(|_| SpeedTestError::InvalidEndpoint)?;
+                let host = url.host_str().ok_or(SpeedTestError::InvalidEndpoint)?;
+                let port = url
+                    .port_or_known_default()
+                    .ok_or(SpeedTestError::InvalidEndpoint)?;
+                Ok(format!("{}:{}", host, port))
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            SpeedTestEndpoint::Http { url } => url.clone(),
+            SpeedTestEndpoint::Tcp { address } => format!("tcp://{}", address),
+        }
+    }
+}
+
+/// How the speed test should run
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SpeedTestConfig {
+    pub endpoint: SpeedTestEndpoint,
+    /// Seconds spent on the download and on the upload
+    pub duration_secs: u64,
+    /// Number of connects used to measure the idle latency
+    pub latency_samples: usize,
+}
+
+/// Part of the test currently running
+#[derive(Debug, Clone, serde::Serialize)]
+pub enum SpeedTestPhase {
+    Latency,
+    Download,
+    Upload,
+}
+
+/// Intermediate result reported while a transfer is running
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SpeedTestProgress {
+    pub phase: SpeedTestPhase,
+    pub bytes: u64,
+    pub elapsed_secs: f64,
+    pub bits_per_second: f64,
+}
+
+/// Final result of a speed test
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SpeedTestResult {
+    pub endpoint: String,
+    /// Network the test ran on, `None` when not connected over Wi-Fi
+    pub ssid: Option<String>,
+    pub bssid: Option<String>,
+    pub download_bps: f64, // in bits per second
+    pub upload_bps: f64,   // in bits per second
+    /// Average TCP connect time before the transfers started
+    pub idle_latency_ms: Option<f64>,
+    /// Average TCP connect time while downloading
+    pub download_latency_ms: Option<f64>,
+    /// Average TCP connect time while uploading
+    pub upload_latency_ms: Option<f64>,
+}
+
+/// Measures latency, download and upload throughput against the configured endpoint
+///
+/// # Arguments
+/// * `config` - endpoint and duration of the test
+/// * `progress` - called periodically while the transfers run
+///
+/// # Returns
+/// - `Ok(SpeedTestResult)` if the test completed
+/// - `Err(SpeedTestError)` if the endpoint is invalid or a transfer fails
+pub async fn run_speed_test(
+    config: &SpeedTestConfig,
+    progress: impl Fn(SpeedTestProgress) + Send + Sync,
+) -> Result<SpeedTestResult, SpeedTestError> {
+    let probe_address = config.endpoint.probe_address()?;
+    let duration = Duration::from_secs(config.duration_secs.max(1));
+    let active_network = get_active_network();
+
+    progress(SpeedTestProgress {
+        phase: SpeedTestPhase::Latency,
+        bytes: 0,
+        elapsed_secs: 0.0,
+        bits_per_second: 0.0,
+    });
+    let mut idle_samples = Vec::new();
+    for _ in 0..config.latency_samples.max(1) {
+        if let Some(ms) = connect_time(&probe_address).await {
+            idle_samples.push(ms);
+        }
+    }
+    if idle_samples.is_empty() {
+        return Err(SpeedTestError::ConnectionFailure);
+    }
+
+    let (download, download_latency_ms) = with_loaded_latency(&probe_address, async {
+        match &config.endpoint {
+            SpeedTestEndpoint::Http { url } => http_download(url, duration, &progress).await,
+            SpeedTestEndpoint::Tcp { address } => tcp_download(address, duration, &progress).await,
+        }
+    })
+    .await;
+    let download_bps = download?;
+
+    let (upload, upload_latency_ms) = with_loaded_latency(&probe_address, async {
+        match &config.endpoint {
+            SpeedTestEndpoint::Http { url } => http_upload(url, duration, &progress).await,
+            SpeedTestEndpoint::Tcp { address } => tcp_upload(address, duration, &progress).await,
+        }
+    })
+    .await;
+    let upload_bps = upload?;
+
+    Ok(SpeedTestResult {
+        endpoint: config.endpoint.describe(),
+        ssid: active_network.as_ref().map(|n| n.ssid.clone()),
+        bssid: active_network.map(|n| n.bssid),
+        download_bps,
+        upload_bps,
+        idle_latency_ms: average(&idle_samples),
+        download_latency_ms,
+        upload_latency_ms,
+    })
+}
+
+/// Time it takes to open a TCP connection in ms
+async fn connect_time(address: &str) -> Option<f64> {
+    let start = Instant::now();
+    match time::timeout(Duration::from_secs(2), TcpStream::connect(address)).await {
+        Ok(Ok(_)) => Some(start.elapsed().as_secs_f64() * 1000.0),
+        _ => None,
+    }
+}
+
+/// Runs `transfer` while sampling the connect latency in the background
+async fn with_loaded_latency<T>(
+    address: &str,
+    transfer: impl std::future::Future<Output = T>,
+) -> (T, Option<f64>) {
+    let samples = Arc::new(Mutex::new(Vec::new()));
+
+    let sampler = {
+        let samples = samples.clone();
+        let address = address.to_string();
+        tokio::spawn(async move {
+            loop {
+                time::sleep(Duration::from_millis(250)).await;
+                if let Some(ms) = connect_time(&address).await {
+                    samples.lock().unwrap().push(ms);
+                }
+            }
+        })
+    };
+
+    let result = transfer.await;
+    sampler.abort();
+
+    let samples = samples.lock().unwrap();
+    (result, average(&samples))
+}
+
+fn average(samples: &[f64]) -> Option<f64> {
+    if samples.is_empty() {
+        None
+    } else {
+        Some(samples.iter().sum::<f64>() / samples.len() as f64)
+    }
+}
+
+fn bits_per_second(bytes: u64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs == 0.0 {
+        0.0
+    } else {
+        bytes as f64 * 8.0 / secs
+    }
+}
+
+/// Calls `progress` at most every [`PROGRESS_INTERVAL`]
+struct ProgressThrottle {
+    phase: SpeedTestPhase,
+    start: Instant,
+    last: Instant,
+}
+
+impl ProgressThrottle {
+    fn new(phase: SpeedTestPhase) -> Self {
+        let now = Instant::now();
+        ProgressThrottle {
+            phase,
+            start: now,
+            last: now,
+        }
+    }
+
+    fn report(&mut self, bytes: u64, progress: &impl Fn(SpeedTestProgress)) {
+        if self.last.elapsed() < PROGRESS_INTERVAL {
+            return;
+        }
+        self.last = Instant::now();
+
+        let elapsed = self.start.elapsed();
+        progress(SpeedTestProgress {
+            phase: self.phase.clone(),
+            bytes,
+            elapsed_secs: elapsed.as_secs_f64(),
+            bits_per_second: bits_per_second(bytes, elapsed),
+        });
+    }
+}
+
+async fn http_download(
+    url: &str,
+    duration: Duration,
+    progress: &impl Fn(SpeedTestProgress),
+) -> Result<f64, SpeedTestError> {
+    let url = format!(
+        "{}/download?bytes={}",
+        url.trim_end_matches('/'),
+        HTTP_DOWNLOAD_BYTES
+    );
+    let mut response = reqwest::get(&url)
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| {
+            eprintln!("Speed test download request failed: {:?}", e);
+            SpeedTestError::ConnectionFailure
+        })?;
+
+    let mut throttle = ProgressThrottle::new(SpeedTestPhase::Download);
+    let start = Instant::now();
+    let mut bytes: u64 = 0;
+
+    while let Some(remaining) = duration.checked_sub(start.elapsed()) {
+        match time::timeout(remaining, response.chunk()).await {
+            Ok(Ok(Some(chunk))) => bytes += chunk.len() as u64,
+            Ok(Ok(None)) | Err(_) => break,
+            Ok(Err(e)) => {
+                eprintln!("Speed test download failed: {:?}", e);
+                return Err(SpeedTestError::TransferFailure);
+            }
+        }
+        throttle.report(bytes, progress);
+    }
+
+    Ok(bits_per_second(bytes, start.elapsed()))
+}
+
+async fn http_upload(
+    url: &str,
+    duration: Duration,
+    progress: &impl Fn(SpeedTestProgress),
+) -> Result<f64, SpeedTestError> {
+    let url = format!("{}/upload", url.trim_end_matches('/'));
+    let client = reqwest::Client::new();
+    let payload = vec![0u8; HTTP_UPLOAD_CHUNK];
+
+    let mut throttle = ProgressThrottle::new(SpeedTestPhase::Upload);
+    let start = Instant::now();
+    let mut bytes: u64 = 0;
+
+    while let Some(remaining) = duration.checked_sub(start.elapsed()) {
+        let request = client.post(&url).body(payload.clone()).send();
+        // A request still running when the test time is up is not counted
+        let Ok(response) = time::timeout(remaining, request).await else {
+            break;
+        };
+        response.and_then(|r| r.error_for_status()).map_err(|e| {
+            eprintln!("Speed test upload failed: {:?}", e);
+            SpeedTestError::TransferFailure
+        })?;
+        bytes += payload.len() as u64;
+        throttle.report(bytes, progress);
+    }
+
+    Ok(bits_per_second(bytes, start.elapsed()))
+}
+
+/// Opens a raw TCP test connection and sends the command header
+async fn tcp_start(
+    address: &str,
+    command: u8,
+    duration: Duration,
+) -> Result<TcpStream, SpeedTestError> {
+    let mut stream = TcpStream::connect(address).await.map_err(|e| {
+        eprintln!("Speed test connection failed: {:?}", e);
+        SpeedTestError::ConnectionFailure
+    })?;
+
+    let mut header = vec![command];
+    header.extend_from_slice(&(duration.as_millis() as u64).to_be_bytes());
+    stream
+        .write_all(&header)
+        .await
+        .map_err(|_| SpeedTestError::TransferFailure)?;
+
+    Ok(stream)
+}
+
+async fn tcp_download(
+    address: &str,
+    duration: Duration,
+    progress: &impl Fn(SpeedTestProgress),
+) -> Result<f64, SpeedTestError> {
+    let mut stream = tcp_start(address, TCP_DOWNLOAD_COMMAND, duration).await?;
+
+    let mut throttle = ProgressThrottle::new(SpeedTestPhase::Download);
+    let start = Instant::now();
+    let mut buffer = vec![0u8; TCP_BUFFER_SIZE];
+    let mut bytes: u64 = 0;
+
+    while let Some(remaining) = duration.checked_sub(start.elapsed()) {
+        match time::timeout(remaining, stream.read(&mut buffer)).await {
+            Ok(Ok(0)) | Err(_) => break,
+            Ok(Ok(n)) => bytes += n as u64,
+            Ok(Err(_)) => return Err(SpeedTestError::TransferFailure),
+        }
+        throttle.report(bytes, progress);
+    }
+
+    Ok(bits_per_second(bytes, start.elapsed()))
+}
+
+async fn tcp_upload(
+    address: &str,
+    duration: Duration,
+    progress: &impl Fn(SpeedTestProgress),
+) -> Result<f64, SpeedTestError> {
+    let mut stream = tcp_start(address, TCP_UPLOAD_COMMAND, duration).await?;
+
+    let mut throttle = ProgressThrottle::new(SpeedTestPhase::Upload);
+    let start = Instant::now();
+    let buffer = vec![0u8; TCP_BUFFER_SIZE];
+    let mut bytes: u64 = 0;
+
+    while start.elapsed() < duration {
+        stream
+            .write_all(&buffer)
+            .await
+            .map_err(|_| SpeedTestError::TransferFailure)?;
+        bytes += buffer.len() as u64;
+        throttle.report(bytes, progress);
+    }
+    stream
+        .shutdown()
+        .await
+        .map_err(|_| SpeedTestError::TransferFailure)?;
+    let elapsed = start.elapsed();
+
+    // The server answers with the number of bytes it actually received
+    let received = stream.read_u64().await.unwrap_or(bytes);
+
+    Ok(bits_per_second(received, elapsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wlan::speed_test_server::{bind_speed_test_server, serve_speed_tests};
+
+    /// Starts the bundled server on a free loopback port
+    fn start_server() -> String {
+        let listener = bind_speed_test_server("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(serve_speed_tests(listener));
+        address
+    }
+
+    async fn run(endpoint: SpeedTestEndpoint) -> (SpeedTestResult, Vec<String>) {
+        let config = SpeedTestConfig {
+            endpoint,
+            duration_secs: 1,
+            latency_samples: 3,
+        };
+        let phases = Mutex::new(Vec::new());
+        let result = run_speed_test(&config, |progress| {
+            phases.lock().unwrap().push(format!("{:?}", progress.phase));
+        })
+        .await
+        .unwrap();
+
+        let mut phases = phases.into_inner().unwrap();
+        phases.dedup();
+        (result, phases)
+    }
+
+    #[tokio::test]
+    async fn measures_the_bundled_server_over_tcp() {
+        let address = start_server();
+        let (result, phases) = run(SpeedTestEndpoint::Tcp {
+            address: address.clone(),
+        })
+        .await;
+
+        assert_eq!(result.endpoint, format!("tcp://{}", address));
+        assert!(result.download_bps > 0.0);
+        assert!(result.upload_bps > 0.0);
+        assert!(result.idle_latency_ms.is_some());
+        assert_eq!(phases, ["Latency", "Download", "Upload"]);
+    }
+
+    #[tokio::test]
+    async fn measures_the_bundled_server_over_http() {
+        let url = format!("http://{}/", start_server());
+        let (result, phases) = run(SpeedTestEndpoint::Http { url: url.clone() }).await;
+
+        assert_eq!(result.endpoint, url);
+        assert!(result.download_bps > 0.0);
+        assert!(result.upload_bps > 0.0);
+        assert_eq!(phases, ["Latency", "Download", "Upload"]);
+    }
+
+    #[tokio::test]
+    async fn fails_without_a_server() {
+        // Bound but not listening, connects are refused
+        let socket = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap().to_string();
+        drop(socket);
+
+        let config = SpeedTestConfig {
+            endpoint: SpeedTestEndpoint::Tcp { address },
+            duration_secs: 1,
+            latency_samples: 1,
+        };
+        let result = run_speed_test(&config, |_| {}).await;
+        assert!(matches!(result, Err(SpeedTestError::ConnectionFailure)));
+    }
+
+    #[test]
+    fn probes_the_port_of_http_endpoints() {
+        let http = SpeedTestEndpoint::Http {
+            url: "http://speed.example".to_string(),
+        };
+        assert_eq!(http.probe_address().unwrap(), "speed.example:80");
+        let invalid = SpeedTestEndpoint::Http {
+            url: "not a url".to_string(),
+        };
+        assert!(matches!(
+            invalid.probe_address(),
+            Err(SpeedTestError::InvalidEndpoint)
+        ));
+    }
+}
@@ -0,0 +1,269 @@
+use std::time::{Duration, Instant};
+
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    time,
+};
+
+use super::{
+    networkmanager_error::SpeedTestError,
+    speed_test::{DEFAULT_SERVER_PORT, TCP_DOWNLOAD_COMMAND, TCP_UPLOAD_COMMAND},
+};
+
+/// Address the server listens on by default
+///
+/// The server is an unauthenticated bandwidth sink and source, so it only
+/// listens on every interface when `lan` is set explicitly.
+pub fn default_server_address(lan: bool) -> String {
+    let host = if lan { "0.0.0.0" } else { "127.0.0.1" };
+    format!("{}:{}", host, DEFAULT_SERVER_PORT)
+}
+
+/// Longest transfer a client may ask for
+const MAX_TCP_DURATION: Duration = Duration::from_secs(120);
+/// Time on top of a transfer for the handshake and the final answer,
+/// connections still open after it are closed
+const GRACE_PERIOD: Duration = Duration::from_secs(10);
+/// Most bytes sent or received in a single transfer
+const MAX_TRANSFER_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+/// Most bytes of an HTTP request line and headers
+const MAX_HEADER_BYTES: u64 = 16 * 1024;
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Runs the speed test server until the process exits
+///
+/// # Arguments
+/// * `address` - address to listen on, e.g. `127.0.0.1:5210`
+///
+/// # Returns
+/// - `Err(SpeedTestError)` if the address cannot be bound
+pub async fn run_speed_test_server(address: &str) -> Result<(), SpeedTestError> {
+    let listener = bind_speed_test_server(address)?;
+    println!("Speed test server listening on {}", address);
+    serve_speed_tests(listener).await;
+    Ok(())
+}
+
+/// Binds the listening socket of the speed test server
+///
+/// Binding does not wait, so the daemon can start the server while its
+/// monitor registry is locked. Must be called within the Tokio runtime.
+pub fn bind_speed_test_server(address: &str) -> Result<TcpListener, SpeedTestError> {
+    std::net::TcpListener::bind(address)
+        .and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            TcpListener::from_std(listener)
+        })
+        .map_err(|e| {
+            eprintln!("Error binding speed test server to {}: {:?}", address, e);
+            SpeedTestError::ServerBindFailure
+        })
+}
+
+/// Serves speed test clients until the task is aborted
+///
+/// A single port serves both protocols understood by the speed test client:
+/// connections starting with a raw TCP command byte get the raw protocol,
+/// everything else is treated as an HTTP/1.1 request. No connection stays
+/// open longer than the longest transfer.
+pub async fn serve_speed_tests(listener: TcpListener) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Error accepting speed test connection: {:?}", e);
+                continue;
+            }
+        };
+
+        tokio::spawn(async move {
+            match time::timeout(MAX_TCP_DURATION + GRACE_PERIOD, handle_connection(stream)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("Speed test connection from {} failed: {:?}", peer, e),
+                Err(_) => eprintln!("Speed test connection from {} timed out", peer),
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    // Latency probes connect and close right away
+    let first = match reader.fill_buf().await?.first() {
+        Some(b) => *b,
+        None => return Ok(()),
+    };
+
+    match first {
+        TCP_DOWNLOAD_COMMAND | TCP_UPLOAD_COMMAND => handle_tcp(reader).await,
+        _ => handle_http(reader).await,
+    }
+}
+
+async fn handle_tcp(mut reader: BufReader<TcpStream>) -> io::Result<()> {
+    let command = reader.read_u8().await?;
+    let duration = Duration::from_millis(reader.read_u64().await?).min(MAX_TCP_DURATION);
+
+    if command == TCP_DOWNLOAD_COMMAND {
+        let buffer = vec![0u8; BUFFER_SIZE];
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            reader.get_mut().write_all(&buffer).await?;
+        }
+        reader.get_mut().shutdown().await
+    } else {
+        // The client shuts down its side once it is done sending
+        let (mut limited, mut sink) = ((&mut reader).take(MAX_TRANSFER_BYTES), io::sink());
+        let upload = io::copy(&mut limited, &mut sink);
+        let received = time::timeout(duration + GRACE_PERIOD, upload)
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+        reader.get_mut().write_u64(received).await?;
+        reader.get_mut().shutdown().await
+    }
+}
+
+async fn handle_http(mut reader: BufReader<TcpStream>) -> io::Result<()> {
+    // Lines are read through a limit so a client cannot send endless headers
+    let mut head = (&mut reader).take(MAX_HEADER_BYTES);
+    let mut request_line = String::new();
+    head.read_line(&mut request_line).await?;
+
+    let mut content_length: u64 = 0;
+    loop {
+        let mut header = String::new();
+        if head.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    // GET /download?bytes=1048576 HTTP/1.1
+    let parts: Vec<&str> = request_line.split_whitespace().collect();
+    let (method, target) = match parts.as_slice() {
+        [method, target, ..] => (*method, *target),
+        _ => return write_response(reader.get_mut(), "400 Bad Request", "").await,
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    match (method, path) {
+        ("GET", "/download") => {
+            let bytes: u64 = query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == "bytes")
+                .and_then(|(_, value)| value.parse().ok())
+                .unwrap_or(0)
+                .min(MAX_TRANSFER_BYTES);
+
+            let stream = reader.get_mut();
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        bytes
+                    )
+                    .as_bytes(),
+                )
+                .await?;
+
+            let buffer = vec![0u8; BUFFER_SIZE];
+            let mut remaining = bytes;
+            while remaining > 0 {
+                let n = remaining.min(BUFFER_SIZE as u64) as usize;
+                // The client hangs up once its test duration is over
+                if stream.write_all(&buffer[..n]).await.is_err() {
+                    return Ok(());
+                }
+                remaining -= n as u64;
+            }
+            stream.shutdown().await
+        }
+        ("POST", "/upload") if content_length > MAX_TRANSFER_BYTES => {
+            write_response(reader.get_mut(), "413 Payload Too Large", "").await
+        }
+        ("POST", "/upload") => {
+            let received =
+                io::copy(&mut (&mut reader).take(content_length), &mut io::sink()).await?;
+            write_response(reader.get_mut(), "200 OK", &received.to_string()).await
+        }
+        _ => write_response(reader.get_mut(), "404 Not Found", "").await,
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, body: &str) -> io::Result<()> {
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn request(request: &str) -> String {
+        let listener = bind_speed_test_server("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve_speed_tests(listener));
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        // Only the head of the answer is of interest
+        let mut buffer = vec![0u8; 1024];
+        let read = stream.read(&mut buffer).await.unwrap();
+        server.abort();
+        String::from_utf8_lossy(&buffer[..read]).to_string()
+    }
+
+    #[tokio::test]
+    async fn caps_the_download_size() {
+        let response = request("GET /download?bytes=99999999999999 HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(&format!("Content-Length: {}\r\n", MAX_TRANSFER_BYTES)));
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_uploads() {
+        let response = request(&format!(
+            "POST /upload HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_TRANSFER_BYTES + 1
+        ))
+        .await;
+        assert!(response.starts_with("HTTP/1.1 413"));
+    }
+
+    #[tokio::test]
+    async fn answers_unknown_paths_with_not_found() {
+        let response = request("GET /index.html HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404"));
+    }
+
+    #[tokio::test]
+    async fn counts_raw_tcp_uploads() {
+        let listener = bind_speed_test_server("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve_speed_tests(listener));
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_u8(TCP_UPLOAD_COMMAND).await.unwrap();
+        stream.write_u64(1000).await.unwrap();
+        stream.write_all(&[0u8; 3000]).await.unwrap();
+        stream.shutdown().await.unwrap();
+        assert_eq!(stream.read_u64().await.unwrap(), 3000);
+    }
+}
@@ -0,0 +1,461 @@
+use std::{path::Path, process::Command};
+
+use qrcode::{
+    render::{svg, unicode},
+    types::Color,
+    EcLevel, QrCode,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    get_networks::split_terse_line, networkmanager_error::QrError, profiles::get_profiles,
+};
+
+/// Characters the `WIFI:` scheme escapes with a backslash
+const SPECIAL_CHARACTERS: [char; 5] = ['\\', ';', ',', ':', '"'];
+
+/// Light modules around the code that scanners need to find it
+const QUIET_ZONE: usize = 4;
+
+/// Authentication type of a `WIFI:` URI (`T:` field)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QrSecurity {
+    /// `WPA`, WPA and WPA2 Personal
+    Wpa,
+    /// `SAE`, WPA3 Personal
+    Sae,
+    Wep,
+    /// `nopass`, an open network
+    Nopass,
+}
+
+impl QrSecurity {
+    fn code(&self) -> &'static str {
+        match self {
+            QrSecurity::Wpa => "WPA",
+            QrSecurity::Sae => "SAE",
+            QrSecurity::Wep => "WEP",
+            QrSecurity::Nopass => "nopass",
+        }
+    }
+}
+
+/// Credentials of a network as carried by a Wi-Fi QR code
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WifiCredentials {
+    pub ssid: String,
+    pub password: Option<String>,
+    pub security: QrSecurity,
+    /// Whether the network does not broadcast its SSID
+    pub hidden: bool,
+}
+
+impl WifiCredentials {
+    /// Encodes the credentials as `WIFI:T:WPA;S:<ssid>;P:<password>;H:true;;`
+    ///
+    /// Special characters in SSID and password are escaped, a password that
+    /// could be read as hex digits is quoted.
+    pub fn to_uri(&self) -> String {
+        let mut uri = format!("WIFI:T:{};S:{};", self.security.code(), escape(&self.ssid));
+        if let Some(password) = self
+            .password
+            .as_deref()
+            .filter(|_| self.security != QrSecurity::Nopass)
+        {
+            let is_hex = !password.is_empty() && password.chars().all(|c| c.is_ascii_hexdigit());
+            match is_hex {
+                true => uri.push_str(&format!("P:\"{}\";", escape(password))),
+                false => uri.push_str(&format!("P:{};", escape(password))),
+            }
+        }
+        if self.hidden {
+            uri.push_str("H:true;");
+        }
+        uri.push(';');
+
+        uri
+    }
+
+    /// Parses a `WIFI:` URI
+    ///
+    /// Fields may come in any order and unknown fields are ignored.
+    ///
+    /// # Returns
+    /// - `Ok(WifiCredentials)` with the unescaped SSID and password
+    /// - `Err(QrError::InvalidUri)` if the scheme or the SSID is missing
+    /// - `Err(QrError::UnsupportedSecurity)` for enterprise networks
+    pub fn parse(uri: &str) -> Result<Self, QrError> {
+        let uri = uri.trim();
+        let body = match uri.get(..5) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("WIFI:") => &uri[5..],
+            _ => return Err(QrError::InvalidUri("Missing WIFI: scheme".to_string())),
+        };
+
+        let mut ssid = None;
+        let mut password = None;
+        let mut security = None;
+        let mut hidden = false;
+        for field in split_fields(body) {
+            let Some((name, value)) = field.split_once(':') else {
+                continue;
+            };
+            match name.to_ascii_uppercase().as_str() {
+                "S" => ssid = Some(unescape(value)),
+                "P" => password = Some(unescape(value)).filter(|p| !p.is_empty()),
+                "H" => hidden = value.eq_ignore_ascii_case("true"),
+                "T" => {
+                    security = Some(match value.to_ascii_uppercase().as_str() {
+                        "WPA" | "WPA2" => QrSecurity::Wpa,
+                        "SAE" | "WPA3" => QrSecurity::Sae,
+                        "WEP" => QrSecurity::Wep,
+                        "" | "NOPASS" => QrSecurity::Nopass,
+                        _ => return Err(QrError::UnsupportedSecurity),
+                    })
+                }
+                _ => {}
+            }
+        }
+
+        let ssid = ssid
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| QrError::InvalidUri("Missing SSID".to_string()))?;
+        let security = security.unwrap_or(match password {
+            Some(_) => QrSecurity::Wpa,
+            None => QrSecurity::Nopass,
+        });
+
+        Ok(WifiCredentials {
+            ssid,
+            password: password.filter(|_| security != QrSecurity::Nopass),
+            security,
+            hidden,
+        })
+    }
+
+    fn qr_code(&self) -> Result<QrCode, QrError> {
+        QrCode::with_error_correction_level(self.to_uri(), EcLevel::M)
+            .map_err(|_| QrError::EncodingFailure)
+    }
+
+    /// Renders the QR code as an SVG document
+    pub fn to_svg(&self) -> Result<String, QrError> {
+        Ok(self
+            .qr_code()?
+            .render::<svg::Color>()
+            .min_dimensions(256, 256)
+            .build())
+    }
+
+    /// Renders the QR code with half-block characters, light on dark so it
+    /// scans from a dark terminal
+    pub fn to_terminal(&self) -> Result<String, QrError> {
+        Ok(self
+            .qr_code()?
+            .render::<unicode::Dense1x2>()
+            .dark_color(unicode::Dense1x2::Light)
+            .light_color(unicode::Dense1x2::Dark)
+            .build())
+    }
+
+    /// Renders the QR code as a grayscale PNG
+    ///
+    /// # Arguments
+    /// * `scale` - pixels per module
+    pub fn to_png(&self, scale: usize) -> Result<Vec<u8>, QrError> {
+        let code = self.qr_code()?;
+        let colors = code.to_colors();
+        let modules = code.width();
+        let scale = scale.max(1);
+        let size = (modules + 2 * QUIET_ZONE) * scale;
+
+        let mut pixels = vec![255u8; size * size];
+        for (index, color) in colors.iter().enumerate() {
+            if *color != Color::Dark {
+                continue;
+            }
+            let x = (index % modules + QUIET_ZONE) * scale;
+            let y = (index / modules + QUIET_ZONE) * scale;
+            for row in y..y + scale {
+                pixels[row * size + x..row * size + x + scale].fill(0);
+            }
+        }
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&pixels))
+            .map_err(|_| QrError::EncodingFailure)?;
+
+        Ok(png)
+    }
+}
+
+/// Reads the credentials of a saved Wi-Fi profile, including its password
+///
+/// # Arguments
+/// * `profile` - name or UUID of the profile, the active Wi-Fi profile if
+///   not set
+///
+/// # Returns
+/// - `Ok(WifiCredentials)` of the profile
+/// - `Err(QrError::NoSuchProfile)` if there is no such Wi-Fi profile
+/// - `Err(QrError::NotConnected)` if no profile was given and none is active
+/// - `Err(QrError::UnsupportedSecurity)` for enterprise networks
+pub fn get_profile_credentials(profile: Option<&str>) -> Result<WifiCredentials, QrError> {
+    let profiles = get_profiles(true).map_err(|_| QrError::CommandExecutionFailure)?;
+    let uuid = match profile {
+        Some(profile) => profiles
+            .iter()
+            .find(|p| p.uuid == profile || p.name == profile)
+            .ok_or(QrError::NoSuchProfile)?,
+        None => profiles
+            .iter()
+            .find(|p| p.active)
+            .ok_or(QrError::NotConnected)?,
+    }
+    .uuid
+    .clone();
+
+    let output = Command::new("nmcli")
+        .args([
+            "--show-secrets",
+            "-g",
+            "802-11-wireless.ssid,802-11-wireless.hidden,802-11-wireless-security.key-mgmt,802-11-wireless-security.psk,802-11-wireless-security.wep-key0",
+            "connection",
+            "show",
+            "uuid",
+            &uuid,
+        ])
+        .output()
+        .map_err(|_| QrError::CommandExecutionFailure)?;
+    if !output.status.success() {
+        eprintln!(
+            "nmcli command failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(QrError::CommandExecutionFailure);
+    }
+
+    // One value per line, colons escaped
+    let values: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| split_terse_line(line).join(":"))
+        .collect();
+    let value = |index: usize| values.get(index).cloned().filter(|v| !v.is_empty());
+
+    let security = match value(2).as_deref() {
+        None | Some("owe") => QrSecurity::Nopass,
+        Some("wpa-psk") => QrSecurity::Wpa,
+        Some("sae") => QrSecurity::Sae,
+        Some("none") => QrSecurity::Wep,
+        Some(_) => return Err(QrError::UnsupportedSecurity),
+    };
+    let password = match security {
+        QrSecurity::Wpa | QrSecurity::Sae => value(3),
+        QrSecurity::Wep => value(4),
+        QrSecurity::Nopass => None,
+    };
+
+    Ok(WifiCredentials {
+        ssid: value(0).ok_or(QrError::NoSuchProfile)?,
+        password,
+        security,
+        hidden: value(1).as_deref() == Some("yes"),
+    })
+}
+
+/// Reads the text of the QR code in an image file with `zbarimg`
+///
+/// # Returns
+/// - `Ok(String)` with the text of the first QR code
+/// - `Err(QrError::NoQrCode)` if the image contains none
+/// - `Err(QrError::CommandExecutionFailure)` if zbarimg is not installed
+pub fn decode_image(path: &Path) -> Result<String, QrError> {
+    let output = Command::new("zbarimg")
+        .args(["--quiet", "--raw", "-Sdisable", "-Sqrcode.enable"])
+        .arg(path)
+        .output()
+        .map_err(|_| QrError::CommandExecutionFailure)?;
+
+    // zbarimg exits with 4 if it found no code
+    if !output.status.success() {
+        return Err(QrError::NoQrCode);
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .ok_or(QrError::NoQrCode)
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if SPECIAL_CHARACTERS.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Removes the backslashes, and the quotes around a quoted value
+fn unescape(value: &str) -> String {
+    let value = match value.len() >= 2
+        && value.starts_with('"')
+        && value.ends_with('"')
+        && !value.ends_with("\\\"")
+    {
+        true => &value[1..value.len() - 1],
+        false => value,
+    };
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
+
+/// Splits the fields at semicolons that are not escaped, still escaped
+fn split_fields(body: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+
+    for (index, c) in body.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ';' => {
+                fields.push(&body[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&body[start..]);
+
+    fields
+        .into_iter()
+        .filter(|field| !field.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials(ssid: &str, password: Option<&str>) -> WifiCredentials {
+        WifiCredentials {
+            ssid: ssid.to_string(),
+            password: password.map(str::to_string),
+            security: QrSecurity::Wpa,
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn encodes_plain_credentials() {
+        let uri = credentials("Home", Some("secret123")).to_uri();
+        assert_eq!(uri, "WIFI:T:WPA;S:Home;P:secret123;;");
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        let uri = credentials(r#"My;Net,"1":x\y"#, Some("p;a:s,s\\")).to_uri();
+        assert_eq!(uri, r#"WIFI:T:WPA;S:My\;Net\,\"1\"\:x\\y;P:p\;a\:s\,s\\;;"#);
+    }
+
+    #[test]
+    fn round_trips_special_characters() {
+        for (ssid, password) in [
+            ("a;b", "c;d"),
+            (r"back\slash", r"\\"),
+            (r#""quoted""#, r#"""#),
+            ("colon:comma,", ":,;"),
+            ("Café ☕", "pässwörd"),
+            ("trailing\\", "x\\"),
+        ] {
+            let original = credentials(ssid, Some(password));
+            let parsed = WifiCredentials::parse(&original.to_uri()).unwrap();
+            assert_eq!(parsed, original, "{}", original.to_uri());
+        }
+    }
+
+    #[test]
+    fn quotes_hex_passwords() {
+        let original = credentials("Net", Some("deadbeef"));
+        assert_eq!(original.to_uri(), r#"WIFI:T:WPA;S:Net;P:"deadbeef";;"#);
+        assert_eq!(
+            WifiCredentials::parse(&original.to_uri()).unwrap(),
+            original
+        );
+    }
+
+    #[test]
+    fn parses_fields_in_any_order() {
+        let parsed = WifiCredentials::parse("wifi:P:pw12345678;H:true;S:Hidden;T:SAE;;").unwrap();
+        assert_eq!(parsed.ssid, "Hidden");
+        assert_eq!(parsed.password.as_deref(), Some("pw12345678"));
+        assert_eq!(parsed.security, QrSecurity::Sae);
+        assert!(parsed.hidden);
+    }
+
+    #[test]
+    fn parses_open_networks() {
+        let parsed = WifiCredentials::parse("WIFI:T:nopass;S:Cafe;P:;;").unwrap();
+        assert_eq!(parsed.security, QrSecurity::Nopass);
+        assert_eq!(parsed.password, None);
+
+        let open = WifiCredentials {
+            security: QrSecurity::Nopass,
+            ..credentials("Cafe", Some("ignored"))
+        };
+        assert_eq!(open.to_uri(), "WIFI:T:nopass;S:Cafe;;");
+    }
+
+    #[test]
+    fn infers_security_from_password() {
+        let parsed = WifiCredentials::parse("WIFI:S:Net;P:secret;;").unwrap();
+        assert_eq!(parsed.security, QrSecurity::Wpa);
+        let parsed = WifiCredentials::parse("WIFI:S:Net;;").unwrap();
+        assert_eq!(parsed.security, QrSecurity::Nopass);
+    }
+
+    #[test]
+    fn rejects_invalid_uris() {
+        assert!(matches!(
+            WifiCredentials::parse("http://example.com"),
+            Err(QrError::InvalidUri(_))
+        ));
+        assert!(matches!(
+            WifiCredentials::parse("WIFI:T:WPA;P:x;;"),
+            Err(QrError::InvalidUri(_))
+        ));
+        assert!(matches!(
+            WifiCredentials::parse("WIFI:T:WPA2-EAP;S:Corp;E:PEAP;;"),
+            Err(QrError::UnsupportedSecurity)
+        ));
+    }
+
+    #[test]
+    fn renders_png_and_svg() {
+        let credentials = credentials("Home", Some("secret123"));
+        let png = credentials.to_png(4).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        assert!(credentials.to_svg().unwrap().contains("<svg"));
+    }
+}
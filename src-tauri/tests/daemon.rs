@@ -0,0 +1,256 @@
+//! Runs the daemon on a socket in a temporary directory and talks JSON-RPC to
+//! it, with nmcli replaced by a script and the D-Bus services unreachable.
+
+use std::{
+    env, fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::Once,
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixStream,
+    },
+    time::{sleep, timeout},
+};
+use wiblue_lib::daemon::{
+    exporter::ExporterSettings,
+    protocol::{INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR},
+    server::run_daemon,
+};
+
+/// Answers `nmcli connection show` with two saved profiles, fails otherwise
+const FAKE_NMCLI: &str = r#"#!/bin/sh
+case "$*" in
+  *"NAME,UUID,TYPE,DEVICE,ACTIVE,AUTOCONNECT connection show")
+    printf 'Home:u1:802-11-wireless:wlan0:yes:yes\nOffice:u2:802-11-wireless::no:no\nWired:u3:802-3-ethernet:eth0:yes:yes\n' ;;
+  *) exit 1 ;;
+esac
+"#;
+
+fn test_dir() -> PathBuf {
+    env::temp_dir().join(format!("wiblue-daemon-test-{}", std::process::id()))
+}
+
+/// Points the daemon at the fake nmcli and at directories of its own
+fn setup() {
+    static SETUP: Once = Once::new();
+    SETUP.call_once(|| {
+        let dir = test_dir();
+        let bin = dir.join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        let nmcli = bin.join("nmcli");
+        fs::write(&nmcli, FAKE_NMCLI).unwrap();
+        fs::set_permissions(&nmcli, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path = env::var("PATH").unwrap_or_default();
+        env::set_var("PATH", format!("{}:{}", bin.display(), path));
+        env::set_var("XDG_CONFIG_HOME", dir.join("config"));
+        env::set_var("XDG_DATA_HOME", dir.join("data"));
+        env::set_var("XDG_RUNTIME_DIR", dir.join("run"));
+        let unreachable = format!("unix:path={}", dir.join("no-bus").display());
+        env::set_var("WIBLUE_BLUEZ_BUS_ADDRESS", &unreachable);
+        env::set_var("WIBLUE_OBEX_BUS_ADDRESS", &unreachable);
+        env::set_var("WIBLUE_SESSION_BUS_ADDRESS", &unreachable);
+    });
+}
+
+/// Starts a daemon listening on `<name>.sock` in the test directory
+async fn start_daemon(name: &str) -> PathBuf {
+    setup();
+    let socket = test_dir().join(format!("{}.sock", name));
+    let _ = fs::remove_file(&socket);
+
+    let path = socket.clone();
+    tokio::spawn(async move {
+        run_daemon(&path, &ExporterSettings::default())
+            .await
+            .unwrap();
+    });
+    for _ in 0..100 {
+        if UnixStream::connect(&socket).await.is_ok() {
+            return socket;
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+    panic!("The daemon did not start listening on {}", socket.display());
+}
+
+struct Client {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    next_id: u64,
+}
+
+impl Client {
+    async fn connect(socket: &Path) -> Self {
+        let (reader, writer) = UnixStream::connect(socket).await.unwrap().into_split();
+        Client {
+            lines: BufReader::new(reader).lines(),
+            writer,
+            next_id: 1,
+        }
+    }
+
+    /// Sends a raw line and reads the response to it
+    async fn send(&mut self, line: &str) -> Value {
+        self.writer
+            .write_all(format!("{}\n", line).as_bytes())
+            .await
+            .unwrap();
+        let line = timeout(Duration::from_secs(10), self.lines.next_line())
+            .await
+            .expect("the daemon did not respond")
+            .unwrap()
+            .expect("the daemon closed the connection");
+        serde_json::from_str(&line).unwrap()
+    }
+
+    async fn call(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let response = self.send(&request.to_string()).await;
+        assert_eq!(response["id"], id);
+        response
+    }
+
+    /// Calls a method that must succeed, returning its message
+    async fn message(&mut self, method: &str, params: Value) -> Value {
+        let response = self.call(method, params).await;
+        assert_eq!(response["error"], Value::Null, "{} failed", method);
+        response["result"]["message"].clone()
+    }
+}
+
+fn error_code(response: &Value) -> i64 {
+    response["error"]["code"]
+        .as_i64()
+        .expect("an error response")
+}
+
+#[tokio::test]
+async fn answers_requests_and_reports_protocol_errors() {
+    let socket = start_daemon("protocol").await;
+    let mut client = Client::connect(&socket).await;
+
+    let version = client.message("daemon.version", json!({})).await;
+    assert_eq!(version["api"], 1);
+
+    let invalid = client.send("{ not json").await;
+    assert_eq!(error_code(&invalid), PARSE_ERROR);
+    assert_eq!(invalid["id"], Value::Null);
+
+    let old = client
+        .send(r#"{"jsonrpc":"1.0","id":7,"method":"daemon.version"}"#)
+        .await;
+    assert_eq!(error_code(&old), INVALID_REQUEST);
+    assert_eq!(old["id"], 7);
+
+    let unknown = client.call("wifi.teleport", json!({})).await;
+    assert_eq!(error_code(&unknown), METHOD_NOT_FOUND);
+
+    let wrong_type = client
+        .call("speed_test.history", json!({ "limit": "ten" }))
+        .await;
+    assert_eq!(error_code(&wrong_type), INVALID_PARAMS);
+
+    let missing = client.call("wifi.delete_profile", json!({})).await;
+    assert_eq!(error_code(&missing), INVALID_PARAMS);
+}
+
+#[tokio::test]
+async fn reads_profiles_from_nmcli() {
+    let socket = start_daemon("profiles").await;
+    let mut client = Client::connect(&socket).await;
+
+    let profiles = client.message("wifi.profiles", json!({})).await;
+    let names: Vec<&str> = profiles
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["Home", "Office"]);
+    assert_eq!(profiles[0]["device"], "wlan0");
+    assert_eq!(profiles[0]["active"], true);
+
+    // The fake nmcli fails every other command
+    let disconnect = client.call("wifi.disconnect", json!({})).await;
+    assert!(error_code(&disconnect) >= 400);
+}
+
+#[tokio::test]
+async fn shares_monitors_until_the_last_client_leaves() {
+    let socket = start_daemon("monitors").await;
+    let mut first = Client::connect(&socket).await;
+    let mut second = Client::connect(&socket).await;
+    let lo = json!({ "interface": "lo" });
+
+    let missing = first
+        .call("net.monitor_stats", json!({ "interface": "nosuch0" }))
+        .await;
+    assert_eq!(error_code(&missing), 404);
+
+    assert_eq!(
+        first.message("net.monitor_stats", lo.clone()).await,
+        "Started monitoring"
+    );
+    assert_eq!(
+        second.message("net.monitor_stats", lo.clone()).await,
+        "Already monitoring"
+    );
+
+    // The monitor keeps running for the second client
+    drop(first);
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(
+        second.message("net.monitor_stats_stop", lo.clone()).await,
+        "Stopped monitoring"
+    );
+    let stopped = second.call("net.monitor_stats_stop", lo.clone()).await;
+    assert_eq!(error_code(&stopped), 404);
+
+    assert_eq!(
+        second.message("net.monitor_stats", lo.clone()).await,
+        "Started monitoring"
+    );
+}
+
+#[tokio::test]
+async fn restarts_the_bluetooth_monitor_with_a_new_config() {
+    let socket = start_daemon("bluetooth_monitor").await;
+    let mut first = Client::connect(&socket).await;
+    let mut second = Client::connect(&socket).await;
+
+    assert_eq!(
+        first.message("bt.monitor_stats", json!({})).await,
+        "Started monitoring"
+    );
+    assert_eq!(
+        second.message("bt.monitor_stats", json!({})).await,
+        "Already monitoring"
+    );
+    let config = json!({ "config": { "interval_secs": 5, "low_battery_thresholds": [15] } });
+    assert_eq!(
+        second.message("bt.monitor_stats", config).await,
+        "Started monitoring"
+    );
+
+    // Both clients stay subscribed to the restarted monitor
+    assert_eq!(
+        first.message("bt.monitor_stats_stop", json!({})).await,
+        "Stopped monitoring"
+    );
+    assert_eq!(
+        second.message("bt.monitor_stats_stop", json!({})).await,
+        "Stopped monitoring"
+    );
+    let stopped = second.call("bt.monitor_stats_stop", json!({})).await;
+    assert_eq!(error_code(&stopped), 404);
+}
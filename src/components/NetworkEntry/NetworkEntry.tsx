@@ -30,7 +30,16 @@ export default function NetworkEntry({
       .then((r) => {
         const response: JsonResponse = JSON.parse(r as string);
         console.log(response.message);
-        toast.success("Connected successfully!");
+        if (response.status === 511) {
+          toast("Captive portal detected, opening login page");
+          invoke("open_captive_portal", { url: null }).catch((e) =>
+            console.log(e),
+          );
+        } else if (response.status === 206) {
+          toast("Connected, but there is no internet access");
+        } else {
+          toast.success("Connected successfully!");
+        }
         if (onConnectSuccess) onConnectSuccess();
       })
       .catch((er) => {